@@ -51,14 +51,192 @@ defmodule Cyanea.Align do
   ## Options
 
     * `:mode` - `:global` (default), `:local`, or `:semiglobal`
-    * `:matrix` - `:blosum62` (default), `:blosum45`, `:blosum80`, or `:pam250`
+    * `:matrix` - `:blosum62` (default), `:blosum45`, `:blosum80`, `:pam250`,
+      or a scheme from `scoring_scheme/2`
 
   """
   @spec protein(binary(), binary(), keyword()) :: {:ok, struct()} | {:error, term()}
   def protein(query, target, opts \\ []) when is_binary(query) and is_binary(target) do
     mode = mode_string(Keyword.get(opts, :mode, :global))
-    matrix = matrix_string(Keyword.get(opts, :matrix, :blosum62))
-    nif_call(fn -> Native.align_protein(query, target, mode, matrix) end)
+
+    case Keyword.get(opts, :matrix, :blosum62) do
+      scheme when is_reference(scheme) ->
+        nif_call(fn -> Native.align_with_scheme(query, target, mode, scheme) end)
+
+      matrix ->
+        matrix = matrix_string(matrix)
+        nif_call(fn -> Native.align_protein(query, target, mode, matrix) end)
+    end
+  end
+
+  # ===========================================================================
+  # Scoring schemes
+  # ===========================================================================
+
+  @doc """
+  Build a reusable scoring scheme: a substitution matrix with affine gap
+  penalties, held by the NIF so it is parsed once.
+
+  `source` is a built-in matrix (`:blosum62`, `:blosum45`, `:blosum80`,
+  `:pam250`, or `:nuc44`, the NUC.4.4 nucleotide matrix with IUPAC codes),
+  `{:file, path}` for any NCBI-format matrix file (BLOSUM30-100, PAM30/70,
+  VTML, ...), or `{:matrix, name, text}` for an inline NCBI-format matrix
+  over any alphabet.
+
+  ## Options
+
+    * `:gap_open` - score of a gap's first position, <= 0 (default: -10)
+    * `:gap_extend` - score of each further gap position, <= 0 (default: -1)
+
+  """
+  @spec scoring_scheme(atom() | tuple(), keyword()) :: {:ok, reference()} | {:error, term()}
+  def scoring_scheme(source, opts \\ []) do
+    gap_open = Keyword.get(opts, :gap_open, -10)
+    gap_extend = Keyword.get(opts, :gap_extend, -1)
+
+    case source do
+      name when name in [:blosum62, :blosum45, :blosum80, :pam250] ->
+        nif_call(fn -> Native.scoring_scheme_builtin(Atom.to_string(name), gap_open, gap_extend) end)
+
+      :nuc44 -> nif_call(fn -> Native.scoring_scheme_builtin("nuc.4.4", gap_open, gap_extend) end)
+      {:file, path} -> nif_call(fn -> Native.scoring_scheme_load(path, gap_open, gap_extend) end)
+      {:matrix, name, text} -> nif_call(fn -> Native.scoring_scheme_parse(name, text, gap_open, gap_extend) end)
+    end
+  end
+
+  @doc "Describe a scoring scheme as `%Cyanea.Native.ScoringSchemeInfo{}`."
+  @spec scoring_scheme_info(reference()) :: {:ok, struct()} | {:error, term()}
+  def scoring_scheme_info(scheme) when is_reference(scheme),
+    do: nif_call(fn -> Native.scoring_scheme_info(scheme) end)
+
+  @doc """
+  Affine-gap alignment of any two sequences under a scoring scheme from
+  `scoring_scheme/2`. Every symbol must be in the scheme's matrix.
+
+  ## Options
+
+    * `:mode` - `:global` (default), `:local`, or `:semiglobal`
+
+  """
+  @spec pairwise(binary(), binary(), reference(), keyword()) :: {:ok, struct()} | {:error, term()}
+  def pairwise(query, target, scheme, opts \\ [])
+      when is_binary(query) and is_binary(target) and is_reference(scheme) do
+    mode = mode_string(Keyword.get(opts, :mode, :global))
+    nif_call(fn -> Native.align_with_scheme(query, target, mode, scheme) end)
+  end
+
+  # ===========================================================================
+  # Database search
+  # ===========================================================================
+
+  @doc """
+  Search `query` against every record of a FASTA database (plain or
+  gzipped) with Smith-Waterman, streaming the file one record at a time.
+
+  Returns `{:ok, %Cyanea.Native.DbSearch{}}` with the best hits ranked by
+  score, each carrying its bit score, Karlin-Altschul E-value and full
+  local alignment.  BLOSUM62 at the default gaps uses BLAST's gapped λ and
+  K; other schemes use ungapped values computed from the matrix unless
+  `:lambda` and `:k` are given.
+
+  ## Options
+
+    * `:type` - `:protein` (default; BLOSUM62, gaps -12/-1) or `:dna`
+      (NUC.4.4, gaps -16/-4)
+    * `:scheme` - scoring scheme from `scoring_scheme/2`, instead of `:type`
+    * `:max_hits` - hits to keep (default: 50)
+    * `:max_evalue` - drop hits with a larger E-value (default: 10.0)
+    * `:lambda`, `:k` - Karlin-Altschul parameters for the scheme
+
+  """
+  @spec search_fasta(binary(), Path.t(), keyword()) :: {:ok, struct()} | {:error, term()}
+  def search_fasta(query, db_path, opts \\ []) when is_binary(query) and is_binary(db_path) do
+    options = %Native.DbSearchOptions{
+      max_hits: Keyword.get(opts, :max_hits, 50),
+      max_evalue: Keyword.get(opts, :max_evalue, 10.0) / 1,
+      lambda: float_or_nil(Keyword.get(opts, :lambda)),
+      k: float_or_nil(Keyword.get(opts, :k))
+    }
+
+    with {:ok, scheme} <- search_scheme(opts) do
+      nif_call(fn -> Native.search_fasta(query, db_path, scheme, options) end)
+    end
+  end
+
+  defp search_scheme(opts) do
+    case Keyword.get(opts, :scheme) do
+      nil ->
+        case Keyword.get(opts, :type, :protein) do
+          :protein -> scoring_scheme(:blosum62, gap_open: -12, gap_extend: -1)
+          :dna -> scoring_scheme(:nuc44, gap_open: -16, gap_extend: -4)
+        end
+
+      scheme when is_reference(scheme) ->
+        {:ok, scheme}
+    end
+  end
+
+  defp float_or_nil(nil), do: nil
+  defp float_or_nil(value), do: value / 1
+
+  # ===========================================================================
+  # Read mapping
+  # ===========================================================================
+
+  @doc """
+  Index a reference FASTA (plain or gzipped) for `map_reads/4`. The index
+  is built once and can be reused across runs.
+  """
+  @spec mapper_index(Path.t()) :: {:ok, reference()} | {:error, term()}
+  def mapper_index(reference_path) when is_binary(reference_path),
+    do: nif_call(fn -> Native.read_mapper_index(reference_path) end)
+
+  @doc """
+  Map short reads against an index from `mapper_index/1` and write every
+  read, mapped or not, to `output` as SAM or BAM with `NM`, `MD` and `AS`
+  tags.
+
+  `reads` is a FASTQ path, or `{r1, r2}` for paired-end reads. Seeds are
+  found with the FM-index and extended with banded alignment; unaligned
+  read ends are soft-clipped. Meant for small references such as viral or
+  bacterial genomes and amplicon panels.
+
+  Returns `{:ok, %Cyanea.Native.MapStats{}}`.
+
+  ## Options
+
+    * `:format` - `:sam` or `:bam` (default: from the output extension)
+    * `:seed_length` - k-mer seed length, >= 8 (default: 19)
+    * `:max_occurrences` - skip seeds more frequent than this (default: 100)
+    * `:bandwidth` - largest indel shift tolerated (default: 16)
+    * `:min_score` - minimum alignment score to report a hit (default: 30)
+    * `:max_insert` - longest fragment of a proper pair (default: 1000)
+
+  """
+  @spec map_reads(reference(), Path.t() | {Path.t(), Path.t()}, Path.t(), keyword()) ::
+          {:ok, struct()} | {:error, term()}
+  def map_reads(index, reads, output, opts \\ []) when is_reference(index) and is_binary(output) do
+    reads =
+      case reads do
+        {r1, r2} -> [r1, r2]
+        path when is_binary(path) -> [path]
+      end
+
+    format =
+      case Keyword.get(opts, :format) do
+        nil -> if Path.extname(output) == ".bam", do: "bam", else: "sam"
+        format -> Atom.to_string(format)
+      end
+
+    options = %Native.MapOptions{
+      seed_length: Keyword.get(opts, :seed_length, 19),
+      max_occurrences: Keyword.get(opts, :max_occurrences, 100),
+      bandwidth: Keyword.get(opts, :bandwidth, 16),
+      min_score: Keyword.get(opts, :min_score, 30),
+      max_insert: Keyword.get(opts, :max_insert, 1000)
+    }
+
+    nif_call(fn -> Native.map_reads(index, reads, output, format, options) end)
   end
 
   # ===========================================================================
@@ -71,12 +249,28 @@ defmodule Cyanea.Align do
   ## Options
 
     * `:mode` - `:local` (default), `:global`, or `:semiglobal`
+    * `:scheme` - scoring scheme from `scoring_scheme/2` instead of the
+      default DNA scoring (not with `:async`)
+    * `:async` - when `true`, return `{:ok, ref}` at once and run on the
+      native job pool; the result arrives as `{:cyanea_result, ref, result}`
+      (see `Cyanea.Job.await/2`)
+    * `:cancel` - cancel token from `Cyanea.Job.cancel_token/0` (async only)
 
   """
-  @spec batch(list(), keyword()) :: {:ok, list()} | {:error, term()}
+  @spec batch(list(), keyword()) :: {:ok, list() | reference()} | {:error, term()}
   def batch(pairs, opts \\ []) when is_list(pairs) do
     mode = mode_string(Keyword.get(opts, :mode, :local))
-    nif_call(fn -> Native.align_batch_dna(pairs, mode) end)
+
+    cond do
+      scheme = Keyword.get(opts, :scheme) ->
+        nif_call(fn -> Native.align_batch_with_scheme(pairs, mode, scheme) end)
+
+      Keyword.get(opts, :async, false) ->
+        nif_call(fn -> Native.align_batch_dna_async(pairs, mode, Keyword.get(opts, :cancel)) end)
+
+      true ->
+        nif_call(fn -> Native.align_batch_dna(pairs, mode) end)
+    end
   end
 
   # ===========================================================================
@@ -84,19 +278,178 @@ defmodule Cyanea.Align do
   # ===========================================================================
 
   @doc """
-  Progressive multiple sequence alignment.
+  Progressive multiple sequence alignment. Returns a
+  `%Cyanea.Native.MsaResult{}` whose `conservation` is the mean of the
+  per-column conservation.
+
+  This is `msa_report/2` with its default scheme for `:mode`, a UPGMA
+  guide tree and no refinement; use `msa_report/2` to pick those.
 
   ## Options
 
     * `:mode` - `:dna` (default) or `:protein`
+    * `:cancel` - cancel token from `Cyanea.Job.cancel_token/0`
+    * `:progress` - pid, or `{pid, job_ref}`, receiving `{:progress, job_ref, fraction}`
+    * `:async` - when `true`, return `{:ok, ref}` at once and run on the
+      native job pool; the result arrives as `{:cyanea_result, ref, result}`
+      (see `Cyanea.Job.await/2`)
 
   """
-  @spec msa(list(), keyword()) :: {:ok, struct()} | {:error, term()}
+  @spec msa(list(), keyword()) :: {:ok, struct() | reference()} | {:error, term()}
   def msa(sequences, opts \\ []) when is_list(sequences) do
     mode = msa_mode_string(Keyword.get(opts, :mode, :dna))
-    nif_call(fn -> Native.progressive_msa(sequences, mode) end)
+    {cancel, progress} = job_args(opts)
+
+    if Keyword.get(opts, :async, false) do
+      nif_call(fn -> Native.progressive_msa_async(sequences, mode, cancel) end)
+    else
+      nif_call(fn -> Native.progressive_msa(sequences, mode, cancel, progress) end)
+    end
+  end
+
+  @doc """
+  Configurable progressive multiple sequence alignment. Returns a
+  `%Cyanea.Native.MsaReport{}`: the guide tree as Newick, per-column
+  `conservation` (0..1) and `entropy` (bits) lists for coloring a viewer,
+  a majority consensus and the sum-of-pairs score.
+
+  ## Options
+
+    * `:mode` - `:dna` (default) or `:protein`
+    * `:scheme` - a scheme from `scoring_scheme/2`; defaults to NUC.4.4
+      (gaps -16/-4) for `:dna` and BLOSUM62 (gaps -10/-1) for `:protein`
+    * `:gap_open` / `:gap_extend` - gap penalties for the default scheme
+    * `:guide_tree` - `:upgma` (default) or `:nj`
+    * `:refine` - tree-based refinement rounds (default: 0)
+    * `:names` - guide tree leaf names (default: `seq1`, `seq2`, ...)
+    * `:cancel` - cancel token from `Cyanea.Job.cancel_token/0`; checked after
+      each merge
+    * `:progress` - pid, or `{pid, job_ref}`, receiving `{:progress, job_ref, fraction}`
+    * `:async` - when `true`, return `{:ok, ref}` at once and run on the
+      native job pool; the result arrives as `{:cyanea_result, ref, result}`
+
+  """
+  @spec msa_report(list(), keyword()) :: {:ok, struct() | reference()} | {:error, term()}
+  def msa_report(sequences, opts \\ []) when is_list(sequences) do
+    mode = Keyword.get(opts, :mode, :dna)
+    {cancel, progress} = job_args(opts)
+
+    options = %Native.MsaOptions{
+      guide_tree: guide_tree_string(Keyword.get(opts, :guide_tree, :upgma)),
+      refine_rounds: Keyword.get(opts, :refine, 0)
+    }
+
+    names = Keyword.get(opts, :names, [])
+
+    with {:ok, scheme} <- msa_scheme(mode, opts) do
+      if Keyword.get(opts, :async, false) do
+        nif_call(fn ->
+          Native.progressive_msa_with_scheme_async(sequences, names, scheme, options, cancel)
+        end)
+      else
+        nif_call(fn ->
+          Native.progressive_msa_with_scheme(sequences, names, scheme, options, cancel, progress)
+        end)
+      end
+    end
+  end
+
+  defp msa_scheme(mode, opts) do
+    case Keyword.get(opts, :scheme) do
+      nil ->
+        case mode do
+          :dna ->
+            scoring_scheme(:nuc44,
+              gap_open: Keyword.get(opts, :gap_open, -16),
+              gap_extend: Keyword.get(opts, :gap_extend, -4)
+            )
+
+          :protein ->
+            scoring_scheme(:blosum62,
+              gap_open: Keyword.get(opts, :gap_open, -10),
+              gap_extend: Keyword.get(opts, :gap_extend, -1)
+            )
+        end
+
+      scheme when is_reference(scheme) ->
+        {:ok, scheme}
+    end
+  end
+
+  defp guide_tree_string(:upgma), do: "upgma"
+  defp guide_tree_string(:nj), do: "nj"
+
+  @doc """
+  Add sequences to an existing alignment without realigning it. Each new
+  sequence is aligned to a profile of the alignment's columns, which stay
+  fixed; residues a new sequence inserts between columns go into new
+  columns, shared and left-aligned across the new sequences.
+
+  `alignment` is a list of aligned rows, an `%MsaResult{}`, `%MsaReport{}`
+  or `%MsaMerge{}`, or `{:stockholm, path}` / `{:clustal, path}`. Returns a
+  `%Cyanea.Native.MsaMerge{}` with the existing rows first.
+
+  ## Options
+
+    * `:mode`, `:scheme`, `:gap_open`, `:gap_extend` - scoring, as in `msa_report/2`
+    * `:names` - names of the new sequences (default: `seq<row>`)
+    * `:keep_length` - drop insertions so the alignment keeps its columns
+      (default: `false`)
+    * `:band` - only fill DP cells within this many columns of a chain of
+      k-mer seeds shared with the alignment's consensus, so trimmed or
+      partial sequences still align; set it for genome-length alignments,
+      where the full DP is slow (default: full DP, in linear memory)
+    * `:cancel` - cancel token from `Cyanea.Job.cancel_token/0`
+    * `:progress` - pid, or `{pid, job_ref}`, receiving `{:progress, job_ref, fraction}`
+
+  """
+  @spec msa_add(term(), list(), keyword()) :: {:ok, struct()} | {:error, term()}
+  def msa_add(alignment, sequences, opts \\ []) when is_list(sequences) do
+    {cancel, progress} = job_args(opts)
+    source = msa_source(alignment)
+
+    options = %Native.MsaAddOptions{
+      names: Keyword.get(opts, :names, []),
+      keep_length: Keyword.get(opts, :keep_length, false),
+      band: Keyword.get(opts, :band)
+    }
+
+    with {:ok, scheme} <- msa_scheme(Keyword.get(opts, :mode, :dna), opts) do
+      nif_call(fn -> Native.msa_add_sequences(source, sequences, scheme, options, cancel, progress) end)
+    end
+  end
+
+  @doc """
+  Merge two alignments by aligning their profiles. Columns within each
+  alignment are kept; the rows of `a` come first. Alignments are given as
+  for `msa_add/3`.
+
+  ## Options
+
+    * `:mode`, `:scheme`, `:gap_open`, `:gap_extend` - scoring, as in `msa_report/2`
+    * `:band` - DP band half-width around shared k-mer seeds, as in
+      `msa_add/3` (default: full DP)
+    * `:cancel` - cancel token from `Cyanea.Job.cancel_token/0`
+    * `:progress` - pid, or `{pid, job_ref}`, receiving `{:progress, job_ref, fraction}`
+
+  """
+  @spec msa_merge(term(), term(), keyword()) :: {:ok, struct()} | {:error, term()}
+  def msa_merge(a, b, opts \\ []) do
+    {cancel, progress} = job_args(opts)
+    {a, b} = {msa_source(a), msa_source(b)}
+
+    with {:ok, scheme} <- msa_scheme(Keyword.get(opts, :mode, :dna), opts) do
+      nif_call(fn -> Native.msa_merge(a, b, scheme, Keyword.get(opts, :band), cancel, progress) end)
+    end
   end
 
+  defp msa_source(%Native.MsaResult{aligned: aligned}), do: aligned
+  defp msa_source(%Native.MsaReport{names: names, aligned: aligned}), do: Enum.zip(names, aligned)
+  defp msa_source(%Native.MsaMerge{names: names, aligned: aligned}), do: Enum.zip(names, aligned)
+  defp msa_source({:stockholm, path}) when is_binary(path), do: %Native.MsaFile{path: path, format: "stockholm"}
+  defp msa_source({:clustal, path}) when is_binary(path), do: %Native.MsaFile{path: path, format: "clustal"}
+  defp msa_source(rows) when is_list(rows), do: rows
+
   # ===========================================================================
   # Banded alignment
   # ===========================================================================
@@ -137,8 +490,23 @@ defmodule Cyanea.Align do
   # POA consensus
   # ===========================================================================
 
-  @doc "Compute consensus from multiple sequences using Partial Order Alignment."
-  @spec consensus(list()) :: {:ok, binary()} | {:error, term()}
-  def consensus(sequences) when is_list(sequences),
-    do: nif_call(fn -> Native.poa_consensus(sequences) end)
+  @doc """
+  Compute consensus from multiple sequences using Partial Order Alignment.
+
+  ## Options
+
+    * `:async` - when `true`, return `{:ok, ref}` at once and run on the
+      native job pool; the result arrives as `{:cyanea_result, ref, result}`
+      (see `Cyanea.Job.await/2`)
+    * `:cancel` - cancel token from `Cyanea.Job.cancel_token/0` (async only)
+
+  """
+  @spec consensus(list(), keyword()) :: {:ok, binary() | reference()} | {:error, term()}
+  def consensus(sequences, opts \\ []) when is_list(sequences) do
+    if Keyword.get(opts, :async, false) do
+      nif_call(fn -> Native.poa_consensus_async(sequences, Keyword.get(opts, :cancel)) end)
+    else
+      nif_call(fn -> Native.poa_consensus(sequences) end)
+    end
+  end
 end
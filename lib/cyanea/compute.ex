@@ -53,7 +53,7 @@ defmodule Cyanea.Compute do
 
   @doc "Extract k-mers from a DNA sequence."
   def sequence_kmers(data, k) when is_binary(data) and is_integer(k),
-    do: nif_call(fn -> Native.sequence_kmers(data, k) end)
+    do: nif_call(fn -> Native.sequence_kmers(data, k, "dna") end)
 
   @doc "Calculate molecular weight of a protein (Daltons)."
   def protein_molecular_weight(data) when is_binary(data),
@@ -72,13 +72,13 @@ defmodule Cyanea.Compute do
 
   # --- FM-Index (new) -------------------------------------------------------
 
-  @doc "Build an FM-index from text. Returns serialized index data."
+  @doc "Build an FM-index from text. Returns an index resource reference."
   def fm_index_build(text) when is_binary(text),
     do: nif_call(fn -> Native.fm_index_build(text) end)
 
-  @doc "Count occurrences of pattern in FM-index."
-  def fm_index_count(index_data, pattern) when is_binary(index_data) and is_binary(pattern),
-    do: nif_call(fn -> Native.fm_index_count(index_data, pattern) end)
+  @doc "Count occurrences of pattern in an FM-index resource."
+  def fm_index_count(index, pattern) when is_reference(index) and is_binary(pattern),
+    do: nif_call(fn -> Native.fm_index_count(index, pattern) end)
 
   # --- ORF finding (new) ----------------------------------------------------
 
@@ -88,10 +88,10 @@ defmodule Cyanea.Compute do
 
   # --- MinHash (new) --------------------------------------------------------
 
-  @doc "Compute MinHash sketch of a sequence. Returns list of hash values."
+  @doc "Compute MinHash sketch of a sequence. Returns a `%Cyanea.Native.MinHashSketch{}`."
   def minhash_sketch(seq, k, sketch_size)
       when is_binary(seq) and is_integer(k) and is_integer(sketch_size),
-      do: nif_call(fn -> Native.minhash_sketch(seq, k, sketch_size) end)
+      do: nif_call(fn -> Native.minhash_sketch(seq, k, sketch_size, "dna") end)
 
   # ===========================================================================
   # File analysis
@@ -253,7 +253,7 @@ defmodule Cyanea.Compute do
   Mode is `"dna"` or `"protein"`. Returns `{:ok, %MsaResult{}}`.
   """
   def progressive_msa(sequences, mode \\ "dna") when is_list(sequences) and is_binary(mode),
-    do: nif_call(fn -> Native.progressive_msa(sequences, mode) end)
+    do: nif_call(fn -> Native.progressive_msa(sequences, mode, nil, nil) end)
 
   # --- Banded & POA (new) ---------------------------------------------------
 
@@ -432,13 +432,13 @@ defmodule Cyanea.Compute do
   def tsne(data, n_features, n_components \\ 2, perplexity \\ 30.0, n_iter \\ 1000)
       when is_list(data) and is_integer(n_features)
       and is_integer(n_components) and is_number(perplexity) and is_integer(n_iter),
-      do: nif_call(fn -> Native.tsne(data, n_features, n_components, perplexity, n_iter) end)
+      do: nif_call(fn -> Native.tsne(data, n_features, n_components, perplexity, n_iter, nil, nil) end)
 
   @doc "UMAP dimensionality reduction. Returns `{:ok, %UmapResult{}}`."
   def umap(data, n_features, n_components \\ 2, n_neighbors \\ 15, min_dist \\ 0.1,
            n_epochs \\ 200, metric \\ "euclidean", seed \\ 42)
       when is_list(data) and is_integer(n_features),
-      do: nif_call(fn -> Native.umap(data, n_features, n_components, n_neighbors, min_dist, n_epochs, metric, seed) end)
+      do: nif_call(fn -> Native.umap(data, n_features, n_components, n_neighbors, min_dist, n_epochs, metric, seed, nil, nil) end)
 
   @doc "Compute normalized k-mer frequency embedding for a sequence."
   def kmer_embedding(sequence, k, alphabet \\ "dna")
@@ -473,16 +473,16 @@ defmodule Cyanea.Compute do
       when is_list(weights) and is_number(bias) and is_list(queries) and is_integer(n_features),
       do: nif_call(fn -> Native.linear_regression_predict(weights, bias, queries, n_features) end)
 
-  @doc "Fit a random forest classifier. Returns serialized model as binary."
+  @doc "Fit a random forest classifier. Returns a model resource reference."
   def random_forest_fit(data, n_features, labels, n_trees \\ 10, max_depth \\ 5, seed \\ 42)
       when is_list(data) and is_integer(n_features) and is_list(labels)
       and is_integer(n_trees) and is_integer(max_depth) and is_integer(seed),
-      do: nif_call(fn -> Native.random_forest_fit(data, n_features, labels, n_trees, max_depth, seed) end)
+      do: nif_call(fn -> Native.random_forest_fit(data, n_features, labels, n_trees, max_depth, seed, nil, nil) end)
 
-  @doc "Predict class label using a serialized random forest model."
-  def random_forest_predict(model_data, sample, n_features)
-      when is_binary(model_data) and is_list(sample) and is_integer(n_features),
-      do: nif_call(fn -> Native.random_forest_predict(model_data, sample, n_features) end)
+  @doc "Predict class labels for a batch of samples using a fitted random forest model."
+  def random_forest_predict(model, samples, n_features)
+      when is_reference(model) and is_list(samples) and is_integer(n_features),
+      do: nif_call(fn -> Native.random_forest_predict(model, samples, n_features) end)
 
   # --- HMM (new) ------------------------------------------------------------
 
@@ -513,7 +513,10 @@ defmodule Cyanea.Compute do
       when is_list(data) and is_integer(n_features) and is_list(labels) and is_binary(metric),
       do: nif_call(fn -> Native.silhouette_score(data, n_features, labels, metric) end)
 
-  @doc "Compute Jaccard similarity between two MinHash sketches."
+  @doc "Compute Jaccard similarity between two MinHash sketches or their hash lists."
+  def minhash_jaccard(%Native.MinHashSketch{hashes: a}, %Native.MinHashSketch{hashes: b}),
+    do: minhash_jaccard(a, b)
+
   def minhash_jaccard(sketch_a, sketch_b) when is_list(sketch_a) and is_list(sketch_b),
     do: nif_call(fn -> Native.minhash_jaccard(sketch_a, sketch_b) end)
 
@@ -633,7 +636,7 @@ defmodule Cyanea.Compute do
   @doc "Build a Neighbor-Joining tree from aligned sequences. Returns Newick string."
   def build_nj(sequences, names, model \\ "p")
       when is_list(sequences) and is_list(names) and is_binary(model),
-      do: nif_call(fn -> Native.build_nj(sequences, names, model) end)
+      do: nif_call(fn -> Native.build_nj(sequences, names, model, nil, nil) end)
 
   @doc "Parse NEXUS format text and return taxa and tree data."
   def nexus_parse(nexus_text) when is_binary(nexus_text),
@@ -652,7 +655,7 @@ defmodule Cyanea.Compute do
   def bootstrap_support(sequences, tree_newick, n_replicates \\ 100, model \\ "p")
       when is_list(sequences) and is_binary(tree_newick)
       and is_integer(n_replicates) and is_binary(model),
-      do: nif_call(fn -> Native.bootstrap_support(sequences, tree_newick, n_replicates, model) end)
+      do: nif_call(fn -> Native.bootstrap_support(sequences, tree_newick, n_replicates, model, nil, nil) end)
 
   @doc "Ancestral state reconstruction using Fitch parsimony."
   def ancestral_reconstruction(tree_newick, leaf_states)
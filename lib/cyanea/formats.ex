@@ -36,11 +36,59 @@ defmodule Cyanea.Formats do
   def vcf_stats(path) when is_binary(path),
     do: nif_call(fn -> Native.vcf_stats(path) end)
 
-  @doc "Parse a VCF file and return all variant records."
+  @doc "Parse a VCF file (plain or bgzipped) and return all variant records."
   @spec parse_vcf(binary()) :: {:ok, list()} | {:error, term()}
   def parse_vcf(path) when is_binary(path),
     do: nif_call(fn -> Native.parse_vcf(path) end)
 
+  @doc """
+  Open a VCF file (plain or bgzipped) as a streaming reader.
+
+  The header is parsed on open; use `vcf_header/1` to inspect it and
+  `next_vcf_batch/2` to read records in chunks.
+  """
+  @spec open_vcf(binary()) :: {:ok, reference()} | {:error, term()}
+  def open_vcf(path) when is_binary(path),
+    do: nif_call(fn -> Native.vcf_open(path) end)
+
+  @doc "Get the parsed header of an open VCF reader."
+  @spec vcf_header(reference()) :: {:ok, struct()} | {:error, term()}
+  def vcf_header(reader) when is_reference(reader),
+    do: nif_call(fn -> Native.vcf_header(reader) end)
+
+  @doc "Read up to `n` records from an open VCF reader. Returns `{:ok, []}` at end of file."
+  @spec next_vcf_batch(reference(), pos_integer()) :: {:ok, list()} | {:error, term()}
+  def next_vcf_batch(reader, n) when is_reference(reader) and is_integer(n) and n > 0,
+    do: nif_call(fn -> Native.vcf_next_batch(reader, n) end)
+
+  @doc """
+  Stream the records of a VCF file lazily, reading in batches.
+
+  ## Options
+
+    * `:batch_size` - records read per NIF call (default: 1000)
+
+  """
+  @spec stream_vcf(binary(), keyword()) :: {:ok, Enumerable.t()} | {:error, term()}
+  def stream_vcf(path, opts \\ []) when is_binary(path) do
+    batch_size = Keyword.get(opts, :batch_size, 1000)
+
+    with {:ok, reader} <- open_vcf(path) do
+      {:ok,
+       Stream.resource(
+         fn -> reader end,
+         fn reader ->
+           case next_vcf_batch(reader, batch_size) do
+             {:ok, []} -> {:halt, reader}
+             {:ok, records} -> {records, reader}
+             {:error, reason} -> raise ArgumentError, "VCF read failed: #{error_message(reason)}"
+           end
+         end,
+         fn _reader -> :ok end
+       )}
+    end
+  end
+
   # ===========================================================================
   # BED
   # ===========================================================================
@@ -78,7 +126,7 @@ defmodule Cyanea.Formats do
   # SAM/BAM
   # ===========================================================================
 
-  @doc "Get statistics from a SAM file."
+  @doc "Get statistics from a SAM file, including flagstat-style duplicate, pairing and insert-size figures."
   @spec sam_stats(binary()) :: {:ok, struct()} | {:error, term()}
   def sam_stats(path) when is_binary(path),
     do: nif_call(fn -> Native.sam_stats(path) end)
@@ -88,7 +136,7 @@ defmodule Cyanea.Formats do
   def parse_sam(path) when is_binary(path),
     do: nif_call(fn -> Native.parse_sam(path) end)
 
-  @doc "Get statistics from a BAM file."
+  @doc "Get statistics from a BAM file, including flagstat-style duplicate, pairing and insert-size figures."
   @spec bam_stats(binary()) :: {:ok, struct()} | {:error, term()}
   def bam_stats(path) when is_binary(path),
     do: nif_call(fn -> Native.bam_stats(path) end)
@@ -98,6 +146,157 @@ defmodule Cyanea.Formats do
   def parse_bam(path) when is_binary(path),
     do: nif_call(fn -> Native.parse_bam(path) end)
 
+  @doc """
+  Decode a SAM FLAG into a `%Cyanea.Native.SamFlags{}` of named booleans.
+
+  Accepts the integer flag or a `%Cyanea.Native.SamRecord{}`.
+  """
+  @spec sam_flags(non_neg_integer() | struct()) :: {:ok, struct()} | {:error, term()}
+  def sam_flags(%Native.SamRecord{flag: flag}), do: sam_flags(flag)
+
+  def sam_flags(flag) when is_integer(flag) and flag >= 0 and flag <= 0xFFFF,
+    do: nif_call(fn -> Native.sam_flags(flag) end)
+
+  # ===========================================================================
+  # Writers
+  # ===========================================================================
+  #
+  # All writers take `:compression` (`:none`, `:gzip`, or `:bgzip`). It
+  # defaults to `:bgzip` for paths ending in `.gz` (gzip-compatible and
+  # indexable with `index_tabix/2`) and `:none` otherwise. Each returns
+  # `{:ok, count}` with the number of records written.
+
+  @doc """
+  Write a VCF file from a `%Cyanea.Native.VcfHeader{}` and a list of
+  `%Cyanea.Native.VcfRecord{}` structs.
+  """
+  @spec write_vcf(binary(), struct(), list(), keyword()) ::
+          {:ok, non_neg_integer()} | {:error, term()}
+  def write_vcf(path, header, records, opts \\ [])
+      when is_binary(path) and is_struct(header) and is_list(records) do
+    compression = compression_string(path, opts)
+    nif_call(fn -> Native.write_vcf(path, header, records, compression) end)
+  end
+
+  @doc """
+  Write `%Cyanea.Native.BedRecord{}` structs to a BED file.
+
+  Output is BED3 unless some record has a name, score, or strand, in which
+  case it is BED6.
+  """
+  @spec write_bed(binary(), list(), keyword()) :: {:ok, non_neg_integer()} | {:error, term()}
+  def write_bed(path, records, opts \\ []) when is_binary(path) and is_list(records) do
+    compression = compression_string(path, opts)
+    nif_call(fn -> Native.write_bed(path, records, compression) end)
+  end
+
+  @doc "Write `%Cyanea.Native.GffGene{}` structs as GFF3 `gene` features."
+  @spec write_gff3(binary(), list(), keyword()) :: {:ok, non_neg_integer()} | {:error, term()}
+  def write_gff3(path, genes, opts \\ []) when is_binary(path) and is_list(genes) do
+    compression = compression_string(path, opts)
+    nif_call(fn -> Native.write_gff3(path, genes, compression) end)
+  end
+
+  @doc """
+  Write `%Cyanea.Native.SamRecord{}` structs to a SAM file.
+
+  ## Options
+
+    * `:header` - raw `@HD`/`@SQ`/`@RG`/... header text (default: a minimal
+      `@HD` line)
+    * `:compression` - see above
+
+  """
+  @spec write_sam(binary(), list(), keyword()) :: {:ok, non_neg_integer()} | {:error, term()}
+  def write_sam(path, records, opts \\ []) when is_binary(path) and is_list(records) do
+    header = Keyword.get(opts, :header, "")
+    compression = compression_string(path, opts)
+    nif_call(fn -> Native.write_sam(path, header, records, compression) end)
+  end
+
+  # ===========================================================================
+  # Indexed region queries (BAI / CSI / tabix)
+  # ===========================================================================
+
+  @typedoc "A region as `\"chr1:10000-20000\"` or `{chrom, start, end}` (1-based, inclusive)."
+  @type region :: binary() | {binary(), pos_integer(), pos_integer()}
+
+  @doc """
+  Open a BAM file together with its `.bai` or `.csi` index.
+
+  The index is looked up as `<path>.bai`, `<path>.csi`, or with the `.bam`
+  extension replaced. Use `index_bam/2` to create one.
+  """
+  @spec open_indexed_bam(binary()) :: {:ok, reference()} | {:error, term()}
+  def open_indexed_bam(path) when is_binary(path),
+    do: nif_call(fn -> Native.bam_index_open(path) end)
+
+  @doc "Fetch the alignments overlapping a region from an indexed BAM."
+  @spec fetch_bam(reference(), region()) :: {:ok, list()} | {:error, term()}
+  def fetch_bam(bam, region) when is_reference(bam),
+    do: nif_call(fn -> Native.bam_fetch(bam, region_string(region)) end)
+
+  @doc """
+  Open a bgzipped, coordinate-sorted VCF, BED or GFF file together with its
+  `.tbi` or `.csi` index.
+  """
+  @spec open_tabix(binary()) :: {:ok, reference()} | {:error, term()}
+  def open_tabix(path) when is_binary(path),
+    do: nif_call(fn -> Native.tabix_open(path) end)
+
+  @doc "Fetch the VCF records overlapping a region from a tabix handle."
+  @spec fetch_vcf(reference(), region()) :: {:ok, list()} | {:error, term()}
+  def fetch_vcf(tabix, region) when is_reference(tabix),
+    do: nif_call(fn -> Native.vcf_fetch(tabix, region_string(region)) end)
+
+  @doc "Fetch the BED records overlapping a region from a tabix handle."
+  @spec fetch_bed(reference(), region()) :: {:ok, list()} | {:error, term()}
+  def fetch_bed(tabix, region) when is_reference(tabix),
+    do: nif_call(fn -> Native.bed_fetch(tabix, region_string(region)) end)
+
+  @doc "Fetch the raw data lines overlapping a region from a tabix handle."
+  @spec fetch_lines(reference(), region()) :: {:ok, [binary()]} | {:error, term()}
+  def fetch_lines(tabix, region) when is_reference(tabix),
+    do: nif_call(fn -> Native.tabix_fetch_lines(tabix, region_string(region)) end)
+
+  @doc """
+  Build an index for a coordinate-sorted BAM. Returns the index path.
+
+  ## Options
+
+    * `:format` - `:bai` or `:csi` (default: `:bai`; use `:csi` for
+      references longer than 512 Mb)
+
+  """
+  @spec index_bam(binary(), keyword()) :: {:ok, binary()} | {:error, term()}
+  def index_bam(path, opts \\ []) when is_binary(path) do
+    format = opts |> Keyword.get(:format, :bai) |> to_string()
+    nif_call(fn -> Native.bam_index_build(path, format) end)
+  end
+
+  @doc """
+  Build a tabix index for a bgzipped, coordinate-sorted text file. Returns
+  the index path.
+
+  ## Options
+
+    * `:preset` - column layout, `:vcf`, `:bed`, or `:gff` (default: `:vcf`)
+    * `:format` - `:tbi` or `:csi` (default: `:tbi`)
+
+  """
+  @spec index_tabix(binary(), keyword()) :: {:ok, binary()} | {:error, term()}
+  def index_tabix(path, opts \\ []) when is_binary(path) do
+    preset = opts |> Keyword.get(:preset, :vcf) |> to_string()
+    format = opts |> Keyword.get(:format, :tbi) |> to_string()
+    nif_call(fn -> Native.tabix_index_build(path, preset, format) end)
+  end
+
+  defp region_string(region) when is_binary(region), do: region
+
+  defp region_string({chrom, start, stop})
+       when is_binary(chrom) and is_integer(start) and is_integer(stop),
+       do: "#{chrom}:#{start}-#{stop}"
+
   # ===========================================================================
   # Parquet
   # ===========================================================================
@@ -1,8 +1,15 @@
 defmodule Cyanea.GPU do
-  @moduledoc "GPU-accelerated compute operations."
+  @moduledoc """
+  GPU-accelerated compute operations.
+
+  Matrix operands may be flat row-major lists, lists of rows or binary
+  `%Cyanea.Native.Matrix{}` values; a binary operand must match the given
+  dimensions, and a binary first operand makes the result binary too.
+  """
 
   import Cyanea.NifHelper
   alias Cyanea.Native
+  alias Cyanea.Native.Matrix
 
   @doc "Get GPU backend info (available backends, current selection)."
   @spec info() :: {:ok, struct()} | {:error, term()}
@@ -16,27 +23,27 @@ defmodule Cyanea.GPU do
     * `:metric` - `:euclidean` (default), `:manhattan`, or `:cosine`
 
   """
-  @spec distances(list(), integer(), integer(), keyword()) :: {:ok, list()} | {:error, term()}
+  @spec distances(Matrix.input(), integer(), integer(), keyword()) :: {:ok, Matrix.output()} | {:error, term()}
   def distances(data, n, dim, opts \\ [])
-      when is_list(data) and is_integer(n) and is_integer(dim) do
+      when is_matrix(data) and is_integer(n) and is_integer(dim) do
     metric = metric_string(Keyword.get(opts, :metric, :euclidean))
     nif_call(fn -> Native.gpu_pairwise_distances(data, n, dim, metric) end)
   end
 
   @doc "Matrix multiplication on GPU (a: m*k, b: k*n -> result: m*n)."
-  @spec matrix_multiply(list(), list(), integer(), integer(), integer()) :: {:ok, list()} | {:error, term()}
+  @spec matrix_multiply(Matrix.input(), Matrix.input(), integer(), integer(), integer()) :: {:ok, Matrix.output()} | {:error, term()}
   def matrix_multiply(a, b, m, k, n)
-      when is_list(a) and is_list(b) and is_integer(m) and is_integer(k) and is_integer(n),
+      when is_matrix(a) and is_matrix(b) and is_integer(m) and is_integer(k) and is_integer(n),
       do: nif_call(fn -> Native.gpu_matrix_multiply(a, b, m, k, n) end)
 
   @doc "Sum reduction on GPU."
-  @spec reduce_sum(list()) :: {:ok, float()} | {:error, term()}
-  def reduce_sum(data) when is_list(data),
+  @spec reduce_sum(Matrix.input()) :: {:ok, float()} | {:error, term()}
+  def reduce_sum(data) when is_matrix(data),
     do: nif_call(fn -> Native.gpu_reduce_sum(data) end)
 
   @doc "Batch z-score normalization on GPU (per-row)."
-  @spec batch_z_score(list(), integer(), integer()) :: {:ok, list()} | {:error, term()}
+  @spec batch_z_score(Matrix.input(), integer(), integer()) :: {:ok, Matrix.output()} | {:error, term()}
   def batch_z_score(data, n_rows, n_cols)
-      when is_list(data) and is_integer(n_rows) and is_integer(n_cols),
+      when is_matrix(data) and is_integer(n_rows) and is_integer(n_cols),
       do: nif_call(fn -> Native.gpu_batch_z_score(data, n_rows, n_cols) end)
 end
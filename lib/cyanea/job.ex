@@ -0,0 +1,75 @@
+defmodule Cyanea.Job do
+  @moduledoc """
+  Cancellation and progress for long-running native computations.
+
+  `Cyanea.Align.msa/2`, `Cyanea.Align.msa_report/2`, `Cyanea.ML.tsne/3`,
+  `Cyanea.ML.umap/3`, `Cyanea.ML.fit_forest/4`, `Cyanea.Phylo.build_nj/3`
+  and `Cyanea.Phylo.bootstrap/3` accept `:cancel` and `:progress` options:
+
+      {:ok, token} = Cyanea.Job.cancel_token()
+      ref = make_ref()
+      parent = self()
+
+      task =
+        Task.async(fn ->
+          Cyanea.Phylo.bootstrap(seqs, tree, cancel: token, progress: {parent, ref})
+        end)
+
+      # elsewhere, e.g. from a "Stop" button
+      Cyanea.Job.cancel(token)
+
+  The progress pid receives `{:progress, ref, fraction}` messages with
+  `fraction` in `0.0..1.0`. A cancelled job, or one whose calling process
+  exits, returns `{:error, %Cyanea.Native.Error{kind: :cancelled}}` and
+  frees its dirty scheduler right away.
+
+  The same functions take `async: true` to return `{:ok, ref}` immediately
+  and run on a bounded native pool (`:align`, `:ml` or `:phylo`) instead of
+  a dirty scheduler, so one user's large batch cannot starve the others.
+  The caller receives `{:cyanea_result, ref, result}` when the job ends;
+  `await/2` waits for it. `pool_stats/1` reports queue depth and
+  `set_pool_concurrency/2` changes how many jobs a pool runs at once.
+  """
+
+  import Cyanea.NifHelper
+  alias Cyanea.Native
+
+  @doc "Create a cancel token to pass as the `:cancel` option."
+  @spec cancel_token() :: {:ok, reference()} | {:error, term()}
+  def cancel_token, do: nif_call(fn -> Native.cancel_token_new() end)
+
+  @doc "Cancel every job running with `token`. Returns `{:ok, false}` if already cancelled."
+  @spec cancel(reference()) :: {:ok, boolean()} | {:error, term()}
+  def cancel(token) when is_reference(token),
+    do: nif_call(fn -> Native.cancel_token_cancel(token) end)
+
+  @doc "Whether `token` has been cancelled."
+  @spec cancelled?(reference()) :: {:ok, boolean()} | {:error, term()}
+  def cancelled?(token) when is_reference(token),
+    do: nif_call(fn -> Native.cancel_token_cancelled(token) end)
+
+  @doc """
+  Wait for the result of an async job started by the calling process.
+  Returns the job's `{:ok, value}` or `{:error, reason}`, or
+  `{:error, :timeout}` if it does not finish in time.
+  """
+  @spec await(reference(), timeout()) :: {:ok, term()} | {:error, term()}
+  def await(ref, timeout \\ :infinity) when is_reference(ref) do
+    receive do
+      {:cyanea_result, ^ref, result} -> result
+    after
+      timeout -> {:error, :timeout}
+    end
+  end
+
+  @doc "Concurrency limit, queued and running jobs of a pool. Returns `{:ok, %AsyncPoolStats{}}`."
+  @spec pool_stats(atom() | binary()) :: {:ok, struct()} | {:error, term()}
+  def pool_stats(pool) when is_atom(pool) or is_binary(pool),
+    do: nif_call(fn -> Native.async_pool_stats(pool_string(pool)) end)
+
+  @doc "Set how many jobs a pool runs at once, for jobs submitted from now on."
+  @spec set_pool_concurrency(atom() | binary(), pos_integer()) :: {:ok, struct()} | {:error, term()}
+  def set_pool_concurrency(pool, max_concurrency)
+      when (is_atom(pool) or is_binary(pool)) and is_integer(max_concurrency) and max_concurrency > 0,
+      do: nif_call(fn -> Native.async_pool_set_concurrency(pool_string(pool), max_concurrency) end)
+end
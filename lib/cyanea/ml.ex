@@ -1,8 +1,19 @@
 defmodule Cyanea.ML do
-  @moduledoc "Machine learning: clustering, dimensionality reduction, classification."
+  @moduledoc """
+  Machine learning: clustering, dimensionality reduction, classification.
+
+  Functions taking a data matrix accept a flat row-major list of floats
+  (with `n_features` per row), a list of rows, or a binary
+  `%Cyanea.Native.Matrix{}` (see `Cyanea.Native.Matrix.new/3`). Binary
+  input avoids building a list term per element and is the fastest path
+  for large matrices; matrix-shaped results (centroids, embeddings,
+  components, distances, predictions) come back in the same form as the
+  input, so a binary matrix in gives binary matrices out.
+  """
 
   import Cyanea.NifHelper
   alias Cyanea.Native
+  alias Cyanea.Native.Matrix
 
   # ===========================================================================
   # Clustering
@@ -11,7 +22,8 @@ defmodule Cyanea.ML do
   @doc """
   K-means clustering.
 
-  `data` is a flat list of floats (row-major), `n_features` per row.
+  `data` is a flat list of floats (row-major) with `n_features` per row, a
+  list of rows, or a `%Cyanea.Native.Matrix{}`.
 
   ## Options
 
@@ -19,9 +31,9 @@ defmodule Cyanea.ML do
     * `:seed` - random seed (default: 42)
 
   """
-  @spec kmeans(list(), integer(), integer(), keyword()) :: {:ok, struct()} | {:error, term()}
+  @spec kmeans(Matrix.input(), integer(), integer(), keyword()) :: {:ok, struct()} | {:error, term()}
   def kmeans(data, n_features, k, opts \\ [])
-      when is_list(data) and is_integer(n_features) and is_integer(k) do
+      when is_matrix(data) and is_integer(n_features) and is_integer(k) do
     max_iter = Keyword.get(opts, :max_iter, 100)
     seed = Keyword.get(opts, :seed, 42)
     nif_call(fn -> Native.kmeans(data, n_features, k, max_iter, seed) end)
@@ -35,9 +47,9 @@ defmodule Cyanea.ML do
     * `:metric` - `:euclidean` (default), `:manhattan`, or `:cosine`
 
   """
-  @spec dbscan(list(), integer(), number(), integer(), keyword()) :: {:ok, struct()} | {:error, term()}
+  @spec dbscan(Matrix.input(), integer(), number(), integer(), keyword()) :: {:ok, struct()} | {:error, term()}
   def dbscan(data, n_features, eps, min_samples, opts \\ [])
-      when is_list(data) and is_integer(n_features)
+      when is_matrix(data) and is_integer(n_features)
       and is_number(eps) and is_integer(min_samples) do
     metric = metric_string(Keyword.get(opts, :metric, :euclidean))
     nif_call(fn -> Native.dbscan(data, n_features, eps, min_samples, metric) end)
@@ -50,14 +62,26 @@ defmodule Cyanea.ML do
 
     * `:linkage` - `:average` (default), `:single`, `:complete`, or `:ward`
     * `:metric` - `:euclidean` (default), `:manhattan`, or `:cosine`
+    * `:async` - when `true`, return `{:ok, ref}` at once and run on the
+      native job pool; the result arrives as `{:cyanea_result, ref, result}`
+      (see `Cyanea.Job.await/2`)
+    * `:cancel` - cancel token from `Cyanea.Job.cancel_token/0` (async only)
 
   """
-  @spec hierarchical(list(), integer(), integer(), keyword()) :: {:ok, struct()} | {:error, term()}
+  @spec hierarchical(Matrix.input(), integer(), integer(), keyword()) ::
+          {:ok, struct() | reference()} | {:error, term()}
   def hierarchical(data, n_features, k, opts \\ [])
-      when is_list(data) and is_integer(n_features) and is_integer(k) do
+      when is_matrix(data) and is_integer(n_features) and is_integer(k) do
     linkage = linkage_string(Keyword.get(opts, :linkage, :average))
     metric = metric_string(Keyword.get(opts, :metric, :euclidean))
-    nif_call(fn -> Native.hierarchical_cluster(data, n_features, k, linkage, metric) end)
+
+    if Keyword.get(opts, :async, false) do
+      nif_call(fn ->
+        Native.hierarchical_cluster_async(data, n_features, k, linkage, metric, Keyword.get(opts, :cancel))
+      end)
+    else
+      nif_call(fn -> Native.hierarchical_cluster(data, n_features, k, linkage, metric) end)
+    end
   end
 
   # ===========================================================================
@@ -65,9 +89,9 @@ defmodule Cyanea.ML do
   # ===========================================================================
 
   @doc "Principal component analysis."
-  @spec pca(list(), integer(), integer()) :: {:ok, struct()} | {:error, term()}
+  @spec pca(Matrix.input(), integer(), integer()) :: {:ok, struct()} | {:error, term()}
   def pca(data, n_features, n_components)
-      when is_list(data) and is_integer(n_features) and is_integer(n_components),
+      when is_matrix(data) and is_integer(n_features) and is_integer(n_components),
       do: nif_call(fn -> Native.pca(data, n_features, n_components) end)
 
   @doc """
@@ -78,15 +102,26 @@ defmodule Cyanea.ML do
     * `:n_components` - output dimensions (default: 2)
     * `:perplexity` - perplexity parameter (default: 30.0)
     * `:n_iter` - number of iterations (default: 1000)
+    * `:cancel` - cancel token from `Cyanea.Job.cancel_token/0`
+    * `:progress` - pid, or `{pid, job_ref}`, receiving `{:progress, job_ref, fraction}`
+    * `:async` - when `true`, return `{:ok, ref}` at once and run on the
+      native job pool; the result arrives as `{:cyanea_result, ref, result}`
+      (see `Cyanea.Job.await/2`)
 
   """
-  @spec tsne(list(), integer(), keyword()) :: {:ok, struct()} | {:error, term()}
+  @spec tsne(Matrix.input(), integer(), keyword()) :: {:ok, struct() | reference()} | {:error, term()}
   def tsne(data, n_features, opts \\ [])
-      when is_list(data) and is_integer(n_features) do
+      when is_matrix(data) and is_integer(n_features) do
     n_components = Keyword.get(opts, :n_components, 2)
     perplexity = Keyword.get(opts, :perplexity, 30.0)
     n_iter = Keyword.get(opts, :n_iter, 1000)
-    nif_call(fn -> Native.tsne(data, n_features, n_components, perplexity, n_iter) end)
+    {cancel, progress} = job_args(opts)
+
+    if Keyword.get(opts, :async, false) do
+      nif_call(fn -> Native.tsne_async(data, n_features, n_components, perplexity, n_iter, cancel) end)
+    else
+      nif_call(fn -> Native.tsne(data, n_features, n_components, perplexity, n_iter, cancel, progress) end)
+    end
   end
 
   @doc """
@@ -100,18 +135,33 @@ defmodule Cyanea.ML do
     * `:n_epochs` - number of epochs (default: 200)
     * `:metric` - `:euclidean` (default), `:manhattan`, or `:cosine`
     * `:seed` - random seed (default: 42)
+    * `:cancel` - cancel token from `Cyanea.Job.cancel_token/0`
+    * `:progress` - pid, or `{pid, job_ref}`, receiving `{:progress, job_ref, fraction}`
+    * `:async` - when `true`, return `{:ok, ref}` at once and run on the
+      native job pool; the result arrives as `{:cyanea_result, ref, result}`
+      (see `Cyanea.Job.await/2`)
 
   """
-  @spec umap(list(), integer(), keyword()) :: {:ok, struct()} | {:error, term()}
+  @spec umap(Matrix.input(), integer(), keyword()) :: {:ok, struct() | reference()} | {:error, term()}
   def umap(data, n_features, opts \\ [])
-      when is_list(data) and is_integer(n_features) do
+      when is_matrix(data) and is_integer(n_features) do
     n_components = Keyword.get(opts, :n_components, 2)
     n_neighbors = Keyword.get(opts, :n_neighbors, 15)
     min_dist = Keyword.get(opts, :min_dist, 0.1)
     n_epochs = Keyword.get(opts, :n_epochs, 200)
     metric = metric_string(Keyword.get(opts, :metric, :euclidean))
     seed = Keyword.get(opts, :seed, 42)
-    nif_call(fn -> Native.umap(data, n_features, n_components, n_neighbors, min_dist, n_epochs, metric, seed) end)
+    {cancel, progress} = job_args(opts)
+
+    if Keyword.get(opts, :async, false) do
+      nif_call(fn ->
+        Native.umap_async(data, n_features, n_components, n_neighbors, min_dist, n_epochs, metric, seed, cancel)
+      end)
+    else
+      nif_call(fn ->
+        Native.umap(data, n_features, n_components, n_neighbors, min_dist, n_epochs, metric, seed, cancel, progress)
+      end)
+    end
   end
 
   # ===========================================================================
@@ -156,9 +206,9 @@ defmodule Cyanea.ML do
     * `:metric` - `:euclidean` (default), `:manhattan`, or `:cosine`
 
   """
-  @spec distances(list(), integer(), keyword()) :: {:ok, list()} | {:error, term()}
+  @spec distances(Matrix.input(), integer(), keyword()) :: {:ok, Matrix.output()} | {:error, term()}
   def distances(data, n_features, opts \\ [])
-      when is_list(data) and is_integer(n_features) do
+      when is_matrix(data) and is_integer(n_features) do
     metric = metric_string(Keyword.get(opts, :metric, :euclidean))
     nif_call(fn -> Native.pairwise_distances(data, n_features, metric) end)
   end
@@ -175,50 +225,87 @@ defmodule Cyanea.ML do
     * `:metric` - `:euclidean` (default), `:manhattan`, or `:cosine`
 
   """
-  @spec knn(list(), integer(), integer(), list(), list(), keyword()) :: {:ok, integer()} | {:error, term()}
+  @spec knn(Matrix.input(), integer(), integer(), list(), list(), keyword()) :: {:ok, integer()} | {:error, term()}
   def knn(data, n_features, k, labels, query, opts \\ [])
-      when is_list(data) and is_integer(n_features) and is_integer(k)
+      when is_matrix(data) and is_integer(n_features) and is_integer(k)
       and is_list(labels) and is_list(query) do
     metric = metric_string(Keyword.get(opts, :metric, :euclidean))
     nif_call(fn -> Native.knn_classify(data, n_features, k, metric, labels, query) end)
   end
 
   @doc "Fit a linear regression model. Returns `{:ok, %LinearRegressionResult{}}`."
-  @spec fit_linear(list(), integer(), list()) :: {:ok, struct()} | {:error, term()}
+  @spec fit_linear(Matrix.input(), integer(), list()) :: {:ok, struct()} | {:error, term()}
   def fit_linear(data, n_features, targets)
-      when is_list(data) and is_integer(n_features) and is_list(targets),
+      when is_matrix(data) and is_integer(n_features) and is_list(targets),
       do: nif_call(fn -> Native.linear_regression_fit(data, n_features, targets) end)
 
   @doc "Predict using linear regression weights and bias."
-  @spec predict_linear(list(), number(), list(), integer()) :: {:ok, list()} | {:error, term()}
+  @spec predict_linear(list(), number(), Matrix.input(), integer()) :: {:ok, Matrix.output()} | {:error, term()}
   def predict_linear(weights, bias, queries, n_features)
-      when is_list(weights) and is_number(bias) and is_list(queries) and is_integer(n_features),
+      when is_list(weights) and is_number(bias) and is_matrix(queries) and is_integer(n_features),
       do: nif_call(fn -> Native.linear_regression_predict(weights, bias, queries, n_features) end)
 
   @doc """
   Fit a random forest classifier.
 
+  Returns an opaque model reference held by the NIF. Use it with
+  `predict_forest/3`, `predict_forest_proba/3`, `forest_importances/1` and
+  `forest_info/1`. Out-of-bag error and permutation importances are
+  computed during the fit; the model does not keep the training data.
+
   ## Options
 
     * `:n_trees` - number of trees (default: 10)
     * `:max_depth` - maximum tree depth (default: 5)
     * `:seed` - random seed (default: 42)
+    * `:cancel` - cancel token from `Cyanea.Job.cancel_token/0`
+    * `:progress` - pid, or `{pid, job_ref}`, receiving `{:progress, job_ref, fraction}`
+      as trees are fitted and scored
+    * `:async` - when `true`, return `{:ok, ref}` at once and run on the
+      native job pool; the result arrives as `{:cyanea_result, ref, result}`
+      (see `Cyanea.Job.await/2`)
 
   """
-  @spec fit_forest(list(), integer(), list(), keyword()) :: {:ok, binary()} | {:error, term()}
+  @spec fit_forest(Matrix.input(), integer(), list(), keyword()) :: {:ok, reference()} | {:error, term()}
   def fit_forest(data, n_features, labels, opts \\ [])
-      when is_list(data) and is_integer(n_features) and is_list(labels) do
+      when is_matrix(data) and is_integer(n_features) and is_list(labels) do
     n_trees = Keyword.get(opts, :n_trees, 10)
     max_depth = Keyword.get(opts, :max_depth, 5)
     seed = Keyword.get(opts, :seed, 42)
-    nif_call(fn -> Native.random_forest_fit(data, n_features, labels, n_trees, max_depth, seed) end)
+    {cancel, progress} = job_args(opts)
+
+    if Keyword.get(opts, :async, false) do
+      nif_call(fn ->
+        Native.random_forest_fit_async(data, n_features, labels, n_trees, max_depth, seed, cancel)
+      end)
+    else
+      nif_call(fn ->
+        Native.random_forest_fit(data, n_features, labels, n_trees, max_depth, seed, cancel, progress)
+      end)
+    end
   end
 
-  @doc "Predict class label using a serialized random forest model."
-  @spec predict_forest(binary(), list(), integer()) :: {:ok, integer()} | {:error, term()}
-  def predict_forest(model, sample, n_features)
-      when is_binary(model) and is_list(sample) and is_integer(n_features),
-      do: nif_call(fn -> Native.random_forest_predict(model, sample, n_features) end)
+  @doc "Predict class labels for a batch of samples with a fitted forest."
+  @spec predict_forest(reference(), Matrix.input(), integer()) :: {:ok, [integer()]} | {:error, term()}
+  def predict_forest(model, samples, n_features)
+      when is_reference(model) and is_matrix(samples) and is_integer(n_features),
+      do: nif_call(fn -> Native.random_forest_predict(model, samples, n_features) end)
+
+  @doc "Predict per-class probabilities for a batch of samples. Returns one row per sample."
+  @spec predict_forest_proba(reference(), Matrix.input(), integer()) :: {:ok, Matrix.output()} | {:error, term()}
+  def predict_forest_proba(model, samples, n_features)
+      when is_reference(model) and is_matrix(samples) and is_integer(n_features),
+      do: nif_call(fn -> Native.random_forest_predict_proba(model, samples, n_features) end)
+
+  @doc "Per-feature permutation importances of a fitted forest."
+  @spec forest_importances(reference()) :: {:ok, [float()]} | {:error, term()}
+  def forest_importances(model) when is_reference(model),
+    do: nif_call(fn -> Native.random_forest_feature_importances(model) end)
+
+  @doc "Info about a fitted forest, including out-of-bag error. Returns `{:ok, %RandomForestInfo{}}`."
+  @spec forest_info(reference()) :: {:ok, struct()} | {:error, term()}
+  def forest_info(model) when is_reference(model),
+    do: nif_call(fn -> Native.random_forest_info(model) end)
 
   # ===========================================================================
   # HMM
@@ -243,11 +330,11 @@ defmodule Cyanea.ML do
   # ===========================================================================
 
   @doc "Normalize data. Accepts `:min_max` or `:z_score`."
-  @spec normalize(list(), :min_max | :z_score) :: {:ok, list()} | {:error, term()}
-  def normalize(data, :min_max) when is_list(data),
+  @spec normalize(Matrix.input(), :min_max | :z_score) :: {:ok, Matrix.output()} | {:error, term()}
+  def normalize(data, :min_max) when is_matrix(data),
     do: nif_call(fn -> Native.normalize_min_max(data) end)
 
-  def normalize(data, :z_score) when is_list(data),
+  def normalize(data, :z_score) when is_matrix(data),
     do: nif_call(fn -> Native.normalize_z_score(data) end)
 
   @doc """
@@ -258,9 +345,9 @@ defmodule Cyanea.ML do
     * `:metric` - `:euclidean` (default), `:manhattan`, or `:cosine`
 
   """
-  @spec silhouette(list(), integer(), list(), keyword()) :: {:ok, float()} | {:error, term()}
+  @spec silhouette(Matrix.input(), integer(), list(), keyword()) :: {:ok, float()} | {:error, term()}
   def silhouette(data, n_features, labels, opts \\ [])
-      when is_list(data) and is_integer(n_features) and is_list(labels) do
+      when is_matrix(data) and is_integer(n_features) and is_list(labels) do
     metric = metric_string(Keyword.get(opts, :metric, :euclidean))
     nif_call(fn -> Native.silhouette_score(data, n_features, labels, metric) end)
   end
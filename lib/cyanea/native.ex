@@ -9,7 +9,7 @@ defmodule Cyanea.Native do
 
   - **Hashing** — SHA256 checksums (cyanea-core)
   - **Compression** — zstd/gzip compress/decompress (cyanea-core)
-  - **Sequences** — Validation, operations, k-mers, FASTA/FASTQ parsing, pattern matching, ORFs, MinHash (cyanea-seq)
+  - **Sequences** — Validation, operations, k-mers, FASTA/FASTQ parsing, pattern matching, FM-index resources, ORFs, MinHash (cyanea-seq)
   - **File Formats** — CSV, VCF, BED, GFF3, SAM, BAM parsing + stats (cyanea-io)
   - **Alignment** — Pairwise DNA/protein, batch, banded, MSA, POA consensus (cyanea-align)
   - **Statistics** — Descriptive, correlation, hypothesis testing, p-value correction, distributions, effect sizes, Bayesian (cyanea-stats)
@@ -19,6 +19,28 @@ defmodule Cyanea.Native do
   - **Structures** — PDB/mmCIF parsing, secondary structure, RMSD, Kabsch, contact maps, Ramachandran, B-factor (cyanea-struct)
   - **Phylogenetics** — Newick/NEXUS I/O, tree distances, tree building, bootstrap, ancestral reconstruction (cyanea-phylo)
   - **GPU** — Backend detection, pairwise distances, matrix multiply, reduction, z-score (cyanea-gpu)
+  - **Jobs** — Cancel tokens and progress messages for long-running NIFs
+
+  ## Cancellation and progress
+
+  Long-running NIFs (`progressive_msa`, `progressive_msa_with_scheme`,
+  `msa_add_sequences`, `msa_merge`, `tsne`, `umap`, `random_forest_fit`,
+  `build_nj`, `bootstrap_support`) take two trailing arguments: a cancel
+  token from `cancel_token_new/0` (or `nil`) and a `{pid, job_ref}` tuple
+  (or `nil`). The pid receives `{:progress, job_ref, fraction}` messages
+  while the job runs, starting at `0.0` and ending at `1.0`. Cancelling
+  the token, or the calling process exiting, makes the NIF return
+  `{:error, %Cyanea.Native.Error{kind: :cancelled}}`.
+
+  ## Async variants
+
+  The `*_async` NIFs decode their arguments on a dirty scheduler, queue
+  the work on a bounded native thread pool (`"align"`, `"ml"` or `"phylo"`)
+  and return a reference immediately. The caller later receives
+  `{:cyanea_result, ref, result}`, where `result` is `{:ok, value}` or
+  `{:error, %Cyanea.Native.Error{}}`. Their last argument is an optional
+  cancel token; cancelling it while the job runs delivers a `:cancelled`
+  error at once.
   """
 
   use Rustler,
@@ -55,6 +77,30 @@ defmodule Cyanea.Native do
   @doc "Get statistics from a FASTA/FASTQ file"
   def fasta_stats(_path), do: :erlang.nif_error(:nif_not_loaded)
 
+  # --- FASTA ----------------------------------------------------------------
+
+  @doc "Parse a FASTA file (plain or gzipped) and return all records"
+  def parse_fasta(_path), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Build a samtools-compatible .fai index next to an uncompressed FASTA. Returns the index path"
+  def fasta_index_build(_path), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Load the .fai entries of a FASTA file (scans the file when no .fai exists)"
+  def fasta_index_load(_path), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Read bases start..end (1-based, inclusive) of one sequence from an uncompressed FASTA path"
+  def fasta_fetch(_path, _chrom, _start, _end), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Open an uncompressed FASTA with its .fai entries (scans the file once when no .fai exists)"
+  def fasta_open(_path), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "fasta_fetch/4 on a handle opened with fasta_open/1"
+  def fasta_fetch_handle(_fasta, _chrom, _start, _end), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Write FASTA records, wrapping lines at line_width (0 = no wrap). Compression: none, gzip, bgzip"
+  def write_fasta(_path, _records, _line_width, _compression),
+    do: :erlang.nif_error(:nif_not_loaded)
+
   # --- Sequence validation --------------------------------------------------
 
   @doc "Validate and uppercase a DNA sequence (IUPAC alphabet)"
@@ -77,6 +123,35 @@ defmodule Cyanea.Native do
   @doc "Calculate GC content of a DNA sequence (fraction 0.0–1.0)"
   def dna_gc_content(_data), do: :erlang.nif_error(:nif_not_loaded)
 
+  # --- Alphabet-aware operations ----------------------------------------------
+
+  @doc "GC content of a nucleotide sequence. Alphabet: dna, rna, iupac (ambiguity codes weighted, N skipped)"
+  def sequence_gc_content(_data, _alphabet), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Symbol counts of a sequence as {symbol, count} pairs. Alphabet: dna, rna, protein, iupac"
+  def sequence_composition(_data, _alphabet), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "List the supported NCBI genetic codes as {id, name} pairs"
+  def genetic_codes(), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Translate DNA/RNA (IUPAC allowed) with an NCBI genetic code in frames 1..3, -1..-3. Returns {frame, protein} pairs"
+  def sequence_translate(_data, _table, _frames), do: :erlang.nif_error(:nif_not_loaded)
+
+  # --- Oligos & primer design -----------------------------------------------
+
+  @doc "Nearest-neighbor melting temperature (°C) of an oligo under %OligoConditions{}"
+  def oligo_tm(_data, _conditions), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Tm, GC, duplex ΔH/ΔS/ΔG and hairpin/self-dimer ΔG of an oligo. Returns %OligoThermo{}"
+  def oligo_thermo(_data, _conditions), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "ΔG (kcal/mol, 37 °C) of the most stable dimer two oligos form"
+  def oligo_dimer_dg(_a, _b), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Design primer pairs flanking [target_start, target_end) per %PrimerDesignOptions{}. Returns ranked [%PrimerDesign{}]"
+  def primer_design(_template, _target_start, _target_end, _options),
+    do: :erlang.nif_error(:nif_not_loaded)
+
   # --- RNA operations -------------------------------------------------------
 
   @doc "Translate an RNA sequence to protein (NCBI Table 1)"
@@ -84,8 +159,14 @@ defmodule Cyanea.Native do
 
   # --- K-mers ---------------------------------------------------------------
 
-  @doc "Extract k-mers from a DNA sequence as a list of binaries"
-  def sequence_kmers(_data, _k), do: :erlang.nif_error(:nif_not_loaded)
+  @doc "Extract k-mers from a sequence as a list of binaries. Alphabet: dna, rna, protein, iupac"
+  def sequence_kmers(_data, _k, _alphabet), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Count canonical k-mers (k <= 31) of a DNA sequence. Returns %KmerCounts{} with top_n k-mers and spectrum"
+  def kmer_count_sequence(_data, _k, _top_n), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Count canonical k-mers (k <= 31) across a FASTA/FASTQ file (plain or gzipped). Returns %KmerCounts{}"
+  def kmer_count_file(_path, _k, _top_n), do: :erlang.nif_error(:nif_not_loaded)
 
   # --- FASTQ ----------------------------------------------------------------
 
@@ -95,6 +176,19 @@ defmodule Cyanea.Native do
   @doc "Get streaming statistics from a FASTQ file"
   def fastq_stats(_path), do: :erlang.nif_error(:nif_not_loaded)
 
+  @doc "Write FASTQ records (quality as Phred scores, written as Phred+33). Compression: none, gzip, bgzip"
+  def write_fastq(_path, _records, _compression), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "FastQC-style report of a FASTQ file in one streaming pass. Returns %FastqReport{}"
+  def fastq_report(_path), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Split FASTQ (one input, or R1/R2) by sample barcodes (mode: index, inline) into output_dir. Returns %DemuxResult{}"
+  def fastq_demultiplex(_inputs, _samples, _mode, _max_mismatches, _output_dir, _compression),
+    do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Trim and filter FASTQ (one input, or R1/R2) into outputs per %TrimOptions{}. Returns %FastqTrimStats{}"
+  def fastq_trim(_inputs, _outputs, _options, _compression), do: :erlang.nif_error(:nif_not_loaded)
+
   # --- Protein --------------------------------------------------------------
 
   @doc "Calculate molecular weight of a protein sequence (Daltons)"
@@ -108,13 +202,49 @@ defmodule Cyanea.Native do
   @doc "Approximate pattern matching using Myers bit-parallel algorithm"
   def myers_search(_text, _pattern, _max_dist), do: :erlang.nif_error(:nif_not_loaded)
 
+  @doc "Search {name, sequence} patterns in every record of a FASTA file, optionally on both strands. Returns [%PatternHit{}]"
+  def pattern_search_fasta(_path, _patterns, _max_distance, _both_strands),
+    do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Search {name, sequence} patterns in a list of %FastaRecord{}, optionally on both strands. Returns [%PatternHit{}]"
+  def pattern_search_records(_records, _patterns, _max_distance, _both_strands),
+    do: :erlang.nif_error(:nif_not_loaded)
+
+  # --- Cloning --------------------------------------------------------------
+
+  @doc "List the built-in restriction enzymes as [%RestrictionEnzyme{}]"
+  def restriction_enzymes(), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Digest a sequence with named enzymes (linear or circular). Returns %RestrictionDigest{} with cuts and fragments"
+  def restriction_digest(_seq, _enzymes, _circular), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "In-silico PCR of [%PrimerPair{}] on [%FastaRecord{}] templates. Returns [%Amplicon{}]"
+  def pcr_simulate(_templates, _primers, _max_mismatches, _max_length, _circular),
+    do: :erlang.nif_error(:nif_not_loaded)
+
   # --- FM-Index (new) -------------------------------------------------------
 
-  @doc "Build an FM-index from text. Returns serialized index as binary"
+  @doc "Build an FM-index from text (case-sensitive). Returns an index resource reference"
   def fm_index_build(_text), do: :erlang.nif_error(:nif_not_loaded)
 
-  @doc "Count occurrences of pattern in FM-index"
-  def fm_index_count(_index_data, _pattern), do: :erlang.nif_error(:nif_not_loaded)
+  @doc "Count occurrences of pattern in an FM-index resource"
+  def fm_index_count(_index, _pattern), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Locate all exact occurrences of pattern. Returns sorted 0-based positions"
+  def fm_index_locate(_index, _pattern), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Locate occurrences with up to max_mismatches substitutions. Returns {position, mismatches} pairs"
+  def fm_index_search_mismatches(_index, _pattern, _max_mismatches),
+    do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Length of the text indexed by an FM-index resource"
+  def fm_index_text_length(_index), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Serialize an FM-index resource to a compact binary (zstd-compressed)"
+  def fm_index_serialize(_index), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Load an FM-index resource from a binary produced by fm_index_serialize/1"
+  def fm_index_deserialize(_data), do: :erlang.nif_error(:nif_not_loaded)
 
   # --- ORF finding (new) ----------------------------------------------------
 
@@ -123,8 +253,31 @@ defmodule Cyanea.Native do
 
   # --- MinHash (new) --------------------------------------------------------
 
-  @doc "Compute MinHash sketch of a sequence"
-  def minhash_sketch(_seq, _k, _sketch_size), do: :erlang.nif_error(:nif_not_loaded)
+  @doc "Compute an unnamed %MinHashSketch{} (seed 42) of a sequence. Alphabet: dna, rna, protein, iupac"
+  def minhash_sketch(_seq, _k, _sketch_size, _alphabet), do: :erlang.nif_error(:nif_not_loaded)
+
+  # --- MinHash sketches -----------------------------------------------------
+
+  @doc "Build a named %MinHashSketch{} (MurmurHash3, bottom-k) of a sequence"
+  def minhash_sketch_build(_seq, _name, _k, _sketch_size, _seed, _alphabet),
+    do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Build one %MinHashSketch{} from all records of a FASTA file (plain or gzipped)"
+  def minhash_sketch_fasta(_path, _k, _sketch_size, _seed, _alphabet),
+    do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Compare two sketches. Returns %MinHashDistance{} with Jaccard, containment, Mash distance, ANI, p-value"
+  def minhash_compare(_query, _reference), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Compare every query sketch with every reference sketch, keeping pairs within max_distance"
+  def minhash_compare_many(_queries, _references, _max_distance),
+    do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Save sketches to a zstd-compressed sketch file. Returns the count written"
+  def minhash_sketch_save(_path, _sketches), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Load the sketches of a sketch file written by minhash_sketch_save/2"
+  def minhash_sketch_load(_path), do: :erlang.nif_error(:nif_not_loaded)
 
   # ===========================================================================
   # cyanea-io — File Format Parsing
@@ -147,9 +300,18 @@ defmodule Cyanea.Native do
 
   # --- New file format parsers -----------------------------------------------
 
-  @doc "Parse a VCF file and return all variant records"
+  @doc "Parse a VCF file (plain or bgzipped) and return all variant records"
   def parse_vcf(_path), do: :erlang.nif_error(:nif_not_loaded)
 
+  @doc "Open a VCF file (plain or bgzipped) as a streaming reader resource"
+  def vcf_open(_path), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Return the parsed header (samples, contigs, INFO/FORMAT definitions) of a VCF reader"
+  def vcf_header(_reader), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Read up to n records from a VCF reader. Returns [] at end of file"
+  def vcf_next_batch(_reader, _n), do: :erlang.nif_error(:nif_not_loaded)
+
   @doc "Parse a BED file and return all records"
   def parse_bed(_path), do: :erlang.nif_error(:nif_not_loaded)
 
@@ -168,9 +330,52 @@ defmodule Cyanea.Native do
   @doc "Parse a BAM file and return all alignment records"
   def parse_bam(_path), do: :erlang.nif_error(:nif_not_loaded)
 
+  @doc "Decode a SAM FLAG value into named booleans (paired, duplicate, secondary, ...)"
+  def sam_flags(_flag), do: :erlang.nif_error(:nif_not_loaded)
+
   @doc "Parse a BED file and return genomic intervals"
   def parse_bed_intervals(_path), do: :erlang.nif_error(:nif_not_loaded)
 
+  # --- Writers --------------------------------------------------------------
+
+  @doc "Write a VCF file from a header and records. Compression: none, gzip, bgzip"
+  def write_vcf(_path, _header, _records, _compression), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Write a BED file (BED3 or BED6). Compression: none, gzip, bgzip"
+  def write_bed(_path, _records, _compression), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Write gene records as a GFF3 file. Compression: none, gzip, bgzip"
+  def write_gff3(_path, _genes, _compression), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Write a SAM file from raw header text and records. Compression: none, gzip, bgzip"
+  def write_sam(_path, _header, _records, _compression), do: :erlang.nif_error(:nif_not_loaded)
+
+  # --- Indexed region queries (BAI / CSI / tabix) ----------------------------
+
+  @doc "Open a BAM file with its .bai or .csi index for region queries"
+  def bam_index_open(_path), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Fetch alignments overlapping a region (\"chr1:10000-20000\", 1-based inclusive)"
+  def bam_fetch(_bam, _region), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Open a bgzipped text file (VCF, BED, GFF) with its .tbi or .csi index"
+  def tabix_open(_path), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Fetch VCF records overlapping a region from a tabix handle"
+  def vcf_fetch(_tabix, _region), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Fetch BED records overlapping a region from a tabix handle"
+  def bed_fetch(_tabix, _region), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Fetch raw data lines overlapping a region from a tabix handle"
+  def tabix_fetch_lines(_tabix, _region), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Build a .bai or .csi index for a coordinate-sorted BAM. Returns the index path"
+  def bam_index_build(_path, _format), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Build a .tbi or .csi index for a bgzipped file (preset: vcf, bed, gff). Returns the index path"
+  def tabix_index_build(_path, _preset, _format), do: :erlang.nif_error(:nif_not_loaded)
+
   # ===========================================================================
   # cyanea-align — Sequence Alignment
   # ===========================================================================
@@ -188,8 +393,60 @@ defmodule Cyanea.Native do
   @doc "Batch-align a list of {query, target} DNA pairs (runs on DirtyCpu scheduler)"
   def align_batch_dna(_pairs, _mode), do: :erlang.nif_error(:nif_not_loaded)
 
-  @doc "Progressive multiple sequence alignment. Mode: \"dna\" or \"protein\""
-  def progressive_msa(_sequences, _mode), do: :erlang.nif_error(:nif_not_loaded)
+  @doc "Built-in scoring scheme (\"blosum62\", \"blosum45\", \"blosum80\", \"pam250\", \"nuc.4.4\") with gap penalties (<= 0). Returns a scheme reference"
+  def scoring_scheme_builtin(_name, _gap_open, _gap_extend), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Load an NCBI-format substitution matrix file as a scoring scheme reference"
+  def scoring_scheme_load(_path, _gap_open, _gap_extend), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Parse an inline NCBI-format substitution matrix as a scoring scheme reference"
+  def scoring_scheme_parse(_name, _text, _gap_open, _gap_extend), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Describe a scoring scheme reference. Returns %ScoringSchemeInfo{}"
+  def scoring_scheme_info(_scheme), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Affine-gap alignment of two sequences under a scoring scheme reference"
+  def align_with_scheme(_query, _target, _mode, _scheme), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Batch-align {query, target} pairs under a scoring scheme reference (DirtyCpu)"
+  def align_batch_with_scheme(_pairs, _mode, _scheme), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Index a reference FASTA for read mapping. Returns an index reference"
+  def read_mapper_index(_reference_path), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Map one FASTQ (or two, paired-end) against a mapper index, writing \"sam\" or \"bam\" per %MapOptions{}. Returns %MapStats{}"
+  def map_reads(_index, _reads, _output, _format, _options), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Smith-Waterman search of a query against every record of a FASTA file per %DbSearchOptions{}. Returns %DbSearch{} (DirtyCpu)"
+  def search_fasta(_query, _db_path, _scheme, _options), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Progressive MSA with the default scheme for mode \"dna\" or \"protein\", UPGMA guide tree, no refinement"
+  def progressive_msa(_sequences, _mode, _cancel, _progress),
+    do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Progressive MSA under a scoring scheme with guide tree and refinement per %MsaOptions{}. Returns %MsaReport{}"
+  def progressive_msa_with_scheme(_sequences, _names, _scheme, _options, _cancel, _progress),
+    do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Align sequences to an existing alignment (rows, {name, row} pairs or %MsaFile{}) per %MsaAddOptions{}. Returns %MsaMerge{}"
+  def msa_add_sequences(_alignment, _sequences, _scheme, _options, _cancel, _progress),
+    do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Merge two alignments by profile-profile alignment, optionally banded. Returns %MsaMerge{}"
+  def msa_merge(_a, _b, _scheme, _band, _cancel, _progress), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Async batch DNA alignment on the align pool. Returns a job reference"
+  def align_batch_dna_async(_pairs, _mode, _cancel), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Async progressive MSA on the align pool. Returns a job reference"
+  def progressive_msa_async(_sequences, _mode, _cancel), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Async progressive_msa_with_scheme on the align pool. Returns a job reference"
+  def progressive_msa_with_scheme_async(_sequences, _names, _scheme, _options, _cancel),
+    do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Async POA consensus on the align pool. Returns a job reference"
+  def poa_consensus_async(_sequences, _cancel), do: :erlang.nif_error(:nif_not_loaded)
 
   # --- New alignment functions -----------------------------------------------
 
@@ -302,18 +559,18 @@ defmodule Cyanea.Native do
   def genomic_coverage(_chroms, _starts, _ends, _query_chrom),
     do: :erlang.nif_error(:nif_not_loaded)
 
-  @doc "Compute summary statistics for an expression matrix (2D list of floats)"
+  @doc "Compute summary statistics for an expression matrix (list of rows or Matrix)"
   def expression_summary(_data, _feature_names, _sample_names),
     do: :erlang.nif_error(:nif_not_loaded)
 
-  @doc "Log2-transform a matrix: log2(x + pseudocount) for all values"
+  @doc "Log2-transform a matrix: log2(x + pseudocount) for all values, keeping the input form"
   def log_transform_matrix(_data, _pseudocount), do: :erlang.nif_error(:nif_not_loaded)
 
   # ===========================================================================
   # cyanea-ml — ML Primitives
   # ===========================================================================
 
-  @doc "K-means clustering. Data is a flat list (row-major), n_features per row"
+  @doc "K-means clustering. Data is a flat row-major list, list of rows or Matrix, n_features per row"
   def kmeans(_data, _n_features, _k, _max_iter, _seed),
     do: :erlang.nif_error(:nif_not_loaded)
 
@@ -321,15 +578,31 @@ defmodule Cyanea.Native do
   def dbscan(_data, _n_features, _eps, _min_samples, _metric),
     do: :erlang.nif_error(:nif_not_loaded)
 
-  @doc "Principal component analysis on flat row-major data"
+  @doc "Principal component analysis on flat row-major, row-list or Matrix data"
   def pca(_data, _n_features, _n_components), do: :erlang.nif_error(:nif_not_loaded)
 
-  @doc "t-SNE dimensionality reduction on flat row-major data"
-  def tsne(_data, _n_features, _n_components, _perplexity, _n_iter),
+  @doc "t-SNE dimensionality reduction on flat row-major, row-list or Matrix data"
+  def tsne(_data, _n_features, _n_components, _perplexity, _n_iter, _cancel, _progress),
     do: :erlang.nif_error(:nif_not_loaded)
 
   @doc "UMAP dimensionality reduction. Metric: \"euclidean\", \"manhattan\", or \"cosine\""
-  def umap(_data, _n_features, _n_components, _n_neighbors, _min_dist, _n_epochs, _metric, _seed),
+  def umap(_data, _n_features, _n_components, _n_neighbors, _min_dist, _n_epochs, _metric, _seed, _cancel, _progress),
+    do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Async t-SNE on the ml pool. Returns a job reference"
+  def tsne_async(_data, _n_features, _n_components, _perplexity, _n_iter, _cancel),
+    do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Async UMAP on the ml pool. Returns a job reference"
+  def umap_async(_data, _n_features, _n_components, _n_neighbors, _min_dist, _n_epochs, _metric, _seed, _cancel),
+    do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Async hierarchical clustering on the ml pool. Returns a job reference"
+  def hierarchical_cluster_async(_data, _n_features, _n_clusters, _linkage, _metric, _cancel),
+    do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Async random forest fit on the ml pool. Returns a job reference"
+  def random_forest_fit_async(_data, _n_features, _labels, _n_trees, _max_depth, _seed, _cancel),
     do: :erlang.nif_error(:nif_not_loaded)
 
   @doc "Compute normalized k-mer frequency embedding for a sequence"
@@ -338,7 +611,7 @@ defmodule Cyanea.Native do
   @doc "Batch k-mer frequency embeddings for multiple sequences (DirtyCpu)"
   def batch_embed(_sequences, _k, _alphabet), do: :erlang.nif_error(:nif_not_loaded)
 
-  @doc "Compute pairwise distance matrix (condensed upper-triangle)"
+  @doc "Compute pairwise distance matrix (condensed upper-triangle; {n, 1} Matrix for Matrix input)"
   def pairwise_distances(_data, _n_features, _metric),
     do: :erlang.nif_error(:nif_not_loaded)
 
@@ -360,14 +633,24 @@ defmodule Cyanea.Native do
   def linear_regression_predict(_weights, _bias, _queries, _n_features),
     do: :erlang.nif_error(:nif_not_loaded)
 
-  @doc "Fit a random forest classifier. Returns serialized model as binary"
-  def random_forest_fit(_data, _n_features, _labels, _n_trees, _max_depth, _seed),
+  @doc "Fit a random forest classifier. Returns a model resource reference"
+  def random_forest_fit(_data, _n_features, _labels, _n_trees, _max_depth, _seed, _cancel, _progress),
+    do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Predict class labels for a batch of samples (flat row-major, rows or Matrix) with a fitted model"
+  def random_forest_predict(_model, _samples, _n_features),
     do: :erlang.nif_error(:nif_not_loaded)
 
-  @doc "Predict class label using a serialized random forest model"
-  def random_forest_predict(_model_data, _sample, _n_features),
+  @doc "Predict per-class probabilities (tree vote fractions) for a batch of samples"
+  def random_forest_predict_proba(_model, _samples, _n_features),
     do: :erlang.nif_error(:nif_not_loaded)
 
+  @doc "Per-feature permutation importances (mean decrease in out-of-bag accuracy)"
+  def random_forest_feature_importances(_model), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Fitted model info (trees, features, classes, samples, out-of-bag error)"
+  def random_forest_info(_model), do: :erlang.nif_error(:nif_not_loaded)
+
   @doc "HMM Viterbi decoding. Returns {most_likely_path, log_probability}"
   def hmm_viterbi(_n_states, _n_symbols, _initial, _transition, _emission, _observations),
     do: :erlang.nif_error(:nif_not_loaded)
@@ -376,17 +659,17 @@ defmodule Cyanea.Native do
   def hmm_forward(_n_states, _n_symbols, _initial, _transition, _emission, _observations),
     do: :erlang.nif_error(:nif_not_loaded)
 
-  @doc "Min-max normalize data to [0, 1]"
+  @doc "Min-max normalize data to [0, 1], keeping the input form"
   def normalize_min_max(_data), do: :erlang.nif_error(:nif_not_loaded)
 
-  @doc "Z-score normalize data (zero mean, unit variance)"
+  @doc "Z-score normalize data (zero mean, unit variance), keeping the input form"
   def normalize_z_score(_data), do: :erlang.nif_error(:nif_not_loaded)
 
   @doc "Compute silhouette score for clustering quality"
   def silhouette_score(_data, _n_features, _labels, _metric),
     do: :erlang.nif_error(:nif_not_loaded)
 
-  @doc "Compute Jaccard similarity between two MinHash sketches"
+  @doc "Estimate Jaccard similarity of two bottom-k MinHash sketches (sketches may differ in size)"
   def minhash_jaccard(_sketch_a, _sketch_b), do: :erlang.nif_error(:nif_not_loaded)
 
   # ===========================================================================
@@ -477,7 +760,8 @@ defmodule Cyanea.Native do
   def build_upgma(_sequences, _names, _model), do: :erlang.nif_error(:nif_not_loaded)
 
   @doc "Build Neighbor-Joining tree from sequences. Returns Newick string"
-  def build_nj(_sequences, _names, _model), do: :erlang.nif_error(:nif_not_loaded)
+  def build_nj(_sequences, _names, _model, _cancel, _progress),
+    do: :erlang.nif_error(:nif_not_loaded)
 
   # --- New phylo functions ---------------------------------------------------
 
@@ -492,7 +776,17 @@ defmodule Cyanea.Native do
     do: :erlang.nif_error(:nif_not_loaded)
 
   @doc "Compute bootstrap support values for tree branches"
-  def bootstrap_support(_sequences, _tree_newick, _n_replicates, _model),
+  def bootstrap_support(_sequences, _tree_newick, _n_replicates, _model, _cancel, _progress),
+    do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Async UPGMA tree building on the phylo pool. Returns a job reference"
+  def build_upgma_async(_sequences, _names, _model, _cancel), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Async Neighbor-Joining tree building on the phylo pool. Returns a job reference"
+  def build_nj_async(_sequences, _names, _model, _cancel), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Async bootstrap support on the phylo pool. Returns a job reference"
+  def bootstrap_support_async(_sequences, _tree_newick, _n_replicates, _model, _cancel),
     do: :erlang.nif_error(:nif_not_loaded)
 
   @doc "Ancestral state reconstruction using Fitch parsimony"
@@ -569,6 +863,25 @@ defmodule Cyanea.Native do
 
   @doc "Get bedGraph file statistics (record count, chromosome count)"
   def bedgraph_stats(_path), do: :erlang.nif_error(:nif_not_loaded)
+
+  # ===========================================================================
+  # Jobs — Cancellation & progress
+  # ===========================================================================
+
+  @doc "Create a cancel token for a long-running NIF"
+  def cancel_token_new(), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Cancel a token. Returns true if it was not already cancelled"
+  def cancel_token_cancel(_token), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Whether a token has been cancelled"
+  def cancel_token_cancelled(_token), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Concurrency limit and queue depth of an async job pool (\"align\", \"ml\", \"phylo\")"
+  def async_pool_stats(_pool), do: :erlang.nif_error(:nif_not_loaded)
+
+  @doc "Set the concurrency limit of an async job pool. Returns the updated stats"
+  def async_pool_set_concurrency(_pool, _max_concurrency), do: :erlang.nif_error(:nif_not_loaded)
 end
 
 # ===========================================================================
@@ -587,6 +900,16 @@ defmodule Cyanea.Native.CsvInfo do
   defstruct [:row_count, :column_count, :columns, :has_headers]
 end
 
+defmodule Cyanea.Native.FastaRecord do
+  @moduledoc "FASTA record (cyanea-seq)"
+  defstruct [:name, :description, :sequence]
+end
+
+defmodule Cyanea.Native.FaiEntry do
+  @moduledoc "FASTA .fai index entry (cyanea-seq)"
+  defstruct [:name, :length, :offset, :line_bases, :line_width]
+end
+
 defmodule Cyanea.Native.FastqRecord do
   @moduledoc "FASTQ record (cyanea-seq)"
   defstruct [:name, :description, :sequence, :quality]
@@ -613,6 +936,36 @@ defmodule Cyanea.Native.AlignmentResult do
              :num_gaps, :alignment_length]
 end
 
+defmodule Cyanea.Native.ScoringSchemeInfo do
+  @moduledoc "Loaded substitution matrix: name, symbols, gap penalties and score range"
+  defstruct [:name, :alphabet, :gap_open, :gap_extend, :min_score, :max_score]
+end
+
+defmodule Cyanea.Native.MapOptions do
+  @moduledoc "Read mapper settings: seed length, repeat cutoff, bandwidth, minimum score, maximum insert"
+  defstruct [:seed_length, :max_occurrences, :bandwidth, :min_score, :max_insert]
+end
+
+defmodule Cyanea.Native.MapStats do
+  @moduledoc "Read mapping counts: reads written, mapped, in proper pairs"
+  defstruct [:reads, :mapped, :properly_paired]
+end
+
+defmodule Cyanea.Native.DbSearchOptions do
+  @moduledoc "Database search settings; lambda/k override the scheme's Karlin-Altschul parameters"
+  defstruct [:max_hits, :max_evalue, :lambda, :k]
+end
+
+defmodule Cyanea.Native.DbSearchHit do
+  @moduledoc "Database hit: record, raw and bit score, E-value and local alignment"
+  defstruct [:record, :description, :length, :score, :bit_score, :evalue, :alignment]
+end
+
+defmodule Cyanea.Native.DbSearch do
+  @moduledoc "Database search result: database size, lambda/k and their source, ranked hits"
+  defstruct [:query_length, :db_records, :db_length, :lambda, :k, :statistics, :hits]
+end
+
 defmodule Cyanea.Native.CigarStats do
   @moduledoc "CIGAR string statistics (cyanea-align)"
   defstruct [:cigar_string, :reference_consumed, :query_consumed,
@@ -621,10 +974,37 @@ defmodule Cyanea.Native.CigarStats do
 end
 
 defmodule Cyanea.Native.MsaResult do
-  @moduledoc "Multiple sequence alignment result (cyanea-align)"
+  @moduledoc "Multiple sequence alignment with its mean column conservation"
   defstruct [:aligned, :n_sequences, :n_columns, :conservation]
 end
 
+defmodule Cyanea.Native.MsaOptions do
+  @moduledoc "Progressive MSA settings: guide tree method (\"upgma\" or \"nj\") and refinement rounds"
+  defstruct [:guide_tree, :refine_rounds]
+end
+
+defmodule Cyanea.Native.MsaReport do
+  @moduledoc "Multiple alignment with Newick guide tree, per-column conservation/entropy, consensus and SP score"
+  defstruct [:aligned, :names, :n_sequences, :n_columns, :guide_tree, :conservation, :entropy,
+             :consensus, :sp_score]
+end
+
+defmodule Cyanea.Native.MsaFile do
+  @moduledoc "Alignment file reference: format is \"stockholm\" or \"clustal\""
+  defstruct [:path, :format]
+end
+
+defmodule Cyanea.Native.MsaAddOptions do
+  @moduledoc "Settings for adding sequences to an alignment: new names, keep_length, DP band"
+  defstruct [:names, :keep_length, :band]
+end
+
+defmodule Cyanea.Native.MsaMerge do
+  @moduledoc "Alignment extended with new sequences or merged from two, with per-column scores"
+  defstruct [:aligned, :names, :n_sequences, :n_columns, :inserted_columns, :score, :conservation,
+             :entropy, :consensus]
+end
+
 # --- cyanea-stats ---
 
 defmodule Cyanea.Native.DescriptiveStats do
@@ -679,8 +1059,30 @@ end
 # --- cyanea-io (record types — new) ---
 
 defmodule Cyanea.Native.VcfRecord do
-  @moduledoc "VCF variant record (cyanea-io)"
-  defstruct [:chrom, :position, :ref_allele, :alt_alleles, :quality, :filter]
+  @moduledoc "VCF variant record with typed INFO and per-sample genotypes (cyanea-io)"
+  defstruct [:chrom, :position, :id, :ref_allele, :alt_alleles, :quality, :filter,
+             :info, :format, :samples]
+end
+
+defmodule Cyanea.Native.VcfSample do
+  @moduledoc "Per-sample VCF genotype data (GT/DP/GQ/AD decoded, other FORMAT keys raw)"
+  defstruct [:genotype, :alleles, :phased, :depth, :genotype_quality,
+             :allele_depths, :other]
+end
+
+defmodule Cyanea.Native.VcfFieldDef do
+  @moduledoc "VCF INFO/FORMAT header definition"
+  defstruct [:id, :number, :value_type, :description]
+end
+
+defmodule Cyanea.Native.VcfHeader do
+  @moduledoc """
+  Parsed VCF header.
+
+  `meta` holds every `##` line except `fileformat`, verbatim and without the
+  leading `##`, so writing the header back reproduces the input.
+  """
+  defstruct [:fileformat, :samples, :contigs, :filters, :info, :format, meta: []]
 end
 
 defmodule Cyanea.Native.BedRecord do
@@ -694,13 +1096,79 @@ defmodule Cyanea.Native.GffGene do
 end
 
 defmodule Cyanea.Native.SamRecord do
-  @moduledoc "SAM/BAM alignment record (cyanea-io)"
-  defstruct [:qname, :flag, :rname, :pos, :mapq, :cigar, :sequence, :quality]
+  @moduledoc """
+  SAM/BAM alignment record (cyanea-io).
+
+  `tags` maps two-letter tag names (`"NM"`, `"MD"`, `"RG"`, `"CB"`, ...) to
+  integers, floats, strings, or lists for `B` arrays. `A` and `H` values are
+  `{:char, "x"}` and `{:hex, "1AE3"}`, so records write back with the same
+  tag types.
+  """
+  defstruct [
+    :qname,
+    :flag,
+    :rname,
+    :pos,
+    :mapq,
+    :cigar,
+    :rnext,
+    :pnext,
+    :tlen,
+    :sequence,
+    :quality,
+    :tags
+  ]
+end
+
+defmodule Cyanea.Native.SamFlags do
+  @moduledoc "Decoded SAM FLAG bits (cyanea-io)"
+  defstruct [
+    :paired,
+    :proper_pair,
+    :unmapped,
+    :mate_unmapped,
+    :reverse,
+    :mate_reverse,
+    :first_in_pair,
+    :second_in_pair,
+    :secondary,
+    :qc_fail,
+    :duplicate,
+    :supplementary
+  ]
 end
 
 defmodule Cyanea.Native.SamStats do
-  @moduledoc "SAM/BAM alignment statistics (cyanea-io)"
-  defstruct [:total_reads, :mapped, :unmapped, :avg_mapq, :avg_length]
+  @moduledoc """
+  SAM/BAM alignment statistics (cyanea-io), including samtools-flagstat
+  style pair counts. `duplicate_rate` is relative to primary reads and
+  `insert_size_histogram` is a list of `{size, count}` over non-duplicate
+  pairs with both mates on the same reference.
+  """
+  defstruct [
+    :total_reads,
+    :mapped,
+    :unmapped,
+    :avg_mapq,
+    :avg_length,
+    :qc_failed,
+    :secondary,
+    :supplementary,
+    :duplicates,
+    :duplicate_rate,
+    :paired,
+    :read1,
+    :read2,
+    :properly_paired,
+    :properly_paired_fraction,
+    :both_mapped,
+    :singletons,
+    :mate_on_different_chrom,
+    :insert_size_mean,
+    :insert_size_sd,
+    :insert_size_median,
+    :insert_size_histogram
+  ]
 end
 
 # --- cyanea-ml ---
@@ -741,6 +1209,11 @@ defmodule Cyanea.Native.LinearRegressionResult do
   defstruct [:weights, :bias, :r_squared]
 end
 
+defmodule Cyanea.Native.RandomForestInfo do
+  @moduledoc "Fitted random forest info (cyanea-ml)"
+  defstruct [:n_trees, :n_features, :n_classes, :n_samples, :oob_error]
+end
+
 # --- cyanea-chem ---
 
 defmodule Cyanea.Native.MolecularProperties do
@@ -857,3 +1330,329 @@ defmodule Cyanea.Native.BedGraphStats do
   @moduledoc "bedGraph file statistics (cyanea-io)"
   defstruct [:record_count, :chrom_count]
 end
+
+defmodule Cyanea.Native.AsyncPoolStats do
+  @moduledoc "Async job pool limits and load (queued jobs wait for a free slot)"
+  defstruct [:name, :max_concurrency, :queued, :running]
+end
+
+defmodule Cyanea.Native.TrimOptions do
+  @moduledoc """
+  FASTQ trimming steps: adapter preset (none/illumina/nextera/all) and extra
+  adapters, poly-G tail, sliding-window and 3' quality trimming, N and
+  minimum-length filters. Zero or nil disables a step
+  """
+  defstruct [:adapter_preset, :adapters, :adapter_min_overlap, :adapter_error_rate,
+             :poly_g_min_length, :window_size, :window_quality, :trailing_quality,
+             :max_n, :min_length]
+end
+
+defmodule Cyanea.Native.FastqTrimStats do
+  @moduledoc """
+  Per-step counts of a FASTQ trimming run. Trim counts are per read; record,
+  filter and base totals are per record (per pair for paired input)
+  """
+  defstruct [:paired, :records_in, :records_out, :bases_in, :bases_out,
+             :adapter_trimmed_reads, :adapter_trimmed_bases,
+             :poly_g_trimmed_reads, :poly_g_trimmed_bases,
+             :quality_trimmed_reads, :quality_trimmed_bases,
+             :too_many_n, :too_short]
+end
+
+defmodule Cyanea.Native.SampleBarcode do
+  @moduledoc "Sample sheet row: sample name and its i7/i5 index or inline barcode"
+  defstruct [:sample, :i7, :i5, :inline]
+end
+
+defmodule Cyanea.Native.DemuxSample do
+  @moduledoc "Records written for one sample (and how many matched perfectly), with its output paths"
+  defstruct [:sample, :records, :perfect, :paths]
+end
+
+defmodule Cyanea.Native.DemuxResult do
+  @moduledoc """
+  Demultiplexing outcome: total records, undetermined records (including
+  `ambiguous` ones matching two samples equally well), their output paths,
+  and a `%DemuxSample{}` per sample
+  """
+  defstruct [:records, :undetermined, :ambiguous, :undetermined_paths, :samples]
+end
+
+defmodule Cyanea.Native.PositionQuality do
+  @moduledoc "Phred quality at one read position (1-based): mean, 10th/25th/50th/75th/90th percentiles"
+  defstruct [:position, :mean, :p10, :q1, :median, :q3, :p90]
+end
+
+defmodule Cyanea.Native.BaseContent do
+  @moduledoc "Fractions of A, C, G, T and N at one read position (1-based)"
+  defstruct [:position, :a, :c, :g, :t, :n]
+end
+
+defmodule Cyanea.Native.OverrepresentedSequence do
+  @moduledoc "A sequence making up more than 0.1% of reads, with a matching adapter when known"
+  defstruct [:sequence, :count, :fraction, :possible_source]
+end
+
+defmodule Cyanea.Native.AdapterContent do
+  @moduledoc "Cumulative fraction of reads containing an adapter probe by each position"
+  defstruct [:name, :sequence, :fractions]
+end
+
+defmodule Cyanea.Native.FastqReport do
+  @moduledoc """
+  FastQC-style FASTQ report: `summary` (a `%FastqStats{}`), per-position
+  quality and base content, per-read GC distribution (index = GC %),
+  `{length, reads}` distribution, duplication levels as `{level, fraction}`,
+  overrepresented sequences and adapter content curves
+  """
+  defstruct [:summary, :per_position_quality, :per_position_content, :gc_distribution,
+             :length_distribution, :duplication_levels, :deduplicated_fraction,
+             :overrepresented, :adapter_content]
+end
+
+defmodule Cyanea.Native.MinHashSketch do
+  @moduledoc """
+  Bottom-k MinHash sketch: sorted `hashes` plus the `k`, `sketch_size`,
+  `seed`, `hash_function` and `alphabet` (`"dna"` or `"protein"`) they were
+  built with, and the sketched `length` used for p-values
+  """
+  defstruct [:name, :k, :sketch_size, :seed, :hash_function, :alphabet, :length, :hashes]
+end
+
+defmodule Cyanea.Native.MinHashDistance do
+  @moduledoc """
+  Comparison of two MinHash sketches: Jaccard estimate, containment of the
+  query in the reference, Mash distance, ANI (1 - distance) and p-value
+  """
+  defstruct [:query, :reference, :jaccard, :containment, :mash_distance, :ani,
+             :p_value, :shared_hashes, :compared_hashes]
+end
+
+defmodule Cyanea.Native.KmerCounts do
+  @moduledoc """
+  Canonical k-mer counts: top k-mers as `{kmer, count}`, the spectrum as
+  `{count, distinct_kmers}`, and peak coverage, genome size and
+  heterozygosity estimated from the spectrum (nil without a clear peak)
+  """
+  defstruct [:k, :total_kmers, :distinct_kmers, :top_kmers, :spectrum,
+             :peak_coverage, :genome_size, :heterozygosity]
+end
+
+defmodule Cyanea.Native.PatternHit do
+  @moduledoc """
+  One pattern occurrence in a record. `start` and `end` are 0-based,
+  end-exclusive forward-strand coordinates; `strand` is `"-"` when the
+  pattern's reverse complement matched
+  """
+  defstruct [:pattern, :record, :strand, :start, :end, :distance]
+end
+
+defmodule Cyanea.Native.OligoConditions do
+  @moduledoc "Reaction conditions for melting temperatures: Na+, Mg2+ and dNTPs in mM, oligo in nM"
+  defstruct [:na_mm, :mg_mm, :dntp_mm, :oligo_nm]
+end
+
+defmodule Cyanea.Native.OligoThermo do
+  @moduledoc """
+  Oligo thermodynamics: Tm (°C), GC fraction, duplex ΔH (kcal/mol), ΔS
+  (cal/K/mol) and ΔG, hairpin and self-dimer ΔG (kcal/mol at 37 °C)
+  """
+  defstruct [:length, :tm, :gc_content, :delta_h, :delta_s, :delta_g, :hairpin_dg, :self_dimer_dg]
+end
+
+defmodule Cyanea.Native.PrimerDesignOptions do
+  @moduledoc "Primer design constraints (GC as fractions, ΔG limits in kcal/mol) and %OligoConditions{}"
+  defstruct [:min_length, :max_length, :opt_tm, :min_tm, :max_tm, :min_gc, :max_gc,
+             :max_tm_diff, :min_product, :max_product, :max_hairpin_dg, :max_dimer_dg,
+             :num_return, :conditions]
+end
+
+defmodule Cyanea.Native.Primer do
+  @moduledoc "Designed primer (5'->3') with its binding site start on the template's forward strand"
+  defstruct [:sequence, :start, :length, :tm, :gc_content, :hairpin_dg, :self_dimer_dg]
+end
+
+defmodule Cyanea.Native.PrimerDesign do
+  @moduledoc "Ranked primer pair: forward and reverse %Primer{}, product size, Tm difference, pair dimer ΔG and penalty"
+  defstruct [:forward, :reverse, :product_size, :tm_difference, :pair_dimer_dg, :penalty]
+end
+
+defmodule Cyanea.Native.RestrictionEnzyme do
+  @moduledoc """
+  Built-in restriction enzyme: IUPAC recognition `site` and REBASE-style
+  `top_cut`/`bottom_cut` offsets from the start of the site
+  """
+  defstruct [:name, :site, :top_cut, :bottom_cut]
+end
+
+defmodule Cyanea.Native.RestrictionCut do
+  @moduledoc "One restriction cut: forward-strand top and bottom cut positions and the site that made it"
+  defstruct [:enzyme, :site_start, :strand, :top_cut, :bottom_cut]
+end
+
+defmodule Cyanea.Native.DigestFragment do
+  @moduledoc """
+  Digest fragment between two top-strand cuts, with the enzymes at each end
+  (nil at a linear sequence end). The fragment through a circular origin
+  has `end < start`
+  """
+  defstruct [:start, :end, :length, :left_enzyme, :right_enzyme]
+end
+
+defmodule Cyanea.Native.RestrictionDigest do
+  @moduledoc "Restriction digest of one sequence: cuts ordered by position and the resulting fragments"
+  defstruct [:length, :circular, :cuts, :fragments]
+end
+
+defmodule Cyanea.Native.PrimerPair do
+  @moduledoc "Named PCR primer pair, both primers written 5'->3'"
+  defstruct [:name, :forward, :reverse]
+end
+
+defmodule Cyanea.Native.Amplicon do
+  @moduledoc """
+  Predicted PCR product: forward-strand coordinates, primer mismatches and
+  the product sequence read from the forward primer
+  """
+  defstruct [:primer_pair, :record, :strand, :start, :end, :length,
+             :forward_mismatches, :reverse_mismatches, :product]
+end
+
+defmodule Cyanea.Native.Error do
+  @moduledoc """
+  Structured error returned by the native NIFs as
+  `{:error, %Cyanea.Native.Error{}}`.
+
+  `kind` is one of `:io`, `:parse`, `:invalid_input`, `:not_found`,
+  `:unsupported`, `:cancelled` or `:internal`. Parsers also fill in `file`, `line`
+  (1-based) and/or `offset` (byte offset) when they know where the input
+  went wrong.
+  """
+  defstruct [:kind, :message, :file, :line, :offset]
+
+  @type kind ::
+          :io | :parse | :invalid_input | :not_found | :unsupported | :cancelled | :internal
+
+  @type t :: %__MODULE__{
+          kind: kind(),
+          message: String.t(),
+          file: String.t() | nil,
+          line: non_neg_integer() | nil,
+          offset: non_neg_integer() | nil
+        }
+
+  @doc """
+  Human-readable message with the location prefixed when known, e.g.
+  `"line 1432: invalid base 'J'"`. The file path is left out so server
+  paths are not shown to users.
+  """
+  @spec message(t()) :: String.t()
+  def message(%__MODULE__{line: line, message: message}) when is_integer(line),
+    do: "line #{line}: #{message}"
+
+  def message(%__MODULE__{offset: offset, message: message}) when is_integer(offset),
+    do: "byte #{offset}: #{message}"
+
+  def message(%__MODULE__{message: message}), do: message
+end
+
+defmodule Cyanea.Native.Matrix do
+  @moduledoc """
+  Dense numeric matrix passed across the NIF boundary as a binary (cyanea-ml).
+
+  `data` holds `rows * cols` little-endian floats in row-major order. This
+  is the layout `Nx.to_binary/1` produces for a 2-D `{:f, 32}` or
+  `{:f, 64}` tensor, so tensors move in and out without building lists:
+
+      matrix = Cyanea.Native.Matrix.new(Nx.to_binary(t), Nx.shape(t), Nx.type(t))
+
+      matrix.data
+      |> Nx.from_binary(Cyanea.Native.Matrix.nx_type(matrix))
+      |> Nx.reshape(matrix.shape)
+
+  NIFs returning matrices answer in the form they were given: binary
+  input gives binary output with the same `dtype`.
+  """
+  defstruct [:data, :shape, :dtype]
+
+  @type dtype :: :f32 | :f64
+
+  @type t :: %__MODULE__{
+          data: binary(),
+          shape: {non_neg_integer(), non_neg_integer()},
+          dtype: dtype()
+        }
+
+  @typedoc "Matrix data accepted by the NIFs: flat row-major list, list of rows, or binary."
+  @type input :: [number()] | [[number()]] | t()
+
+  @typedoc "Matrix data returned by the NIFs, in the form of the input."
+  @type output :: [float()] | [[float()]] | t()
+
+  @doc """
+  Wrap a row-major binary of `rows * cols` floats. `type` is `:f32`,
+  `:f64`, or an Nx float type (`{:f, 32}` / `{:f, 64}`).
+
+  Raises `ArgumentError` when the binary size does not match the shape.
+  """
+  @spec new(binary(), {non_neg_integer(), non_neg_integer()}, dtype() | {:f, 32 | 64}) :: t()
+  def new(data, {rows, cols} = shape, type \\ :f64)
+      when is_binary(data) and is_integer(rows) and rows >= 0 and is_integer(cols) and cols >= 0 do
+    dtype = dtype(type)
+    expected = rows * cols * width(dtype)
+
+    if byte_size(data) != expected do
+      raise ArgumentError,
+            "expected #{expected} bytes for a #{rows}x#{cols} #{dtype} matrix, got #{byte_size(data)}"
+    end
+
+    %__MODULE__{data: data, shape: shape, dtype: dtype}
+  end
+
+  @doc "Encode a list of equal-length rows as a binary matrix."
+  @spec from_rows([[number()]], dtype() | {:f, 32 | 64}) :: t()
+  def from_rows(rows, type \\ :f64) when is_list(rows) do
+    dtype = dtype(type)
+    cols = rows |> List.first([]) |> length()
+
+    data =
+      for row <- rows, into: <<>> do
+        if length(row) != cols, do: raise(ArgumentError, "rows must all have #{cols} values")
+        for x <- row, into: <<>>, do: encode(x, dtype)
+      end
+
+    %__MODULE__{data: data, shape: {length(rows), cols}, dtype: dtype}
+  end
+
+  @doc "Decode a binary matrix into a list of rows."
+  @spec to_rows(t()) :: [[float()]]
+  def to_rows(%__MODULE__{shape: {_rows, 0}}), do: []
+
+  def to_rows(%__MODULE__{data: data, shape: {_rows, cols}, dtype: dtype}) do
+    data |> decode(dtype) |> Enum.chunk_every(cols)
+  end
+
+  @doc "Decode a binary matrix into a flat row-major list."
+  @spec to_list(t()) :: [float()]
+  def to_list(%__MODULE__{data: data, dtype: dtype}), do: decode(data, dtype)
+
+  @doc "The Nx type of the matrix elements, for `Nx.from_binary/2`."
+  @spec nx_type(t()) :: {:f, 32 | 64}
+  def nx_type(%__MODULE__{dtype: :f32}), do: {:f, 32}
+  def nx_type(%__MODULE__{dtype: :f64}), do: {:f, 64}
+
+  defp dtype(:f32), do: :f32
+  defp dtype(:f64), do: :f64
+  defp dtype({:f, 32}), do: :f32
+  defp dtype({:f, 64}), do: :f64
+  defp dtype(other), do: raise(ArgumentError, "unsupported matrix type: #{inspect(other)}")
+
+  defp width(:f32), do: 4
+  defp width(:f64), do: 8
+
+  defp encode(x, :f32), do: <<x::float-little-32>>
+  defp encode(x, :f64), do: <<x::float-little-64>>
+
+  defp decode(data, :f32), do: for(<<x::float-little-32 <- data>>, do: x)
+  defp decode(data, :f64), do: for(<<x::float-little-64 <- data>>, do: x)
+end
@@ -1,6 +1,9 @@
 defmodule Cyanea.NifHelper do
   @moduledoc false
 
+  @doc "Guard for numeric matrix input: a flat list, a list of rows, or a `Cyanea.Native.Matrix`."
+  defguard is_matrix(data) when is_list(data) or is_struct(data, Cyanea.Native.Matrix)
+
   @doc "Wrap a NIF call, normalizing bare returns to {:ok, result} tuples."
   def nif_call(fun) do
     case fun.() do
@@ -12,6 +15,53 @@ defmodule Cyanea.NifHelper do
     ErlangError -> {:error, :nif_not_loaded}
   end
 
+  @doc """
+  HTTP status for a NIF error reason: 4xx when the input is at fault
+  (malformed file, bad argument, unknown name), 5xx otherwise.
+  """
+  def http_status(%Cyanea.Native.Error{kind: kind}) do
+    case kind do
+      :parse -> 422
+      :invalid_input -> 422
+      :not_found -> 404
+      :unsupported -> 415
+      :cancelled -> 499
+      _ -> 500
+    end
+  end
+
+  def http_status(:nif_not_loaded), do: 503
+  def http_status(_reason), do: 500
+
+  @doc "Readable text for a NIF error reason, for logs and raised errors."
+  def error_message(%Cyanea.Native.Error{} = error), do: Cyanea.Native.Error.message(error)
+  def error_message(reason) when is_binary(reason), do: reason
+  def error_message(reason), do: inspect(reason)
+
+  @doc """
+  Cancel token and progress target for a long-running NIF, from the
+  `:cancel` and `:progress` options (see `Cyanea.Job`). A bare pid as
+  `:progress` tags its messages with the cancel token, or `nil` without one.
+  """
+  def job_args(opts) do
+    cancel = Keyword.get(opts, :cancel)
+
+    progress =
+      case Keyword.get(opts, :progress) do
+        nil -> nil
+        pid when is_pid(pid) -> {pid, cancel}
+        {pid, job_ref} when is_pid(pid) -> {pid, job_ref}
+      end
+
+    {cancel, progress}
+  end
+
+  @doc "Convert atom/string async job pool name to string."
+  def pool_string(:align), do: "align"
+  def pool_string(:ml), do: "ml"
+  def pool_string(:phylo), do: "phylo"
+  def pool_string(s) when is_binary(s), do: s
+
   @doc "Convert atom/string alignment mode to string."
   def mode_string(:local), do: "local"
   def mode_string(:global), do: "global"
@@ -49,6 +99,7 @@ defmodule Cyanea.NifHelper do
   def alphabet_string(:dna), do: "dna"
   def alphabet_string(:rna), do: "rna"
   def alphabet_string(:protein), do: "protein"
+  def alphabet_string(:iupac), do: "iupac"
   def alphabet_string(s) when is_binary(s), do: s
 
   @doc "Convert atom/string correlation method to string."
@@ -61,6 +112,23 @@ defmodule Cyanea.NifHelper do
   def adjust_string(:bh), do: "bh"
   def adjust_string(s) when is_binary(s), do: s
 
+  @doc """
+  Resolve the output compression for a writer: the `:compression` option
+  when given, otherwise bgzip for `.gz`/`.bgz` paths and none elsewhere.
+  """
+  def compression_string(path, opts) do
+    case Keyword.get(opts, :compression) do
+      nil -> if String.ends_with?(path, [".gz", ".bgz"]), do: "bgzip", else: "none"
+      compression -> compression_string(compression)
+    end
+  end
+
+  @doc "Convert atom/string output compression to string."
+  def compression_string(:none), do: "none"
+  def compression_string(:gzip), do: "gzip"
+  def compression_string(:bgzip), do: "bgzip"
+  def compression_string(s) when is_binary(s), do: s
+
   @doc "Convert atom/string MSA mode to string."
   def msa_mode_string(:dna), do: "dna"
   def msa_mode_string(:protein), do: "protein"
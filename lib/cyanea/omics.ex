@@ -20,20 +20,23 @@ defmodule Cyanea.Omics do
   def coverage(chroms, starts, ends, query_chrom),
     do: nif_call(fn -> Native.genomic_coverage(chroms, starts, ends, query_chrom) end)
 
-  @doc "Compute expression matrix summary statistics."
-  @spec expression_summary(list(), list(), list()) :: {:ok, struct()} | {:error, term()}
+  @doc "Compute expression matrix summary statistics (features as rows, samples as columns)."
+  @spec expression_summary(Cyanea.Native.Matrix.input(), list(), list()) :: {:ok, struct()} | {:error, term()}
   def expression_summary(data, features, samples),
     do: nif_call(fn -> Native.expression_summary(data, features, samples) end)
 
   @doc """
   Log2-transform a matrix: log2(x + pseudocount).
 
+  Accepts a list of rows, a flat list or a `%Cyanea.Native.Matrix{}`, and
+  returns the result in the same form and shape.
+
   ## Options
 
     * `:pseudocount` - value added before log (default: 1.0)
 
   """
-  @spec log_transform(list(), keyword()) :: {:ok, list()} | {:error, term()}
+  @spec log_transform(Cyanea.Native.Matrix.input(), keyword()) :: {:ok, Cyanea.Native.Matrix.output()} | {:error, term()}
   def log_transform(data, opts \\ []) do
     pseudocount = Keyword.get(opts, :pseudocount, 1.0)
     nif_call(fn -> Native.log_transform_matrix(data, pseudocount) end)
@@ -76,13 +76,22 @@ defmodule Cyanea.Phylo do
   ## Options
 
     * `:model` - `:p` (default), `:jc`, or `:k2p`
+    * `:async` - when `true`, return `{:ok, ref}` at once and run on the
+      native job pool; the result arrives as `{:cyanea_result, ref, result}`
+      (see `Cyanea.Job.await/2`)
+    * `:cancel` - cancel token from `Cyanea.Job.cancel_token/0` (async only)
 
   """
-  @spec build_upgma(list(), list(), keyword()) :: {:ok, binary()} | {:error, term()}
+  @spec build_upgma(list(), list(), keyword()) :: {:ok, binary() | reference()} | {:error, term()}
   def build_upgma(sequences, names, opts \\ [])
       when is_list(sequences) and is_list(names) do
     model = model_string(Keyword.get(opts, :model, :p))
-    nif_call(fn -> Native.build_upgma(sequences, names, model) end)
+
+    if Keyword.get(opts, :async, false) do
+      nif_call(fn -> Native.build_upgma_async(sequences, names, model, Keyword.get(opts, :cancel)) end)
+    else
+      nif_call(fn -> Native.build_upgma(sequences, names, model) end)
+    end
   end
 
   @doc """
@@ -91,13 +100,24 @@ defmodule Cyanea.Phylo do
   ## Options
 
     * `:model` - `:p` (default), `:jc`, or `:k2p`
+    * `:cancel` - cancel token from `Cyanea.Job.cancel_token/0`
+    * `:progress` - pid, or `{pid, job_ref}`, receiving `{:progress, job_ref, fraction}`
+    * `:async` - when `true`, return `{:ok, ref}` at once and run on the
+      native job pool; the result arrives as `{:cyanea_result, ref, result}`
+      (see `Cyanea.Job.await/2`)
 
   """
-  @spec build_nj(list(), list(), keyword()) :: {:ok, binary()} | {:error, term()}
+  @spec build_nj(list(), list(), keyword()) :: {:ok, binary() | reference()} | {:error, term()}
   def build_nj(sequences, names, opts \\ [])
       when is_list(sequences) and is_list(names) do
     model = model_string(Keyword.get(opts, :model, :p))
-    nif_call(fn -> Native.build_nj(sequences, names, model) end)
+    {cancel, progress} = job_args(opts)
+
+    if Keyword.get(opts, :async, false) do
+      nif_call(fn -> Native.build_nj_async(sequences, names, model, cancel) end)
+    else
+      nif_call(fn -> Native.build_nj(sequences, names, model, cancel, progress) end)
+    end
   end
 
   # ===========================================================================
@@ -111,14 +131,28 @@ defmodule Cyanea.Phylo do
 
     * `:n_replicates` - number of bootstrap replicates (default: 100)
     * `:model` - `:p` (default), `:jc`, or `:k2p`
+    * `:cancel` - cancel token from `Cyanea.Job.cancel_token/0`
+    * `:progress` - pid, or `{pid, job_ref}`, receiving `{:progress, job_ref, fraction}`
+      after each replicate
+    * `:async` - when `true`, return `{:ok, ref}` at once and run on the
+      native job pool; the result arrives as `{:cyanea_result, ref, result}`
+      (see `Cyanea.Job.await/2`)
 
   """
-  @spec bootstrap(list(), binary(), keyword()) :: {:ok, list()} | {:error, term()}
+  @spec bootstrap(list(), binary(), keyword()) :: {:ok, list() | reference()} | {:error, term()}
   def bootstrap(sequences, tree, opts \\ [])
       when is_list(sequences) and is_binary(tree) do
     n_replicates = Keyword.get(opts, :n_replicates, 100)
     model = model_string(Keyword.get(opts, :model, :p))
-    nif_call(fn -> Native.bootstrap_support(sequences, tree, n_replicates, model) end)
+    {cancel, progress} = job_args(opts)
+
+    if Keyword.get(opts, :async, false) do
+      nif_call(fn -> Native.bootstrap_support_async(sequences, tree, n_replicates, model, cancel) end)
+    else
+      nif_call(fn ->
+        Native.bootstrap_support(sequences, tree, n_replicates, model, cancel, progress)
+      end)
+    end
   end
 
   @doc "Ancestral state reconstruction using Fitch parsimony."
@@ -10,6 +10,9 @@ defmodule Cyanea.Seq do
 
   @type seq_type :: :dna | :rna | :protein
 
+  @typedoc "Sequence alphabet; `:iupac` is DNA or RNA with ambiguity codes and gaps."
+  @type alphabet :: :dna | :rna | :protein | :iupac
+
   @doc "Validate and normalize a sequence of the given type. Returns `{:ok, binary}` or `{:error, reason}`."
   @spec validate(binary(), seq_type()) :: {:ok, binary()} | {:error, term()}
   def validate(seq, :dna) when is_binary(seq), do: nif_call(fn -> Native.validate_dna(seq) end)
@@ -21,7 +24,7 @@ defmodule Cyanea.Seq do
   def validate!(seq, type) when is_binary(seq) do
     case validate(seq, type) do
       {:ok, result} -> result
-      {:error, reason} -> raise ArgumentError, "validation failed: #{inspect(reason)}"
+      {:error, reason} -> raise ArgumentError, "validation failed: #{error_message(reason)}"
     end
   end
 
@@ -39,26 +42,223 @@ defmodule Cyanea.Seq do
   def transcribe(seq) when is_binary(seq),
     do: nif_call(fn -> Native.dna_transcribe(seq) end)
 
-  @doc "Translate RNA to protein (NCBI Table 1)."
-  @spec translate(binary()) :: {:ok, binary()} | {:error, term()}
-  def translate(seq) when is_binary(seq),
-    do: nif_call(fn -> Native.rna_translate(seq) end)
+  @doc """
+  Translate DNA or RNA to protein.
+
+  IUPAC ambiguity codes are resolved per codon: an ambiguous codon becomes
+  the amino acid all of its possible codons encode (e.g. `GCN` is `A`),
+  otherwise `X`. A trailing partial codon is dropped and stops are `*`.
+
+  ## Options
+
+    * `:table` - NCBI genetic code id, see `genetic_codes/0` (default: 1).
+      For example 2 (vertebrate mitochondrial), 4 (mold/protozoan
+      mitochondrial and Mycoplasma), 5 (invertebrate mitochondrial),
+      6 (ciliate nuclear) or 11 (bacterial, archaeal and plant plastid)
+    * `:frame` - `1`, `2`, `3` on the given strand, `-1`, `-2`, `-3` on the
+      reverse complement, or `:all` for all six frames as
+      `[{frame, protein}]` (default: 1)
+
+  """
+  @spec translate(binary(), keyword()) ::
+          {:ok, binary() | [{integer(), binary()}]} | {:error, term()}
+  def translate(seq, opts \\ []) when is_binary(seq) do
+    table = Keyword.get(opts, :table, 1)
+
+    case Keyword.get(opts, :frame, 1) do
+      :all ->
+        nif_call(fn -> Native.sequence_translate(seq, table, [1, 2, 3, -1, -2, -3]) end)
+
+      frame when is_integer(frame) ->
+        with {:ok, [{^frame, protein}]} <-
+               nif_call(fn -> Native.sequence_translate(seq, table, [frame]) end),
+             do: {:ok, protein}
+    end
+  end
+
+  @doc "List the supported NCBI genetic codes as `{id, name}` pairs."
+  @spec genetic_codes() :: {:ok, [{pos_integer(), binary()}]} | {:error, term()}
+  def genetic_codes, do: nif_call(fn -> Native.genetic_codes() end)
+
+  @doc """
+  Calculate GC content of a nucleotide sequence (fraction 0.0-1.0).
+
+  With `alphabet: :iupac` ambiguity codes count by the share of their bases
+  that are G or C (`S` is 1, `R` is 0.5) and `N` and gaps are skipped.
+
+  ## Options
+
+    * `:alphabet` - `:dna` (default), `:rna`, or `:iupac`
+
+  """
+  @spec gc_content(binary(), keyword()) :: {:ok, float()} | {:error, term()}
+  def gc_content(seq, opts \\ []) when is_binary(seq) do
+    alphabet = alphabet_string(Keyword.get(opts, :alphabet, :dna))
+    nif_call(fn -> Native.sequence_gc_content(seq, alphabet) end)
+  end
+
+  @doc """
+  Count the symbols of a sequence. Returns `{:ok, %{"A" => count, ...}}`.
+
+  ## Options
+
+    * `:alphabet` - `:dna` (default), `:rna`, `:protein`, or `:iupac`
+
+  """
+  @spec composition(binary(), keyword()) :: {:ok, %{binary() => non_neg_integer()}} | {:error, term()}
+  def composition(seq, opts \\ []) when is_binary(seq) do
+    alphabet = alphabet_string(Keyword.get(opts, :alphabet, :dna))
+
+    with {:ok, counts} <- nif_call(fn -> Native.sequence_composition(seq, alphabet) end),
+         do: {:ok, Map.new(counts)}
+  end
+
+  @doc """
+  Extract k-mers from a sequence.
+
+  ## Options
+
+    * `:alphabet` - `:dna` (default), `:rna`, `:protein`, or `:iupac`
 
-  @doc "Calculate GC content of a DNA sequence (fraction 0.0-1.0)."
-  @spec gc_content(binary()) :: {:ok, float()} | {:error, term()}
-  def gc_content(seq) when is_binary(seq),
-    do: nif_call(fn -> Native.dna_gc_content(seq) end)
+  """
+  @spec kmers(binary(), pos_integer(), keyword()) :: {:ok, [binary()]} | {:error, term()}
+  def kmers(seq, k, opts \\ []) when is_binary(seq) and is_integer(k) do
+    alphabet = alphabet_string(Keyword.get(opts, :alphabet, :dna))
+    nif_call(fn -> Native.sequence_kmers(seq, k, alphabet) end)
+  end
+
+  @doc """
+  Count canonical k-mers of a DNA sequence without materializing them.
+
+  K-mers and their reverse complements are counted together and k-mers
+  spanning non-ACGT bases are skipped. Returns
+  `{:ok, %Cyanea.Native.KmerCounts{}}` with the most frequent k-mers, the
+  k-mer spectrum and spectrum-based genome size and heterozygosity
+  estimates (meaningful for read sets, not single sequences).
+
+  ## Options
+
+    * `:k` - k-mer size, 1 to 31 (default: 21)
+    * `:top` - number of most frequent k-mers to return (default: 10)
+
+  """
+  @spec count_kmers(binary(), keyword()) :: {:ok, struct()} | {:error, term()}
+  def count_kmers(seq, opts \\ []) when is_binary(seq) do
+    k = Keyword.get(opts, :k, 21)
+    top = Keyword.get(opts, :top, 10)
+    nif_call(fn -> Native.kmer_count_sequence(seq, k, top) end)
+  end
 
-  @doc "Extract k-mers from a DNA sequence."
-  @spec kmers(binary(), pos_integer()) :: {:ok, [binary()]} | {:error, term()}
-  def kmers(seq, k) when is_binary(seq) and is_integer(k),
-    do: nif_call(fn -> Native.sequence_kmers(seq, k) end)
+  @doc """
+  Count canonical k-mers across all records of a FASTA or FASTQ file
+  (plain or gzipped), streaming it. Takes the same options as
+  `count_kmers/2`; the spectrum of a sequencing run is the usual input for
+  k-mer based QC.
+  """
+  @spec count_kmers_file(binary(), keyword()) :: {:ok, struct()} | {:error, term()}
+  def count_kmers_file(path, opts \\ []) when is_binary(path) do
+    k = Keyword.get(opts, :k, 21)
+    top = Keyword.get(opts, :top, 10)
+    nif_call(fn -> Native.kmer_count_file(path, k, top) end)
+  end
 
   @doc "Calculate molecular weight of a protein (Daltons)."
   @spec molecular_weight(binary()) :: {:ok, float()} | {:error, term()}
   def molecular_weight(seq) when is_binary(seq),
     do: nif_call(fn -> Native.protein_molecular_weight(seq) end)
 
+  # ===========================================================================
+  # Oligos & primer design
+  # ===========================================================================
+
+  @doc """
+  Nearest-neighbor melting temperature (°C) of an oligo, using SantaLucia
+  (1998) parameters with salt and Mg2+ corrections.
+
+  ## Options
+
+    * `:na_mm` - monovalent cations, mM (default: 50)
+    * `:mg_mm` - Mg2+, mM (default: 1.5)
+    * `:dntp_mm` - dNTPs, mM (default: 0.6)
+    * `:oligo_nm` - oligo concentration, nM (default: 50)
+
+  """
+  @spec melting_temp(binary(), keyword()) :: {:ok, float()} | {:error, term()}
+  def melting_temp(seq, opts \\ []) when is_binary(seq),
+    do: nif_call(fn -> Native.oligo_tm(seq, oligo_conditions(opts)) end)
+
+  @doc """
+  Thermodynamics of an oligo: Tm, GC fraction, duplex ΔH/ΔS/ΔG and the
+  most stable hairpin and self-dimer ΔG (kcal/mol at 37 °C).
+
+  Takes the same options as `melting_temp/2`.
+  """
+  @spec oligo_thermo(binary(), keyword()) :: {:ok, struct()} | {:error, term()}
+  def oligo_thermo(seq, opts \\ []) when is_binary(seq),
+    do: nif_call(fn -> Native.oligo_thermo(seq, oligo_conditions(opts)) end)
+
+  @doc "ΔG (kcal/mol at 37 °C) of the most stable dimer two oligos form, e.g. a primer pair."
+  @spec dimer_dg(binary(), binary()) :: {:ok, float()} | {:error, term()}
+  def dimer_dg(a, b) when is_binary(a) and is_binary(b),
+    do: nif_call(fn -> Native.oligo_dimer_dg(a, b) end)
+
+  @doc """
+  Design primer pairs that amplify the `{start, stop}` target region
+  (0-based, end-exclusive) of a template.
+
+  Returns `{:ok, [%Cyanea.Native.PrimerDesign{}]}`, best first.
+
+  ## Options
+
+    * `:length` - primer length range (default: `18..25`)
+    * `:tm` - `{min, optimal, max}` Tm in °C (default: `{57.0, 60.0, 63.0}`)
+    * `:gc` - `{min, max}` GC fraction (default: `{0.4, 0.6}`)
+    * `:max_tm_diff` - largest Tm difference within a pair (default: 3.0)
+    * `:product_size` - product size range (default: `100..1000`)
+    * `:max_hairpin_dg` - most negative hairpin ΔG allowed (default: -3.0)
+    * `:max_dimer_dg` - most negative self- or pair-dimer ΔG allowed (default: -9.0)
+    * `:num_return` - pairs to return (default: 5)
+    * the reaction conditions of `melting_temp/2`
+
+  """
+  @spec design_primers(binary(), {non_neg_integer(), non_neg_integer()}, keyword()) ::
+          {:ok, list()} | {:error, term()}
+  def design_primers(template, {start, stop}, opts \\ [])
+      when is_binary(template) and is_integer(start) and is_integer(stop) do
+    min_length..max_length//_ = Keyword.get(opts, :length, 18..25)
+    {min_tm, opt_tm, max_tm} = Keyword.get(opts, :tm, {57.0, 60.0, 63.0})
+    {min_gc, max_gc} = Keyword.get(opts, :gc, {0.4, 0.6})
+    min_product..max_product//_ = Keyword.get(opts, :product_size, 100..1000)
+
+    options = %Native.PrimerDesignOptions{
+      min_length: min_length,
+      max_length: max_length,
+      opt_tm: opt_tm / 1,
+      min_tm: min_tm / 1,
+      max_tm: max_tm / 1,
+      min_gc: min_gc / 1,
+      max_gc: max_gc / 1,
+      max_tm_diff: Keyword.get(opts, :max_tm_diff, 3.0) / 1,
+      min_product: min_product,
+      max_product: max_product,
+      max_hairpin_dg: Keyword.get(opts, :max_hairpin_dg, -3.0) / 1,
+      max_dimer_dg: Keyword.get(opts, :max_dimer_dg, -9.0) / 1,
+      num_return: Keyword.get(opts, :num_return, 5),
+      conditions: oligo_conditions(opts)
+    }
+
+    nif_call(fn -> Native.primer_design(template, start, stop, options) end)
+  end
+
+  defp oligo_conditions(opts) do
+    %Native.OligoConditions{
+      na_mm: Keyword.get(opts, :na_mm, 50.0) / 1,
+      mg_mm: Keyword.get(opts, :mg_mm, 1.5) / 1,
+      dntp_mm: Keyword.get(opts, :dntp_mm, 0.6) / 1,
+      oligo_nm: Keyword.get(opts, :oligo_nm, 50.0) / 1
+    }
+  end
+
   # ===========================================================================
   # Pattern matching
   # ===========================================================================
@@ -88,19 +288,178 @@ defmodule Cyanea.Seq do
     end
   end
 
+  @doc """
+  Search many patterns at once across a FASTA file or a list of records.
+
+  `target` is a FASTA path (plain or gzipped, streamed record by record) or
+  a list of `%Cyanea.Native.FastaRecord{}` structs or `{name, sequence}`
+  tuples. Patterns are nucleotide binaries, named by their sequence, or
+  `{name, sequence}` tuples; IUPAC ambiguity codes are allowed.
+
+  Returns `{:ok, [%Cyanea.Native.PatternHit{}]}` ordered by record and
+  position. Exact plain patterns may be any length; approximate and IUPAC
+  patterns are limited to 64 bases.
+
+  ## Options
+
+    * `:max_distance` - maximum edit distance per hit (default: 0)
+    * `:both_strands` - also search reverse complements (default: true)
+
+  """
+  @spec search_patterns(binary() | list(), list(), keyword()) :: {:ok, list()} | {:error, term()}
+  def search_patterns(target, patterns, opts \\ [])
+
+  def search_patterns(path, patterns, opts) when is_binary(path) and is_list(patterns) do
+    {patterns, max_distance, both_strands} = pattern_args(patterns, opts)
+    nif_call(fn -> Native.pattern_search_fasta(path, patterns, max_distance, both_strands) end)
+  end
+
+  def search_patterns(records, patterns, opts) when is_list(records) and is_list(patterns) do
+    records = Enum.map(records, &to_fasta_record/1)
+    {patterns, max_distance, both_strands} = pattern_args(patterns, opts)
+    nif_call(fn -> Native.pattern_search_records(records, patterns, max_distance, both_strands) end)
+  end
+
+  defp pattern_args(patterns, opts) do
+    patterns =
+      Enum.map(patterns, fn
+        {name, seq} when is_binary(name) and is_binary(seq) -> {name, seq}
+        seq when is_binary(seq) -> {seq, seq}
+      end)
+
+    {patterns, Keyword.get(opts, :max_distance, 0), Keyword.get(opts, :both_strands, true)}
+  end
+
+  defp to_fasta_record(%Native.FastaRecord{} = record), do: record
+
+  defp to_fasta_record({name, seq}) when is_binary(name) and is_binary(seq),
+    do: %Native.FastaRecord{name: name, description: "", sequence: seq}
+
+  # ===========================================================================
+  # Cloning
+  # ===========================================================================
+
+  @doc "List the built-in restriction enzymes as `%Cyanea.Native.RestrictionEnzyme{}` structs."
+  @spec restriction_enzymes() :: {:ok, list()} | {:error, term()}
+  def restriction_enzymes, do: nif_call(fn -> Native.restriction_enzymes() end)
+
+  @doc """
+  Digest a sequence with one or more enzymes from `restriction_enzymes/0`
+  (names are case-insensitive).
+
+  Returns `{:ok, %Cyanea.Native.RestrictionDigest{}}` with the cuts and the
+  fragments between them, for cloning plans and gel-size predictions.
+
+  ## Options
+
+    * `:circular` - treat the sequence as circular, e.g. a plasmid (default: false)
+
+  """
+  @spec digest(binary(), binary() | [binary()], keyword()) :: {:ok, struct()} | {:error, term()}
+  def digest(seq, enzymes, opts \\ [])
+
+  def digest(seq, enzyme, opts) when is_binary(enzyme), do: digest(seq, [enzyme], opts)
+
+  def digest(seq, enzymes, opts) when is_binary(seq) and is_list(enzymes) do
+    circular = Keyword.get(opts, :circular, false)
+    nif_call(fn -> Native.restriction_digest(seq, enzymes, circular) end)
+  end
+
+  @doc """
+  Predict PCR products of primer pairs on one or more templates.
+
+  `templates` is a sequence binary, or a list of `%Cyanea.Native.FastaRecord{}`
+  structs or `{name, sequence}` tuples. Primer pairs are
+  `%Cyanea.Native.PrimerPair{}` structs, `{name, forward, reverse}` or
+  `{forward, reverse}` tuples, with primers written 5'->3'. A primer binds
+  with up to `:max_mismatches` mismatches, but never with a mismatched 3'
+  base.
+
+  Returns `{:ok, [%Cyanea.Native.Amplicon{}]}`.
+
+  ## Options
+
+    * `:max_mismatches` - mismatches allowed per primer (default: 0)
+    * `:max_length` - longest product reported (default: 10_000)
+    * `:circular` - treat templates as circular (default: false)
+
+  """
+  @spec pcr(binary() | list(), list(), keyword()) :: {:ok, list()} | {:error, term()}
+  def pcr(templates, primers, opts \\ [])
+
+  def pcr(seq, primers, opts) when is_binary(seq), do: pcr([{"template", seq}], primers, opts)
+
+  def pcr(templates, primers, opts) when is_list(templates) and is_list(primers) do
+    templates = Enum.map(templates, &to_fasta_record/1)
+    primers = Enum.map(primers, &to_primer_pair/1)
+    max_mismatches = Keyword.get(opts, :max_mismatches, 0)
+    max_length = Keyword.get(opts, :max_length, 10_000)
+    circular = Keyword.get(opts, :circular, false)
+
+    nif_call(fn ->
+      Native.pcr_simulate(templates, primers, max_mismatches, max_length, circular)
+    end)
+  end
+
+  defp to_primer_pair(%Native.PrimerPair{} = pair), do: pair
+
+  defp to_primer_pair({name, forward, reverse}),
+    do: %Native.PrimerPair{name: name, forward: forward, reverse: reverse}
+
+  defp to_primer_pair({forward, reverse}),
+    do: %Native.PrimerPair{name: forward <> "/" <> reverse, forward: forward, reverse: reverse}
+
   # ===========================================================================
   # FM-Index
   # ===========================================================================
 
-  @doc "Build an FM-index from text. Returns serialized index data."
-  @spec build_index(binary()) :: {:ok, binary()} | {:error, term()}
+  @doc """
+  Build an FM-index from text.
+
+  Returns an opaque index reference held by the NIF. Queries against the
+  reference reuse the index, so it should be built once and shared across
+  many lookups. Text and patterns are matched as given, so upcase both for
+  case-insensitive searches.
+  """
+  @spec build_index(binary()) :: {:ok, reference()} | {:error, term()}
   def build_index(text) when is_binary(text),
     do: nif_call(fn -> Native.fm_index_build(text) end)
 
-  @doc "Count occurrences of pattern in FM-index."
-  @spec count_occurrences(binary(), binary()) :: {:ok, non_neg_integer()} | {:error, term()}
-  def count_occurrences(index_data, pattern) when is_binary(index_data) and is_binary(pattern),
-    do: nif_call(fn -> Native.fm_index_count(index_data, pattern) end)
+  @doc "Count occurrences of pattern in an FM-index."
+  @spec count_occurrences(reference(), binary()) :: {:ok, non_neg_integer()} | {:error, term()}
+  def count_occurrences(index, pattern) when is_reference(index) and is_binary(pattern),
+    do: nif_call(fn -> Native.fm_index_count(index, pattern) end)
+
+  @doc """
+  Locate occurrences of pattern in an FM-index.
+
+  Returns sorted 0-based positions for exact matches. With `:max_mismatches`
+  greater than 0, returns `{position, mismatches}` pairs instead.
+
+  ## Options
+
+    * `:max_mismatches` - maximum number of substitutions allowed (default: 0)
+
+  """
+  @spec locate(reference(), binary(), keyword()) :: {:ok, list()} | {:error, term()}
+  def locate(index, pattern, opts \\ [])
+
+  def locate(index, pattern, opts) when is_reference(index) and is_binary(pattern) do
+    case Keyword.get(opts, :max_mismatches, 0) do
+      0 -> nif_call(fn -> Native.fm_index_locate(index, pattern) end)
+      k -> nif_call(fn -> Native.fm_index_search_mismatches(index, pattern, k) end)
+    end
+  end
+
+  @doc "Serialize an FM-index to a compact binary suitable for storing as a blob."
+  @spec dump_index(reference()) :: {:ok, binary()} | {:error, term()}
+  def dump_index(index) when is_reference(index),
+    do: nif_call(fn -> Native.fm_index_serialize(index) end)
+
+  @doc "Load an FM-index from a binary produced by `dump_index/1`."
+  @spec load_index(binary()) :: {:ok, reference()} | {:error, term()}
+  def load_index(data) when is_binary(data),
+    do: nif_call(fn -> Native.fm_index_deserialize(data) end)
 
   # ===========================================================================
   # ORF finding
@@ -125,26 +484,122 @@ defmodule Cyanea.Seq do
   # ===========================================================================
 
   @doc """
-  Compute MinHash sketch of a sequence.
+  Compute an unnamed MinHash sketch of a sequence.
+
+  Returns a `%Cyanea.Native.MinHashSketch{}` built as `sketch/2` builds it
+  with the default seed, so the two can be compared with each other.
+  Nucleotide sketches use canonical k-mers; `:iupac` sequences skip
+  k-mers containing ambiguity codes. Protein sketches hash residues as-is.
 
   ## Options
 
     * `:k` - k-mer size (default: 21)
     * `:sketch_size` - number of hash values (default: 1000)
+    * `:alphabet` - `:dna` (default), `:rna`, `:protein`, or `:iupac`
 
   """
-  @spec minhash(binary(), keyword()) :: {:ok, list()} | {:error, term()}
+  @spec minhash(binary(), keyword()) :: {:ok, struct()} | {:error, term()}
   def minhash(seq, opts \\ []) when is_binary(seq) do
     k = Keyword.get(opts, :k, 21)
     sketch_size = Keyword.get(opts, :sketch_size, 1000)
-    nif_call(fn -> Native.minhash_sketch(seq, k, sketch_size) end)
+    alphabet = alphabet_string(Keyword.get(opts, :alphabet, :dna))
+    nif_call(fn -> Native.minhash_sketch(seq, k, sketch_size, alphabet) end)
   end
 
-  @doc "Compute Jaccard similarity between two MinHash sketches."
-  @spec minhash_jaccard(list(), list()) :: {:ok, float()} | {:error, term()}
+  @doc """
+  Estimate the Jaccard similarity of two sketches from `minhash/2`, or of
+  two lists of their hashes.
+
+  Uses the bottom-k of the union of both sketches, so sketches of
+  different sizes can be compared.
+  """
+  @spec minhash_jaccard(struct() | list(), struct() | list()) :: {:ok, float()} | {:error, term()}
+  def minhash_jaccard(%Native.MinHashSketch{hashes: a}, %Native.MinHashSketch{hashes: b}),
+    do: minhash_jaccard(a, b)
+
   def minhash_jaccard(sketch_a, sketch_b) when is_list(sketch_a) and is_list(sketch_b),
     do: nif_call(fn -> Native.minhash_jaccard(sketch_a, sketch_b) end)
 
+  @doc """
+  Build a `%Cyanea.Native.MinHashSketch{}` of a sequence.
+
+  Like `minhash/2` but named and with a configurable seed. The sketch
+  carries its parameters and hash function (MurmurHash3, as in Mash) so it
+  can be compared with `compare_sketches/2` and stored with
+  `save_sketches/2`.
+
+  ## Options
+
+    * `:name` - sketch name reported in comparisons (default: `""`)
+    * `:k` - k-mer size, 1 to 32 (default: 21)
+    * `:sketch_size` - number of hash values (default: 1000)
+    * `:seed` - hash seed (default: 42, as in Mash)
+    * `:alphabet` - `:dna` (default), `:rna`, `:protein`, or `:iupac`
+
+  """
+  @spec sketch(binary(), keyword()) :: {:ok, struct()} | {:error, term()}
+  def sketch(seq, opts \\ []) when is_binary(seq) do
+    {k, sketch_size, seed, alphabet} = sketch_opts(opts, :dna)
+    name = Keyword.get(opts, :name, "")
+    nif_call(fn -> Native.minhash_sketch_build(seq, name, k, sketch_size, seed, alphabet) end)
+  end
+
+  @doc """
+  Build one sketch from all records of a FASTA file (plain or gzipped),
+  named after the path.
+
+  Takes the options of `sketch/2` except `:name`; `:alphabet` defaults to
+  `:iupac` so assemblies with `N` runs are accepted.
+  """
+  @spec sketch_file(binary(), keyword()) :: {:ok, struct()} | {:error, term()}
+  def sketch_file(path, opts \\ []) when is_binary(path) do
+    {k, sketch_size, seed, alphabet} = sketch_opts(opts, :iupac)
+    nif_call(fn -> Native.minhash_sketch_fasta(path, k, sketch_size, seed, alphabet) end)
+  end
+
+  defp sketch_opts(opts, default_alphabet) do
+    {Keyword.get(opts, :k, 21), Keyword.get(opts, :sketch_size, 1000), Keyword.get(opts, :seed, 42),
+     alphabet_string(Keyword.get(opts, :alphabet, default_alphabet))}
+  end
+
+  @doc """
+  Compare two sketches built with the same k, seed and alphabet.
+
+  Returns `{:ok, %Cyanea.Native.MinHashDistance{}}` with the Jaccard
+  estimate, the containment of `query` in `reference`, the Mash distance,
+  ANI and the p-value of seeing that many shared hashes by chance.
+  """
+  @spec compare_sketches(struct(), struct()) :: {:ok, struct()} | {:error, term()}
+  def compare_sketches(%Native.MinHashSketch{} = query, %Native.MinHashSketch{} = reference),
+    do: nif_call(fn -> Native.minhash_compare(query, reference) end)
+
+  @doc """
+  Compare every query sketch with every reference sketch, e.g. uploaded
+  assemblies against a sketch database loaded with `load_sketches/1`.
+
+  ## Options
+
+    * `:max_distance` - only return pairs with a Mash distance up to this
+      (default: 1.0, all pairs)
+
+  """
+  @spec compare_sketches_many([struct()], [struct()], keyword()) :: {:ok, [struct()]} | {:error, term()}
+  def compare_sketches_many(queries, references, opts \\ [])
+      when is_list(queries) and is_list(references) do
+    max_distance = Keyword.get(opts, :max_distance, 1.0)
+    nif_call(fn -> Native.minhash_compare_many(queries, references, max_distance) end)
+  end
+
+  @doc "Save sketches to a sketch file. Returns `{:ok, count}`."
+  @spec save_sketches(binary(), [struct()]) :: {:ok, non_neg_integer()} | {:error, term()}
+  def save_sketches(path, sketches) when is_binary(path) and is_list(sketches),
+    do: nif_call(fn -> Native.minhash_sketch_save(path, sketches) end)
+
+  @doc "Load the sketches from a file written by `save_sketches/2`."
+  @spec load_sketches(binary()) :: {:ok, [struct()]} | {:error, term()}
+  def load_sketches(path) when is_binary(path),
+    do: nif_call(fn -> Native.minhash_sketch_load(path) end)
+
   # ===========================================================================
   # File I/O (sequence-specific)
   # ===========================================================================
@@ -154,13 +609,231 @@ defmodule Cyanea.Seq do
   def fasta_stats(path) when is_binary(path),
     do: nif_call(fn -> Native.fasta_stats(path) end)
 
+  @doc "Parse all records from a FASTA file (plain or gzipped)."
+  @spec parse_fasta(binary()) :: {:ok, list()} | {:error, term()}
+  def parse_fasta(path) when is_binary(path),
+    do: nif_call(fn -> Native.parse_fasta(path) end)
+
+  @doc """
+  Build a samtools-compatible `.fai` index next to an uncompressed FASTA file.
+
+  Returns `{:ok, index_path}`.
+  """
+  @spec index_fasta(binary()) :: {:ok, binary()} | {:error, term()}
+  def index_fasta(path) when is_binary(path),
+    do: nif_call(fn -> Native.fasta_index_build(path) end)
+
+  @doc """
+  Load the `.fai` entries (name, length, offsets) of a FASTA file.
+
+  Reads `<path>.fai` when present and scans the FASTA otherwise.
+  """
+  @spec fasta_index(binary()) :: {:ok, list()} | {:error, term()}
+  def fasta_index(path) when is_binary(path),
+    do: nif_call(fn -> Native.fasta_index_load(path) end)
+
+  @doc """
+  Open an uncompressed FASTA file for repeated `fetch_fasta/4` calls.
+
+  The `.fai` entries are loaded once, from `<path>.fai` when present (see
+  `index_fasta/1`) and by scanning the FASTA otherwise.
+  """
+  @spec open_fasta(binary()) :: {:ok, reference()} | {:error, term()}
+  def open_fasta(path) when is_binary(path),
+    do: nif_call(fn -> Native.fasta_open(path) end)
+
+  @doc """
+  Fetch a subsequence without loading the whole file.
+
+  Coordinates are 1-based and inclusive, as in `samtools faidx`; `stop` is
+  clamped to the sequence length. Takes a path, whose `.fai` is loaded for
+  this one fetch, or a handle from `open_fasta/1` for repeated fetches.
+  """
+  @spec fetch_fasta(reference() | binary(), binary(), pos_integer(), pos_integer()) ::
+          {:ok, binary()} | {:error, term()}
+  def fetch_fasta(path, chrom, start, stop)
+      when is_binary(path) and is_binary(chrom) and is_integer(start) and start > 0 and
+             is_integer(stop) and stop >= start do
+    nif_call(fn -> Native.fasta_fetch(path, chrom, start, stop) end)
+  end
+
+  def fetch_fasta(fasta, chrom, start, stop)
+      when is_reference(fasta) and is_binary(chrom) and is_integer(start) and start > 0 and
+             is_integer(stop) and stop >= start,
+      do: nif_call(fn -> Native.fasta_fetch_handle(fasta, chrom, start, stop) end)
+
   @doc "Get FASTQ file statistics (includes quality metrics)."
   @spec fastq_stats(binary()) :: {:ok, struct()} | {:error, term()}
   def fastq_stats(path) when is_binary(path),
     do: nif_call(fn -> Native.fastq_stats(path) end)
 
+  @doc """
+  Demultiplex a pooled FASTQ file, or a pair of R1/R2 files, by sample.
+
+  `samples` is the sample sheet: `%Cyanea.Native.SampleBarcode{}` structs
+  or maps with `:sample` and `:i7` (plus optional `:i5`), or `:inline`.
+  Writes `<sample>.fastq` (or `<sample>_R1`/`_R2` for pairs, `.fastq.gz`
+  when compressed) and `Undetermined` files into `output_dir`. Returns
+  `{:ok, %Cyanea.Native.DemuxResult{}}` with per-sample counts.
+
+  ## Options
+
+    * `:mode` - `:index` (default) reads the i7/i5 index from the Illumina
+      read header (`1:N:0:ACGTACGT+TTGGCCAA`); `:inline` matches the
+      barcode at the start of read 1 and trims it off
+    * `:max_mismatches` - edit distance allowed per barcode (default: 1)
+    * `:compression` - `:none` (default), `:gzip`, or `:bgzip`
+
+  """
+  @spec demultiplex(binary() | {binary(), binary()}, [struct() | map()], binary(), keyword()) ::
+          {:ok, struct()} | {:error, term()}
+  def demultiplex(input, samples, output_dir, opts \\ [])
+
+  def demultiplex({r1, r2}, samples, output_dir, opts)
+      when is_binary(r1) and is_binary(r2) and is_list(samples) and is_binary(output_dir),
+      do: do_demultiplex([r1, r2], samples, output_dir, opts)
+
+  def demultiplex(input, samples, output_dir, opts)
+      when is_binary(input) and is_list(samples) and is_binary(output_dir),
+      do: do_demultiplex([input], samples, output_dir, opts)
+
+  defp do_demultiplex(inputs, samples, output_dir, opts) do
+    samples =
+      Enum.map(samples, fn
+        %Native.SampleBarcode{} = row -> row
+        %{} = row -> struct(Native.SampleBarcode, row)
+      end)
+
+    mode = Atom.to_string(Keyword.get(opts, :mode, :index))
+    max_mismatches = Keyword.get(opts, :max_mismatches, 1)
+    compression = compression_string(Keyword.get(opts, :compression, :none))
+
+    nif_call(fn ->
+      Native.fastq_demultiplex(inputs, samples, mode, max_mismatches, output_dir, compression)
+    end)
+  end
+
+  @doc """
+  Build a FastQC-style quality report of a FASTQ file (plain or gzipped) in
+  a single streaming pass.
+
+  Returns `{:ok, %Cyanea.Native.FastqReport{}}` with per-position quality
+  quantiles and base content, the per-read GC and length distributions,
+  duplication levels, overrepresented sequences and adapter content. Its
+  `summary` holds the same figures as `fastq_stats/1`.
+  """
+  @spec fastq_report(binary()) :: {:ok, struct()} | {:error, term()}
+  def fastq_report(path) when is_binary(path),
+    do: nif_call(fn -> Native.fastq_report(path) end)
+
   @doc "Parse all records from a FASTQ file."
   @spec parse_fastq(binary()) :: {:ok, list()} | {:error, term()}
   def parse_fastq(path) when is_binary(path),
     do: nif_call(fn -> Native.parse_fastq(path) end)
+
+  @doc """
+  Trim and filter a FASTQ file, or a pair of R1/R2 files, writing the
+  surviving reads to `output` (a path, or `{r1_out, r2_out}` for pairs).
+
+  Each read goes through adapter trimming, 3' poly-G trimming, quality
+  trimming, then the N and length filters; a pair is kept only if both
+  mates pass. Returns `{:ok, %Cyanea.Native.FastqTrimStats{}}` with
+  per-step counts.
+
+  ## Options
+
+    * `:adapters` - `:illumina`, `:nextera`, `:all` (default), `:none`, or
+      a list of adapter sequences
+    * `:adapter_min_overlap` - shortest adapter prefix trimmed at the 3'
+      end (default: 3)
+    * `:adapter_error_rate` - mismatches allowed per adapter base
+      (default: 0.1)
+    * `:poly_g` - minimum poly-G tail to trim, `0` to disable (default: 10)
+    * `:window` - `{size, quality}` sliding-window trimming, `nil` to
+      disable (default: `nil`)
+    * `:trailing_quality` - 3' quality threshold, BWA-style, `0` to disable
+      (default: 20)
+    * `:max_n` - drop reads with more `N` bases, `nil` to disable (default: 5)
+    * `:min_length` - drop reads shorter than this after trimming (default: 15)
+    * `:compression` - `:none`, `:gzip`, or `:bgzip` (default: `:bgzip` for
+      output paths ending in `.gz`, otherwise `:none`)
+
+  """
+  @spec trim_fastq(binary() | {binary(), binary()}, binary() | {binary(), binary()}, keyword()) ::
+          {:ok, struct()} | {:error, term()}
+  def trim_fastq(input, output, opts \\ [])
+
+  def trim_fastq({r1, r2}, {out1, out2}, opts)
+      when is_binary(r1) and is_binary(r2) and is_binary(out1) and is_binary(out2) do
+    options = trim_options(opts)
+    compression = compression_string(out1, opts)
+    nif_call(fn -> Native.fastq_trim([r1, r2], [out1, out2], options, compression) end)
+  end
+
+  def trim_fastq(input, output, opts) when is_binary(input) and is_binary(output) do
+    options = trim_options(opts)
+    compression = compression_string(output, opts)
+    nif_call(fn -> Native.fastq_trim([input], [output], options, compression) end)
+  end
+
+  defp trim_options(opts) do
+    {preset, adapters} =
+      case Keyword.get(opts, :adapters, :all) do
+        list when is_list(list) -> {"none", list}
+        preset when is_atom(preset) -> {Atom.to_string(preset), []}
+        preset when is_binary(preset) -> {preset, []}
+      end
+
+    {window_size, window_quality} = Keyword.get(opts, :window) || {0, 0}
+
+    %Native.TrimOptions{
+      adapter_preset: preset,
+      adapters: adapters,
+      adapter_min_overlap: Keyword.get(opts, :adapter_min_overlap, 3),
+      adapter_error_rate: Keyword.get(opts, :adapter_error_rate, 0.1),
+      poly_g_min_length: Keyword.get(opts, :poly_g, 10),
+      window_size: window_size,
+      window_quality: window_quality,
+      trailing_quality: Keyword.get(opts, :trailing_quality, 20),
+      max_n: Keyword.get(opts, :max_n, 5),
+      min_length: Keyword.get(opts, :min_length, 15)
+    }
+  end
+
+  @doc """
+  Write `%Cyanea.Native.FastaRecord{}` structs to a FASTA file.
+
+  Returns `{:ok, count}` with the number of records written.
+
+  ## Options
+
+    * `:line_width` - bases per sequence line, `0` for unwrapped (default: 60)
+    * `:compression` - `:none`, `:gzip`, or `:bgzip` (default: `:bgzip` for
+      paths ending in `.gz`, otherwise `:none`)
+
+  """
+  @spec write_fasta(binary(), list(), keyword()) :: {:ok, non_neg_integer()} | {:error, term()}
+  def write_fasta(path, records, opts \\ []) when is_binary(path) and is_list(records) do
+    line_width = Keyword.get(opts, :line_width, 60)
+    compression = compression_string(path, opts)
+    nif_call(fn -> Native.write_fasta(path, records, line_width, compression) end)
+  end
+
+  @doc """
+  Write `%Cyanea.Native.FastqRecord{}` structs to a FASTQ file.
+
+  Quality values are Phred scores (as returned by `parse_fastq/1`) and are
+  written Phred+33 encoded. Returns `{:ok, count}`.
+
+  ## Options
+
+    * `:compression` - `:none`, `:gzip`, or `:bgzip` (default: `:bgzip` for
+      paths ending in `.gz`, otherwise `:none`)
+
+  """
+  @spec write_fastq(binary(), list(), keyword()) :: {:ok, non_neg_integer()} | {:error, term()}
+  def write_fastq(path, records, opts \\ []) when is_binary(path) and is_list(records) do
+    compression = compression_string(path, opts)
+    nif_call(fn -> Native.write_fastq(path, records, compression) end)
+  end
 end
@@ -4,6 +4,7 @@ defmodule CyaneaWeb.Api.V1.FallbackController do
   """
   use CyaneaWeb, :controller
 
+  alias Cyanea.NifHelper
   alias CyaneaWeb.Api.V1.ApiHelpers
 
   def call(conn, {:error, :not_found}) do
@@ -54,6 +55,23 @@ defmodule CyaneaWeb.Api.V1.FallbackController do
     |> json(%{error: %{status: 422, message: "Validation failed", details: ApiHelpers.format_errors(changeset)}})
   end
 
+  def call(conn, {:error, %Cyanea.Native.Error{} = error}) do
+    status = NifHelper.http_status(error)
+
+    message =
+      if status < 500, do: Cyanea.Native.Error.message(error), else: "Internal server error"
+
+    conn
+    |> put_status(status)
+    |> json(%{error: %{status: status, kind: error.kind, line: error.line, message: message}})
+  end
+
+  def call(conn, {:error, :nif_not_loaded}) do
+    conn
+    |> put_status(:service_unavailable)
+    |> json(%{error: %{status: 503, message: "Native extensions unavailable"}})
+  end
+
   def call(conn, {:error, _reason}) do
     conn
     |> put_status(:internal_server_error)
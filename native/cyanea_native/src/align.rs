@@ -1,37 +1,79 @@
-//! cyanea-align NIFs — Pairwise alignment, batch, MSA, banded, POA.
+//! cyanea-align NIFs — Pairwise alignment, batch, banded, POA (MSA is in `msa.rs`).
 
 use crate::bridge::*;
+use crate::job::{CancelTokenResource, JobState};
+use crate::pool::{self, PoolId};
 use crate::to_nif_error;
+use rustler::{Env, Reference, ResourceArc};
 
 // ===========================================================================
 // Helpers
 // ===========================================================================
 
-pub(crate) fn parse_alignment_mode(mode: &str) -> Result<cyanea_align::AlignmentMode, String> {
+pub(crate) fn parse_alignment_mode(mode: &str) -> Result<cyanea_align::AlignmentMode, NifError> {
     match mode {
         "local" => Ok(cyanea_align::AlignmentMode::Local),
         "global" => Ok(cyanea_align::AlignmentMode::Global),
         "semiglobal" => Ok(cyanea_align::AlignmentMode::SemiGlobal),
-        _ => Err(format!("unknown alignment mode: {mode} (expected local, global, or semiglobal)")),
+        _ => Err(NifError::invalid(format!("unknown alignment mode: {mode} (expected local, global, or semiglobal)"))),
     }
 }
 
-fn parse_substitution_matrix(name: &str) -> Result<cyanea_align::SubstitutionMatrix, String> {
+fn parse_substitution_matrix(name: &str) -> Result<cyanea_align::SubstitutionMatrix, NifError> {
     match name {
         "blosum62" => Ok(cyanea_align::SubstitutionMatrix::blosum62()),
         "blosum45" => Ok(cyanea_align::SubstitutionMatrix::blosum45()),
         "blosum80" => Ok(cyanea_align::SubstitutionMatrix::blosum80()),
         "pam250" => Ok(cyanea_align::SubstitutionMatrix::pam250()),
-        _ => Err(format!("unknown substitution matrix: {name} (expected blosum62, blosum45, blosum80, or pam250)")),
+        _ => Err(NifError::invalid(format!("unknown substitution matrix: {name} (expected blosum62, blosum45, blosum80, or pam250)"))),
     }
 }
 
+/// Pairs aligned between cancellation checks in [`align_batch`].
+const BATCH_CHUNK: usize = 64;
+
+fn align_batch(
+    pairs: &[(Vec<u8>, Vec<u8>)],
+    mode: cyanea_align::AlignmentMode,
+    job: &JobState,
+) -> Result<Vec<AlignmentResultNif>, NifError> {
+    let scoring = cyanea_align::ScoringScheme::Simple(cyanea_align::ScoringMatrix::dna_default());
+    let mut results = Vec::with_capacity(pairs.len());
+    for chunk in pairs.chunks(BATCH_CHUNK) {
+        job.check()?;
+        let refs: Vec<(&[u8], &[u8])> =
+            chunk.iter().map(|(q, t)| (q.as_slice(), t.as_slice())).collect();
+        let aligned = cyanea_align::align_batch(&refs, mode, &scoring).map_err(to_nif_error)?;
+        results.extend(aligned.into_iter().map(AlignmentResultNif::from));
+        job.set_progress(results.len() as f64 / pairs.len() as f64);
+    }
+    Ok(results)
+}
+
+fn poa(sequences: &[Vec<u8>], job: &JobState) -> Result<Vec<u8>, NifError> {
+    if sequences.is_empty() {
+        return Err(NifError::invalid("at least one sequence required"));
+    }
+    let scoring = cyanea_align::poa::PoaScoring {
+        match_score: 2,
+        mismatch_score: -1,
+        gap_score: -2,
+    };
+    let mut graph = cyanea_align::poa::PoaGraph::from_sequence(&sequences[0]);
+    for (i, seq) in sequences[1..].iter().enumerate() {
+        job.check()?;
+        graph.add_sequence(seq, &scoring).map_err(to_nif_error)?;
+        job.set_progress((i + 1) as f64 / (sequences.len() - 1) as f64);
+    }
+    Ok(graph.consensus())
+}
+
 // ===========================================================================
 // Existing NIFs
 // ===========================================================================
 
 #[rustler::nif]
-pub fn align_dna(query: Vec<u8>, target: Vec<u8>, mode: String) -> Result<AlignmentResultNif, String> {
+pub fn align_dna(query: Vec<u8>, target: Vec<u8>, mode: String) -> Result<AlignmentResultNif, NifError> {
     let mode = parse_alignment_mode(&mode)?;
     let scoring = cyanea_align::ScoringScheme::Simple(cyanea_align::ScoringMatrix::dna_default());
     cyanea_align::align(&query, &target, mode, &scoring)
@@ -48,7 +90,7 @@ pub fn align_dna_custom(
     mismatch_score: i32,
     gap_open: i32,
     gap_extend: i32,
-) -> Result<AlignmentResultNif, String> {
+) -> Result<AlignmentResultNif, NifError> {
     let mode = parse_alignment_mode(&mode)?;
     let matrix = cyanea_align::ScoringMatrix::new(match_score, mismatch_score, gap_open, gap_extend)
         .map_err(to_nif_error)?;
@@ -64,7 +106,7 @@ pub fn align_protein(
     target: Vec<u8>,
     mode: String,
     matrix: String,
-) -> Result<AlignmentResultNif, String> {
+) -> Result<AlignmentResultNif, NifError> {
     let mode = parse_alignment_mode(&mode)?;
     let sub_matrix = parse_substitution_matrix(&matrix)?;
     let scoring = cyanea_align::ScoringScheme::Substitution(sub_matrix);
@@ -77,37 +119,8 @@ pub fn align_protein(
 pub fn align_batch_dna(
     pairs: Vec<(Vec<u8>, Vec<u8>)>,
     mode: String,
-) -> Result<Vec<AlignmentResultNif>, String> {
-    let mode = parse_alignment_mode(&mode)?;
-    let scoring = cyanea_align::ScoringScheme::Simple(cyanea_align::ScoringMatrix::dna_default());
-    let refs: Vec<(&[u8], &[u8])> = pairs.iter().map(|(q, t)| (q.as_slice(), t.as_slice())).collect();
-    cyanea_align::align_batch(&refs, mode, &scoring)
-        .map(|results| results.into_iter().map(AlignmentResultNif::from).collect())
-        .map_err(to_nif_error)
-}
-
-#[rustler::nif(schedule = "DirtyCpu")]
-pub fn progressive_msa(sequences: Vec<Vec<u8>>, mode: String) -> Result<MsaResultNif, String> {
-    let refs: Vec<&[u8]> = sequences.iter().map(|s| s.as_slice()).collect();
-    let scoring = match mode.as_str() {
-        "dna" => {
-            cyanea_align::ScoringScheme::Simple(cyanea_align::ScoringMatrix::dna_default())
-        }
-        "protein" => cyanea_align::ScoringScheme::Substitution(
-            cyanea_align::SubstitutionMatrix::blosum62(),
-        ),
-        _ => return Err(format!("unknown MSA mode: {mode} (expected dna or protein)")),
-    };
-    let result = cyanea_align::msa::progressive_msa(&refs, &scoring).map_err(to_nif_error)?;
-    let n_sequences = result.n_sequences();
-    let n_columns = result.n_columns;
-    let conservation = result.conservation();
-    Ok(MsaResultNif {
-        aligned: result.aligned,
-        n_sequences,
-        n_columns,
-        conservation,
-    })
+) -> Result<Vec<AlignmentResultNif>, NifError> {
+    align_batch(&pairs, parse_alignment_mode(&mode)?, &JobState::new(None))
 }
 
 // ===========================================================================
@@ -120,7 +133,7 @@ pub fn banded_align_dna(
     target: Vec<u8>,
     mode: String,
     bandwidth: usize,
-) -> Result<AlignmentResultNif, String> {
+) -> Result<AlignmentResultNif, NifError> {
     let mode = parse_alignment_mode(&mode)?;
     let scoring = cyanea_align::ScoringScheme::Simple(cyanea_align::ScoringMatrix::dna_default());
     let result = match mode {
@@ -143,7 +156,7 @@ pub fn banded_score_only(
     target: Vec<u8>,
     mode: String,
     bandwidth: usize,
-) -> Result<i32, String> {
+) -> Result<i32, NifError> {
     let mode = parse_alignment_mode(&mode)?;
     let scoring = cyanea_align::ScoringScheme::Simple(cyanea_align::ScoringMatrix::dna_default());
     cyanea_align::simd::banded_score_only(&query, &target, &scoring, bandwidth, mode)
@@ -151,20 +164,32 @@ pub fn banded_score_only(
 }
 
 #[rustler::nif(schedule = "DirtyCpu")]
-pub fn poa_consensus(sequences: Vec<Vec<u8>>) -> Result<Vec<u8>, String> {
-    if sequences.is_empty() {
-        return Err("at least one sequence required".into());
-    }
-    let scoring = cyanea_align::poa::PoaScoring {
-        match_score: 2,
-        mismatch_score: -1,
-        gap_score: -2,
-    };
-    let mut graph = cyanea_align::poa::PoaGraph::from_sequence(&sequences[0]);
-    for seq in &sequences[1..] {
-        graph.add_sequence(seq, &scoring).map_err(to_nif_error)?;
-    }
-    Ok(graph.consensus())
+pub fn poa_consensus(sequences: Vec<Vec<u8>>) -> Result<Vec<u8>, NifError> {
+    poa(&sequences, &JobState::new(None))
+}
+
+// ===========================================================================
+// Async variants (results are sent as {:cyanea_result, ref, result})
+// ===========================================================================
+
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn align_batch_dna_async<'a>(
+    env: Env<'a>,
+    pairs: Vec<(Vec<u8>, Vec<u8>)>,
+    mode: String,
+    cancel: Option<ResourceArc<CancelTokenResource>>,
+) -> Result<Reference<'a>, NifError> {
+    let mode = parse_alignment_mode(&mode)?;
+    pool::spawn(env, PoolId::Align, cancel, move |job| align_batch(&pairs, mode, job))
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn poa_consensus_async<'a>(
+    env: Env<'a>,
+    sequences: Vec<Vec<u8>>,
+    cancel: Option<ResourceArc<CancelTokenResource>>,
+) -> Result<Reference<'a>, NifError> {
+    pool::spawn(env, PoolId::Align, cancel, move |job| poa(&sequences, job))
 }
 
 // ===========================================================================
@@ -172,20 +197,20 @@ pub fn poa_consensus(sequences: Vec<Vec<u8>>) -> Result<Vec<u8>, String> {
 // ===========================================================================
 
 #[rustler::nif]
-pub fn parse_cigar(cigar: String) -> Result<Vec<(String, usize)>, String> {
+pub fn parse_cigar(cigar: String) -> Result<Vec<(String, usize)>, NifError> {
     let ops = cyanea_align::cigar::parse_cigar(&cigar).map_err(to_nif_error)?;
     Ok(ops.iter().map(|op| (op.code().to_string(), op.len())).collect())
 }
 
 #[rustler::nif]
-pub fn validate_cigar(cigar: String) -> Result<bool, String> {
+pub fn validate_cigar(cigar: String) -> Result<bool, NifError> {
     let ops = cyanea_align::cigar::parse_cigar(&cigar).map_err(to_nif_error)?;
     cyanea_align::cigar::validate_cigar(&ops).map_err(to_nif_error)?;
     Ok(true)
 }
 
 #[rustler::nif]
-pub fn cigar_stats(cigar: String) -> Result<CigarStatsNif, String> {
+pub fn cigar_stats(cigar: String) -> Result<CigarStatsNif, NifError> {
     let ops = cyanea_align::cigar::parse_cigar(&cigar).map_err(to_nif_error)?;
     let (soft, hard) = cyanea_align::cigar::clipped_bases(&ops);
     Ok(CigarStatsNif {
@@ -206,13 +231,13 @@ pub fn cigar_to_alignment(
     cigar: String,
     query: Vec<u8>,
     target: Vec<u8>,
-) -> Result<(Vec<u8>, Vec<u8>), String> {
+) -> Result<(Vec<u8>, Vec<u8>), NifError> {
     let ops = cyanea_align::cigar::parse_cigar(&cigar).map_err(to_nif_error)?;
     cyanea_align::cigar::cigar_to_alignment(&ops, &query, &target).map_err(to_nif_error)
 }
 
 #[rustler::nif]
-pub fn alignment_to_cigar(query: Vec<u8>, target: Vec<u8>) -> Result<String, String> {
+pub fn alignment_to_cigar(query: Vec<u8>, target: Vec<u8>) -> Result<String, NifError> {
     let ops =
         cyanea_align::cigar::alignment_to_cigar(&query, &target).map_err(to_nif_error)?;
     Ok(cyanea_align::cigar::cigar_string(&ops))
@@ -223,13 +248,13 @@ pub fn generate_md_tag(
     cigar: String,
     query: Vec<u8>,
     reference: Vec<u8>,
-) -> Result<String, String> {
+) -> Result<String, NifError> {
     let ops = cyanea_align::cigar::parse_cigar(&cigar).map_err(to_nif_error)?;
     cyanea_align::cigar::generate_md_tag(&ops, &query, &reference).map_err(to_nif_error)
 }
 
 #[rustler::nif]
-pub fn merge_cigar(cigar: String) -> Result<String, String> {
+pub fn merge_cigar(cigar: String) -> Result<String, NifError> {
     let ops = cyanea_align::cigar::parse_cigar(&cigar).map_err(to_nif_error)?;
     Ok(cyanea_align::cigar::cigar_string(
         &cyanea_align::cigar::merge_adjacent(&ops),
@@ -237,7 +262,7 @@ pub fn merge_cigar(cigar: String) -> Result<String, String> {
 }
 
 #[rustler::nif]
-pub fn reverse_cigar(cigar: String) -> Result<String, String> {
+pub fn reverse_cigar(cigar: String) -> Result<String, NifError> {
     let ops = cyanea_align::cigar::parse_cigar(&cigar).map_err(to_nif_error)?;
     Ok(cyanea_align::cigar::cigar_string(
         &cyanea_align::cigar::reverse_cigar(&ops),
@@ -245,7 +270,7 @@ pub fn reverse_cigar(cigar: String) -> Result<String, String> {
 }
 
 #[rustler::nif]
-pub fn collapse_cigar(cigar: String) -> Result<String, String> {
+pub fn collapse_cigar(cigar: String) -> Result<String, NifError> {
     let ops = cyanea_align::cigar::parse_cigar(&cigar).map_err(to_nif_error)?;
     Ok(cyanea_align::cigar::cigar_string(
         &cyanea_align::cigar::collapse_matches(&ops),
@@ -253,7 +278,7 @@ pub fn collapse_cigar(cigar: String) -> Result<String, String> {
 }
 
 #[rustler::nif]
-pub fn hard_clip_to_soft(cigar: String) -> Result<String, String> {
+pub fn hard_clip_to_soft(cigar: String) -> Result<String, NifError> {
     let ops = cyanea_align::cigar::parse_cigar(&cigar).map_err(to_nif_error)?;
     Ok(cyanea_align::cigar::cigar_string(
         &cyanea_align::cigar::hard_clip_to_soft(&ops),
@@ -261,7 +286,7 @@ pub fn hard_clip_to_soft(cigar: String) -> Result<String, String> {
 }
 
 #[rustler::nif]
-pub fn split_cigar(cigar: String, ref_pos: usize) -> Result<(String, String), String> {
+pub fn split_cigar(cigar: String, ref_pos: usize) -> Result<(String, String), NifError> {
     let ops = cyanea_align::cigar::parse_cigar(&cigar).map_err(to_nif_error)?;
     let (left, right) = cyanea_align::cigar::split_at_reference(&ops, ref_pos);
     Ok((
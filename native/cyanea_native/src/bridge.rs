@@ -4,7 +4,8 @@
 //! Cyanea Labs type.  All `#[module = "..."]` values must match the Elixir
 //! `defstruct` module in `native.ex`.
 
-use rustler::NifStruct;
+use rustler::{Binary, Env, NifRecord, NifStruct, NifUnitEnum, NifUntaggedEnum, OwnedBinary};
+use std::collections::HashMap;
 
 // ── Traits needed for conversions ──────────────────────────────────────────
 
@@ -34,6 +35,25 @@ impl From<cyanea_seq::FastaStats> for FastaStatsNif {
     }
 }
 
+#[derive(Debug, NifStruct)]
+#[module = "Cyanea.Native.FastaRecord"]
+pub struct FastaRecordNif {
+    pub name: String,
+    pub description: String,
+    pub sequence: Vec<u8>,
+}
+
+/// One line of a samtools-compatible `.fai` index.
+#[derive(Debug, Clone, NifStruct)]
+#[module = "Cyanea.Native.FaiEntry"]
+pub struct FaiEntryNif {
+    pub name: String,
+    pub length: u64,
+    pub offset: u64,
+    pub line_bases: u64,
+    pub line_width: u64,
+}
+
 #[derive(Debug, NifStruct)]
 #[module = "Cyanea.Native.FastqRecord"]
 pub struct FastqRecordNif {
@@ -185,10 +205,67 @@ impl From<cyanea_io::GffStats> for GffStatsNif {
 pub struct VcfRecordNif {
     pub chrom: String,
     pub position: u64,
+    pub id: Option<String>,
     pub ref_allele: String,
     pub alt_alleles: Vec<String>,
     pub quality: Option<f64>,
     pub filter: String,
+    pub info: HashMap<String, Option<VcfValueNif>>,
+    pub format: Vec<String>,
+    pub samples: Vec<VcfSampleNif>,
+}
+
+/// A single typed VCF value (INFO or FORMAT), typed by the header `Type=`.
+#[derive(Debug, Clone, NifUntaggedEnum)]
+pub enum VcfScalarNif {
+    Integer(i64),
+    Float(f64),
+    String(String),
+}
+
+/// An INFO value: a flag, a single value (`Number=1`) or a list of values
+/// where missing entries (`.`) are `nil`.
+#[derive(Debug, Clone, NifUntaggedEnum)]
+pub enum VcfValueNif {
+    Flag(bool),
+    Scalar(VcfScalarNif),
+    List(Vec<Option<VcfScalarNif>>),
+}
+
+/// Per-sample genotype data. GT/DP/GQ/AD are decoded; any other FORMAT keys
+/// are kept as raw strings in `other`.
+#[derive(Debug, NifStruct)]
+#[module = "Cyanea.Native.VcfSample"]
+pub struct VcfSampleNif {
+    pub genotype: Option<String>,
+    pub alleles: Vec<Option<u32>>,
+    pub phased: bool,
+    pub depth: Option<i64>,
+    pub genotype_quality: Option<i64>,
+    pub allele_depths: Vec<Option<i64>>,
+    pub other: HashMap<String, String>,
+}
+
+#[derive(Debug, Clone, NifStruct)]
+#[module = "Cyanea.Native.VcfFieldDef"]
+pub struct VcfFieldDefNif {
+    pub id: String,
+    pub number: String,
+    pub value_type: String,
+    pub description: String,
+}
+
+#[derive(Debug, Clone, NifStruct)]
+#[module = "Cyanea.Native.VcfHeader"]
+pub struct VcfHeaderNif {
+    pub fileformat: String,
+    pub samples: Vec<String>,
+    pub contigs: Vec<String>,
+    pub filters: Vec<String>,
+    pub info: Vec<VcfFieldDefNif>,
+    pub format: Vec<VcfFieldDefNif>,
+    /// Every `##` line but `fileformat`, verbatim and without the `##`.
+    pub meta: Vec<String>,
 }
 
 #[derive(Debug, NifStruct)]
@@ -224,8 +301,57 @@ pub struct SamRecordNif {
     pub pos: u64,
     pub mapq: u8,
     pub cigar: String,
+    pub rnext: String,
+    pub pnext: u64,
+    pub tlen: i64,
     pub sequence: String,
     pub quality: String,
+    pub tags: HashMap<String, SamTagValueNif>,
+}
+
+/// Optional SAM field value: `Z` becomes a string, `i` an integer, `f` a
+/// float and `B` an array of either. `A` and `H` come back as
+/// `{:char, "x"}` and `{:hex, "1AE3"}` so writing the record keeps the type.
+#[derive(Debug, NifUntaggedEnum)]
+pub enum SamTagValueNif {
+    Integer(i64),
+    Float(f64),
+    String(String),
+    Char(SamCharTagNif),
+    Hex(SamHexTagNif),
+    IntegerArray(Vec<i64>),
+    FloatArray(Vec<f64>),
+}
+
+/// A SAM `A` (single printable character) tag value, `{:char, value}`.
+#[derive(Debug, NifRecord)]
+#[tag = "char"]
+pub struct SamCharTagNif {
+    pub value: String,
+}
+
+/// A SAM `H` (hex byte array) tag value, `{:hex, value}`.
+#[derive(Debug, NifRecord)]
+#[tag = "hex"]
+pub struct SamHexTagNif {
+    pub value: String,
+}
+
+#[derive(Debug, NifStruct)]
+#[module = "Cyanea.Native.SamFlags"]
+pub struct SamFlagsNif {
+    pub paired: bool,
+    pub proper_pair: bool,
+    pub unmapped: bool,
+    pub mate_unmapped: bool,
+    pub reverse: bool,
+    pub mate_reverse: bool,
+    pub first_in_pair: bool,
+    pub second_in_pair: bool,
+    pub secondary: bool,
+    pub qc_fail: bool,
+    pub duplicate: bool,
+    pub supplementary: bool,
 }
 
 #[derive(Debug, NifStruct)]
@@ -236,6 +362,23 @@ pub struct SamStatsNif {
     pub unmapped: usize,
     pub avg_mapq: f64,
     pub avg_length: f64,
+    pub qc_failed: usize,
+    pub secondary: usize,
+    pub supplementary: usize,
+    pub duplicates: usize,
+    pub duplicate_rate: f64,
+    pub paired: usize,
+    pub read1: usize,
+    pub read2: usize,
+    pub properly_paired: usize,
+    pub properly_paired_fraction: f64,
+    pub both_mapped: usize,
+    pub singletons: usize,
+    pub mate_on_different_chrom: usize,
+    pub insert_size_mean: f64,
+    pub insert_size_sd: f64,
+    pub insert_size_median: f64,
+    pub insert_size_histogram: Vec<(u64, usize)>,
 }
 
 // ===========================================================================
@@ -280,6 +423,77 @@ impl From<cyanea_align::AlignmentResult> for AlignmentResultNif {
     }
 }
 
+/// Summary of a loaded substitution matrix and its gap penalties.
+#[derive(Debug, NifStruct)]
+#[module = "Cyanea.Native.ScoringSchemeInfo"]
+pub struct ScoringSchemeInfoNif {
+    pub name: String,
+    /// Matrix symbols in header order.
+    pub alphabet: String,
+    pub gap_open: i32,
+    pub gap_extend: i32,
+    pub min_score: i32,
+    pub max_score: i32,
+}
+
+/// Read mapper settings.  Seeds seen more than `max_occurrences` times in
+/// the reference are skipped as repeats; `bandwidth` bounds the indel
+/// shift tolerated by extension; `max_insert` is the longest proper pair.
+#[derive(Debug, NifStruct)]
+#[module = "Cyanea.Native.MapOptions"]
+pub struct MapOptionsNif {
+    pub seed_length: usize,
+    pub max_occurrences: usize,
+    pub bandwidth: usize,
+    pub min_score: i32,
+    pub max_insert: usize,
+}
+
+#[derive(Debug, NifStruct)]
+#[module = "Cyanea.Native.MapStats"]
+pub struct MapStatsNif {
+    pub reads: usize,
+    pub mapped: usize,
+    pub properly_paired: usize,
+}
+
+/// Database search settings.  `lambda` and `k` override the Karlin-Altschul
+/// parameters otherwise derived from the scoring scheme.
+#[derive(Debug, NifStruct)]
+#[module = "Cyanea.Native.DbSearchOptions"]
+pub struct DbSearchOptionsNif {
+    pub max_hits: usize,
+    pub max_evalue: f64,
+    pub lambda: Option<f64>,
+    pub k: Option<f64>,
+}
+
+#[derive(Debug, NifStruct)]
+#[module = "Cyanea.Native.DbSearchHit"]
+pub struct DbSearchHitNif {
+    pub record: String,
+    pub description: String,
+    pub length: usize,
+    pub score: i32,
+    pub bit_score: f64,
+    pub evalue: f64,
+    pub alignment: AlignmentResultNif,
+}
+
+/// Database search result.  `statistics` says where λ and K came from:
+/// `"gapped"`, `"ungapped"` or `"custom"`.
+#[derive(Debug, NifStruct)]
+#[module = "Cyanea.Native.DbSearch"]
+pub struct DbSearchNif {
+    pub query_length: usize,
+    pub db_records: usize,
+    pub db_length: usize,
+    pub lambda: f64,
+    pub k: f64,
+    pub statistics: String,
+    pub hits: Vec<DbSearchHitNif>,
+}
+
 #[derive(Debug, NifStruct)]
 #[module = "Cyanea.Native.CigarStats"]
 pub struct CigarStatsNif {
@@ -303,6 +517,73 @@ pub struct MsaResultNif {
     pub conservation: f64,
 }
 
+/// Options for `progressive_msa_with_scheme`: `guide_tree` is `"upgma"` or
+/// `"nj"`.
+#[derive(Debug, NifStruct)]
+#[module = "Cyanea.Native.MsaOptions"]
+pub struct MsaOptionsNif {
+    pub guide_tree: String,
+    pub refine_rounds: usize,
+}
+
+/// A multiple alignment with its guide tree (Newick) and per-column
+/// conservation and entropy scores.
+#[derive(Debug, NifStruct)]
+#[module = "Cyanea.Native.MsaReport"]
+pub struct MsaReportNif {
+    pub aligned: Vec<Vec<u8>>,
+    pub names: Vec<String>,
+    pub n_sequences: usize,
+    pub n_columns: usize,
+    pub guide_tree: String,
+    pub conservation: Vec<f64>,
+    pub entropy: Vec<f64>,
+    pub consensus: String,
+    pub sp_score: i64,
+}
+
+/// An existing alignment: bare rows, `{name, row}` pairs, or a file.
+#[derive(Debug, NifUntaggedEnum)]
+pub enum MsaSourceNif {
+    Rows(Vec<Vec<u8>>),
+    Named(Vec<(String, Vec<u8>)>),
+    File(MsaFileNif),
+}
+
+/// An alignment file; `format` is `"stockholm"` or `"clustal"`.
+#[derive(Debug, NifStruct)]
+#[module = "Cyanea.Native.MsaFile"]
+pub struct MsaFileNif {
+    pub path: String,
+    pub format: String,
+}
+
+/// Options for `msa_add_sequences`: names of the new sequences, whether to
+/// drop their insertions relative to the alignment, and an optional DP band.
+#[derive(Debug, NifStruct)]
+#[module = "Cyanea.Native.MsaAddOptions"]
+pub struct MsaAddOptionsNif {
+    pub names: Vec<String>,
+    pub keep_length: bool,
+    pub band: Option<usize>,
+}
+
+/// An alignment extended with new sequences or merged from two alignments.
+/// `inserted_columns` counts the columns added to the first alignment.
+#[derive(Debug, NifStruct)]
+#[module = "Cyanea.Native.MsaMerge"]
+pub struct MsaMergeNif {
+    pub aligned: Vec<Vec<u8>>,
+    pub names: Vec<String>,
+    pub n_sequences: usize,
+    pub n_columns: usize,
+    pub inserted_columns: usize,
+    pub score: f64,
+    pub conservation: Vec<f64>,
+    pub entropy: Vec<f64>,
+    pub consensus: String,
+}
+
 // ===========================================================================
 // cyanea-stats
 // ===========================================================================
@@ -422,23 +703,27 @@ pub struct ExpressionSummaryNif {
 
 #[derive(Debug, NifStruct)]
 #[module = "Cyanea.Native.KMeansResult"]
-pub struct KMeansResultNif {
+pub struct KMeansResultNif<'a> {
     pub labels: Vec<usize>,
-    pub centroids: Vec<f64>,
+    pub centroids: MatrixOutput<'a>,
     pub n_features: usize,
     pub inertia: f64,
     pub n_iter: usize,
 }
 
-impl From<cyanea_ml::KMeansResult> for KMeansResultNif {
-    fn from(r: cyanea_ml::KMeansResult) -> Self {
-        Self {
+impl<'a> KMeansResultNif<'a> {
+    pub fn new(
+        r: cyanea_ml::KMeansResult,
+        env: Env<'a>,
+        form: MatrixForm,
+    ) -> Result<Self, NifError> {
+        Ok(Self {
             labels: r.labels,
-            centroids: r.centroids,
+            centroids: form.matrix(env, r.centroids, r.n_features)?,
             n_features: r.n_features,
             inertia: r.inertia,
             n_iter: r.n_iter,
-        }
+        })
     }
 }
 
@@ -460,65 +745,65 @@ impl From<cyanea_ml::DbscanResult> for DbscanResultNif {
 
 #[derive(Debug, NifStruct)]
 #[module = "Cyanea.Native.PcaResult"]
-pub struct PcaResultNif {
-    pub transformed: Vec<f64>,
+pub struct PcaResultNif<'a> {
+    pub transformed: MatrixOutput<'a>,
     pub explained_variance: Vec<f64>,
     pub explained_variance_ratio: Vec<f64>,
-    pub components: Vec<f64>,
+    pub components: MatrixOutput<'a>,
     pub n_components: usize,
     pub n_features: usize,
 }
 
-impl From<cyanea_ml::PcaResult> for PcaResultNif {
-    fn from(r: cyanea_ml::PcaResult) -> Self {
-        Self {
-            transformed: r.transformed,
+impl<'a> PcaResultNif<'a> {
+    pub fn new(r: cyanea_ml::PcaResult, env: Env<'a>, form: MatrixForm) -> Result<Self, NifError> {
+        Ok(Self {
+            transformed: form.matrix(env, r.transformed, r.n_components)?,
             explained_variance: r.explained_variance,
             explained_variance_ratio: r.explained_variance_ratio,
-            components: r.components,
+            components: form.matrix(env, r.components, r.n_features)?,
             n_components: r.n_components,
             n_features: r.n_features,
-        }
+        })
     }
 }
 
 #[derive(Debug, NifStruct)]
 #[module = "Cyanea.Native.TsneResult"]
-pub struct TsneResultNif {
-    pub embedding: Vec<f64>,
+pub struct TsneResultNif<'a> {
+    pub embedding: MatrixOutput<'a>,
     pub n_samples: usize,
     pub n_components: usize,
     pub kl_divergence: f64,
 }
 
-impl From<cyanea_ml::TsneResult> for TsneResultNif {
-    fn from(r: cyanea_ml::TsneResult) -> Self {
-        Self {
-            embedding: r.embedding,
+impl<'a> TsneResultNif<'a> {
+    pub fn new(r: cyanea_ml::TsneResult, env: Env<'a>, form: MatrixForm) -> Result<Self, NifError> {
+        Ok(Self {
+            embedding: form.matrix(env, r.embedding, r.n_components)?,
             n_samples: r.n_samples,
             n_components: r.n_components,
             kl_divergence: r.kl_divergence,
-        }
+        })
     }
 }
 
 #[derive(Debug, NifStruct)]
 #[module = "Cyanea.Native.UmapResult"]
-pub struct UmapResultNif {
-    pub embedding: Vec<f64>,
+pub struct UmapResultNif<'a> {
+    pub embedding: MatrixOutput<'a>,
     pub n_samples: usize,
     pub n_components: usize,
     pub n_epochs: usize,
 }
 
-impl From<cyanea_ml::UmapResult> for UmapResultNif {
-    fn from(r: cyanea_ml::UmapResult) -> Self {
-        Self {
-            embedding: r.embedding,
+impl<'a> UmapResultNif<'a> {
+    pub fn new(r: cyanea_ml::UmapResult, env: Env<'a>, form: MatrixForm) -> Result<Self, NifError> {
+        Ok(Self {
+            embedding: form.matrix(env, r.embedding, r.n_components)?,
             n_samples: r.n_samples,
             n_components: r.n_components,
             n_epochs: r.n_epochs,
-        }
+        })
     }
 }
 
@@ -537,6 +822,16 @@ pub struct LinearRegressionResultNif {
     pub r_squared: f64,
 }
 
+#[derive(Debug, NifStruct)]
+#[module = "Cyanea.Native.RandomForestInfo"]
+pub struct RandomForestInfoNif {
+    pub n_trees: usize,
+    pub n_features: usize,
+    pub n_classes: usize,
+    pub n_samples: usize,
+    pub oob_error: Option<f64>,
+}
+
 // ===========================================================================
 // cyanea-chem
 // ===========================================================================
@@ -741,6 +1036,722 @@ pub struct BedGraphStatsNif {
     pub chrom_count: usize,
 }
 
+// ===========================================================================
+// Async job pools
+// ===========================================================================
+
+#[derive(Debug, NifStruct)]
+#[module = "Cyanea.Native.AsyncPoolStats"]
+pub struct AsyncPoolStatsNif {
+    pub name: String,
+    pub max_concurrency: usize,
+    pub queued: usize,
+    pub running: usize,
+}
+
+// ===========================================================================
+// FASTQ processing
+// ===========================================================================
+
+/// Steps of the FASTQ trimming pipeline; a zero/`None` value disables a step.
+#[derive(Debug, NifStruct)]
+#[module = "Cyanea.Native.TrimOptions"]
+pub struct TrimOptionsNif {
+    /// Built-in adapters: `"none"`, `"illumina"`, `"nextera"`, or `"all"`.
+    pub adapter_preset: String,
+    /// Extra adapter sequences to trim.
+    pub adapters: Vec<String>,
+    pub adapter_min_overlap: usize,
+    pub adapter_error_rate: f64,
+    pub poly_g_min_length: usize,
+    pub window_size: usize,
+    pub window_quality: u8,
+    pub trailing_quality: u8,
+    pub max_n: Option<usize>,
+    pub min_length: usize,
+}
+
+/// Per-step counts of a trimming run.  Trim counts are per read; filter and
+/// total counts are per record, i.e. per pair for paired input.
+#[derive(Debug, Default, NifStruct)]
+#[module = "Cyanea.Native.FastqTrimStats"]
+pub struct FastqTrimStatsNif {
+    pub paired: bool,
+    pub records_in: u64,
+    pub records_out: u64,
+    pub bases_in: u64,
+    pub bases_out: u64,
+    pub adapter_trimmed_reads: u64,
+    pub adapter_trimmed_bases: u64,
+    pub poly_g_trimmed_reads: u64,
+    pub poly_g_trimmed_bases: u64,
+    pub quality_trimmed_reads: u64,
+    pub quality_trimmed_bases: u64,
+    pub too_many_n: u64,
+    pub too_short: u64,
+}
+
+/// One sample sheet row: the i7/i5 index barcodes (read from the FASTQ
+/// header) or the inline barcode at the start of read 1.
+#[derive(Debug, NifStruct)]
+#[module = "Cyanea.Native.SampleBarcode"]
+pub struct SampleBarcodeNif {
+    pub sample: String,
+    pub i7: Option<String>,
+    pub i5: Option<String>,
+    pub inline: Option<String>,
+}
+
+#[derive(Debug, NifStruct)]
+#[module = "Cyanea.Native.DemuxSample"]
+pub struct DemuxSampleNif {
+    pub sample: String,
+    pub records: u64,
+    /// Records whose barcodes matched without any edits.
+    pub perfect: u64,
+    pub paths: Vec<String>,
+}
+
+/// Outcome of a demultiplexing run; counts are per record (per pair for
+/// paired input).  Ambiguous records are also counted as undetermined.
+#[derive(Debug, NifStruct)]
+#[module = "Cyanea.Native.DemuxResult"]
+pub struct DemuxResultNif {
+    pub records: u64,
+    pub undetermined: u64,
+    pub ambiguous: u64,
+    pub undetermined_paths: Vec<String>,
+    pub samples: Vec<DemuxSampleNif>,
+}
+
+/// Phred quality distribution at one read position (1-based), as the
+/// whiskers and box of a FastQC per-base quality plot.
+#[derive(Debug, NifStruct)]
+#[module = "Cyanea.Native.PositionQuality"]
+pub struct PositionQualityNif {
+    pub position: usize,
+    pub mean: f64,
+    pub p10: u8,
+    pub q1: u8,
+    pub median: u8,
+    pub q3: u8,
+    pub p90: u8,
+}
+
+/// Base fractions at one read position (1-based).
+#[derive(Debug, NifStruct)]
+#[module = "Cyanea.Native.BaseContent"]
+pub struct BaseContentNif {
+    pub position: usize,
+    pub a: f64,
+    pub c: f64,
+    pub g: f64,
+    pub t: f64,
+    pub n: f64,
+}
+
+#[derive(Debug, NifStruct)]
+#[module = "Cyanea.Native.OverrepresentedSequence"]
+pub struct OverrepresentedSequenceNif {
+    pub sequence: String,
+    pub count: u64,
+    pub fraction: f64,
+    pub possible_source: Option<String>,
+}
+
+/// Cumulative fraction of reads in which `sequence` has been seen by each
+/// position (index 0 is position 1).
+#[derive(Debug, NifStruct)]
+#[module = "Cyanea.Native.AdapterContent"]
+pub struct AdapterContentNif {
+    pub name: String,
+    pub sequence: String,
+    pub fractions: Vec<f64>,
+}
+
+#[derive(Debug, NifStruct)]
+#[module = "Cyanea.Native.FastqReport"]
+pub struct FastqReportNif {
+    pub summary: FastqStatsNif,
+    pub per_position_quality: Vec<PositionQualityNif>,
+    pub per_position_content: Vec<BaseContentNif>,
+    /// Reads per GC percentage, index 0..=100.
+    pub gc_distribution: Vec<u64>,
+    /// `{length, reads}`, by length.
+    pub length_distribution: Vec<(usize, u64)>,
+    /// `{level, fraction of reads}` for levels "1".."9", ">10", ">50",
+    /// ">100", ">500", ">1k", ">5k", ">10k".
+    pub duplication_levels: Vec<(String, f64)>,
+    /// Fraction of reads left after removing duplicates.
+    pub deduplicated_fraction: f64,
+    pub overrepresented: Vec<OverrepresentedSequenceNif>,
+    pub adapter_content: Vec<AdapterContentNif>,
+}
+
+// ===========================================================================
+// K-mer counting
+// ===========================================================================
+
+/// Canonical k-mer counts with the count spectrum and, when the spectrum
+/// has a clear coverage peak, genome size and heterozygosity estimates.
+#[derive(Debug, NifStruct)]
+#[module = "Cyanea.Native.KmerCounts"]
+pub struct KmerCountsNif {
+    pub k: usize,
+    pub total_kmers: u64,
+    pub distinct_kmers: usize,
+    /// Most frequent k-mers, highest count first.
+    pub top_kmers: Vec<(String, u64)>,
+    /// `{count, number of distinct k-mers seen that often}`, by count.
+    pub spectrum: Vec<(u64, u64)>,
+    pub peak_coverage: Option<u64>,
+    pub genome_size: Option<f64>,
+    pub heterozygosity: Option<f64>,
+}
+
+// ===========================================================================
+// MinHash sketches
+// ===========================================================================
+
+/// A bottom-k MinHash sketch: the `sketch_size` smallest k-mer hashes,
+/// sorted ascending, with the parameters needed to compare it.
+#[derive(Debug, Clone, NifStruct)]
+#[module = "Cyanea.Native.MinHashSketch"]
+pub struct MinHashSketchNif {
+    pub name: String,
+    pub k: usize,
+    pub sketch_size: usize,
+    pub seed: u32,
+    pub hash_function: String,
+    /// `"dna"` for nucleotide sketches (DNA, RNA or IUPAC input) or `"protein"`.
+    pub alphabet: String,
+    /// Number of residues sketched, used for p-values.
+    pub length: u64,
+    pub hashes: Vec<u64>,
+}
+
+#[derive(Debug, NifStruct)]
+#[module = "Cyanea.Native.MinHashDistance"]
+pub struct MinHashDistanceNif {
+    pub query: String,
+    pub reference: String,
+    pub jaccard: f64,
+    /// Estimated fraction of the query's k-mers found in the reference.
+    pub containment: f64,
+    pub mash_distance: f64,
+    pub ani: f64,
+    pub p_value: f64,
+    pub shared_hashes: usize,
+    pub compared_hashes: usize,
+}
+
+// ===========================================================================
+// Oligo thermodynamics & primer design
+// ===========================================================================
+
+/// Reaction conditions for melting temperatures.
+#[derive(Debug, Clone, NifStruct)]
+#[module = "Cyanea.Native.OligoConditions"]
+pub struct OligoConditionsNif {
+    /// Monovalent cations (Na+, K+), mM.
+    pub na_mm: f64,
+    pub mg_mm: f64,
+    pub dntp_mm: f64,
+    /// Oligo strand concentration, nM.
+    pub oligo_nm: f64,
+}
+
+/// Nearest-neighbour thermodynamics of one oligo.  ΔH is in kcal/mol, ΔS
+/// in cal/(K·mol) and every ΔG in kcal/mol at 37 °C.
+#[derive(Debug, NifStruct)]
+#[module = "Cyanea.Native.OligoThermo"]
+pub struct OligoThermoNif {
+    pub length: usize,
+    pub tm: f64,
+    pub gc_content: f64,
+    pub delta_h: f64,
+    pub delta_s: f64,
+    pub delta_g: f64,
+    pub hairpin_dg: f64,
+    pub self_dimer_dg: f64,
+}
+
+/// Primer design constraints.  GC bounds are fractions; ΔG limits are the
+/// most negative hairpin / dimer ΔG (kcal/mol) a primer may have.
+#[derive(Debug, NifStruct)]
+#[module = "Cyanea.Native.PrimerDesignOptions"]
+pub struct PrimerDesignOptionsNif {
+    pub min_length: usize,
+    pub max_length: usize,
+    pub opt_tm: f64,
+    pub min_tm: f64,
+    pub max_tm: f64,
+    pub min_gc: f64,
+    pub max_gc: f64,
+    pub max_tm_diff: f64,
+    pub min_product: usize,
+    pub max_product: usize,
+    pub max_hairpin_dg: f64,
+    pub max_dimer_dg: f64,
+    pub num_return: usize,
+    pub conditions: OligoConditionsNif,
+}
+
+/// One designed primer, written 5'->3'.  `start` is the first base of its
+/// binding site on the template's forward strand.
+#[derive(Debug, NifStruct)]
+#[module = "Cyanea.Native.Primer"]
+pub struct PrimerNif {
+    pub sequence: String,
+    pub start: usize,
+    pub length: usize,
+    pub tm: f64,
+    pub gc_content: f64,
+    pub hairpin_dg: f64,
+    pub self_dimer_dg: f64,
+}
+
+#[derive(Debug, NifStruct)]
+#[module = "Cyanea.Native.PrimerDesign"]
+pub struct PrimerDesignNif {
+    pub forward: PrimerNif,
+    pub reverse: PrimerNif,
+    pub product_size: usize,
+    pub tm_difference: f64,
+    pub pair_dimer_dg: f64,
+    /// Lower is better.
+    pub penalty: f64,
+}
+
+// ===========================================================================
+// Multi-pattern search
+// ===========================================================================
+
+/// One pattern occurrence.  Coordinates are 0-based, end-exclusive and on
+/// the forward strand of the record whichever strand matched.
+#[derive(Debug, NifStruct)]
+#[module = "Cyanea.Native.PatternHit"]
+pub struct PatternHitNif {
+    pub pattern: String,
+    pub record: String,
+    /// `"+"` or `"-"` (the pattern's reverse complement matched).
+    pub strand: String,
+    pub start: usize,
+    pub end: usize,
+    pub distance: usize,
+}
+
+// ===========================================================================
+// Restriction digests & in-silico PCR
+// ===========================================================================
+
+/// A built-in restriction enzyme.  Cut offsets count from the first base
+/// of the site on the top strand, REBASE style (`G^AATTC` is `1, 5`).
+#[derive(Debug, NifStruct)]
+#[module = "Cyanea.Native.RestrictionEnzyme"]
+pub struct RestrictionEnzymeNif {
+    pub name: String,
+    pub site: String,
+    pub top_cut: i64,
+    pub bottom_cut: i64,
+}
+
+/// Where one enzyme cuts, in forward-strand coordinates; a cut at `n`
+/// falls between bases `n - 1` and `n`.
+#[derive(Debug, NifStruct)]
+#[module = "Cyanea.Native.RestrictionCut"]
+pub struct RestrictionCutNif {
+    pub enzyme: String,
+    pub site_start: usize,
+    pub strand: String,
+    pub top_cut: usize,
+    pub bottom_cut: usize,
+}
+
+/// A fragment between two top-strand cuts.  On a circular sequence the
+/// fragment through the origin has `end < start`.
+#[derive(Debug, NifStruct)]
+#[module = "Cyanea.Native.DigestFragment"]
+pub struct DigestFragmentNif {
+    pub start: usize,
+    pub end: usize,
+    pub length: usize,
+    /// Enzyme(s) that made the left end, `/`-joined; nil at a sequence end.
+    pub left_enzyme: Option<String>,
+    pub right_enzyme: Option<String>,
+}
+
+#[derive(Debug, NifStruct)]
+#[module = "Cyanea.Native.RestrictionDigest"]
+pub struct RestrictionDigestNif {
+    pub length: usize,
+    pub circular: bool,
+    pub cuts: Vec<RestrictionCutNif>,
+    pub fragments: Vec<DigestFragmentNif>,
+}
+
+#[derive(Debug, NifStruct)]
+#[module = "Cyanea.Native.PrimerPair"]
+pub struct PrimerPairNif {
+    pub name: String,
+    pub forward: String,
+    pub reverse: String,
+}
+
+/// A predicted PCR product.  `start`/`end` are forward-strand, end-exclusive
+/// coordinates (`end < start` when it runs through a circular origin).
+#[derive(Debug, NifStruct)]
+#[module = "Cyanea.Native.Amplicon"]
+pub struct AmpliconNif {
+    pub primer_pair: String,
+    pub record: String,
+    /// `"+"` when the forward primer binds the top strand, else `"-"`.
+    pub strand: String,
+    pub start: usize,
+    pub end: usize,
+    pub length: usize,
+    pub forward_mismatches: usize,
+    pub reverse_mismatches: usize,
+    /// Product 5'->3' from the forward primer, primers included.
+    pub product: Vec<u8>,
+}
+
+// ===========================================================================
+// Numeric matrices
+// ===========================================================================
+
+/// Element type of a [`MatrixNif`] binary.
+#[derive(Debug, Clone, Copy, PartialEq, NifUnitEnum)]
+pub enum DTypeNif {
+    F32,
+    F64,
+}
+
+impl DTypeNif {
+    pub fn width(self) -> usize {
+        match self {
+            Self::F32 => 4,
+            Self::F64 => 8,
+        }
+    }
+}
+
+/// Row-major matrix backed by a little-endian f32/f64 binary — the layout
+/// `Nx.to_binary/1` produces for `{:f, 32}` and `{:f, 64}` tensors.
+#[derive(NifStruct)]
+#[module = "Cyanea.Native.Matrix"]
+pub struct MatrixNif<'a> {
+    pub data: Binary<'a>,
+    pub shape: (usize, usize),
+    pub dtype: DTypeNif,
+}
+
+impl std::fmt::Debug for MatrixNif<'_> {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        f.debug_struct("MatrixNif")
+            .field("shape", &self.shape)
+            .field("dtype", &self.dtype)
+            .finish_non_exhaustive()
+    }
+}
+
+impl<'a> MatrixNif<'a> {
+    /// Decode all elements (row-major), checking the binary against the shape.
+    pub fn values(&self) -> Result<Vec<f64>, NifError> {
+        let (rows, cols) = self.shape;
+        let bytes = self.data.as_slice();
+        let expected = rows
+            .checked_mul(cols)
+            .and_then(|n| n.checked_mul(self.dtype.width()));
+        if expected != Some(bytes.len()) {
+            return Err(NifError::invalid(format!(
+                "matrix binary has {} bytes but a {rows}x{cols} {:?} matrix needs {}",
+                bytes.len(),
+                self.dtype,
+                rows.saturating_mul(cols).saturating_mul(self.dtype.width())
+            )));
+        }
+        Ok(match self.dtype {
+            DTypeNif::F32 => bytes
+                .chunks_exact(4)
+                .map(|b| f32::from_le_bytes(b.try_into().unwrap()) as f64)
+                .collect(),
+            DTypeNif::F64 => bytes
+                .chunks_exact(8)
+                .map(|b| f64::from_le_bytes(b.try_into().unwrap()))
+                .collect(),
+        })
+    }
+
+    /// Encode row-major `values` as a new binary matrix.
+    pub fn encode(
+        env: Env<'a>,
+        values: &[f64],
+        shape: (usize, usize),
+        dtype: DTypeNif,
+    ) -> Result<Self, NifError> {
+        let mut out = OwnedBinary::new(values.len() * dtype.width())
+            .ok_or_else(|| NifError::internal("failed to allocate binary"))?;
+        let buf = out.as_mut_slice();
+        match dtype {
+            DTypeNif::F32 => {
+                for (dst, v) in buf.chunks_exact_mut(4).zip(values) {
+                    dst.copy_from_slice(&(*v as f32).to_le_bytes());
+                }
+            }
+            DTypeNif::F64 => {
+                for (dst, v) in buf.chunks_exact_mut(8).zip(values) {
+                    dst.copy_from_slice(&v.to_le_bytes());
+                }
+            }
+        }
+        Ok(Self {
+            data: out.release(env),
+            shape,
+            dtype,
+        })
+    }
+}
+
+/// Numeric NIF argument: a binary matrix, a flat row-major list, or a list
+/// of rows.
+#[derive(Debug, NifUntaggedEnum)]
+pub enum MatrixInput<'a> {
+    Matrix(MatrixNif<'a>),
+    Flat(Vec<f64>),
+    Rows(Vec<Vec<f64>>),
+}
+
+/// Numeric NIF result, returned in the representation of the input it was
+/// computed from (see [`MatrixForm`]).
+#[derive(Debug, NifUntaggedEnum)]
+pub enum MatrixOutput<'a> {
+    Matrix(MatrixNif<'a>),
+    Flat(Vec<f64>),
+    Rows(Vec<Vec<f64>>),
+}
+
+/// How a [`MatrixInput`] was encoded, so results can be encoded the same way.
+#[derive(Debug, Clone, Copy)]
+pub enum MatrixForm {
+    Binary(DTypeNif),
+    Flat,
+    Rows,
+}
+
+impl MatrixInput<'_> {
+    pub fn form(&self) -> MatrixForm {
+        match self {
+            Self::Matrix(m) => MatrixForm::Binary(m.dtype),
+            Self::Flat(_) => MatrixForm::Flat,
+            Self::Rows(_) => MatrixForm::Rows,
+        }
+    }
+
+    /// `(rows, cols)` for binary matrices and lists of rows; `None` for
+    /// flat lists, whose shape is given separately.
+    pub fn shape(&self) -> Option<(usize, usize)> {
+        match self {
+            Self::Matrix(m) => Some(m.shape),
+            Self::Flat(_) => None,
+            Self::Rows(rows) => Some((rows.len(), rows.first().map_or(0, Vec::len))),
+        }
+    }
+
+    /// All elements in row-major order, whatever the shape.
+    pub fn into_values(self) -> Result<Vec<f64>, NifError> {
+        match self {
+            Self::Matrix(m) => m.values(),
+            Self::Flat(values) => Ok(values),
+            Self::Rows(rows) => Ok(rows.concat()),
+        }
+    }
+
+    /// Row-major elements of a matrix with `n_cols` columns.
+    pub fn into_flat(self, n_cols: usize) -> Result<Vec<f64>, NifError> {
+        if n_cols == 0 {
+            return Err(NifError::invalid("n_features must be > 0"));
+        }
+        match &self {
+            Self::Matrix(m) if m.shape.1 != n_cols => {
+                return Err(NifError::invalid(format!(
+                    "matrix has {} columns but n_features is {n_cols}",
+                    m.shape.1
+                )));
+            }
+            Self::Flat(values) if values.len() % n_cols != 0 => {
+                return Err(NifError::invalid(format!(
+                    "data length {} is not divisible by n_features {n_cols}",
+                    values.len()
+                )));
+            }
+            Self::Rows(rows) => {
+                if let Some((i, row)) = rows.iter().enumerate().find(|(_, r)| r.len() != n_cols) {
+                    return Err(NifError::invalid(format!(
+                        "row {i} has {} values but n_features is {n_cols}",
+                        row.len()
+                    )));
+                }
+            }
+            _ => {}
+        }
+        self.into_values()
+    }
+
+    /// The matrix as a list of rows. Flat lists are rejected since their
+    /// row length is unknown.
+    pub fn into_rows(self) -> Result<Vec<Vec<f64>>, NifError> {
+        match self {
+            Self::Matrix(m) => {
+                let cols = m.shape.1.max(1);
+                Ok(m.values()?.chunks(cols).map(<[f64]>::to_vec).collect())
+            }
+            Self::Flat(_) => Err(NifError::invalid("expected a matrix or a list of rows, got a flat list")),
+            Self::Rows(rows) => Ok(rows),
+        }
+    }
+}
+
+impl MatrixForm {
+    /// Encode a row-major matrix with `n_cols` columns.
+    pub fn matrix(
+        self,
+        env: Env<'_>,
+        values: Vec<f64>,
+        n_cols: usize,
+    ) -> Result<MatrixOutput<'_>, NifError> {
+        let n_rows = if n_cols == 0 { 0 } else { values.len() / n_cols };
+        Ok(match self {
+            Self::Binary(dtype) => {
+                MatrixOutput::Matrix(MatrixNif::encode(env, &values, (n_rows, n_cols), dtype)?)
+            }
+            Self::Flat => MatrixOutput::Flat(values),
+            Self::Rows => {
+                MatrixOutput::Rows(values.chunks(n_cols.max(1)).map(<[f64]>::to_vec).collect())
+            }
+        })
+    }
+
+    /// Encode one value per row: an `{n, 1}` binary matrix or a flat list.
+    pub fn vector(self, env: Env<'_>, values: Vec<f64>) -> Result<MatrixOutput<'_>, NifError> {
+        match self {
+            Self::Binary(_) => self.matrix(env, values, 1),
+            Self::Flat | Self::Rows => Ok(MatrixOutput::Flat(values)),
+        }
+    }
+}
+
+// ===========================================================================
+// Errors
+// ===========================================================================
+
+pub mod error_kind {
+    rustler::atoms! {
+        io,
+        parse,
+        invalid_input,
+        not_found,
+        unsupported,
+        cancelled,
+        internal,
+    }
+}
+
+/// Error term returned by every fallible NIF as
+/// `{:error, %Cyanea.Native.Error{}}`.
+///
+/// `kind` keeps the error variant; parsers also fill in `file`, `line`
+/// (1-based) and/or `offset` (byte offset) so callers can point at the
+/// offending input.
+#[derive(Debug, NifStruct)]
+#[module = "Cyanea.Native.Error"]
+pub struct NifError {
+    pub kind: rustler::Atom,
+    pub message: String,
+    pub file: Option<String>,
+    pub line: Option<u64>,
+    pub offset: Option<u64>,
+}
+
+impl NifError {
+    pub fn new(kind: rustler::Atom, message: impl Into<String>) -> Self {
+        Self {
+            kind,
+            message: message.into(),
+            file: None,
+            line: None,
+            offset: None,
+        }
+    }
+
+    pub fn io(message: impl Into<String>) -> Self {
+        Self::new(error_kind::io(), message)
+    }
+
+    pub fn parse(message: impl Into<String>) -> Self {
+        Self::new(error_kind::parse(), message)
+    }
+
+    pub fn invalid(message: impl Into<String>) -> Self {
+        Self::new(error_kind::invalid_input(), message)
+    }
+
+    pub fn not_found(message: impl Into<String>) -> Self {
+        Self::new(error_kind::not_found(), message)
+    }
+
+    pub fn unsupported(message: impl Into<String>) -> Self {
+        Self::new(error_kind::unsupported(), message)
+    }
+
+    pub fn cancelled(message: impl Into<String>) -> Self {
+        Self::new(error_kind::cancelled(), message)
+    }
+
+    pub fn internal(message: impl Into<String>) -> Self {
+        Self::new(error_kind::internal(), message)
+    }
+
+    pub fn with_file(mut self, file: impl Into<String>) -> Self {
+        self.file = Some(file.into());
+        self
+    }
+
+    pub fn at_line(mut self, line: u64) -> Self {
+        self.line = Some(line);
+        self
+    }
+
+    pub fn at_offset(mut self, offset: u64) -> Self {
+        self.offset = Some(offset);
+        self
+    }
+}
+
+impl std::fmt::Display for NifError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        if let Some(file) = &self.file {
+            write!(f, "{file}: ")?;
+        }
+        if let Some(line) = self.line {
+            write!(f, "line {line}: ")?;
+        }
+        f.write_str(&self.message)
+    }
+}
+
+impl From<std::io::Error> for NifError {
+    fn from(e: std::io::Error) -> Self {
+        match e.kind() {
+            std::io::ErrorKind::NotFound => Self::not_found(e.to_string()),
+            std::io::ErrorKind::InvalidData | std::io::ErrorKind::UnexpectedEof => {
+                Self::parse(e.to_string())
+            }
+            _ => Self::io(e.to_string()),
+        }
+    }
+}
+
 // ===========================================================================
 // Helper: structure_to_pdb_info
 // ===========================================================================
@@ -8,7 +8,7 @@ use crate::to_nif_error;
 // ===========================================================================
 
 #[rustler::nif]
-pub fn smiles_properties(smiles: String) -> Result<MolecularPropertiesNif, String> {
+pub fn smiles_properties(smiles: String) -> Result<MolecularPropertiesNif, NifError> {
     let mol = cyanea_chem::parse_smiles(&smiles).map_err(to_nif_error)?;
     let props = cyanea_chem::compute_properties(&mol);
     Ok(MolecularPropertiesNif {
@@ -30,7 +30,7 @@ pub fn smiles_fingerprint(
     smiles: String,
     radius: usize,
     nbits: usize,
-) -> Result<Vec<u8>, String> {
+) -> Result<Vec<u8>, NifError> {
     let mol = cyanea_chem::parse_smiles(&smiles).map_err(to_nif_error)?;
     let fp = cyanea_chem::morgan_fingerprint(&mol, radius, nbits);
     Ok(fingerprint_to_bytes(&fp))
@@ -42,7 +42,7 @@ pub fn tanimoto(
     smiles_b: String,
     radius: usize,
     nbits: usize,
-) -> Result<f64, String> {
+) -> Result<f64, NifError> {
     let mol_a = cyanea_chem::parse_smiles(&smiles_a).map_err(to_nif_error)?;
     let mol_b = cyanea_chem::parse_smiles(&smiles_b).map_err(to_nif_error)?;
     let fp_a = cyanea_chem::morgan_fingerprint(&mol_a, radius, nbits);
@@ -51,7 +51,7 @@ pub fn tanimoto(
 }
 
 #[rustler::nif]
-pub fn smiles_substructure(target: String, pattern: String) -> Result<bool, String> {
+pub fn smiles_substructure(target: String, pattern: String) -> Result<bool, NifError> {
     let target_mol = cyanea_chem::parse_smiles(&target).map_err(to_nif_error)?;
     let pattern_mol = cyanea_chem::parse_smiles(&pattern).map_err(to_nif_error)?;
     Ok(cyanea_chem::has_substructure(&target_mol, &pattern_mol))
@@ -62,14 +62,14 @@ pub fn smiles_substructure(target: String, pattern: String) -> Result<bool, Stri
 // ===========================================================================
 
 #[rustler::nif]
-pub fn canonical_smiles(smiles: String) -> Result<String, String> {
+pub fn canonical_smiles(smiles: String) -> Result<String, NifError> {
     let mol = cyanea_chem::parse_smiles(&smiles).map_err(to_nif_error)?;
     Ok(cyanea_chem::canonical_smiles(&mol))
 }
 
 #[rustler::nif(schedule = "DirtyCpu")]
-pub fn parse_sdf_file(path: String) -> Result<Vec<SdfMoleculeNif>, String> {
-    let contents = std::fs::read_to_string(&path).map_err(|e| e.to_string())?;
+pub fn parse_sdf_file(path: String) -> Result<Vec<SdfMoleculeNif>, NifError> {
+    let contents = std::fs::read_to_string(&path).map_err(|e| NifError::from(e).with_file(&path))?;
     let molecules = cyanea_chem::parse_sdf(&contents);
     let mut results = Vec::new();
     for mol_result in molecules {
@@ -87,7 +87,7 @@ pub fn parse_sdf_file(path: String) -> Result<Vec<SdfMoleculeNif>, String> {
 }
 
 #[rustler::nif]
-pub fn maccs_fingerprint(smiles: String) -> Result<Vec<u8>, String> {
+pub fn maccs_fingerprint(smiles: String) -> Result<Vec<u8>, NifError> {
     let mol = cyanea_chem::parse_smiles(&smiles).map_err(to_nif_error)?;
     let fp = cyanea_chem::maccs_fingerprint(&mol);
     Ok(fingerprint_to_bytes(&fp))
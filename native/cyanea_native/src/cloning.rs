@@ -0,0 +1,430 @@
+//! Cloning helpers — restriction digests and in-silico PCR.
+//!
+//! Cut offsets follow REBASE: `top` and `bottom` are where the top and
+//! bottom strands are cut, counted from the first base of the recognition
+//! site on the top strand (`G^AATTC` is `1, 5`).  Sites on the reverse
+//! strand are mirrored, and type IIS enzymes cut outside their site.
+
+use crate::bridge::*;
+use crate::pattern::{normalize_text, PatternSet};
+use crate::seq::{iupac_bases, reverse_complement, to_dna, SeqAlphabet};
+use std::collections::BTreeMap;
+
+/// Built-in enzymes: name, recognition site (IUPAC), top cut, bottom cut.
+const ENZYMES: &[(&str, &str, i64, i64)] = &[
+    ("AatII", "GACGTC", 5, 1),
+    ("AccI", "GTMKAC", 2, 4),
+    ("AfeI", "AGCGCT", 3, 3),
+    ("AflII", "CTTAAG", 1, 5),
+    ("AgeI", "ACCGGT", 1, 5),
+    ("AluI", "AGCT", 2, 2),
+    ("ApaI", "GGGCCC", 5, 1),
+    ("ApaLI", "GTGCAC", 1, 5),
+    ("AscI", "GGCGCGCC", 2, 6),
+    ("AvaI", "CYCGRG", 1, 5),
+    ("AvrII", "CCTAGG", 1, 5),
+    ("BamHI", "GGATCC", 1, 5),
+    ("BbsI", "GAAGAC", 8, 12),
+    ("BclI", "TGATCA", 1, 5),
+    ("BglII", "AGATCT", 1, 5),
+    ("BsaI", "GGTCTC", 7, 11),
+    ("BsiWI", "CGTACG", 1, 5),
+    ("BsmBI", "CGTCTC", 7, 11),
+    ("BspHI", "TCATGA", 1, 5),
+    ("BsrGI", "TGTACA", 1, 5),
+    ("BstBI", "TTCGAA", 2, 4),
+    ("ClaI", "ATCGAT", 2, 4),
+    ("DpnII", "GATC", 0, 4),
+    ("DraI", "TTTAAA", 3, 3),
+    ("EagI", "CGGCCG", 1, 5),
+    ("EcoRI", "GAATTC", 1, 5),
+    ("EcoRV", "GATATC", 3, 3),
+    ("FseI", "GGCCGGCC", 6, 2),
+    ("HaeIII", "GGCC", 2, 2),
+    ("HincII", "GTYRAC", 3, 3),
+    ("HindIII", "AAGCTT", 1, 5),
+    ("HinfI", "GANTC", 1, 4),
+    ("HpaI", "GTTAAC", 3, 3),
+    ("KpnI", "GGTACC", 5, 1),
+    ("MboI", "GATC", 0, 4),
+    ("MfeI", "CAATTG", 1, 5),
+    ("MluI", "ACGCGT", 1, 5),
+    ("MspI", "CCGG", 1, 3),
+    ("NaeI", "GCCGGC", 3, 3),
+    ("NcoI", "CCATGG", 1, 5),
+    ("NdeI", "CATATG", 2, 4),
+    ("NheI", "GCTAGC", 1, 5),
+    ("NotI", "GCGGCCGC", 2, 6),
+    ("NruI", "TCGCGA", 3, 3),
+    ("NsiI", "ATGCAT", 5, 1),
+    ("PacI", "TTAATTAA", 5, 3),
+    ("PmeI", "GTTTAAAC", 4, 4),
+    ("PstI", "CTGCAG", 5, 1),
+    ("PvuI", "CGATCG", 4, 2),
+    ("PvuII", "CAGCTG", 3, 3),
+    ("SacI", "GAGCTC", 5, 1),
+    ("SacII", "CCGCGG", 4, 2),
+    ("SalI", "GTCGAC", 1, 5),
+    ("SapI", "GCTCTTC", 8, 11),
+    ("Sau3AI", "GATC", 0, 4),
+    ("SbfI", "CCTGCAGG", 6, 2),
+    ("ScaI", "AGTACT", 3, 3),
+    ("SfiI", "GGCCNNNNNGGCC", 8, 5),
+    ("SmaI", "CCCGGG", 3, 3),
+    ("SpeI", "ACTAGT", 1, 5),
+    ("SphI", "GCATGC", 5, 1),
+    ("StuI", "AGGCCT", 3, 3),
+    ("SwaI", "ATTTAAAT", 4, 4),
+    ("TaqI", "TCGA", 1, 3),
+    ("XbaI", "TCTAGA", 1, 5),
+    ("XhoI", "CTCGAG", 1, 5),
+    ("XmaI", "CCCGGG", 1, 5),
+];
+
+fn find_enzyme(name: &str) -> Result<(&'static str, &'static str, i64, i64), NifError> {
+    ENZYMES
+        .iter()
+        .find(|(n, ..)| n.eq_ignore_ascii_case(name))
+        .copied()
+        .ok_or_else(|| NifError::not_found(format!("unknown restriction enzyme: {name}")))
+}
+
+/// Validate a nucleotide sequence (IUPAC, DNA or RNA) and return it as DNA.
+fn nucleotides(what: &str, seq: &[u8]) -> Result<Vec<u8>, NifError> {
+    let seq = to_dna(
+        &SeqAlphabet::Iupac
+            .normalize(seq)
+            .map_err(|e| NifError::invalid(format!("{what}: {}", e.message)))?,
+    );
+    if seq.is_empty() || seq.contains(&b'-') {
+        return Err(NifError::invalid(format!(
+            "{what}: must be a non-empty nucleotide sequence without gaps"
+        )));
+    }
+    Ok(seq)
+}
+
+// ===========================================================================
+// Restriction digest
+// ===========================================================================
+
+/// Cut positions of `enzymes` in `seq`, ordered by top-strand cut.  On a
+/// linear sequence cuts that would fall outside it are dropped.
+fn find_cuts(seq: &[u8], enzymes: &[&str], circular: bool) -> Result<Vec<RestrictionCutNif>, NifError> {
+    let len = seq.len();
+    let mut selected = Vec::new();
+    for name in enzymes {
+        let enzyme = find_enzyme(name)?;
+        if !selected.contains(&enzyme) {
+            selected.push(enzyme);
+        }
+    }
+    let set = PatternSet::new(
+        selected
+            .iter()
+            .map(|&(name, site, ..)| (name.to_string(), site.to_string()))
+            .collect(),
+        0,
+        true,
+    )?;
+
+    // Sites spanning the origin of a circular sequence.
+    let mut text = seq.to_vec();
+    if circular {
+        let longest = selected.iter().map(|(_, site, ..)| site.len()).max().unwrap_or(1);
+        text.extend_from_slice(&seq[..(longest - 1).min(len)]);
+    }
+
+    let mut cuts = Vec::new();
+    for hit in set.search("", &text) {
+        if hit.start >= len {
+            continue;
+        }
+        let (name, _, top, bottom) = *selected.iter().find(|(n, ..)| *n == hit.pattern).unwrap();
+        let (start, site_len) = (hit.start as i64, (hit.end - hit.start) as i64);
+        let (top_cut, bottom_cut) = if hit.strand == "+" {
+            (start + top, start + bottom)
+        } else {
+            (start + site_len - bottom, start + site_len - top)
+        };
+        let n = len as i64;
+        let (top_cut, bottom_cut) = if circular {
+            (top_cut.rem_euclid(n), bottom_cut.rem_euclid(n))
+        } else if top_cut > 0 && top_cut < n && (0..=n).contains(&bottom_cut) {
+            (top_cut, bottom_cut)
+        } else {
+            continue;
+        };
+        cuts.push(RestrictionCutNif {
+            enzyme: name.to_string(),
+            site_start: hit.start,
+            strand: hit.strand,
+            top_cut: top_cut as usize,
+            bottom_cut: bottom_cut as usize,
+        });
+    }
+    cuts.sort_by(|a, b| (a.top_cut, &a.enzyme).cmp(&(b.top_cut, &b.enzyme)));
+    Ok(cuts)
+}
+
+/// Fragments between consecutive top-strand cuts.  Enzymes cutting at the
+/// same position are joined with `/`; sequence ends have no enzyme.
+fn fragments(len: usize, cuts: &[RestrictionCutNif], circular: bool) -> Vec<DigestFragmentNif> {
+    let mut positions: BTreeMap<usize, Vec<&str>> = BTreeMap::new();
+    for cut in cuts {
+        let names = positions.entry(cut.top_cut).or_default();
+        if !names.contains(&cut.enzyme.as_str()) {
+            names.push(&cut.enzyme);
+        }
+    }
+    let mut bounds: Vec<(usize, Option<String>)> = positions
+        .into_iter()
+        .map(|(pos, names)| (pos, Some(names.join("/"))))
+        .collect();
+
+    if !circular {
+        bounds.insert(0, (0, None));
+        bounds.push((len, None));
+        return bounds
+            .windows(2)
+            .map(|w| DigestFragmentNif {
+                start: w[0].0,
+                end: w[1].0,
+                length: w[1].0 - w[0].0,
+                left_enzyme: w[0].1.clone(),
+                right_enzyme: w[1].1.clone(),
+            })
+            .collect();
+    }
+    if bounds.is_empty() {
+        return vec![DigestFragmentNif {
+            start: 0,
+            end: len,
+            length: len,
+            left_enzyme: None,
+            right_enzyme: None,
+        }];
+    }
+    // The last fragment wraps through the origin (end < start).
+    (0..bounds.len())
+        .map(|i| {
+            let (start, left) = &bounds[i];
+            let (end, right) = &bounds[(i + 1) % bounds.len()];
+            let length = if bounds.len() == 1 { len } else { (end + len - start) % len };
+            DigestFragmentNif {
+                start: *start,
+                end: *end,
+                length,
+                left_enzyme: left.clone(),
+                right_enzyme: right.clone(),
+            }
+        })
+        .collect()
+}
+
+// ===========================================================================
+// In-silico PCR
+// ===========================================================================
+
+/// Mismatches of `primer` against `text` at `pos`, or `None` past
+/// `max_mismatches` or when the primer's 3' base (last, or first for a
+/// reverse-complemented primer) does not pair — polymerase will not extend
+/// a mismatched 3' end.
+fn primer_mismatches(
+    primer: &[u8],
+    text: &[u8],
+    pos: usize,
+    max_mismatches: usize,
+    three_prime_last: bool,
+) -> Option<usize> {
+    let window = &text[pos..pos + primer.len()];
+    let anchor = if three_prime_last { primer.len() - 1 } else { 0 };
+    if !iupac_bases(primer[anchor]).contains(&window[anchor]) {
+        return None;
+    }
+    let mut mismatches = 0;
+    for (&p, t) in primer.iter().zip(window) {
+        if !iupac_bases(p).contains(t) {
+            mismatches += 1;
+            if mismatches > max_mismatches {
+                return None;
+            }
+        }
+    }
+    Some(mismatches)
+}
+
+/// `(position, mismatches)` of every binding site starting before `limit`.
+fn primer_sites(
+    primer: &[u8],
+    text: &[u8],
+    limit: usize,
+    max_mismatches: usize,
+    three_prime_last: bool,
+) -> Vec<(usize, usize)> {
+    let last = (text.len() + 1).saturating_sub(primer.len()).min(limit);
+    (0..last)
+        .filter_map(|pos| {
+            primer_mismatches(primer, text, pos, max_mismatches, three_prime_last).map(|m| (pos, m))
+        })
+        .collect()
+}
+
+struct PrimerPair {
+    name: String,
+    forward: Vec<u8>,
+    reverse: Vec<u8>,
+}
+
+/// A template with, when circular, its start appended so products can
+/// run through the origin.
+struct Template<'a> {
+    name: &'a str,
+    text: Vec<u8>,
+    len: usize,
+}
+
+/// Products of one primer pair on one template.  `"+"` products have the
+/// forward primer on the template's top strand, `"-"` products on its
+/// bottom strand; either way `product` reads 5'->3' from the forward primer.
+fn amplify(
+    pair: &PrimerPair,
+    template: &Template,
+    bottom_strand: bool,
+    max_mismatches: usize,
+    max_length: usize,
+) -> Vec<AmpliconNif> {
+    let (left, right) = if bottom_strand {
+        (&pair.reverse, &pair.forward)
+    } else {
+        (&pair.forward, &pair.reverse)
+    };
+    let right_rc = reverse_complement(right);
+    let text = &template.text;
+    let left_sites = primer_sites(left, text, template.len, max_mismatches, true);
+    let right_sites = primer_sites(&right_rc, text, usize::MAX, max_mismatches, false);
+    let mut products = Vec::new();
+    for &(start, left_mm) in &left_sites {
+        let first = right_sites.partition_point(|&(pos, _)| pos < start + left.len());
+        for &(pos, right_mm) in &right_sites[first..] {
+            let end = pos + right.len();
+            if end - start > max_length {
+                break;
+            }
+            let mut product = left.to_vec();
+            product.extend_from_slice(&text[start + left.len()..pos]);
+            product.extend_from_slice(&right_rc);
+            let (forward_mismatches, reverse_mismatches) = if bottom_strand {
+                product = reverse_complement(&product);
+                (right_mm, left_mm)
+            } else {
+                (left_mm, right_mm)
+            };
+            products.push(AmpliconNif {
+                primer_pair: pair.name.clone(),
+                record: template.name.to_string(),
+                strand: if bottom_strand { "-" } else { "+" }.to_string(),
+                start,
+                end: if end > template.len { end - template.len } else { end },
+                length: end - start,
+                forward_mismatches,
+                reverse_mismatches,
+                product,
+            });
+        }
+    }
+    products
+}
+
+// ===========================================================================
+// NIFs
+// ===========================================================================
+
+#[rustler::nif]
+pub fn restriction_enzymes() -> Vec<RestrictionEnzymeNif> {
+    ENZYMES
+        .iter()
+        .map(|&(name, site, top_cut, bottom_cut)| RestrictionEnzymeNif {
+            name: name.to_string(),
+            site: site.to_string(),
+            top_cut,
+            bottom_cut,
+        })
+        .collect()
+}
+
+/// Digest `seq` with the named enzymes (case-insensitive).
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn restriction_digest(
+    seq: Vec<u8>,
+    enzymes: Vec<String>,
+    circular: bool,
+) -> Result<RestrictionDigestNif, NifError> {
+    if enzymes.is_empty() {
+        return Err(NifError::invalid("at least one enzyme is required"));
+    }
+    let seq = nucleotides("sequence", &seq)?;
+    let names: Vec<&str> = enzymes.iter().map(String::as_str).collect();
+    let cuts = find_cuts(&seq, &names, circular)?;
+    let fragments = fragments(seq.len(), &cuts, circular);
+    Ok(RestrictionDigestNif {
+        length: seq.len(),
+        circular,
+        cuts,
+        fragments,
+    })
+}
+
+/// Predict the products of each primer pair on each template, in both
+/// orientations.  Primers may carry IUPAC codes; `product` includes the
+/// primer sequences, so 5' mismatches show up in it as designed.
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn pcr_simulate(
+    templates: Vec<FastaRecordNif>,
+    primers: Vec<PrimerPairNif>,
+    max_mismatches: usize,
+    max_length: usize,
+    circular: bool,
+) -> Result<Vec<AmpliconNif>, NifError> {
+    if primers.is_empty() {
+        return Err(NifError::invalid("at least one primer pair is required"));
+    }
+    let pairs = primers
+        .into_iter()
+        .map(|p| {
+            let forward = nucleotides(&format!("{} forward primer", p.name), p.forward.as_bytes())?;
+            let reverse = nucleotides(&format!("{} reverse primer", p.name), p.reverse.as_bytes())?;
+            if forward.len().min(reverse.len()) <= max_mismatches {
+                return Err(NifError::invalid(format!(
+                    "{}: max_mismatches {max_mismatches} must be less than the primer lengths",
+                    p.name
+                )));
+            }
+            Ok(PrimerPair {
+                name: p.name,
+                forward,
+                reverse,
+            })
+        })
+        .collect::<Result<Vec<_>, NifError>>()?;
+
+    let mut amplicons = Vec::new();
+    for record in &templates {
+        let mut text = normalize_text(&record.sequence);
+        let len = text.len();
+        if circular {
+            let wrap = text[..max_length.min(len.saturating_sub(1))].to_vec();
+            text.extend_from_slice(&wrap);
+        }
+        let template = Template {
+            name: &record.name,
+            text,
+            len,
+        };
+        for pair in &pairs {
+            amplicons.extend(amplify(pair, &template, false, max_mismatches, max_length));
+            amplicons.extend(amplify(pair, &template, true, max_mismatches, max_length));
+        }
+    }
+    Ok(amplicons)
+}
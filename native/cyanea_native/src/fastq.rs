@@ -0,0 +1,933 @@
+//! Streaming FASTQ processing — adapter, poly-G and quality trimming, read
+//! filtering (single or paired-end) and FastQC-style quality reports.
+//!
+//! Records are read four lines at a time from plain or gzipped files and
+//! written back out as they pass, so memory use does not grow with the run.
+
+use crate::bridge::*;
+use crate::io::TextWriter;
+use std::collections::HashMap;
+use std::io::{BufRead, Write};
+
+// ===========================================================================
+// Reading & writing
+// ===========================================================================
+
+/// One FASTQ record as read, with quality still Phred+33 encoded.
+pub(crate) struct FastqRead {
+    /// Header line without the leading `@`.
+    pub header: Vec<u8>,
+    pub seq: Vec<u8>,
+    pub qual: Vec<u8>,
+}
+
+impl FastqRead {
+    /// Read name: the header up to the first whitespace.
+    pub fn name(&self) -> &[u8] {
+        self.header
+            .split(|b| b.is_ascii_whitespace())
+            .next()
+            .unwrap_or_default()
+    }
+
+    /// Phred score of base `i`.
+    pub fn phred(&self, i: usize) -> u8 {
+        self.qual[i].saturating_sub(33)
+    }
+
+    fn truncate(&mut self, len: usize) {
+        self.seq.truncate(len);
+        self.qual.truncate(len);
+    }
+}
+
+pub(crate) struct FastqReader {
+    path: String,
+    lines: Box<dyn BufRead + Send>,
+    line_no: u64,
+    buf: Vec<u8>,
+}
+
+impl FastqReader {
+    pub fn open(path: &str) -> Result<Self, NifError> {
+        Ok(Self {
+            path: path.to_string(),
+            lines: crate::io::open_text_reader(path)?,
+            line_no: 0,
+            buf: Vec::new(),
+        })
+    }
+
+    pub fn path(&self) -> &str {
+        &self.path
+    }
+
+    fn line(&mut self) -> Result<Option<Vec<u8>>, NifError> {
+        self.buf.clear();
+        let n = self
+            .lines
+            .read_until(b'\n', &mut self.buf)
+            .map_err(|e| NifError::from(e).with_file(&self.path).at_line(self.line_no + 1))?;
+        if n == 0 {
+            return Ok(None);
+        }
+        self.line_no += 1;
+        Ok(Some(self.buf.trim_ascii_end().to_vec()))
+    }
+
+    fn error(&self, message: &str) -> NifError {
+        NifError::parse(message).with_file(&self.path).at_line(self.line_no)
+    }
+
+    /// The next record, or `None` at end of file.
+    pub fn next_read(&mut self) -> Result<Option<FastqRead>, NifError> {
+        let header = loop {
+            match self.line()? {
+                None => return Ok(None),
+                Some(line) if line.is_empty() => continue,
+                Some(line) => break line,
+            }
+        };
+        let Some(header) = header.strip_prefix(b"@").map(<[u8]>::to_vec) else {
+            return Err(self.error("expected FASTQ header starting with '@'"));
+        };
+        let seq = self.line()?.ok_or_else(|| self.error("truncated FASTQ record"))?;
+        let plus = self.line()?.ok_or_else(|| self.error("truncated FASTQ record"))?;
+        if !plus.starts_with(b"+") {
+            return Err(self.error("expected '+' separator line"));
+        }
+        let qual = self.line()?.ok_or_else(|| self.error("truncated FASTQ record"))?;
+        if qual.len() != seq.len() {
+            return Err(self.error(&format!(
+                "sequence and quality lengths differ ({} vs {})",
+                seq.len(),
+                qual.len()
+            )));
+        }
+        Ok(Some(FastqRead { header, seq, qual }))
+    }
+}
+
+/// The next record from one reader, or the next pair from two read in
+/// lockstep, checking that mates belong together.
+pub(crate) fn next_record(readers: &mut [FastqReader]) -> Result<Option<Vec<FastqRead>>, NifError> {
+    let mut record = Vec::with_capacity(readers.len());
+    for reader in readers.iter_mut() {
+        record.push(reader.next_read()?);
+    }
+    let record: Vec<FastqRead> = match record.iter().filter(|r| r.is_some()).count() {
+        0 => return Ok(None),
+        n if n == readers.len() => record.into_iter().flatten().collect(),
+        _ => {
+            let short = &readers[record.iter().position(Option::is_none).unwrap_or(0)];
+            return Err(NifError::parse("paired files have different numbers of reads")
+                .with_file(short.path()));
+        }
+    };
+    if let [r1, r2] = record.as_slice() {
+        if r1.name().strip_suffix(b"/1").unwrap_or(r1.name())
+            != r2.name().strip_suffix(b"/2").unwrap_or(r2.name())
+        {
+            return Err(NifError::parse(format!(
+                "mates out of sync: {} and {}",
+                String::from_utf8_lossy(r1.name()),
+                String::from_utf8_lossy(r2.name())
+            ))
+            .with_file(readers[1].path()));
+        }
+    }
+    Ok(Some(record))
+}
+
+pub(crate) fn write_read(out: &mut TextWriter, read: &FastqRead) -> std::io::Result<()> {
+    out.write_all(b"@")?;
+    out.write_all(&read.header)?;
+    out.write_all(b"\n")?;
+    out.write_all(&read.seq)?;
+    out.write_all(b"\n+\n")?;
+    out.write_all(&read.qual)?;
+    out.write_all(b"\n")
+}
+
+// ===========================================================================
+// Trimming steps
+// ===========================================================================
+
+const ILLUMINA_ADAPTERS: &[&str] = &[
+    // TruSeq read 1 and read 2 (share the AGATCGGAAGAGC prefix)
+    "AGATCGGAAGAGCACACGTCTGAACTCCAGTCA",
+    "AGATCGGAAGAGCGTCGTGTAGGGAAAGAGTGT",
+    // TruSeq small RNA
+    "TGGAATTCTCGGGTGCCAAGG",
+];
+
+const NEXTERA_ADAPTERS: &[&str] = &["CTGTCTCTTATACACATCT"];
+
+fn adapter_set(options: &TrimOptionsNif) -> Result<Vec<Vec<u8>>, NifError> {
+    let preset: Vec<&str> = match options.adapter_preset.as_str() {
+        "none" => Vec::new(),
+        "illumina" => ILLUMINA_ADAPTERS.to_vec(),
+        "nextera" => NEXTERA_ADAPTERS.to_vec(),
+        "all" => [ILLUMINA_ADAPTERS, NEXTERA_ADAPTERS].concat(),
+        other => {
+            return Err(NifError::invalid(format!(
+                "unknown adapter preset: {other} (expected none, illumina, nextera, or all)"
+            )))
+        }
+    };
+    let mut adapters: Vec<Vec<u8>> = preset.iter().map(|a| a.as_bytes().to_vec()).collect();
+    for a in &options.adapters {
+        if a.is_empty() {
+            return Err(NifError::invalid("adapter sequences must not be empty"));
+        }
+        adapters.push(a.to_ascii_uppercase().into_bytes());
+    }
+    Ok(adapters)
+}
+
+/// Leftmost position where `adapter` starts in `seq`, allowing a partial
+/// adapter at the 3' end down to `min_overlap` bases and Hamming mismatches
+/// up to `error_rate` of the overlap.
+fn find_adapter(seq: &[u8], adapter: &[u8], min_overlap: usize, error_rate: f64) -> Option<usize> {
+    let min_overlap = min_overlap.max(1).min(adapter.len());
+    (0..=seq.len().saturating_sub(min_overlap)).find(|&start| {
+        let overlap = adapter.len().min(seq.len() - start);
+        let allowed = (overlap as f64 * error_rate) as usize;
+        let mut mismatches = 0;
+        for (a, b) in seq[start..start + overlap].iter().zip(adapter) {
+            if !a.eq_ignore_ascii_case(b) {
+                mismatches += 1;
+                if mismatches > allowed {
+                    return false;
+                }
+            }
+        }
+        true
+    })
+}
+
+/// Length of a 3' poly-G tail (two-colour chemistry artefact), allowing one
+/// non-G per eight bases; 0 when shorter than `min_length`.
+fn poly_g_tail(seq: &[u8], min_length: usize) -> usize {
+    let (mut len, mut mismatches, mut best) = (0, 0, 0);
+    for &b in seq.iter().rev() {
+        len += 1;
+        if b.eq_ignore_ascii_case(&b'G') {
+            best = len;
+        } else {
+            mismatches += 1;
+            if mismatches > len / 8 {
+                break;
+            }
+        }
+    }
+    if best >= min_length {
+        best
+    } else {
+        0
+    }
+}
+
+/// Sliding-window cut (as Trimmomatic's SLIDINGWINDOW): the read ends where
+/// the first window with mean quality below `threshold` starts.
+fn window_cut(read: &FastqRead, size: usize, threshold: u8) -> usize {
+    let len = read.seq.len();
+    if size == 0 || len < size {
+        return len;
+    }
+    let mut sum: u32 = (0..size).map(|i| u32::from(read.phred(i))).sum();
+    for start in 0..=len - size {
+        if start > 0 {
+            sum = sum + u32::from(read.phred(start + size - 1)) - u32::from(read.phred(start - 1));
+        }
+        if sum < u32::from(threshold) * size as u32 {
+            return start;
+        }
+    }
+    len
+}
+
+/// 3' quality cut using the BWA algorithm (as cutadapt `-q`): cut where the
+/// sum of `threshold - q` from the 3' end is largest.
+fn trailing_cut(read: &FastqRead, threshold: u8) -> usize {
+    let (mut sum, mut max, mut cut) = (0i64, 0i64, read.seq.len());
+    for i in (0..read.seq.len()).rev() {
+        sum += i64::from(threshold) - i64::from(read.phred(i));
+        if sum < 0 {
+            break;
+        }
+        if sum > max {
+            max = sum;
+            cut = i;
+        }
+    }
+    cut
+}
+
+struct Trimmer {
+    adapters: Vec<Vec<u8>>,
+    options: TrimOptionsNif,
+}
+
+/// Why a record was dropped.
+enum Rejection {
+    TooManyN,
+    TooShort,
+}
+
+impl Trimmer {
+    /// Trim `read` in place, recording what each step removed.  Returns
+    /// the reason to drop the read, if any.
+    fn trim(&self, read: &mut FastqRead, stats: &mut FastqTrimStatsNif) -> Option<Rejection> {
+        let o = &self.options;
+
+        let adapter_at = self
+            .adapters
+            .iter()
+            .filter_map(|a| find_adapter(&read.seq, a, o.adapter_min_overlap, o.adapter_error_rate))
+            .min();
+        if let Some(at) = adapter_at {
+            stats.adapter_trimmed_reads += 1;
+            stats.adapter_trimmed_bases += (read.seq.len() - at) as u64;
+            read.truncate(at);
+        }
+
+        if o.poly_g_min_length > 0 {
+            let tail = poly_g_tail(&read.seq, o.poly_g_min_length);
+            if tail > 0 {
+                stats.poly_g_trimmed_reads += 1;
+                stats.poly_g_trimmed_bases += tail as u64;
+                read.truncate(read.seq.len() - tail);
+            }
+        }
+
+        let before = read.seq.len();
+        read.truncate(window_cut(read, o.window_size, o.window_quality));
+        if o.trailing_quality > 0 {
+            read.truncate(trailing_cut(read, o.trailing_quality));
+        }
+        if read.seq.len() < before {
+            stats.quality_trimmed_reads += 1;
+            stats.quality_trimmed_bases += (before - read.seq.len()) as u64;
+        }
+
+        let n_count = read.seq.iter().filter(|b| b.eq_ignore_ascii_case(&b'N')).count();
+        if o.max_n.is_some_and(|max| n_count > max) {
+            Some(Rejection::TooManyN)
+        } else if read.seq.len() < o.min_length {
+            Some(Rejection::TooShort)
+        } else {
+            None
+        }
+    }
+}
+
+// ===========================================================================
+// NIFs
+// ===========================================================================
+
+/// Trim and filter single-end (one input) or paired-end (two inputs, R1
+/// and R2 in the same order) FASTQ files.  A pair is kept only when both
+/// mates pass.
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn fastq_trim(
+    inputs: Vec<String>,
+    outputs: Vec<String>,
+    options: TrimOptionsNif,
+    compression: String,
+) -> Result<FastqTrimStatsNif, NifError> {
+    if inputs.is_empty() || inputs.len() > 2 {
+        return Err(NifError::invalid("expected one input path, or two for paired-end reads"));
+    }
+    if outputs.len() != inputs.len() {
+        return Err(NifError::invalid(format!(
+            "expected {} output paths, got {}",
+            inputs.len(),
+            outputs.len()
+        )));
+    }
+    if !(0.0..1.0).contains(&options.adapter_error_rate) {
+        return Err(NifError::invalid("adapter_error_rate must be in [0, 1)"));
+    }
+    let trimmer = Trimmer {
+        adapters: adapter_set(&options)?,
+        options,
+    };
+
+    let mut readers = inputs
+        .iter()
+        .map(|p| FastqReader::open(p))
+        .collect::<Result<Vec<_>, _>>()?;
+    let mut writers = outputs
+        .iter()
+        .map(|p| TextWriter::create(p, &compression))
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let mut stats = FastqTrimStatsNif {
+        paired: inputs.len() == 2,
+        ..Default::default()
+    };
+    while let Some(mut record) = next_record(&mut readers)? {
+        stats.records_in += 1;
+        stats.bases_in += record.iter().map(|r| r.seq.len() as u64).sum::<u64>();
+        let mut rejection = None;
+        for read in &mut record {
+            rejection = rejection.or(trimmer.trim(read, &mut stats));
+        }
+        match rejection {
+            Some(Rejection::TooManyN) => stats.too_many_n += 1,
+            Some(Rejection::TooShort) => stats.too_short += 1,
+            None => {
+                stats.records_out += 1;
+                for (read, (out, path)) in record.iter().zip(writers.iter_mut().zip(&outputs)) {
+                    stats.bases_out += read.seq.len() as u64;
+                    write_read(out, read).map_err(|e| NifError::from(e).with_file(path))?;
+                }
+            }
+        }
+    }
+
+    for (out, path) in writers.into_iter().zip(&outputs) {
+        out.finish().map_err(|e| NifError::from(e).with_file(path))?;
+    }
+    Ok(stats)
+}
+
+// ===========================================================================
+// Quality report
+// ===========================================================================
+
+/// Distinct sequences tracked for duplication levels, as in FastQC; later
+/// reads only count towards sequences already seen.
+const DUPLICATION_TRACKED: usize = 100_000;
+
+/// Sequences above this fraction of all reads are reported as overrepresented.
+const OVERREPRESENTED_FRACTION: f64 = 0.001;
+
+/// The 12-mers FastQC searches for in its adapter content module.
+const ADAPTER_PROBES: &[(&str, &str)] = &[
+    ("Illumina Universal Adapter", "AGATCGGAAGAG"),
+    ("Illumina Small RNA 3' Adapter", "TGGAATTCTCGG"),
+    ("Nextera Transposase Sequence", "CTGTCTCTTATA"),
+    ("PolyA", "AAAAAAAAAAAA"),
+    ("PolyG", "GGGGGGGGGGGG"),
+];
+
+const DUPLICATION_LEVELS: &[&str] = &[
+    "1", "2", "3", "4", "5", "6", "7", "8", "9", ">10", ">50", ">100", ">500", ">1k", ">5k", ">10k",
+];
+
+fn duplication_level(count: u64) -> usize {
+    match count {
+        0..=9 => count.saturating_sub(1) as usize,
+        10..=49 => 9,
+        50..=99 => 10,
+        100..=499 => 11,
+        500..=999 => 12,
+        1000..=4999 => 13,
+        5000..=9999 => 14,
+        _ => 15,
+    }
+}
+
+/// Quality scores are bucketed 0..=93 (the printable Phred+33 range).
+const MAX_PHRED: usize = 93;
+
+#[derive(Default)]
+struct ReportBuilder {
+    reads: u64,
+    bases: u64,
+    gc_bases: u64,
+    quality_sum: u64,
+    q20_bases: u64,
+    q30_bases: u64,
+    /// Per position: histogram of Phred scores.
+    quality: Vec<[u64; MAX_PHRED + 1]>,
+    /// Per position: counts of A, C, G, T, N.
+    content: Vec<[u64; 5]>,
+    gc: Vec<u64>,
+    lengths: std::collections::BTreeMap<usize, u64>,
+    sequences: HashMap<Vec<u8>, u64>,
+    tracked_reads: u64,
+    /// Per probe: reads whose first hit starts at each position.
+    adapter_hits: Vec<Vec<u64>>,
+}
+
+impl ReportBuilder {
+    fn new() -> Self {
+        Self {
+            gc: vec![0; 101],
+            adapter_hits: vec![Vec::new(); ADAPTER_PROBES.len()],
+            ..Default::default()
+        }
+    }
+
+    fn add(&mut self, read: &FastqRead) {
+        let len = read.seq.len();
+        if self.quality.len() < len {
+            self.quality.resize(len, [0; MAX_PHRED + 1]);
+            self.content.resize(len, [0; 5]);
+        }
+        self.reads += 1;
+        self.bases += len as u64;
+        *self.lengths.entry(len).or_insert(0) += 1;
+
+        let mut read_gc = 0u64;
+        let mut read_acgt = 0u64;
+        for (i, &b) in read.seq.iter().enumerate() {
+            let q = usize::from(read.phred(i)).min(MAX_PHRED);
+            self.quality[i][q] += 1;
+            self.quality_sum += q as u64;
+            self.q20_bases += u64::from(q >= 20);
+            self.q30_bases += u64::from(q >= 30);
+            let base = match b.to_ascii_uppercase() {
+                b'A' => 0,
+                b'C' => 1,
+                b'G' => 2,
+                b'T' | b'U' => 3,
+                _ => 4,
+            };
+            self.content[i][base] += 1;
+            if base == 1 || base == 2 {
+                read_gc += 1;
+            }
+            if base < 4 {
+                read_acgt += 1;
+            }
+        }
+        self.gc_bases += read_gc;
+        if read_acgt > 0 {
+            self.gc[(read_gc * 100 + read_acgt / 2) as usize / read_acgt as usize] += 1;
+        }
+
+        // As FastQC: long reads are deduplicated on their first 50 bases.
+        let key = (if len > 75 { &read.seq[..50] } else { &read.seq[..] }).to_ascii_uppercase();
+        if let Some(count) = self.sequences.get_mut(&key) {
+            *count += 1;
+            self.tracked_reads += 1;
+        } else if self.sequences.len() < DUPLICATION_TRACKED {
+            self.sequences.insert(key, 1);
+            self.tracked_reads += 1;
+        }
+
+        for (hits, (_, probe)) in self.adapter_hits.iter_mut().zip(ADAPTER_PROBES) {
+            let at = read
+                .seq
+                .windows(probe.len())
+                .position(|w| w.eq_ignore_ascii_case(probe.as_bytes()));
+            if let Some(at) = at {
+                if hits.len() <= at {
+                    hits.resize(at + 1, 0);
+                }
+                hits[at] += 1;
+            }
+        }
+    }
+
+    fn finish(self) -> FastqReportNif {
+        let reads = self.reads.max(1) as f64;
+        let summary = FastqStatsNif {
+            sequence_count: self.reads,
+            total_bases: self.bases,
+            gc_content: self.gc_bases as f64 / self.bases.max(1) as f64,
+            avg_length: self.bases as f64 / reads,
+            mean_quality: self.quality_sum as f64 / self.bases.max(1) as f64,
+            q20_fraction: self.q20_bases as f64 / self.bases.max(1) as f64,
+            q30_fraction: self.q30_bases as f64 / self.bases.max(1) as f64,
+        };
+
+        let per_position_quality = self
+            .quality
+            .iter()
+            .enumerate()
+            .map(|(i, hist)| {
+                let total: u64 = hist.iter().sum();
+                let sum: u64 = hist.iter().enumerate().map(|(q, n)| q as u64 * n).sum();
+                let quantile = |p: f64| {
+                    let target = (p * total as f64).ceil().max(1.0) as u64;
+                    let mut seen = 0;
+                    hist.iter()
+                        .position(|&n| {
+                            seen += n;
+                            seen >= target
+                        })
+                        .unwrap_or(0) as u8
+                };
+                PositionQualityNif {
+                    position: i + 1,
+                    mean: sum as f64 / total.max(1) as f64,
+                    p10: quantile(0.1),
+                    q1: quantile(0.25),
+                    median: quantile(0.5),
+                    q3: quantile(0.75),
+                    p90: quantile(0.9),
+                }
+            })
+            .collect();
+
+        let per_position_content = self
+            .content
+            .iter()
+            .enumerate()
+            .map(|(i, counts)| {
+                let total = counts.iter().sum::<u64>().max(1) as f64;
+                BaseContentNif {
+                    position: i + 1,
+                    a: counts[0] as f64 / total,
+                    c: counts[1] as f64 / total,
+                    g: counts[2] as f64 / total,
+                    t: counts[3] as f64 / total,
+                    n: counts[4] as f64 / total,
+                }
+            })
+            .collect();
+
+        let mut level_reads = [0u64; 16];
+        for &count in self.sequences.values() {
+            level_reads[duplication_level(count)] += count;
+        }
+        let tracked = self.tracked_reads.max(1) as f64;
+        let duplication_levels = DUPLICATION_LEVELS
+            .iter()
+            .zip(level_reads)
+            .map(|(level, n)| (level.to_string(), n as f64 / tracked))
+            .collect();
+
+        let mut overrepresented: Vec<OverrepresentedSequenceNif> = self
+            .sequences
+            .iter()
+            .filter(|&(_, &count)| count as f64 / reads > OVERREPRESENTED_FRACTION && count > 1)
+            .map(|(seq, &count)| OverrepresentedSequenceNif {
+                sequence: String::from_utf8_lossy(seq).into_owned(),
+                count,
+                fraction: count as f64 / reads,
+                possible_source: ADAPTER_PROBES
+                    .iter()
+                    .find(|(_, probe)| seq.windows(probe.len()).any(|w| w == probe.as_bytes()))
+                    .map(|(name, _)| name.to_string()),
+            })
+            .collect();
+        overrepresented.sort_by(|a, b| b.count.cmp(&a.count).then(a.sequence.cmp(&b.sequence)));
+
+        let positions = self.content.len();
+        let adapter_content = ADAPTER_PROBES
+            .iter()
+            .zip(&self.adapter_hits)
+            .map(|((name, probe), hits)| {
+                let mut seen = 0;
+                let fractions = (0..positions)
+                    .map(|i| {
+                        seen += hits.get(i).copied().unwrap_or(0);
+                        seen as f64 / reads
+                    })
+                    .collect();
+                AdapterContentNif {
+                    name: name.to_string(),
+                    sequence: probe.to_string(),
+                    fractions,
+                }
+            })
+            .collect();
+
+        FastqReportNif {
+            summary,
+            per_position_quality,
+            per_position_content,
+            gc_distribution: self.gc,
+            length_distribution: self.lengths.into_iter().collect(),
+            duplication_levels,
+            deduplicated_fraction: self.sequences.len() as f64 / tracked,
+            overrepresented,
+            adapter_content,
+        }
+    }
+}
+
+/// FastQC-style report of a FASTQ file (plain or gzipped) in one streaming
+/// pass.  `summary` has the same fields and definitions as `fastq_stats`.
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn fastq_report(path: String) -> Result<FastqReportNif, NifError> {
+    let mut reader = FastqReader::open(&path)?;
+    let mut report = ReportBuilder::new();
+    while let Some(read) = reader.next_read()? {
+        report.add(&read);
+    }
+    Ok(report.finish())
+}
+
+// ===========================================================================
+// Demultiplexing
+// ===========================================================================
+
+#[derive(Debug, Clone, Copy, PartialEq)]
+enum BarcodeMode {
+    Index,
+    Inline,
+}
+
+fn parse_barcode_mode(s: &str) -> Result<BarcodeMode, NifError> {
+    match s {
+        "index" => Ok(BarcodeMode::Index),
+        "inline" => Ok(BarcodeMode::Inline),
+        _ => Err(NifError::invalid(format!(
+            "unknown barcode mode: {s} (expected index or inline)"
+        ))),
+    }
+}
+
+/// Sentinel prepended to barcodes and reads in [`Demuxer::distance`].
+const ANCHOR: u8 = 0;
+
+enum Assignment {
+    /// `trim` is where the inline barcode ends in the read (0 in index mode).
+    Sample { index: usize, perfect: bool, trim: usize },
+    Undetermined,
+    Ambiguous,
+}
+
+struct Demuxer {
+    mode: BarcodeMode,
+    max_mismatches: usize,
+    /// Expected barcodes per sample, uppercased: `[i7]`, `[i7, i5]` or `[inline]`.
+    barcodes: Vec<Vec<Vec<u8>>>,
+}
+
+impl Demuxer {
+    fn new(
+        samples: &[SampleBarcodeNif],
+        mode: BarcodeMode,
+        max_mismatches: usize,
+    ) -> Result<Self, NifError> {
+        let mut barcodes = Vec::with_capacity(samples.len());
+        for s in samples {
+            let row: Vec<&String> = match mode {
+                BarcodeMode::Index => s.i7.iter().chain(&s.i5).collect(),
+                BarcodeMode::Inline => s.inline.iter().collect(),
+            };
+            if row.is_empty() || row.iter().any(|b| b.is_empty()) {
+                return Err(NifError::invalid(format!(
+                    "sample {}: missing {} barcode",
+                    s.sample,
+                    if mode == BarcodeMode::Index { "i7" } else { "inline" }
+                )));
+            }
+            barcodes.push(row.iter().map(|b| b.to_ascii_uppercase().into_bytes()).collect::<Vec<_>>());
+        }
+        if barcodes.windows(2).any(|w| w[0].len() != w[1].len()) {
+            return Err(NifError::invalid("either every sample or none must have an i5 barcode"));
+        }
+        Ok(Self {
+            mode,
+            max_mismatches,
+            barcodes,
+        })
+    }
+
+    /// Edit distance of `expected` against `observed` and the end of the
+    /// match in `observed`, if at most `max_mismatches`.  Index barcodes
+    /// must match the whole header field; inline barcodes must start at the
+    /// first base of the read but may end anywhere, so an indel in the
+    /// barcode moves the trim point rather than the match.
+    ///
+    /// `myers_bitparallel` lets a match start anywhere in the text, so both
+    /// sides get `max_mismatches + 1` leading sentinel bytes that match no
+    /// base: a match that skips the first base then costs too much to be
+    /// reported, and every hit left is anchored at the start.
+    fn distance(&self, observed: &[u8], expected: &[u8]) -> Option<(usize, usize)> {
+        let anchor = self.max_mismatches + 1;
+        let anchored = |bases: &[u8]| -> Vec<u8> {
+            std::iter::repeat(ANCHOR).take(anchor).chain(bases.iter().copied()).collect()
+        };
+        let hits = cyanea_seq::myers_bitparallel(
+            &anchored(observed),
+            &anchored(expected),
+            self.max_mismatches,
+        );
+        // Hits are (last matched text position, distance).
+        let mut ends = hits
+            .into_iter()
+            .filter_map(|(pos, d)| Some((d, (pos + 1).checked_sub(anchor)?)));
+        match self.mode {
+            BarcodeMode::Index => ends.find(|&(_, end)| end == observed.len()),
+            BarcodeMode::Inline => ends.min_by_key(|&(d, end)| (d, end.abs_diff(expected.len()))),
+        }
+    }
+
+    /// The index barcodes in an Illumina header (`... 1:N:0:ACGT+TTGA`).
+    fn header_barcodes(read: &FastqRead) -> Vec<Vec<u8>> {
+        read.header
+            .split(|b| b.is_ascii_whitespace())
+            .nth(1)
+            .and_then(|desc| desc.rsplit(|&b| b == b':').next())
+            .map(|field| field.split(|&b| b == b'+').map(|b| b.to_ascii_uppercase()).collect())
+            .unwrap_or_default()
+    }
+
+    fn assign(&self, read: &FastqRead) -> Assignment {
+        let seq = read.seq.to_ascii_uppercase();
+        let observed = match self.mode {
+            BarcodeMode::Index => Self::header_barcodes(read),
+            BarcodeMode::Inline => Vec::new(),
+        };
+        let mut best: Option<(usize, usize, usize)> = None;
+        let mut ties = 0;
+        for (index, expected) in self.barcodes.iter().enumerate() {
+            let mut total = 0;
+            let mut trim = 0;
+            for (i, barcode) in expected.iter().enumerate() {
+                let window = match self.mode {
+                    BarcodeMode::Index => observed.get(i).map(Vec::as_slice),
+                    BarcodeMode::Inline => {
+                        Some(&seq[..seq.len().min(barcode.len() + self.max_mismatches)])
+                    }
+                };
+                match window.and_then(|w| self.distance(w, barcode)) {
+                    Some((d, end)) => {
+                        total += d;
+                        if self.mode == BarcodeMode::Inline {
+                            trim = end;
+                        }
+                    }
+                    None => {
+                        total = usize::MAX;
+                        break;
+                    }
+                }
+            }
+            if total == usize::MAX {
+                continue;
+            }
+            match best {
+                Some((_, d, _)) if d < total => {}
+                Some((_, d, _)) if d == total => ties += 1,
+                _ => {
+                    best = Some((index, total, trim));
+                    ties = 0;
+                }
+            }
+        }
+        match best {
+            None => Assignment::Undetermined,
+            Some(_) if ties > 0 => Assignment::Ambiguous,
+            Some((index, d, trim)) => Assignment::Sample {
+                index,
+                perfect: d == 0,
+                trim,
+            },
+        }
+    }
+}
+
+fn open_outputs(
+    dir: &str,
+    name: &str,
+    mates: usize,
+    compression: &str,
+) -> Result<(Vec<TextWriter>, Vec<String>), NifError> {
+    let ext = if compression == "none" { ".fastq" } else { ".fastq.gz" };
+    let paths: Vec<String> = if mates == 1 {
+        vec![format!("{dir}/{name}{ext}")]
+    } else {
+        vec![format!("{dir}/{name}_R1{ext}"), format!("{dir}/{name}_R2{ext}")]
+    };
+    let writers = paths
+        .iter()
+        .map(|p| TextWriter::create(p, compression))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok((writers, paths))
+}
+
+/// Split single or paired FASTQ input by sample into `output_dir`, one file
+/// per sample and mate plus `Undetermined`.  In `inline` mode the barcode is
+/// matched at the start of read 1 and trimmed off up to where it ends; in
+/// `index` mode it is read from the header.  Each barcode may be up to
+/// `max_mismatches` edits away; reads matching two samples equally well are
+/// left undetermined.
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn fastq_demultiplex(
+    inputs: Vec<String>,
+    samples: Vec<SampleBarcodeNif>,
+    mode: String,
+    max_mismatches: usize,
+    output_dir: String,
+    compression: String,
+) -> Result<DemuxResultNif, NifError> {
+    if inputs.is_empty() || inputs.len() > 2 {
+        return Err(NifError::invalid("expected one input path, or two for paired-end reads"));
+    }
+    if samples.is_empty() {
+        return Err(NifError::invalid("sample sheet is empty"));
+    }
+    let mut names = std::collections::HashSet::new();
+    for s in &samples {
+        if s.sample.is_empty() || s.sample.contains('/') || s.sample == "Undetermined" {
+            return Err(NifError::invalid(format!("invalid sample name: {:?}", s.sample)));
+        }
+        if !names.insert(s.sample.as_str()) {
+            return Err(NifError::invalid(format!("duplicate sample: {}", s.sample)));
+        }
+    }
+    let demuxer = Demuxer::new(&samples, parse_barcode_mode(&mode)?, max_mismatches)?;
+
+    let mut readers = inputs
+        .iter()
+        .map(|p| FastqReader::open(p))
+        .collect::<Result<Vec<_>, _>>()?;
+    std::fs::create_dir_all(&output_dir).map_err(|e| NifError::from(e).with_file(&output_dir))?;
+    let mut outputs = Vec::with_capacity(samples.len() + 1);
+    for s in &samples {
+        outputs.push(open_outputs(&output_dir, &s.sample, inputs.len(), &compression)?);
+    }
+    outputs.push(open_outputs(&output_dir, "Undetermined", inputs.len(), &compression)?);
+
+    let mut counts = vec![(0u64, 0u64); samples.len() + 1];
+    let (mut total, mut ambiguous) = (0u64, 0u64);
+    while let Some(mut record) = next_record(&mut readers)? {
+        total += 1;
+
+        let target = match demuxer.assign(&record[0]) {
+            Assignment::Sample { index, perfect, trim } => {
+                record[0].seq.drain(..trim);
+                record[0].qual.drain(..trim);
+                counts[index].1 += u64::from(perfect);
+                index
+            }
+            Assignment::Ambiguous => {
+                ambiguous += 1;
+                samples.len()
+            }
+            Assignment::Undetermined => samples.len(),
+        };
+        counts[target].0 += 1;
+        let (writers, paths) = &mut outputs[target];
+        for ((read, out), path) in record.iter().zip(writers.iter_mut()).zip(paths.iter()) {
+            write_read(out, read).map_err(|e| NifError::from(e).with_file(path))?;
+        }
+    }
+
+    let mut all_paths = Vec::with_capacity(outputs.len());
+    for (writers, paths) in outputs {
+        for (out, path) in writers.into_iter().zip(&paths) {
+            out.finish().map_err(|e| NifError::from(e).with_file(path))?;
+        }
+        all_paths.push(paths);
+    }
+    let undetermined_paths = all_paths.pop().unwrap_or_default();
+    Ok(DemuxResultNif {
+        records: total,
+        undetermined: counts[samples.len()].0,
+        ambiguous,
+        undetermined_paths,
+        samples: samples
+            .into_iter()
+            .zip(all_paths)
+            .zip(counts)
+            .map(|((s, paths), (records, perfect))| DemuxSampleNif {
+                sample: s.sample,
+                records,
+                perfect,
+                paths,
+            })
+            .collect(),
+    })
+}
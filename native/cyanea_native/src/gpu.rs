@@ -2,6 +2,7 @@
 
 use crate::bridge::*;
 use crate::to_nif_error;
+use rustler::Env;
 
 // ===========================================================================
 // Existing NIFs
@@ -25,64 +26,102 @@ pub fn gpu_info() -> GpuInfoNif {
 // New NIFs
 // ===========================================================================
 
-fn parse_gpu_metric(s: &str) -> Result<cyanea_gpu::DistanceMetricGpu, String> {
+fn parse_gpu_metric(s: &str) -> Result<cyanea_gpu::DistanceMetricGpu, NifError> {
     match s {
         "euclidean" => Ok(cyanea_gpu::DistanceMetricGpu::Euclidean),
         "manhattan" => Ok(cyanea_gpu::DistanceMetricGpu::Manhattan),
         "cosine" => Ok(cyanea_gpu::DistanceMetricGpu::Cosine),
-        _ => Err(format!(
+        _ => Err(NifError::invalid(format!(
             "unknown distance metric: {s} (expected euclidean, manhattan, or cosine)"
-        )),
+        ))),
     }
 }
 
 #[rustler::nif(schedule = "DirtyCpu")]
-pub fn gpu_pairwise_distances(
-    data: Vec<f64>,
+pub fn gpu_pairwise_distances<'a>(
+    env: Env<'a>,
+    data: MatrixInput,
     n: usize,
     dim: usize,
     metric: String,
-) -> Result<Vec<f64>, String> {
+) -> Result<MatrixOutput<'a>, NifError> {
+    let form = data.form();
+    let data = shaped_values(data, "data", n, dim)?;
     let backend = cyanea_gpu::auto_backend();
     let metric = parse_gpu_metric(&metric)?;
     let buf = backend.buffer_from_slice(&data).map_err(to_nif_error)?;
     let result = cyanea_gpu::ops::pairwise_distance_matrix(backend.as_ref(), &buf, n, dim, metric)
         .map_err(to_nif_error)?;
-    backend.read_buffer(&result).map_err(to_nif_error)
+    form.matrix(env, backend.read_buffer(&result).map_err(to_nif_error)?, n)
 }
 
 #[rustler::nif(schedule = "DirtyCpu")]
-pub fn gpu_matrix_multiply(
-    a: Vec<f64>,
-    b: Vec<f64>,
+pub fn gpu_matrix_multiply<'a>(
+    env: Env<'a>,
+    a: MatrixInput,
+    b: MatrixInput,
     m: usize,
     k: usize,
     n: usize,
-) -> Result<Vec<f64>, String> {
+) -> Result<MatrixOutput<'a>, NifError> {
+    let form = a.form();
+    let a = shaped_values(a, "a", m, k)?;
+    let b = shaped_values(b, "b", k, n)?;
     let backend = cyanea_gpu::auto_backend();
     let buf_a = backend.buffer_from_slice(&a).map_err(to_nif_error)?;
     let buf_b = backend.buffer_from_slice(&b).map_err(to_nif_error)?;
     let result = cyanea_gpu::ops::matrix_multiply(backend.as_ref(), &buf_a, &buf_b, m, k, n)
         .map_err(to_nif_error)?;
-    backend.read_buffer(&result).map_err(to_nif_error)
+    form.matrix(env, backend.read_buffer(&result).map_err(to_nif_error)?, n)
 }
 
 #[rustler::nif(schedule = "DirtyCpu")]
-pub fn gpu_reduce_sum(data: Vec<f64>) -> Result<f64, String> {
+pub fn gpu_reduce_sum(data: MatrixInput) -> Result<f64, NifError> {
+    let data = data.into_values()?;
     let backend = cyanea_gpu::auto_backend();
     let buf = backend.buffer_from_slice(&data).map_err(to_nif_error)?;
     cyanea_gpu::ops::reduce_sum(backend.as_ref(), &buf).map_err(to_nif_error)
 }
 
 #[rustler::nif(schedule = "DirtyCpu")]
-pub fn gpu_batch_z_score(
-    data: Vec<f64>,
+pub fn gpu_batch_z_score<'a>(
+    env: Env<'a>,
+    data: MatrixInput,
     n_rows: usize,
     n_cols: usize,
-) -> Result<Vec<f64>, String> {
+) -> Result<MatrixOutput<'a>, NifError> {
+    let form = data.form();
+    let data = shaped_values(data, "data", n_rows, n_cols)?;
     let backend = cyanea_gpu::auto_backend();
     let buf = backend.buffer_from_slice(&data).map_err(to_nif_error)?;
     let result = cyanea_gpu::ops::batch_z_score(backend.as_ref(), &buf, n_rows, n_cols)
         .map_err(to_nif_error)?;
-    backend.read_buffer(&result).map_err(to_nif_error)
+    form.matrix(env, backend.read_buffer(&result).map_err(to_nif_error)?, n_cols)
+}
+
+/// Row-major values of a `rows x cols` operand, rejecting binary matrices
+/// and row lists whose shape disagrees with the explicit dimensions, and
+/// ragged row lists.
+fn shaped_values(
+    input: MatrixInput,
+    name: &str,
+    rows: usize,
+    cols: usize,
+) -> Result<Vec<f64>, NifError> {
+    if let MatrixInput::Rows(row_list) = &input {
+        if let Some((i, row)) = row_list.iter().enumerate().find(|(_, r)| r.len() != cols) {
+            return Err(NifError::invalid(format!(
+                "{name} row {i} has {} values but {cols} columns were given",
+                row.len()
+            )));
+        }
+    }
+    if let Some(shape) = input.shape() {
+        if shape != (rows, cols) {
+            return Err(NifError::invalid(format!(
+                "{name} has shape {shape:?} but {rows}x{cols} was given"
+            )));
+        }
+    }
+    input.into_values()
 }
@@ -0,0 +1,119 @@
+//! Bridge to the htslib-compatible plumbing in `cyanea_io::hts` — BGZF
+//! block I/O, BAM records and BAI/CSI/tabix indexes — used by the indexed
+//! region query, index-building and writer NIFs in `io.rs` and by the
+//! mapper.  Only the conversion of BAM aux fields to NIF tag values and a
+//! read-name check before encoding live here.
+
+use crate::bridge::{NifError, SamCharTagNif, SamHexTagNif, SamTagValueNif};
+use crate::to_nif_error;
+use std::collections::HashMap;
+
+pub(crate) use cyanea_io::hts::{
+    parse_region, read_bam_header, read_bam_record, write_bam_header, write_bgzf_file, BamHeader,
+    BamRecord, BgzfReader, BgzfWriter, HtsIndex, IndexBuilder, TabixConf, BAI_DEPTH, BAI_MIN_SHIFT,
+};
+
+/// Open a BGZF file, with errors naming it.
+pub(crate) fn open_bgzf(path: &str) -> Result<BgzfReader, NifError> {
+    BgzfReader::open(path).map_err(|e| to_nif_error(e).with_file(path))
+}
+
+/// Load a BAI, CSI or tabix index, with errors naming it.
+pub(crate) fn load_index(path: &str) -> Result<HtsIndex, NifError> {
+    HtsIndex::load(path).map_err(|e| to_nif_error(e).with_file(path))
+}
+
+/// Longest read name BAM can hold: `l_read_name` is one byte and counts
+/// the trailing NUL.
+const BAM_MAX_QNAME: usize = 254;
+
+/// A record's BAM encoding, refusing read names too long for the format
+/// rather than writing a corrupt record.
+pub(crate) fn encode_bam_record(record: &BamRecord) -> Result<Vec<u8>, NifError> {
+    if record.qname.len() > BAM_MAX_QNAME {
+        return Err(NifError::invalid(format!(
+            "read name of {} bytes is longer than BAM allows ({BAM_MAX_QNAME})",
+            record.qname.len()
+        )));
+    }
+    Ok(record.encode())
+}
+
+/// Decode the binary auxiliary fields of a BAM record into typed tag values.
+pub(crate) fn decode_bam_aux(aux: &[u8]) -> Result<HashMap<String, SamTagValueNif>, NifError> {
+    fn take<'a>(aux: &'a [u8], pos: &mut usize, n: usize) -> Result<&'a [u8], NifError> {
+        let end = *pos + n;
+        let slice = aux.get(*pos..end).ok_or_else(|| NifError::parse("truncated BAM aux field"))?;
+        *pos = end;
+        Ok(slice)
+    }
+    fn int(aux: &[u8], pos: &mut usize, kind: u8) -> Result<Option<i64>, NifError> {
+        Ok(Some(match kind {
+            b'c' => take(aux, pos, 1)?[0] as i8 as i64,
+            b'C' => take(aux, pos, 1)?[0] as i64,
+            b's' => i16::from_le_bytes(take(aux, pos, 2)?.try_into().unwrap()) as i64,
+            b'S' => u16::from_le_bytes(take(aux, pos, 2)?.try_into().unwrap()) as i64,
+            b'i' => i32::from_le_bytes(take(aux, pos, 4)?.try_into().unwrap()) as i64,
+            b'I' => u32::from_le_bytes(take(aux, pos, 4)?.try_into().unwrap()) as i64,
+            _ => return Ok(None),
+        }))
+    }
+    fn float(aux: &[u8], pos: &mut usize) -> Result<f64, NifError> {
+        Ok(f32::from_le_bytes(take(aux, pos, 4)?.try_into().unwrap()) as f64)
+    }
+
+    let mut tags = HashMap::new();
+    let mut pos = 0;
+    while pos < aux.len() {
+        let key = String::from_utf8_lossy(take(aux, &mut pos, 2)?).into_owned();
+        let kind = take(aux, &mut pos, 1)?[0];
+        let value = match kind {
+            b'A' => SamTagValueNif::Char(SamCharTagNif {
+                value: (take(aux, &mut pos, 1)?[0] as char).to_string(),
+            }),
+            b'f' => SamTagValueNif::Float(float(aux, &mut pos)?),
+            b'Z' | b'H' => {
+                let len = aux[pos..]
+                    .iter()
+                    .position(|&b| b == 0)
+                    .ok_or_else(|| NifError::parse("unterminated BAM string tag"))?;
+                let text = String::from_utf8_lossy(take(aux, &mut pos, len)?).into_owned();
+                pos += 1;
+                if kind == b'H' {
+                    SamTagValueNif::Hex(SamHexTagNif { value: text })
+                } else {
+                    SamTagValueNif::String(text)
+                }
+            }
+            b'B' => {
+                let sub = take(aux, &mut pos, 1)?[0];
+                let count = u32::from_le_bytes(take(aux, &mut pos, 4)?.try_into().unwrap()) as usize;
+                if sub == b'f' {
+                    let values = (0..count)
+                        .map(|_| float(aux, &mut pos))
+                        .collect::<Result<_, _>>()?;
+                    SamTagValueNif::FloatArray(values)
+                } else {
+                    let mut values = Vec::with_capacity(count);
+                    for _ in 0..count {
+                        values.push(
+                            int(aux, &mut pos, sub)?
+                                .ok_or_else(|| {
+                                    NifError::parse(format!("invalid B array subtype in tag {key}"))
+                                })?,
+                        );
+                    }
+                    SamTagValueNif::IntegerArray(values)
+                }
+            }
+            _ => SamTagValueNif::Integer(
+                int(aux, &mut pos, kind)?
+                    .ok_or_else(|| {
+                        NifError::parse(format!("invalid type '{}' for tag {key}", kind as char))
+                    })?,
+            ),
+        };
+        tags.insert(key, value);
+    }
+    Ok(tags)
+}
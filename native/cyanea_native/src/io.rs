@@ -2,41 +2,49 @@
 //! Parquet, GenBank, EMBL, Stockholm, Clustal, Phylip, bigWig, bedGraph).
 
 use crate::bridge::*;
+use crate::hts;
 use crate::to_nif_error;
-use std::collections::HashSet;
+use flate2::read::MultiGzDecoder;
+use flate2::write::GzEncoder;
+use flate2::Compression;
+use rustler::ResourceArc;
+use std::collections::{BTreeMap, HashMap, HashSet};
+use std::fs::File;
+use std::io::{BufRead, BufReader, BufWriter, Read, Seek, SeekFrom, Write};
+use std::sync::Mutex;
 
 // ===========================================================================
 // Existing NIFs
 // ===========================================================================
 
 #[rustler::nif(schedule = "DirtyCpu")]
-pub fn csv_info(path: String) -> Result<CsvInfoNif, String> {
+pub fn csv_info(path: String) -> Result<CsvInfoNif, NifError> {
     cyanea_io::parse_csv_info(&path)
         .map(CsvInfoNif::from)
         .map_err(to_nif_error)
 }
 
 #[rustler::nif(schedule = "DirtyCpu")]
-pub fn csv_preview(path: String, limit: usize) -> Result<String, String> {
+pub fn csv_preview(path: String, limit: usize) -> Result<String, NifError> {
     cyanea_io::csv_preview(&path, limit).map_err(to_nif_error)
 }
 
 #[rustler::nif(schedule = "DirtyCpu")]
-pub fn vcf_stats(path: String) -> Result<VcfStatsNif, String> {
+pub fn vcf_stats(path: String) -> Result<VcfStatsNif, NifError> {
     cyanea_io::vcf_stats(&path)
         .map(VcfStatsNif::from)
         .map_err(to_nif_error)
 }
 
 #[rustler::nif(schedule = "DirtyCpu")]
-pub fn bed_stats(path: String) -> Result<BedStatsNif, String> {
+pub fn bed_stats(path: String) -> Result<BedStatsNif, NifError> {
     cyanea_io::bed_stats(&path)
         .map(BedStatsNif::from)
         .map_err(to_nif_error)
 }
 
 #[rustler::nif(schedule = "DirtyCpu")]
-pub fn gff3_stats(path: String) -> Result<GffStatsNif, String> {
+pub fn gff3_stats(path: String) -> Result<GffStatsNif, NifError> {
     cyanea_io::gff3_stats(&path)
         .map(GffStatsNif::from)
         .map_err(to_nif_error)
@@ -47,23 +55,13 @@ pub fn gff3_stats(path: String) -> Result<GffStatsNif, String> {
 // ===========================================================================
 
 #[rustler::nif(schedule = "DirtyCpu")]
-pub fn parse_vcf(path: String) -> Result<Vec<VcfRecordNif>, String> {
-    let variants = cyanea_io::parse_vcf(&path).map_err(to_nif_error)?;
-    Ok(variants
-        .into_iter()
-        .map(|v| VcfRecordNif {
-            chrom: v.chrom.clone(),
-            position: v.position,
-            ref_allele: String::from_utf8_lossy(&v.ref_allele).to_string(),
-            alt_alleles: v.alt_alleles.iter().map(|a| String::from_utf8_lossy(a).to_string()).collect(),
-            quality: v.quality,
-            filter: format!("{:?}", v.filter),
-        })
-        .collect())
+pub fn parse_vcf(path: String) -> Result<Vec<VcfRecordNif>, NifError> {
+    let reader = VcfReaderResource::open(&path)?;
+    reader.next_batch(usize::MAX)
 }
 
 #[rustler::nif(schedule = "DirtyCpu")]
-pub fn parse_bed(path: String) -> Result<Vec<BedRecordNif>, String> {
+pub fn parse_bed(path: String) -> Result<Vec<BedRecordNif>, NifError> {
     let records = cyanea_io::parse_bed(&path).map_err(to_nif_error)?;
     Ok(records
         .into_iter()
@@ -79,7 +77,7 @@ pub fn parse_bed(path: String) -> Result<Vec<BedRecordNif>, String> {
 }
 
 #[rustler::nif(schedule = "DirtyCpu")]
-pub fn parse_gff3(path: String) -> Result<Vec<GffGeneNif>, String> {
+pub fn parse_gff3(path: String) -> Result<Vec<GffGeneNif>, NifError> {
     let genes = cyanea_io::parse_gff3(&path).map_err(to_nif_error)?;
     Ok(genes
         .into_iter()
@@ -97,69 +95,53 @@ pub fn parse_gff3(path: String) -> Result<Vec<GffGeneNif>, String> {
 }
 
 #[rustler::nif(schedule = "DirtyCpu")]
-pub fn sam_stats(path: String) -> Result<SamStatsNif, String> {
-    let records = cyanea_io::parse_sam(&path).map_err(to_nif_error)?;
-    let stats = cyanea_io::sam_stats(&records);
-    Ok(SamStatsNif {
-        total_reads: stats.total_reads,
-        mapped: stats.mapped,
-        unmapped: stats.unmapped,
-        avg_mapq: stats.avg_mapq,
-        avg_length: stats.avg_length,
-    })
+pub fn sam_stats(path: String) -> Result<SamStatsNif, NifError> {
+    let mut flagstat = FlagStats::default();
+    for_each_sam_record(&path, |r| flagstat.add(r))?;
+    Ok(flagstat.finish())
 }
 
 #[rustler::nif(schedule = "DirtyCpu")]
-pub fn bam_stats(path: String) -> Result<SamStatsNif, String> {
-    let records = cyanea_io::parse_bam(&path).map_err(to_nif_error)?;
-    let stats = cyanea_io::sam_stats(&records);
-    Ok(SamStatsNif {
-        total_reads: stats.total_reads,
-        mapped: stats.mapped,
-        unmapped: stats.unmapped,
-        avg_mapq: stats.avg_mapq,
-        avg_length: stats.avg_length,
-    })
+pub fn bam_stats(path: String) -> Result<SamStatsNif, NifError> {
+    let mut flagstat = FlagStats::default();
+    for_each_bam_record(&path, |r| flagstat.add(r))?;
+    Ok(flagstat.finish())
 }
 
 #[rustler::nif(schedule = "DirtyCpu")]
-pub fn parse_sam(path: String) -> Result<Vec<SamRecordNif>, String> {
-    let records = cyanea_io::parse_sam(&path).map_err(to_nif_error)?;
-    Ok(records
-        .into_iter()
-        .map(|r| SamRecordNif {
-            qname: r.qname.clone(),
-            flag: r.flag,
-            rname: r.rname.clone(),
-            pos: r.pos,
-            mapq: r.mapq,
-            cigar: r.cigar.clone(),
-            sequence: r.sequence.clone(),
-            quality: r.quality.clone(),
-        })
-        .collect())
+pub fn parse_sam(path: String) -> Result<Vec<SamRecordNif>, NifError> {
+    let mut records = Vec::new();
+    for_each_sam_record(&path, |r| records.push(r))?;
+    Ok(records)
 }
 
 #[rustler::nif(schedule = "DirtyCpu")]
-pub fn parse_bam(path: String) -> Result<Vec<SamRecordNif>, String> {
-    let records = cyanea_io::parse_bam(&path).map_err(to_nif_error)?;
-    Ok(records
-        .into_iter()
-        .map(|r| SamRecordNif {
-            qname: r.qname.clone(),
-            flag: r.flag,
-            rname: r.rname.clone(),
-            pos: r.pos,
-            mapq: r.mapq,
-            cigar: r.cigar.clone(),
-            sequence: r.sequence.clone(),
-            quality: r.quality.clone(),
-        })
-        .collect())
+pub fn parse_bam(path: String) -> Result<Vec<SamRecordNif>, NifError> {
+    let mut records = Vec::new();
+    for_each_bam_record(&path, |r| records.push(r))?;
+    Ok(records)
+}
+
+#[rustler::nif]
+pub fn sam_flags(flag: u16) -> SamFlagsNif {
+    SamFlagsNif {
+        paired: flag & FLAG_PAIRED != 0,
+        proper_pair: flag & FLAG_PROPER_PAIR != 0,
+        unmapped: flag & FLAG_UNMAPPED != 0,
+        mate_unmapped: flag & FLAG_MATE_UNMAPPED != 0,
+        reverse: flag & FLAG_REVERSE != 0,
+        mate_reverse: flag & FLAG_MATE_REVERSE != 0,
+        first_in_pair: flag & FLAG_READ1 != 0,
+        second_in_pair: flag & FLAG_READ2 != 0,
+        secondary: flag & FLAG_SECONDARY != 0,
+        qc_fail: flag & FLAG_QC_FAIL != 0,
+        duplicate: flag & FLAG_DUPLICATE != 0,
+        supplementary: flag & FLAG_SUPPLEMENTARY != 0,
+    }
 }
 
 #[rustler::nif(schedule = "DirtyCpu")]
-pub fn parse_bed_intervals(path: String) -> Result<Vec<GenomicIntervalNif>, String> {
+pub fn parse_bed_intervals(path: String) -> Result<Vec<GenomicIntervalNif>, NifError> {
     let records = cyanea_io::parse_bed(&path).map_err(to_nif_error)?;
     Ok(records
         .into_iter()
@@ -177,7 +159,7 @@ pub fn parse_bed_intervals(path: String) -> Result<Vec<GenomicIntervalNif>, Stri
 // ===========================================================================
 
 #[rustler::nif(schedule = "DirtyCpu")]
-pub fn parquet_stats(path: String) -> Result<ParquetStatsNif, String> {
+pub fn parquet_stats(path: String) -> Result<ParquetStatsNif, NifError> {
     let info = cyanea_io::parquet_info(&path).map_err(to_nif_error)?;
     Ok(ParquetStatsNif {
         row_count: info.num_rows as u64,
@@ -188,7 +170,7 @@ pub fn parquet_stats(path: String) -> Result<ParquetStatsNif, String> {
 }
 
 #[rustler::nif(schedule = "DirtyCpu")]
-pub fn genbank_stats(path: String) -> Result<GenbankStatsNif, String> {
+pub fn genbank_stats(path: String) -> Result<GenbankStatsNif, NifError> {
     let records = cyanea_io::parse_genbank(&path).map_err(to_nif_error)?;
     let total_features: usize = records.iter().map(|r| r.features.len()).sum();
     let organism = records.first()
@@ -209,8 +191,8 @@ pub fn genbank_stats(path: String) -> Result<GenbankStatsNif, String> {
 }
 
 #[rustler::nif(schedule = "DirtyCpu")]
-pub fn embl_stats(path: String) -> Result<EmblStatsNif, String> {
-    let contents = std::fs::read_to_string(&path).map_err(|e| e.to_string())?;
+pub fn embl_stats(path: String) -> Result<EmblStatsNif, NifError> {
+    let contents = std::fs::read_to_string(&path).map_err(|e| NifError::from(e).with_file(&path))?;
     let records = cyanea_io::parse_embl(&contents).map_err(to_nif_error)?;
     let total_features: usize = records.iter().map(|r| r.features.len()).sum();
     let organism = String::new();
@@ -229,11 +211,11 @@ pub fn embl_stats(path: String) -> Result<EmblStatsNif, String> {
 }
 
 #[rustler::nif(schedule = "DirtyCpu")]
-pub fn newick_file_stats(path: String) -> Result<NewickFileStatsNif, String> {
-    let contents = std::fs::read_to_string(&path).map_err(|e| e.to_string())?;
+pub fn newick_file_stats(path: String) -> Result<NewickFileStatsNif, NifError> {
+    let contents = std::fs::read_to_string(&path).map_err(|e| NifError::from(e).with_file(&path))?;
     let tree = cyanea_phylo::parse_newick(&contents).map_err(to_nif_error)?;
     let taxa_count = tree.leaf_count();
-    let root_node = tree.get_node(tree.root()).ok_or_else(|| "invalid root node".to_string())?;
+    let root_node = tree.get_node(tree.root()).ok_or_else(|| NifError::parse("invalid root node").with_file(&path))?;
     let is_rooted = root_node.children.len() == 2;
     let has_branch_lengths = tree.nodes().iter().any(|n| n.branch_length.is_some());
     Ok(NewickFileStatsNif {
@@ -244,8 +226,8 @@ pub fn newick_file_stats(path: String) -> Result<NewickFileStatsNif, String> {
 }
 
 #[rustler::nif(schedule = "DirtyCpu")]
-pub fn nexus_file_stats(path: String) -> Result<NexusFileStatsNif, String> {
-    let contents = std::fs::read_to_string(&path).map_err(|e| e.to_string())?;
+pub fn nexus_file_stats(path: String) -> Result<NexusFileStatsNif, NifError> {
+    let contents = std::fs::read_to_string(&path).map_err(|e| NifError::from(e).with_file(&path))?;
     let nexus = cyanea_phylo::nexus::parse(&contents).map_err(to_nif_error)?;
     let taxa_count = nexus.taxa.len();
     let tree_count = nexus.trees.len();
@@ -258,8 +240,8 @@ pub fn nexus_file_stats(path: String) -> Result<NexusFileStatsNif, String> {
 }
 
 #[rustler::nif(schedule = "DirtyCpu")]
-pub fn sdf_stats(path: String) -> Result<SdfStatsNif, String> {
-    let contents = std::fs::read_to_string(&path).map_err(|e| e.to_string())?;
+pub fn sdf_stats(path: String) -> Result<SdfStatsNif, NifError> {
+    let contents = std::fs::read_to_string(&path).map_err(|e| NifError::from(e).with_file(&path))?;
     let molecules = cyanea_chem::parse_sdf(&contents);
     let mut molecule_count: usize = 0;
     let mut total_atoms: usize = 0;
@@ -280,8 +262,8 @@ pub fn sdf_stats(path: String) -> Result<SdfStatsNif, String> {
 }
 
 #[rustler::nif(schedule = "DirtyCpu")]
-pub fn pdb_file_stats(path: String) -> Result<PdbFileStatsNif, String> {
-    let contents = std::fs::read_to_string(&path).map_err(|e| e.to_string())?;
+pub fn pdb_file_stats(path: String) -> Result<PdbFileStatsNif, NifError> {
+    let contents = std::fs::read_to_string(&path).map_err(|e| NifError::from(e).with_file(&path))?;
     let structure = cyanea_struct::parse_pdb(&contents).map_err(to_nif_error)?;
     let resolution = extract_pdb_resolution(&contents);
     let method = extract_pdb_method(&contents);
@@ -294,8 +276,8 @@ pub fn pdb_file_stats(path: String) -> Result<PdbFileStatsNif, String> {
 }
 
 #[rustler::nif(schedule = "DirtyCpu")]
-pub fn mmcif_file_stats(path: String) -> Result<PdbFileStatsNif, String> {
-    let contents = std::fs::read_to_string(&path).map_err(|e| e.to_string())?;
+pub fn mmcif_file_stats(path: String) -> Result<PdbFileStatsNif, NifError> {
+    let contents = std::fs::read_to_string(&path).map_err(|e| NifError::from(e).with_file(&path))?;
     let structure = cyanea_struct::parse_mmcif(&contents).map_err(to_nif_error)?;
     let resolution = extract_mmcif_resolution(&contents);
     let method = extract_mmcif_method(&contents);
@@ -308,8 +290,8 @@ pub fn mmcif_file_stats(path: String) -> Result<PdbFileStatsNif, String> {
 }
 
 #[rustler::nif(schedule = "DirtyCpu")]
-pub fn stockholm_stats(path: String) -> Result<AlignmentStatsNif, String> {
-    let contents = std::fs::read_to_string(&path).map_err(|e| e.to_string())?;
+pub fn stockholm_stats(path: String) -> Result<AlignmentStatsNif, NifError> {
+    let contents = std::fs::read_to_string(&path).map_err(|e| NifError::from(e).with_file(&path))?;
     let alignments = cyanea_io::parse_stockholm(&contents).map_err(to_nif_error)?;
     let (seq_count, aln_length) = if let Some(aln) = alignments.first() {
         let sc = aln.sequences.len();
@@ -325,8 +307,8 @@ pub fn stockholm_stats(path: String) -> Result<AlignmentStatsNif, String> {
 }
 
 #[rustler::nif(schedule = "DirtyCpu")]
-pub fn clustal_stats(path: String) -> Result<AlignmentStatsNif, String> {
-    let contents = std::fs::read_to_string(&path).map_err(|e| e.to_string())?;
+pub fn clustal_stats(path: String) -> Result<AlignmentStatsNif, NifError> {
+    let contents = std::fs::read_to_string(&path).map_err(|e| NifError::from(e).with_file(&path))?;
     let aln = cyanea_io::parse_clustal(&contents).map_err(to_nif_error)?;
     let seq_count = aln.sequences.len();
     let aln_length = aln.sequences.first().map(|(_, s)| s.len()).unwrap_or(0);
@@ -337,8 +319,8 @@ pub fn clustal_stats(path: String) -> Result<AlignmentStatsNif, String> {
 }
 
 #[rustler::nif(schedule = "DirtyCpu")]
-pub fn phylip_stats(path: String) -> Result<AlignmentStatsNif, String> {
-    let contents = std::fs::read_to_string(&path).map_err(|e| e.to_string())?;
+pub fn phylip_stats(path: String) -> Result<AlignmentStatsNif, NifError> {
+    let contents = std::fs::read_to_string(&path).map_err(|e| NifError::from(e).with_file(&path))?;
     let aln = cyanea_io::parse_phylip(&contents).map_err(to_nif_error)?;
     Ok(AlignmentStatsNif {
         sequence_count: aln.n_taxa,
@@ -347,7 +329,7 @@ pub fn phylip_stats(path: String) -> Result<AlignmentStatsNif, String> {
 }
 
 #[rustler::nif(schedule = "DirtyCpu")]
-pub fn bigwig_stats(path: String) -> Result<BigWigStatsNif, String> {
+pub fn bigwig_stats(path: String) -> Result<BigWigStatsNif, NifError> {
     let header = cyanea_io::read_bigwig_header(&path).map_err(to_nif_error)?;
     Ok(BigWigStatsNif {
         chrom_count: header.chrom_count as usize,
@@ -356,8 +338,8 @@ pub fn bigwig_stats(path: String) -> Result<BigWigStatsNif, String> {
 }
 
 #[rustler::nif(schedule = "DirtyCpu")]
-pub fn bedgraph_stats(path: String) -> Result<BedGraphStatsNif, String> {
-    let contents = std::fs::read_to_string(&path).map_err(|e| e.to_string())?;
+pub fn bedgraph_stats(path: String) -> Result<BedGraphStatsNif, NifError> {
+    let contents = std::fs::read_to_string(&path).map_err(|e| NifError::from(e).with_file(&path))?;
     let records = cyanea_io::parse_bedgraph_str(&contents).map_err(to_nif_error)?;
     let record_count = records.len();
     let chroms: HashSet<&str> = records.iter().map(|r| r.chrom.as_str()).collect();
@@ -368,6 +350,1393 @@ pub fn bedgraph_stats(path: String) -> Result<BedGraphStatsNif, String> {
     })
 }
 
+// ===========================================================================
+// Streaming VCF reader (resource)
+// ===========================================================================
+
+/// Open a text file for line-by-line reading, transparently decompressing
+/// gzip and bgzip (a series of gzip members) based on the magic bytes.
+pub(crate) fn open_text_reader(path: &str) -> Result<Box<dyn BufRead + Send>, NifError> {
+    let io_err = |e: std::io::Error| NifError::from(e).with_file(path);
+    let mut file = File::open(path).map_err(io_err)?;
+    let mut magic = [0u8; 2];
+    let n = file.read(&mut magic).map_err(io_err)?;
+    file.seek(SeekFrom::Start(0)).map_err(io_err)?;
+    if n == 2 && magic == [0x1f, 0x8b] {
+        Ok(Box::new(BufReader::new(MultiGzDecoder::new(file))))
+    } else {
+        Ok(Box::new(BufReader::new(file)))
+    }
+}
+
+struct VcfCursor {
+    lines: Box<dyn BufRead + Send>,
+    line_no: u64,
+    done: bool,
+}
+
+/// A cursor over a (optionally bgzipped) VCF file. The header is parsed on
+/// open; records are read in batches with `vcf_next_batch`.
+pub struct VcfReaderResource {
+    path: String,
+    header: VcfHeaderNif,
+    cursor: Mutex<VcfCursor>,
+}
+
+#[rustler::resource_impl]
+impl rustler::Resource for VcfReaderResource {}
+
+impl VcfReaderResource {
+    fn open(path: &str) -> Result<Self, NifError> {
+        let mut lines = open_text_reader(path)?;
+        let mut header = VcfHeaderNif {
+            fileformat: String::new(),
+            samples: Vec::new(),
+            contigs: Vec::new(),
+            filters: Vec::new(),
+            info: Vec::new(),
+            format: Vec::new(),
+            meta: Vec::new(),
+        };
+        let mut line_no = 0u64;
+        let mut buf = String::new();
+        loop {
+            buf.clear();
+            let n = lines
+                .read_line(&mut buf)
+                .map_err(|e| NifError::from(e).with_file(path).at_line(line_no + 1))?;
+            if n == 0 {
+                return Err(NifError::parse("missing #CHROM header line").with_file(path));
+            }
+            line_no += 1;
+            let line = buf.trim_end_matches(['\n', '\r']);
+            if let Some(meta) = line.strip_prefix("##") {
+                parse_vcf_meta(meta, &mut header);
+            } else if line.starts_with("#CHROM") {
+                header.samples = line.split('\t').skip(9).map(String::from).collect();
+                break;
+            } else if !line.is_empty() {
+                return Err(NifError::parse("expected header line before records")
+                    .with_file(path)
+                    .at_line(line_no));
+            }
+        }
+        Ok(Self {
+            path: path.to_string(),
+            header,
+            cursor: Mutex::new(VcfCursor {
+                lines,
+                line_no,
+                done: false,
+            }),
+        })
+    }
+
+    fn next_batch(&self, n: usize) -> Result<Vec<VcfRecordNif>, NifError> {
+        let mut cursor = self
+            .cursor
+            .lock()
+            .map_err(|_| NifError::internal("VCF reader lock poisoned"))?;
+        let mut records = Vec::new();
+        let mut buf = String::new();
+        while records.len() < n && !cursor.done {
+            buf.clear();
+            let read = cursor
+                .lines
+                .read_line(&mut buf)
+                .map_err(|e| {
+                    NifError::from(e)
+                        .with_file(&self.path)
+                        .at_line(cursor.line_no + 1)
+                })?;
+            if read == 0 {
+                cursor.done = true;
+                break;
+            }
+            cursor.line_no += 1;
+            let line = buf.trim_end_matches(['\n', '\r']);
+            if line.is_empty() {
+                continue;
+            }
+            records.push(
+                parse_vcf_record(line, &self.header)
+                    .map_err(|e| e.with_file(&self.path).at_line(cursor.line_no))?,
+            );
+        }
+        Ok(records)
+    }
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn vcf_open(path: String) -> Result<ResourceArc<VcfReaderResource>, NifError> {
+    VcfReaderResource::open(&path).map(ResourceArc::new)
+}
+
+#[rustler::nif]
+pub fn vcf_header(reader: ResourceArc<VcfReaderResource>) -> VcfHeaderNif {
+    reader.header.clone()
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn vcf_next_batch(
+    reader: ResourceArc<VcfReaderResource>,
+    n: usize,
+) -> Result<Vec<VcfRecordNif>, NifError> {
+    reader.next_batch(n)
+}
+
+/// Split the body of a structured meta line (`ID=DP,Number=1,...`) into
+/// key/value pairs, honouring double-quoted values.
+fn parse_meta_fields(body: &str) -> HashMap<String, String> {
+    let mut fields = HashMap::new();
+    let mut key = String::new();
+    let mut value = String::new();
+    let mut in_key = true;
+    let mut in_quotes = false;
+    for c in body.chars() {
+        match c {
+            '"' => in_quotes = !in_quotes,
+            '=' if in_key && !in_quotes => in_key = false,
+            ',' if !in_quotes => {
+                fields.insert(std::mem::take(&mut key), std::mem::take(&mut value));
+                in_key = true;
+            }
+            _ if in_key => key.push(c),
+            _ => value.push(c),
+        }
+    }
+    if !key.is_empty() {
+        fields.insert(key, value);
+    }
+    fields
+}
+
+fn parse_vcf_meta(meta: &str, header: &mut VcfHeaderNif) {
+    let Some((key, value)) = meta.split_once('=') else {
+        return;
+    };
+    if key == "fileformat" {
+        header.fileformat = value.to_string();
+        return;
+    }
+    header.meta.push(meta.to_string());
+    let Some(body) = value.strip_prefix('<').and_then(|v| v.strip_suffix('>')) else {
+        return;
+    };
+    let mut fields = parse_meta_fields(body);
+    let id = fields.remove("ID").unwrap_or_default();
+    match key {
+        "contig" => header.contigs.push(id),
+        "FILTER" => header.filters.push(id),
+        "INFO" | "FORMAT" => {
+            let def = VcfFieldDefNif {
+                id,
+                number: fields.remove("Number").unwrap_or_else(|| ".".into()),
+                value_type: fields.remove("Type").unwrap_or_else(|| "String".into()),
+                description: fields.remove("Description").unwrap_or_default(),
+            };
+            if key == "INFO" {
+                header.info.push(def);
+            } else {
+                header.format.push(def);
+            }
+        }
+        _ => {}
+    }
+}
+
+fn parse_vcf_scalar(raw: &str, value_type: &str) -> Result<Option<VcfScalarNif>, NifError> {
+    if raw == "." {
+        return Ok(None);
+    }
+    match value_type {
+        "Integer" => raw
+            .parse::<i64>()
+            .map(|v| Some(VcfScalarNif::Integer(v)))
+            .map_err(|_| NifError::parse(format!("invalid Integer '{raw}'"))),
+        "Float" => raw
+            .parse::<f64>()
+            .map(|v| Some(VcfScalarNif::Float(v)))
+            .map_err(|_| NifError::parse(format!("invalid Float '{raw}'"))),
+        _ => Ok(Some(VcfScalarNif::String(raw.to_string()))),
+    }
+}
+
+fn parse_vcf_info(
+    field: &str,
+    header: &VcfHeaderNif,
+) -> Result<HashMap<String, Option<VcfValueNif>>, NifError> {
+    let mut info = HashMap::new();
+    if field == "." {
+        return Ok(info);
+    }
+    for entry in field.split(';').filter(|e| !e.is_empty()) {
+        let (key, raw) = match entry.split_once('=') {
+            Some((k, v)) => (k, Some(v)),
+            None => (entry, None),
+        };
+        let def = header.info.iter().find(|d| d.id == key);
+        let value = match (raw, def) {
+            (None, _) => Some(VcfValueNif::Flag(true)),
+            (Some(_), Some(def)) if def.value_type == "Flag" => Some(VcfValueNif::Flag(true)),
+            (Some(raw), Some(def)) if def.number == "1" => {
+                parse_vcf_scalar(raw, &def.value_type)
+                    .map_err(|e| info_error(key, e))?
+                    .map(VcfValueNif::Scalar)
+            }
+            (Some(raw), Some(def)) => {
+                let values = raw
+                    .split(',')
+                    .map(|v| parse_vcf_scalar(v, &def.value_type))
+                    .collect::<Result<Vec<_>, _>>()
+                    .map_err(|e| info_error(key, e))?;
+                Some(VcfValueNif::List(values))
+            }
+            // Undeclared keys are kept as untyped strings.
+            (Some(raw), None) => Some(VcfValueNif::Scalar(VcfScalarNif::String(raw.to_string()))),
+        };
+        info.insert(key.to_string(), value);
+    }
+    Ok(info)
+}
+
+fn info_error(key: &str, e: NifError) -> NifError {
+    NifError::parse(format!("INFO {key}: {}", e.message))
+}
+
+/// Parse an integer FORMAT value, tolerating float-formatted values some
+/// callers emit for DP/GQ (e.g. `99.0`).
+fn parse_vcf_int(raw: &str, key: &str) -> Result<Option<i64>, NifError> {
+    if raw == "." || raw.is_empty() {
+        return Ok(None);
+    }
+    raw.parse::<i64>()
+        .ok()
+        .or_else(|| raw.parse::<f64>().ok().map(|v| v.round() as i64))
+        .map(Some)
+        .ok_or_else(|| NifError::parse(format!("invalid {key} value '{raw}'")))
+}
+
+fn parse_vcf_sample(column: &str, format: &[String]) -> Result<VcfSampleNif, NifError> {
+    let mut sample = VcfSampleNif {
+        genotype: None,
+        alleles: Vec::new(),
+        phased: false,
+        depth: None,
+        genotype_quality: None,
+        allele_depths: Vec::new(),
+        other: HashMap::new(),
+    };
+    for (key, raw) in format.iter().zip(column.split(':')) {
+        match key.as_str() {
+            "GT" if raw != "." => {
+                sample.phased = raw.contains('|');
+                sample.alleles = raw.split(['/', '|']).map(|a| a.parse::<u32>().ok()).collect();
+                sample.genotype = Some(raw.to_string());
+            }
+            "GT" => {}
+            "DP" => sample.depth = parse_vcf_int(raw, "DP")?,
+            "GQ" => sample.genotype_quality = parse_vcf_int(raw, "GQ")?,
+            "AD" if raw != "." => {
+                sample.allele_depths = raw
+                    .split(',')
+                    .map(|v| parse_vcf_int(v, "AD"))
+                    .collect::<Result<_, _>>()?;
+            }
+            "AD" => {}
+            _ => {
+                sample.other.insert(key.clone(), raw.to_string());
+            }
+        }
+    }
+    Ok(sample)
+}
+
+fn parse_vcf_record(line: &str, header: &VcfHeaderNif) -> Result<VcfRecordNif, NifError> {
+    let cols: Vec<&str> = line.split('\t').collect();
+    if cols.len() < 8 {
+        return Err(NifError::parse(format!(
+            "expected at least 8 columns but got {}",
+            cols.len()
+        )));
+    }
+    let position = cols[1]
+        .parse::<u64>()
+        .map_err(|_| NifError::parse(format!("invalid POS '{}'", cols[1])))?;
+    let quality = match cols[5] {
+        "." => None,
+        q => Some(
+            q.parse::<f64>()
+                .map_err(|_| NifError::parse(format!("invalid QUAL '{q}'")))?,
+        ),
+    };
+    let format: Vec<String> = match cols.get(8) {
+        Some(f) if *f != "." => f.split(':').map(String::from).collect(),
+        _ => Vec::new(),
+    };
+    let samples = cols
+        .iter()
+        .skip(9)
+        .map(|c| parse_vcf_sample(c, &format))
+        .collect::<Result<Vec<_>, _>>()?;
+    Ok(VcfRecordNif {
+        chrom: cols[0].to_string(),
+        position,
+        id: (cols[2] != ".").then(|| cols[2].to_string()),
+        ref_allele: cols[3].to_string(),
+        alt_alleles: match cols[4] {
+            "." => Vec::new(),
+            alts => alts.split(',').map(String::from).collect(),
+        },
+        quality,
+        filter: cols[6].to_string(),
+        info: parse_vcf_info(cols[7], header)?,
+        format,
+        samples,
+    })
+}
+
+// ===========================================================================
+// SAM/BAM records & flag statistics
+// ===========================================================================
+
+const FLAG_PAIRED: u16 = 0x1;
+const FLAG_PROPER_PAIR: u16 = 0x2;
+const FLAG_UNMAPPED: u16 = 0x4;
+const FLAG_MATE_UNMAPPED: u16 = 0x8;
+const FLAG_REVERSE: u16 = 0x10;
+const FLAG_MATE_REVERSE: u16 = 0x20;
+const FLAG_READ1: u16 = 0x40;
+const FLAG_READ2: u16 = 0x80;
+const FLAG_SECONDARY: u16 = 0x100;
+const FLAG_QC_FAIL: u16 = 0x200;
+const FLAG_DUPLICATE: u16 = 0x400;
+const FLAG_SUPPLEMENTARY: u16 = 0x800;
+
+/// Stream the alignment records of a (plain or gzipped) SAM file.
+fn for_each_sam_record(path: &str, mut f: impl FnMut(SamRecordNif)) -> Result<(), NifError> {
+    let mut lines = open_text_reader(path)?;
+    let mut buf = String::new();
+    let mut line_no = 0u64;
+    loop {
+        buf.clear();
+        let n = lines
+            .read_line(&mut buf)
+            .map_err(|e| NifError::from(e).with_file(path).at_line(line_no + 1))?;
+        if n == 0 {
+            return Ok(());
+        }
+        line_no += 1;
+        let line = buf.trim_end_matches(['\n', '\r']);
+        if line.is_empty() || line.starts_with('@') {
+            continue;
+        }
+        f(parse_sam_line(line, line_no).map_err(|e| e.with_file(path))?);
+    }
+}
+
+/// Stream the alignment records of a BAM file.
+fn for_each_bam_record(path: &str, mut f: impl FnMut(SamRecordNif)) -> Result<(), NifError> {
+    let mut reader = hts::open_bgzf(path)?;
+    let header = hts::read_bam_header(&mut reader).map_err(|e| to_nif_error(e).with_file(path))?;
+    loop {
+        // Errors point at the compressed offset of the record's BGZF block.
+        let offset = reader.virtual_offset() >> 16;
+        let record = hts::read_bam_record(&mut reader)
+            .map_err(to_nif_error)
+            .and_then(|r| r.map(|r| bam_to_sam_record(r, &header.names)).transpose())
+            .map_err(|e| e.with_file(path).at_offset(offset))?;
+        match record {
+            Some(record) => f(record),
+            None => return Ok(()),
+        }
+    }
+}
+
+fn parse_sam_line(line: &str, line_no: u64) -> Result<SamRecordNif, NifError> {
+    let cols: Vec<&str> = line.split('\t').collect();
+    if cols.len() < 11 {
+        return Err(NifError::parse(format!(
+            "expected at least 11 columns, found {}",
+            cols.len()
+        ))
+        .at_line(line_no));
+    }
+    let num = |i: usize, name: &str| {
+        cols[i]
+            .parse::<i64>()
+            .map_err(|_| NifError::parse(format!("invalid {name} '{}'", cols[i])).at_line(line_no))
+    };
+    let mut tags = HashMap::new();
+    for field in &cols[11..] {
+        let (key, value) = parse_sam_tag(field).map_err(|e| e.at_line(line_no))?;
+        tags.insert(key, value);
+    }
+    Ok(SamRecordNif {
+        qname: cols[0].to_string(),
+        flag: u16::try_from(num(1, "FLAG")?).map_err(|_| {
+            NifError::parse(format!("FLAG '{}' out of range 0..=65535", cols[1])).at_line(line_no)
+        })?,
+        rname: cols[2].to_string(),
+        pos: num(3, "POS")?.max(0) as u64,
+        mapq: num(4, "MAPQ")?.clamp(0, 255) as u8,
+        cigar: cols[5].to_string(),
+        rnext: cols[6].to_string(),
+        pnext: num(7, "PNEXT")?.max(0) as u64,
+        tlen: num(8, "TLEN")?,
+        sequence: cols[9].to_string(),
+        quality: cols[10].to_string(),
+        tags,
+    })
+}
+
+/// Parse a `TAG:TYPE:VALUE` optional field.
+fn parse_sam_tag(field: &str) -> Result<(String, SamTagValueNif), NifError> {
+    let mut parts = field.splitn(3, ':');
+    let (Some(key), Some(kind), Some(raw)) = (parts.next(), parts.next(), parts.next()) else {
+        return Err(NifError::parse(format!("malformed tag '{field}'")));
+    };
+    let bad = || NifError::parse(format!("invalid value in tag '{field}'"));
+    let value = match kind {
+        "i" => SamTagValueNif::Integer(raw.parse().map_err(|_| bad())?),
+        "f" => SamTagValueNif::Float(raw.parse().map_err(|_| bad())?),
+        "Z" => SamTagValueNif::String(raw.to_string()),
+        "A" if raw.len() == 1 => SamTagValueNif::Char(SamCharTagNif { value: raw.to_string() }),
+        "A" => return Err(bad()),
+        "H" => SamTagValueNif::Hex(SamHexTagNif { value: raw.to_string() }),
+        "B" => {
+            let mut items = raw.split(',');
+            match items.next() {
+                Some("f") => SamTagValueNif::FloatArray(
+                    items
+                        .map(|v| v.parse().map_err(|_| bad()))
+                        .collect::<Result<_, _>>()?,
+                ),
+                Some("c" | "C" | "s" | "S" | "i" | "I") => SamTagValueNif::IntegerArray(
+                    items
+                        .map(|v| v.parse().map_err(|_| bad()))
+                        .collect::<Result<_, _>>()?,
+                ),
+                _ => return Err(bad()),
+            }
+        }
+        _ => return Err(NifError::parse(format!("unknown type '{kind}' in tag '{field}'"))),
+    };
+    Ok((key.to_string(), value))
+}
+
+fn bam_to_sam_record(r: hts::BamRecord, names: &[String]) -> Result<SamRecordNif, NifError> {
+    let name_of = |tid: i32| {
+        usize::try_from(tid)
+            .ok()
+            .and_then(|tid| names.get(tid))
+            .cloned()
+            .unwrap_or_else(|| "*".into())
+    };
+    let rnext = if r.next_tid >= 0 && r.next_tid == r.tid {
+        "=".to_string()
+    } else {
+        name_of(r.next_tid)
+    };
+    Ok(SamRecordNif {
+        qname: r.qname.clone(),
+        flag: r.flag,
+        rname: name_of(r.tid),
+        pos: (r.pos + 1).max(0) as u64,
+        mapq: r.mapq,
+        cigar: r.cigar_string(),
+        rnext,
+        pnext: (r.next_pos + 1).max(0) as u64,
+        tlen: r.tlen as i64,
+        tags: hts::decode_bam_aux(&r.aux)?,
+        sequence: r.seq,
+        quality: r.qual,
+    })
+}
+
+/// Records passed to `cyanea_io::sam_stats` at a time.
+const SAM_STATS_BATCH: usize = 65_536;
+
+/// samtools-flagstat style counters, accumulated one record at a time.
+///
+/// Totals, mapped counts and the mean MAPQ and read length come from
+/// `cyanea_io::sam_stats`, run over batches of records so the file is
+/// streamed; the pair and duplicate counters are decoded from the flags.
+#[derive(Default)]
+struct FlagStats {
+    batch: Vec<cyanea_io::SamRecord>,
+    total: usize,
+    mapped: usize,
+    mapq_sum: f64,
+    length_sum: f64,
+    qc_failed: usize,
+    secondary: usize,
+    supplementary: usize,
+    primary: usize,
+    duplicates: usize,
+    paired: usize,
+    read1: usize,
+    read2: usize,
+    properly_paired: usize,
+    both_mapped: usize,
+    singletons: usize,
+    mate_on_different_chrom: usize,
+    insert_sizes: BTreeMap<u64, usize>,
+}
+
+impl FlagStats {
+    fn add(&mut self, r: SamRecordNif) {
+        self.count_flags(&r);
+        self.batch.push(cyanea_io::SamRecord {
+            qname: r.qname,
+            flag: r.flag,
+            rname: r.rname,
+            pos: r.pos,
+            mapq: r.mapq,
+            cigar: r.cigar,
+            sequence: r.sequence,
+            quality: r.quality,
+        });
+        if self.batch.len() == SAM_STATS_BATCH {
+            self.flush();
+        }
+    }
+
+    fn flush(&mut self) {
+        let stats = cyanea_io::sam_stats(&self.batch);
+        self.total += stats.total_reads;
+        self.mapped += stats.mapped;
+        self.mapq_sum += stats.avg_mapq * stats.total_reads as f64;
+        self.length_sum += stats.avg_length * stats.total_reads as f64;
+        self.batch.clear();
+    }
+
+    fn count_flags(&mut self, r: &SamRecordNif) {
+        let flag = r.flag;
+        if flag & FLAG_QC_FAIL != 0 {
+            self.qc_failed += 1;
+        }
+        if flag & FLAG_SECONDARY != 0 {
+            self.secondary += 1;
+            return;
+        }
+        if flag & FLAG_SUPPLEMENTARY != 0 {
+            self.supplementary += 1;
+            return;
+        }
+        self.primary += 1;
+        if flag & FLAG_DUPLICATE != 0 {
+            self.duplicates += 1;
+        }
+        if flag & FLAG_PAIRED == 0 {
+            return;
+        }
+        self.paired += 1;
+        if flag & FLAG_READ1 != 0 {
+            self.read1 += 1;
+        }
+        if flag & FLAG_READ2 != 0 {
+            self.read2 += 1;
+        }
+        if flag & FLAG_UNMAPPED != 0 {
+            return;
+        }
+        if flag & FLAG_PROPER_PAIR != 0 {
+            self.properly_paired += 1;
+        }
+        if flag & FLAG_MATE_UNMAPPED != 0 {
+            self.singletons += 1;
+            return;
+        }
+        self.both_mapped += 1;
+        if r.rnext != "=" && r.rnext != r.rname {
+            self.mate_on_different_chrom += 1;
+        } else if r.tlen > 0 && flag & FLAG_DUPLICATE == 0 {
+            // Count each pair once, from its leftmost mate.
+            *self.insert_sizes.entry(r.tlen as u64).or_default() += 1;
+        }
+    }
+
+    fn finish(mut self) -> SamStatsNif {
+        if !self.batch.is_empty() {
+            self.flush();
+        }
+        let ratio = |n: usize, d: usize| if d == 0 { 0.0 } else { n as f64 / d as f64 };
+        let total = self.total;
+        let mean_of = |sum: f64| if total == 0 { 0.0 } else { sum / total as f64 };
+        let n: usize = self.insert_sizes.values().sum();
+        let (mean, sd) = if n == 0 {
+            (0.0, 0.0)
+        } else {
+            let mean = self.insert_sizes.iter().map(|(&v, &c)| v as f64 * c as f64).sum::<f64>()
+                / n as f64;
+            let var = self
+                .insert_sizes
+                .iter()
+                .map(|(&v, &c)| (v as f64 - mean).powi(2) * c as f64)
+                .sum::<f64>()
+                / n as f64;
+            (mean, var.sqrt())
+        };
+        SamStatsNif {
+            total_reads: self.total,
+            mapped: self.mapped,
+            unmapped: self.total - self.mapped,
+            avg_mapq: mean_of(self.mapq_sum),
+            avg_length: mean_of(self.length_sum),
+            qc_failed: self.qc_failed,
+            secondary: self.secondary,
+            supplementary: self.supplementary,
+            duplicates: self.duplicates,
+            duplicate_rate: ratio(self.duplicates, self.primary),
+            paired: self.paired,
+            read1: self.read1,
+            read2: self.read2,
+            properly_paired: self.properly_paired,
+            properly_paired_fraction: ratio(self.properly_paired, self.paired),
+            both_mapped: self.both_mapped,
+            singletons: self.singletons,
+            mate_on_different_chrom: self.mate_on_different_chrom,
+            insert_size_mean: mean,
+            insert_size_sd: sd,
+            insert_size_median: histogram_median(&self.insert_sizes, n),
+            insert_size_histogram: self.insert_sizes.into_iter().collect(),
+        }
+    }
+}
+
+fn histogram_median(hist: &BTreeMap<u64, usize>, n: usize) -> f64 {
+    if n == 0 {
+        return 0.0;
+    }
+    let nth = |k: usize| {
+        let mut seen = 0;
+        for (&v, &c) in hist {
+            seen += c;
+            if seen > k {
+                return v as f64;
+            }
+        }
+        0.0
+    };
+    if n % 2 == 1 {
+        nth(n / 2)
+    } else {
+        (nth(n / 2 - 1) + nth(n / 2)) / 2.0
+    }
+}
+
+// ===========================================================================
+// Indexed region queries (BAI / CSI / tabix)
+// ===========================================================================
+
+/// Find an existing index for `path`, trying each suffix both appended to
+/// the full path and in place of its extension (`x.bam.bai`, then `x.bai`).
+fn find_index(path: &str, suffixes: &[&str]) -> Result<String, NifError> {
+    let stem = std::path::Path::new(path).with_extension("");
+    suffixes
+        .iter()
+        .flat_map(|suffix| {
+            [
+                format!("{path}.{suffix}"),
+                format!("{}.{suffix}", stem.display()),
+            ]
+        })
+        .find(|candidate| std::path::Path::new(candidate).exists())
+        .ok_or_else(|| {
+            NifError::not_found(format!("no .{} index found", suffixes.join(" or .")))
+                .with_file(path)
+        })
+}
+
+/// A BAM file opened together with its `.bai` or `.csi` index.
+pub struct IndexedBamResource {
+    header: hts::BamHeader,
+    index: hts::HtsIndex,
+    reader: Mutex<hts::BgzfReader>,
+}
+
+#[rustler::resource_impl]
+impl rustler::Resource for IndexedBamResource {}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn bam_index_open(path: String) -> Result<ResourceArc<IndexedBamResource>, NifError> {
+    let index = hts::load_index(&find_index(&path, &["bai", "csi"])?)?;
+    let mut reader = hts::open_bgzf(&path)?;
+    let header = hts::read_bam_header(&mut reader).map_err(|e| to_nif_error(e).with_file(&path))?;
+    Ok(ResourceArc::new(IndexedBamResource {
+        header,
+        index,
+        reader: Mutex::new(reader),
+    }))
+}
+
+/// Fetch alignments overlapping `region` (`chrom`, `chrom:start` or
+/// `chrom:start-end`, 1-based inclusive).
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn bam_fetch(
+    bam: ResourceArc<IndexedBamResource>,
+    region: String,
+) -> Result<Vec<SamRecordNif>, NifError> {
+    let (chrom, beg, end) = hts::parse_region(&region, &bam.header.names).map_err(to_nif_error)?;
+    let tid = bam
+        .header
+        .names
+        .iter()
+        .position(|n| *n == chrom)
+        .ok_or_else(|| NifError::not_found(format!("unknown reference: {chrom}")))?;
+    let mut reader = bam
+        .reader
+        .lock()
+        .map_err(|_| NifError::internal("BAM reader lock poisoned"))?;
+    let mut records = Vec::new();
+    'chunks: for chunk in bam.index.query(tid, beg, end) {
+        reader.seek(chunk.beg)?;
+        while reader.virtual_offset() < chunk.end {
+            let Some(record) = hts::read_bam_record(&mut reader).map_err(to_nif_error)? else {
+                break 'chunks;
+            };
+            if record.tid != tid as i32 || record.pos as u64 >= end {
+                break 'chunks;
+            }
+            let rec_end = record.pos as u64 + record.reference_len().max(1);
+            if rec_end > beg {
+                records.push(bam_to_sam_record(record, &bam.header.names)?);
+            }
+        }
+    }
+    Ok(records)
+}
+
+/// A bgzipped, tabix-indexed text file (VCF, BED or GFF).
+pub struct TabixResource {
+    conf: hts::TabixConf,
+    names: Vec<String>,
+    index: hts::HtsIndex,
+    vcf_header: Option<VcfHeaderNif>,
+    reader: Mutex<hts::BgzfReader>,
+}
+
+#[rustler::resource_impl]
+impl rustler::Resource for TabixResource {}
+
+impl TabixResource {
+    /// Data lines overlapping `region`.
+    fn fetch_lines(&self, region: &str) -> Result<Vec<String>, NifError> {
+        let (chrom, beg, end) = hts::parse_region(region, &self.names).map_err(to_nif_error)?;
+        let Some(tid) = self.names.iter().position(|n| *n == chrom) else {
+            return Ok(Vec::new());
+        };
+        let mut reader = self
+            .reader
+            .lock()
+            .map_err(|_| NifError::internal("tabix reader lock poisoned"))?;
+        let mut lines = Vec::new();
+        let mut buf = Vec::new();
+        'chunks: for chunk in self.index.query(tid, beg, end) {
+            reader.seek(chunk.beg)?;
+            while reader.virtual_offset() < chunk.end {
+                if !reader.read_line(&mut buf)? {
+                    break 'chunks;
+                }
+                if buf.is_empty() || buf[0] == self.conf.meta {
+                    continue;
+                }
+                let line = String::from_utf8_lossy(&buf);
+                let Some((line_chrom, line_beg, line_end)) = self.conf.interval(&line) else {
+                    continue;
+                };
+                if line_chrom != chrom || line_beg >= end {
+                    break 'chunks;
+                }
+                if line_end > beg {
+                    lines.push(line.into_owned());
+                }
+            }
+        }
+        Ok(lines)
+    }
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn tabix_open(path: String) -> Result<ResourceArc<TabixResource>, NifError> {
+    let index = hts::load_index(&find_index(&path, &["tbi", "csi"])?)?;
+    let (conf, names) = index
+        .tabix
+        .clone()
+        .ok_or_else(|| NifError::parse("index has no tabix configuration").with_file(&path))?;
+    let vcf_header = if conf.is_vcf() {
+        Some(VcfReaderResource::open(&path)?.header)
+    } else {
+        None
+    };
+    Ok(ResourceArc::new(TabixResource {
+        conf,
+        names,
+        index,
+        vcf_header,
+        reader: Mutex::new(hts::open_bgzf(&path)?),
+    }))
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn vcf_fetch(
+    tabix: ResourceArc<TabixResource>,
+    region: String,
+) -> Result<Vec<VcfRecordNif>, NifError> {
+    let header = tabix
+        .vcf_header
+        .as_ref()
+        .ok_or_else(|| {
+            NifError::unsupported("tabix file is not a VCF (use bed_fetch or tabix_fetch_lines)")
+        })?;
+    tabix
+        .fetch_lines(&region)?
+        .iter()
+        // Tabix queries have no meaningful line numbers.
+        .map(|line| parse_vcf_record(line, header))
+        .collect()
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn bed_fetch(
+    tabix: ResourceArc<TabixResource>,
+    region: String,
+) -> Result<Vec<BedRecordNif>, NifError> {
+    tabix
+        .fetch_lines(&region)?
+        .iter()
+        .map(|line| parse_bed_line(line))
+        .collect()
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn tabix_fetch_lines(
+    tabix: ResourceArc<TabixResource>,
+    region: String,
+) -> Result<Vec<String>, NifError> {
+    tabix.fetch_lines(&region)
+}
+
+/// Build a `.bai` (or `.csi`) index next to a coordinate-sorted BAM.
+/// Returns the index path.
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn bam_index_build(path: String, format: String) -> Result<String, NifError> {
+    let (min_shift, depth) = match format.as_str() {
+        "bai" => (hts::BAI_MIN_SHIFT, hts::BAI_DEPTH),
+        "csi" => (hts::BAI_MIN_SHIFT, 7),
+        _ => return Err(NifError::invalid(format!("unknown index format: {format} (expected bai or csi)"))),
+    };
+    let mut reader = hts::open_bgzf(&path)?;
+    let header = hts::read_bam_header(&mut reader).map_err(|e| to_nif_error(e).with_file(&path))?;
+    let mut builder = hts::IndexBuilder::new(min_shift, depth);
+    loop {
+        let vbeg = reader.virtual_offset();
+        let Some(record) = hts::read_bam_record(&mut reader)
+            .map_err(|e| to_nif_error(e).with_file(&path).at_offset(vbeg >> 16))?
+        else {
+            break;
+        };
+        let vend = reader.virtual_offset();
+        if record.tid < 0 || record.pos < 0 {
+            builder.push_unplaced();
+            continue;
+        }
+        let beg = record.pos.max(0) as u64;
+        let end = beg + record.reference_len().max(1);
+        let mapped = record.flag & FLAG_UNMAPPED == 0;
+        builder
+            .push(record.tid as usize, beg, end, vbeg, vend, mapped)
+            .map_err(|e| {
+                let e = to_nif_error(e);
+                NifError {
+                    message: format!("{} at read {}", e.message, record.qname),
+                    ..e
+                }
+                .with_file(&path)
+                .at_offset(vbeg >> 16)
+            })?;
+    }
+    let index = builder.finish(header.names.len(), None);
+    let index_path = format!("{path}.{format}");
+    if format == "bai" {
+        std::fs::write(&index_path, index.to_bai())
+            .map_err(|e| NifError::from(e).with_file(&index_path))?;
+    } else {
+        hts::write_bgzf_file(&index_path, &index.to_csi())
+            .map_err(|e| to_nif_error(e).with_file(&index_path))?;
+    }
+    Ok(index_path)
+}
+
+/// Build a `.tbi` (or `.csi`) index for a bgzipped, coordinate-sorted text
+/// file. `preset` selects the column layout: `vcf`, `bed` or `gff`.
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn tabix_index_build(path: String, preset: String, format: String) -> Result<String, NifError> {
+    let conf = hts::TabixConf::preset(&preset).map_err(to_nif_error)?;
+    let (min_shift, depth) = match format.as_str() {
+        "tbi" => (hts::BAI_MIN_SHIFT, hts::BAI_DEPTH),
+        "csi" => (hts::BAI_MIN_SHIFT, 7),
+        _ => return Err(NifError::invalid(format!("unknown index format: {format} (expected tbi or csi)"))),
+    };
+    let mut reader = hts::open_bgzf(&path)?;
+    let mut builder = hts::IndexBuilder::new(min_shift, depth);
+    let mut names: Vec<String> = Vec::new();
+    let mut buf = Vec::new();
+    let mut line_no = 0u64;
+    loop {
+        let vbeg = reader.virtual_offset();
+        if !reader
+            .read_line(&mut buf)
+            .map_err(|e| NifError::from(e).with_file(&path).at_line(line_no + 1))?
+        {
+            break;
+        }
+        line_no += 1;
+        let vend = reader.virtual_offset();
+        if buf.is_empty()
+            || buf[0] == conf.meta
+            || buf.starts_with(b"track")
+            || buf.starts_with(b"browser")
+        {
+            continue;
+        }
+        let line = String::from_utf8_lossy(&buf);
+        let (chrom, beg, end) = conf
+            .interval(&line)
+            .ok_or_else(|| {
+                NifError::parse("cannot parse coordinates")
+                    .with_file(&path)
+                    .at_line(line_no)
+            })?;
+        let tid = match names.iter().rposition(|n| n == chrom) {
+            Some(tid) if tid == names.len() - 1 => tid,
+            Some(_) => {
+                return Err(NifError::invalid(format!(
+                    "{chrom} appears in more than one block (file is not sorted)"
+                ))
+                .with_file(&path)
+                .at_line(line_no))
+            }
+            None => {
+                names.push(chrom.to_string());
+                names.len() - 1
+            }
+        };
+        builder
+            .push(tid, beg, end, vbeg, vend, true)
+            .map_err(|e| to_nif_error(e).with_file(&path).at_line(line_no))?;
+    }
+    let n_refs = names.len();
+    let index = builder.finish(n_refs, Some((conf, names)));
+    let index_path = format!("{path}.{format}");
+    let data = if format == "tbi" {
+        index.to_tbi().map_err(to_nif_error)?
+    } else {
+        index.to_csi()
+    };
+    hts::write_bgzf_file(&index_path, &data).map_err(|e| to_nif_error(e).with_file(&index_path))?;
+    Ok(index_path)
+}
+
+fn parse_bed_line(line: &str) -> Result<BedRecordNif, NifError> {
+    let cols: Vec<&str> = line.split('\t').collect();
+    if cols.len() < 3 {
+        return Err(NifError::parse(format!("BED line has fewer than 3 columns: {line}")));
+    }
+    let coord = |raw: &str| {
+        raw.parse::<u64>()
+            .map_err(|_| NifError::parse(format!("invalid BED coordinate '{raw}'")))
+    };
+    Ok(BedRecordNif {
+        chrom: cols[0].to_string(),
+        start: coord(cols[1])?,
+        end: coord(cols[2])?,
+        name: cols.get(3).filter(|n| **n != ".").map(|n| n.to_string()),
+        score: cols.get(4).and_then(|s| s.parse().ok()),
+        strand: cols.get(5).unwrap_or(&".").to_string(),
+    })
+}
+
+// ===========================================================================
+// Writers
+// ===========================================================================
+
+/// Output file for the writer NIFs: plain, gzip, or BGZF (gzip-compatible
+/// and indexable with `tabix_index_build`).
+pub(crate) enum TextWriter {
+    Plain(BufWriter<File>),
+    Gzip(GzEncoder<BufWriter<File>>),
+    Bgzf(hts::BgzfWriter<BufWriter<File>>),
+}
+
+impl TextWriter {
+    pub(crate) fn create(path: &str, compression: &str) -> Result<Self, NifError> {
+        let open = || {
+            File::create(path)
+                .map(BufWriter::new)
+                .map_err(|e| NifError::from(e).with_file(path))
+        };
+        match compression {
+            "none" => Ok(Self::Plain(open()?)),
+            "gzip" => Ok(Self::Gzip(GzEncoder::new(open()?, Compression::default()))),
+            "bgzip" => Ok(Self::Bgzf(hts::BgzfWriter::new(open()?, 6))),
+            _ => Err(NifError::invalid(format!(
+                "unknown compression: {compression} (expected none, gzip, or bgzip)"
+            ))),
+        }
+    }
+
+    /// Flush and write any compression trailer.
+    pub(crate) fn finish(self) -> std::io::Result<()> {
+        match self {
+            Self::Plain(mut w) => w.flush(),
+            Self::Gzip(w) => w.finish()?.flush(),
+            Self::Bgzf(w) => w.finish().map(|_| ()),
+        }
+    }
+}
+
+impl Write for TextWriter {
+    fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+        match self {
+            Self::Plain(w) => w.write(buf),
+            Self::Gzip(w) => w.write(buf),
+            Self::Bgzf(w) => w.write(buf),
+        }
+    }
+
+    fn flush(&mut self) -> std::io::Result<()> {
+        match self {
+            Self::Plain(w) => w.flush(),
+            Self::Gzip(w) => w.flush(),
+            Self::Bgzf(w) => w.flush(),
+        }
+    }
+}
+
+/// Create `path`, let `write` fill it, then finish the compression stream.
+pub(crate) fn write_text_file(
+    path: &str,
+    compression: &str,
+    write: impl FnOnce(&mut TextWriter) -> std::io::Result<()>,
+) -> Result<(), NifError> {
+    let mut out = TextWriter::create(path, compression)?;
+    let io_err = |e: std::io::Error| NifError::from(e).with_file(path);
+    write(&mut out).map_err(io_err)?;
+    out.finish().map_err(io_err)
+}
+
+fn vcf_scalar_string(value: Option<&VcfScalarNif>) -> String {
+    match value {
+        None => ".".into(),
+        Some(VcfScalarNif::Integer(v)) => v.to_string(),
+        Some(VcfScalarNif::Float(v)) => v.to_string(),
+        Some(VcfScalarNif::String(v)) => v.clone(),
+    }
+}
+
+fn vcf_info_string(info: &HashMap<String, Option<VcfValueNif>>, header: &VcfHeaderNif) -> String {
+    // Declared keys in header order, then any others alphabetically.
+    let mut keys: Vec<&String> = header
+        .info
+        .iter()
+        .map(|d| &d.id)
+        .filter(|id| info.contains_key(*id))
+        .collect();
+    let mut extra: Vec<&String> = info.keys().filter(|k| !keys.contains(k)).collect();
+    extra.sort();
+    keys.extend(extra);
+    let fields: Vec<String> = keys
+        .into_iter()
+        .filter_map(|key| match &info[key] {
+            Some(VcfValueNif::Flag(false)) => None,
+            Some(VcfValueNif::Flag(true)) => Some(key.clone()),
+            Some(VcfValueNif::Scalar(v)) => Some(format!("{key}={}", vcf_scalar_string(Some(v)))),
+            Some(VcfValueNif::List(values)) => Some(format!(
+                "{key}={}",
+                values
+                    .iter()
+                    .map(|v| vcf_scalar_string(v.as_ref()))
+                    .collect::<Vec<_>>()
+                    .join(",")
+            )),
+            None => Some(format!("{key}=.")),
+        })
+        .collect();
+    if fields.is_empty() {
+        ".".into()
+    } else {
+        fields.join(";")
+    }
+}
+
+fn vcf_sample_string(sample: &VcfSampleNif, format: &[String]) -> String {
+    let int = |v: Option<i64>| v.map_or(".".to_string(), |v| v.to_string());
+    format
+        .iter()
+        .map(|key| match key.as_str() {
+            "GT" => sample.genotype.clone().unwrap_or_else(|| {
+                if sample.alleles.is_empty() {
+                    ".".into()
+                } else {
+                    let sep = if sample.phased { "|" } else { "/" };
+                    sample
+                        .alleles
+                        .iter()
+                        .map(|a| a.map_or(".".to_string(), |a| a.to_string()))
+                        .collect::<Vec<_>>()
+                        .join(sep)
+                }
+            }),
+            "DP" => int(sample.depth),
+            "GQ" => int(sample.genotype_quality),
+            "AD" if sample.allele_depths.is_empty() => ".".into(),
+            "AD" => sample
+                .allele_depths
+                .iter()
+                .map(|v| int(*v))
+                .collect::<Vec<_>>()
+                .join(","),
+            other => sample.other.get(other).cloned().unwrap_or_else(|| ".".into()),
+        })
+        .collect::<Vec<_>>()
+        .join(":")
+}
+
+fn write_vcf_header(out: &mut impl Write, header: &VcfHeaderNif) -> std::io::Result<()> {
+    let fileformat = if header.fileformat.is_empty() {
+        "VCFv4.2"
+    } else {
+        header.fileformat.as_str()
+    };
+    writeln!(out, "##fileformat={fileformat}")?;
+    // Meta lines read from a file are written back verbatim; structured
+    // entries only get a generated line when no meta line declares them,
+    // as for headers built by hand.
+    let mut declared = HashSet::new();
+    for line in &header.meta {
+        writeln!(out, "##{line}")?;
+        if let Some((key, value)) = line.split_once('=') {
+            if let Some(body) = value.strip_prefix('<').and_then(|v| v.strip_suffix('>')) {
+                if let Some(id) = parse_meta_fields(body).remove("ID") {
+                    declared.insert((key.to_string(), id));
+                }
+            }
+        }
+    }
+    let is_declared = |key: &str, id: &str| declared.contains(&(key.to_string(), id.to_string()));
+    let undeclared_filters = header
+        .filters
+        .iter()
+        .filter(|f| *f != "PASS" && !is_declared("FILTER", f.as_str()));
+    for filter in undeclared_filters {
+        writeln!(out, "##FILTER=<ID={filter},Description=\"{filter}\">")?;
+    }
+    for (kind, defs) in [("INFO", &header.info), ("FORMAT", &header.format)] {
+        for def in defs.iter().filter(|d| !is_declared(kind, d.id.as_str())) {
+            writeln!(
+                out,
+                "##{kind}=<ID={},Number={},Type={},Description=\"{}\">",
+                def.id,
+                def.number,
+                def.value_type,
+                def.description.replace('"', "\\\"")
+            )?;
+        }
+    }
+    for contig in header.contigs.iter().filter(|c| !is_declared("contig", c.as_str())) {
+        writeln!(out, "##contig=<ID={contig}>")?;
+    }
+    write!(out, "#CHROM\tPOS\tID\tREF\tALT\tQUAL\tFILTER\tINFO")?;
+    if !header.samples.is_empty() {
+        write!(out, "\tFORMAT\t{}", header.samples.join("\t"))?;
+    }
+    writeln!(out)
+}
+
+/// Write a VCF file. Returns the number of records written.
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn write_vcf(
+    path: String,
+    header: VcfHeaderNif,
+    records: Vec<VcfRecordNif>,
+    compression: String,
+) -> Result<usize, NifError> {
+    write_text_file(&path, &compression, |out| {
+        write_vcf_header(out, &header)?;
+        for r in &records {
+            write!(
+                out,
+                "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                r.chrom,
+                r.position,
+                r.id.as_deref().unwrap_or("."),
+                r.ref_allele,
+                if r.alt_alleles.is_empty() { ".".to_string() } else { r.alt_alleles.join(",") },
+                r.quality.map_or(".".to_string(), |q| q.to_string()),
+                if r.filter.is_empty() { "." } else { r.filter.as_str() },
+                vcf_info_string(&r.info, &header),
+            )?;
+            if !r.samples.is_empty() {
+                let format = if r.format.is_empty() { vec!["GT".to_string()] } else { r.format.clone() };
+                write!(out, "\t{}", format.join(":"))?;
+                for sample in &r.samples {
+                    write!(out, "\t{}", vcf_sample_string(sample, &format))?;
+                }
+            }
+            writeln!(out)?;
+        }
+        Ok(())
+    })?;
+    Ok(records.len())
+}
+
+/// Map strand spellings (`+`, `Forward`, ...) to the one-character form.
+fn strand_symbol(strand: &str) -> &'static str {
+    match strand {
+        "+" | "Forward" | "Plus" => "+",
+        "-" | "Reverse" | "Minus" => "-",
+        _ => ".",
+    }
+}
+
+/// Write a BED file (BED3, or BED6 when any record has a name, score or
+/// strand). Returns the number of records written.
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn write_bed(
+    path: String,
+    records: Vec<BedRecordNif>,
+    compression: String,
+) -> Result<usize, NifError> {
+    let bed6 = records
+        .iter()
+        .any(|r| r.name.is_some() || r.score.is_some() || strand_symbol(&r.strand) != ".");
+    write_text_file(&path, &compression, |out| {
+        for r in &records {
+            write!(out, "{}\t{}\t{}", r.chrom, r.start, r.end)?;
+            if bed6 {
+                write!(
+                    out,
+                    "\t{}\t{}\t{}",
+                    r.name.as_deref().unwrap_or("."),
+                    r.score.map_or("0".to_string(), |s| s.to_string()),
+                    strand_symbol(&r.strand)
+                )?;
+            }
+            writeln!(out)?;
+        }
+        Ok(())
+    })?;
+    Ok(records.len())
+}
+
+/// Percent-encode the characters GFF3 reserves in column 9.
+fn gff3_escape(value: &str) -> String {
+    let mut out = String::with_capacity(value.len());
+    for c in value.chars() {
+        match c {
+            ';' | '=' | '&' | ',' | '%' | '\t' | '\n' | '\r' => {
+                out.push_str(&format!("%{:02X}", c as u32))
+            }
+            _ => out.push(c),
+        }
+    }
+    out
+}
+
+/// Write gene records as GFF3 `gene` features. Returns the number written.
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn write_gff3(
+    path: String,
+    genes: Vec<GffGeneNif>,
+    compression: String,
+) -> Result<usize, NifError> {
+    write_text_file(&path, &compression, |out| {
+        writeln!(out, "##gff-version 3")?;
+        for g in &genes {
+            let mut attrs = format!("ID={}", gff3_escape(&g.id));
+            if !g.symbol.is_empty() {
+                attrs.push_str(&format!(";Name={}", gff3_escape(&g.symbol)));
+            }
+            if !g.gene_type.is_empty() {
+                attrs.push_str(&format!(";biotype={}", gff3_escape(&g.gene_type)));
+            }
+            writeln!(
+                out,
+                "{}\t.\tgene\t{}\t{}\t.\t{}\t.\t{attrs}",
+                g.chrom,
+                g.start,
+                g.end,
+                strand_symbol(&g.strand)
+            )?;
+        }
+        Ok(())
+    })?;
+    Ok(genes.len())
+}
+
+pub(crate) fn sam_tag_string(key: &str, value: &SamTagValueNif) -> String {
+    let join = |items: Vec<String>| items.join(",");
+    match value {
+        SamTagValueNif::Integer(v) => format!("{key}:i:{v}"),
+        SamTagValueNif::Float(v) => format!("{key}:f:{v}"),
+        SamTagValueNif::String(v) => format!("{key}:Z:{v}"),
+        SamTagValueNif::Char(v) => format!("{key}:A:{}", v.value),
+        SamTagValueNif::Hex(v) => format!("{key}:H:{}", v.value),
+        SamTagValueNif::IntegerArray(v) => {
+            format!("{key}:B:i,{}", join(v.iter().map(|x| x.to_string()).collect()))
+        }
+        SamTagValueNif::FloatArray(v) => {
+            format!("{key}:B:f,{}", join(v.iter().map(|x| x.to_string()).collect()))
+        }
+    }
+}
+
+/// Write a SAM file. `header` is raw `@`-line text; when empty a minimal
+/// `@HD` line is written. Returns the number of records written.
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn write_sam(
+    path: String,
+    header: String,
+    records: Vec<SamRecordNif>,
+    compression: String,
+) -> Result<usize, NifError> {
+    write_text_file(&path, &compression, |out| {
+        let header = header.trim_end();
+        if header.is_empty() {
+            writeln!(out, "@HD\tVN:1.6\tSO:unknown")?;
+        } else {
+            writeln!(out, "{header}")?;
+        }
+        for r in &records {
+            write!(
+                out,
+                "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                r.qname,
+                r.flag,
+                r.rname,
+                r.pos,
+                r.mapq,
+                r.cigar,
+                r.rnext,
+                r.pnext,
+                r.tlen,
+                r.sequence,
+                r.quality
+            )?;
+            let mut keys: Vec<&String> = r.tags.keys().collect();
+            keys.sort();
+            for key in keys {
+                write!(out, "\t{}", sam_tag_string(key, &r.tags[key]))?;
+            }
+            writeln!(out)?;
+        }
+        Ok(())
+    })?;
+    Ok(records.len())
+}
+
 // ===========================================================================
 // Helpers
 // ===========================================================================
@@ -0,0 +1,201 @@
+//! Cooperative cancellation and progress reporting for long-running NIFs.
+//!
+//! Long NIFs take two trailing arguments: an optional cancel token created
+//! with `cancel_token_new/0`, and an optional `{pid, job_ref}` that receives
+//! `{:progress, job_ref, fraction}` messages.  When either is given the work
+//! runs on a helper thread while the dirty scheduler thread polls the token,
+//! the caller's liveness and the reported progress.  Cancelling the token or
+//! killing the calling process returns the scheduler immediately with a
+//! `:cancelled` error; the helper thread stops at its next checkpoint.
+
+use crate::bridge::NifError;
+use rustler::{Env, LocalPid, ResourceArc, Term};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use std::sync::{mpsc, Arc};
+use std::time::Duration;
+
+mod atoms {
+    rustler::atoms! {
+        progress,
+    }
+}
+
+/// How often the scheduler thread checks on a running job.
+const POLL_INTERVAL: Duration = Duration::from_millis(50);
+
+/// A flag the Elixir side can flip to stop a running NIF.
+pub struct CancelTokenResource {
+    cancelled: AtomicBool,
+}
+
+#[rustler::resource_impl]
+impl rustler::Resource for CancelTokenResource {}
+
+#[rustler::nif]
+pub fn cancel_token_new() -> ResourceArc<CancelTokenResource> {
+    ResourceArc::new(CancelTokenResource {
+        cancelled: AtomicBool::new(false),
+    })
+}
+
+#[rustler::nif]
+pub fn cancel_token_cancel(token: ResourceArc<CancelTokenResource>) -> bool {
+    !token.cancelled.swap(true, Ordering::SeqCst)
+}
+
+#[rustler::nif]
+pub fn cancel_token_cancelled(token: ResourceArc<CancelTokenResource>) -> bool {
+    token.cancelled.load(Ordering::SeqCst)
+}
+
+/// Shared state between a job's worker and the thread reporting on it.
+pub struct JobState {
+    token: Option<ResourceArc<CancelTokenResource>>,
+    watched: bool,
+    abandoned: AtomicBool,
+    progress: AtomicU64,
+}
+
+impl JobState {
+    pub(crate) fn new(token: Option<ResourceArc<CancelTokenResource>>) -> Self {
+        Self {
+            watched: token.is_some(),
+            token,
+            abandoned: AtomicBool::new(false),
+            progress: AtomicU64::new(0f64.to_bits()),
+        }
+    }
+
+    /// Whether anything can cancel the job or read its progress.  Unwatched
+    /// jobs may skip fine-grained checkpoints and run in one library call.
+    pub fn is_watched(&self) -> bool {
+        self.watched
+    }
+
+    /// Whether the job should stop: its token was cancelled or the caller
+    /// has gone away.
+    pub fn is_cancelled(&self) -> bool {
+        self.abandoned.load(Ordering::Relaxed)
+            || self
+                .token
+                .as_ref()
+                .is_some_and(|t| t.cancelled.load(Ordering::Relaxed))
+    }
+
+    /// Checkpoint for worker loops: errors once the job is cancelled.
+    pub fn check(&self) -> Result<(), NifError> {
+        if self.is_cancelled() {
+            Err(NifError::cancelled("job was cancelled"))
+        } else {
+            Ok(())
+        }
+    }
+
+    /// Record progress as a fraction in `[0, 1]`.
+    pub fn set_progress(&self, fraction: f64) {
+        self.progress
+            .store(fraction.clamp(0.0, 1.0).to_bits(), Ordering::Relaxed);
+    }
+
+    fn progress(&self) -> f64 {
+        f64::from_bits(self.progress.load(Ordering::Relaxed))
+    }
+}
+
+/// Where progress messages go: the pid and the ref tagging each message.
+pub type ProgressTarget<'a> = Option<(LocalPid, Term<'a>)>;
+
+/// Run `work` with cancellation and progress support.
+///
+/// Without a token or progress target the work runs inline, exactly as
+/// before.  Otherwise it runs on a helper thread; see the module docs.
+pub fn run<'a, T, F>(
+    env: Env<'a>,
+    cancel: Option<ResourceArc<CancelTokenResource>>,
+    progress: ProgressTarget<'a>,
+    work: F,
+) -> Result<T, NifError>
+where
+    T: Send + 'static,
+    F: FnOnce(&JobState) -> Result<T, NifError> + Send + 'static,
+{
+    if cancel.is_none() && progress.is_none() {
+        return work(&JobState::new(None));
+    }
+
+    let state = Arc::new(JobState {
+        watched: true,
+        ..JobState::new(cancel)
+    });
+    let (tx, rx) = mpsc::channel();
+    let worker_state = Arc::clone(&state);
+    std::thread::Builder::new()
+        .name("cyanea-job".into())
+        .spawn(move || {
+            let _ = tx.send(worker_state.check().and_then(|_| work(&worker_state)));
+        })
+        .map_err(|e| NifError::internal(format!("failed to start job thread: {e}")))?;
+
+    let caller = env.pid();
+    let mut reported = None;
+    if let Some((pid, job_ref)) = &progress {
+        let _ = env.send(pid, (atoms::progress(), *job_ref, 0.0));
+        reported = Some(0.0);
+    }
+    loop {
+        let outcome = rx.recv_timeout(POLL_INTERVAL);
+        if let Some((pid, job_ref)) = &progress {
+            let fraction = match outcome {
+                Ok(Ok(_)) => 1.0,
+                _ => state.progress(),
+            };
+            if reported != Some(fraction) {
+                let _ = env.send(pid, (atoms::progress(), *job_ref, fraction));
+                reported = Some(fraction);
+            }
+        }
+        match outcome {
+            Ok(result) => return result,
+            Err(mpsc::RecvTimeoutError::Disconnected) => {
+                return Err(NifError::internal("job thread exited without a result"));
+            }
+            Err(mpsc::RecvTimeoutError::Timeout) => {}
+        }
+        if !env.is_process_alive(caller) {
+            state.abandoned.store(true, Ordering::Relaxed);
+        }
+        if state.is_cancelled() {
+            return Err(NifError::cancelled("job was cancelled"));
+        }
+    }
+}
+
+/// Run `work`, which has no checkpoints of its own, on a helper thread and
+/// wait for it while polling `job`.
+///
+/// Used by pool jobs wrapping a single library call: a cancel returns a
+/// `:cancelled` error at once instead of after the call; the helper thread
+/// runs to completion in the background and its result is dropped.
+pub fn run_detached<T, F>(job: &JobState, work: F) -> Result<T, NifError>
+where
+    T: Send + 'static,
+    F: FnOnce() -> Result<T, NifError> + Send + 'static,
+{
+    job.check()?;
+    let (tx, rx) = mpsc::channel();
+    std::thread::Builder::new()
+        .name("cyanea-job".into())
+        .spawn(move || {
+            let _ = tx.send(work());
+        })
+        .map_err(|e| NifError::internal(format!("failed to start job thread: {e}")))?;
+    loop {
+        match rx.recv_timeout(POLL_INTERVAL) {
+            Ok(result) => return result,
+            Err(mpsc::RecvTimeoutError::Disconnected) => {
+                return Err(NifError::internal("job thread exited without a result"));
+            }
+            Err(mpsc::RecvTimeoutError::Timeout) => job.check()?,
+        }
+    }
+}
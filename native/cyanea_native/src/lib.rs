@@ -1,15 +1,26 @@
-//! Cyanea Native — Thin NIF bridge to Cyanea Labs.
+//! Cyanea Native — NIF bridge to Cyanea Labs.
 //!
 //! This crate exposes Elixir NIFs that delegate to the standalone
-//! libraries in `labs/`. No business logic lives here — only type
-//! conversions between Rustler NIF types and Cyanea Labs types.
+//! libraries in `labs/`, converting between Rustler NIF types and Cyanea
+//! Labs types. Where the libraries have no equivalent yet, the code lives
+//! here: notably `hts` (BGZF, BAI/CSI and tabix indexing) and the streaming
+//! readers and writers in `io`.
 
 pub mod bridge;
 
 mod core;
 mod seq;
+mod fastq;
+mod sketch;
+mod pattern;
+mod cloning;
 mod io;
+mod hts;
 mod align;
+mod scoring;
+mod msa;
+mod search;
+mod mapper;
 mod stats;
 mod omics;
 mod ml;
@@ -17,10 +28,34 @@ mod chem;
 mod structs;
 mod phylo;
 mod gpu;
+mod job;
+mod pool;
 
-/// Convert a `cyanea_core::CyaneaError` into a NIF-friendly `String`.
-pub(crate) fn to_nif_error(e: cyanea_core::CyaneaError) -> String {
-    e.to_string()
+/// Convert a `cyanea_core::CyaneaError` into a structured [`bridge::NifError`].
+///
+/// Parser messages that start with `line N:` have the line moved into
+/// `line`, so it is not rendered twice; other messages are kept as they are.
+pub(crate) fn to_nif_error(e: cyanea_core::CyaneaError) -> bridge::NifError {
+    use cyanea_core::CyaneaError;
+    match e {
+        CyaneaError::Io(e) => bridge::NifError::from(e),
+        CyaneaError::Parse(message) => match split_line_number(&message) {
+            Some((line, rest)) => bridge::NifError::parse(rest).at_line(line),
+            None => bridge::NifError::parse(message),
+        },
+        CyaneaError::InvalidInput(_) => bridge::NifError::invalid(e.to_string()),
+        // Variants added to cyanea-core later are reported as internal errors.
+        _ => bridge::NifError::internal(e.to_string()),
+    }
+}
+
+/// Split a leading `"line N: rest"` into `N` and `"rest"`.
+fn split_line_number(message: &str) -> Option<(u64, String)> {
+    let rest = message.strip_prefix("line ")?;
+    let end = rest.find(|c: char| !c.is_ascii_digit()).unwrap_or(rest.len());
+    let line = rest[..end].parse().ok()?;
+    let rest = rest[end..].strip_prefix(':').unwrap_or(&rest[end..]).trim_start();
+    Some((line, rest.to_string()))
 }
 
 rustler::init!("Elixir.Cyanea.Native");
@@ -0,0 +1,573 @@
+//! Short-read mapping for small references — viral and bacterial genomes,
+//! amplicon panels — by seed and extend on the FM-index.
+//!
+//! Reference contigs are joined with `N` separators into one FM-index,
+//! built once and held as a resource.  Each read is cut into k-mer seeds
+//! that are located on both strands; seeds on nearby diagonals vote for a
+//! placement, and the best placements are extended with banded
+//! Smith-Waterman, soft-clipping read ends that do not align.  Mates are
+//! placed together when one candidate of each forms a proper pair.  Output
+//! is SAM or BAM with `NM`, `MD` and `AS` tags.
+
+use crate::bridge::{MapOptionsNif, MapStatsNif, NifError};
+use crate::fastq::{next_record, FastqRead, FastqReader};
+use crate::hts::{decode_bam_aux, encode_bam_record, write_bam_header, BamRecord, BgzfWriter};
+use crate::io::{sam_tag_string, TextWriter};
+use crate::pattern::normalize_text;
+use crate::seq::{reverse_complement, FastaReader};
+use crate::to_nif_error;
+use rustler::ResourceArc;
+use std::fs::File;
+use std::io::{BufWriter, Write};
+
+/// Placements per read extended with banded alignment.
+const MAX_CANDIDATES: usize = 8;
+/// Highest mapping quality reported.
+const MAX_MAPQ: u8 = 60;
+
+// Scores as in BWA-MEM: match, mismatch, gap open, gap extend.
+const MATCH: i32 = 1;
+const MISMATCH: i32 = -4;
+const GAP_OPEN: i32 = -6;
+const GAP_EXTEND: i32 = -1;
+
+// SAM flags.
+const PAIRED: u16 = 0x1;
+const PROPER_PAIR: u16 = 0x2;
+const UNMAPPED: u16 = 0x4;
+const MATE_UNMAPPED: u16 = 0x8;
+const REVERSE: u16 = 0x10;
+const MATE_REVERSE: u16 = 0x20;
+const FIRST_MATE: u16 = 0x40;
+const SECOND_MATE: u16 = 0x80;
+
+// ===========================================================================
+// Reference index (resource)
+// ===========================================================================
+
+/// Reference contigs and the FM-index over them.
+pub struct ReadMapperIndexResource {
+    names: Vec<String>,
+    /// Offset of each contig in `text`.
+    starts: Vec<usize>,
+    lengths: Vec<usize>,
+    /// Uppercase contigs joined by `N`; other non-ACGT bases are `N` too.
+    text: Vec<u8>,
+    index: cyanea_seq::FmIndex,
+}
+
+#[rustler::resource_impl]
+impl rustler::Resource for ReadMapperIndexResource {}
+
+impl ReadMapperIndexResource {
+    fn build(path: &str) -> Result<Self, NifError> {
+        let mut reader = FastaReader::open(path)?;
+        let (mut names, mut starts, mut lengths) = (Vec::new(), Vec::new(), Vec::new());
+        let mut text = Vec::new();
+        while let Some(record) = reader.next_record()? {
+            if !text.is_empty() {
+                text.push(b'N');
+            }
+            names.push(record.name);
+            starts.push(text.len());
+            lengths.push(record.sequence.len());
+            text.extend(normalize_text(&record.sequence).into_iter().map(|b| match b {
+                b'A' | b'C' | b'G' | b'T' => b,
+                _ => b'N',
+            }));
+        }
+        if names.is_empty() {
+            return Err(NifError::invalid("reference has no sequences").with_file(path));
+        }
+        let index = cyanea_seq::FmIndex::build(&text);
+        Ok(Self {
+            names,
+            starts,
+            lengths,
+            text,
+            index,
+        })
+    }
+
+    /// Contig holding text offset `pos` (separators count as the contig
+    /// before them).
+    fn contig_at(&self, pos: usize) -> usize {
+        self.starts.partition_point(|&s| s <= pos).saturating_sub(1)
+    }
+
+    /// Candidate placements as `(reverse, diagonal)`, where the diagonal is
+    /// the text offset the read's first base would align to, best supported
+    /// first, and the fraction of seeds skipped for occurring more than
+    /// `max_occurrences` times.
+    fn candidates(
+        &self,
+        strands: &[Vec<u8>; 2],
+        options: &MapOptionsNif,
+    ) -> (Vec<(bool, i64)>, f64) {
+        let k = options.seed_length;
+        let mut clusters = Vec::new();
+        let (mut seeds, mut repetitive) = (0usize, 0usize);
+        for (strand, seq) in strands.iter().enumerate() {
+            if seq.len() < k {
+                continue;
+            }
+            // Overlapping seeds every k/2 bases, plus one flush with the end.
+            let last = seq.len() - k;
+            let mut offsets: Vec<usize> = (0..=last).step_by((k / 2).max(1)).collect();
+            if offsets.last() != Some(&last) {
+                offsets.push(last);
+            }
+            let mut diagonals = Vec::new();
+            for offset in offsets {
+                let seed = &seq[offset..offset + k];
+                if !seed.iter().all(|b| matches!(b, b'A' | b'C' | b'G' | b'T')) {
+                    continue;
+                }
+                seeds += 1;
+                let count = self.index.count(seed);
+                if count > options.max_occurrences {
+                    repetitive += 1;
+                }
+                if count == 0 || count > options.max_occurrences {
+                    continue;
+                }
+                diagonals.extend(self.index.locate(seed).into_iter().map(|p| p as i64 - offset as i64));
+            }
+            diagonals.sort_unstable();
+            // Seeds within one bandwidth of the cluster's first diagonal
+            // vote together; the median diagonal represents them.
+            let mut i = 0;
+            while i < diagonals.len() {
+                let j = diagonals[i..].partition_point(|&d| d - diagonals[i] <= options.bandwidth as i64) + i;
+                clusters.push((j - i, strand == 1, diagonals[(i + j) / 2]));
+                i = j;
+            }
+        }
+        clusters.sort_by_key(|&(votes, reverse, diagonal)| (std::cmp::Reverse(votes), reverse, diagonal));
+        let placements = clusters
+            .into_iter()
+            .take(MAX_CANDIDATES)
+            .map(|(_, reverse, diagonal)| (reverse, diagonal))
+            .collect();
+        (placements, repetitive as f64 / seeds.max(1) as f64)
+    }
+
+    /// Banded local alignment of an oriented read around `diagonal`.
+    fn extend(
+        &self,
+        read: &[u8],
+        reverse: bool,
+        diagonal: i64,
+        options: &MapOptionsNif,
+        scoring: &cyanea_align::ScoringScheme,
+    ) -> Result<Option<Hit>, NifError> {
+        let band = options.bandwidth as i64;
+        // Place by the read's middle, so reads hanging off a contig start
+        // are not put on the contig before it.
+        let middle = diagonal + read.len() as i64 / 2;
+        let contig = self.contig_at(middle.clamp(0, self.text.len() as i64 - 1) as usize);
+        let contig_start = self.starts[contig] as i64;
+        let contig_end = contig_start + self.lengths[contig] as i64;
+        let window_start = (diagonal - band).clamp(contig_start, contig_end) as usize;
+        let window_end = (diagonal + read.len() as i64 + band).clamp(contig_start, contig_end) as usize;
+        if window_end <= window_start {
+            return Ok(None);
+        }
+        let window = &self.text[window_start..window_end];
+        // The read starts about `band` into the window, so the band must
+        // reach twice as far from the main diagonal.
+        let result = cyanea_align::simd::banded_sw(read, window, scoring, 2 * options.bandwidth)
+            .map_err(to_nif_error)?;
+        if result.score < options.min_score {
+            return Ok(None);
+        }
+
+        let mut cigar: Vec<(u32, u8)> = Vec::new();
+        let mut push = |op: u8, len: u32| {
+            if len == 0 {
+                return;
+            }
+            match cigar.last_mut() {
+                Some((n, last)) if *last == op => *n += len,
+                _ => cigar.push((len, op)),
+            }
+        };
+        push(b'S', result.query_start as u32);
+        let mut edits = 0;
+        for (&q, &t) in result.aligned_query.iter().zip(&result.aligned_target) {
+            let op = match (q, t) {
+                (b'-', _) => b'D',
+                (_, b'-') => b'I',
+                _ => b'M',
+            };
+            if op != b'M' || !q.eq_ignore_ascii_case(&t) || q == b'N' {
+                edits += 1;
+            }
+            push(op, 1);
+        }
+        push(b'S', (read.len() - result.query_end) as u32);
+
+        let reference = &window[result.target_start..result.target_end];
+        let cigar_string: String = cigar.iter().map(|(len, op)| format!("{len}{}", *op as char)).collect();
+        let ops = cyanea_align::cigar::parse_cigar(&cigar_string).map_err(to_nif_error)?;
+        let md = cyanea_align::cigar::generate_md_tag(&ops, read, reference).map_err(to_nif_error)?;
+        Ok(Some(Hit {
+            contig,
+            pos: window_start - self.starts[contig] + result.target_start,
+            end: window_start - self.starts[contig] + result.target_end,
+            reverse,
+            score: result.score,
+            cigar,
+            edits,
+            md,
+        }))
+    }
+
+    /// Distinct alignments of a read, best score first.  Seed clusters
+    /// that extend to overlapping alignments on one strand are one locus;
+    /// only its best alignment is kept.
+    fn map(
+        &self,
+        read: &FastqRead,
+        options: &MapOptionsNif,
+        scoring: &cyanea_align::ScoringScheme,
+    ) -> Result<Mapping, NifError> {
+        let forward = normalize_text(&read.seq);
+        let reverse = reverse_complement(&forward);
+        let strands = [forward, reverse];
+        let (candidates, repetitive) = self.candidates(&strands, options);
+        let mut hits: Vec<Hit> = Vec::new();
+        for (reverse, diagonal) in candidates {
+            let seq = &strands[usize::from(reverse)];
+            let Some(hit) = self.extend(seq, reverse, diagonal, options, scoring)? else {
+                continue;
+            };
+            let same_locus = hits.iter_mut().find(|h| {
+                (h.contig, h.reverse) == (hit.contig, hit.reverse)
+                    && h.pos < hit.end
+                    && hit.pos < h.end
+            });
+            match same_locus {
+                Some(h) if h.score < hit.score => *h = hit,
+                Some(_) => {}
+                None => hits.push(hit),
+            }
+        }
+        hits.sort_by_key(|h| std::cmp::Reverse(h.score));
+        Ok(Mapping { hits, repetitive })
+    }
+}
+
+// ===========================================================================
+// Placement
+// ===========================================================================
+
+/// One alignment of a read; coordinates are 0-based on the contig.
+struct Hit {
+    contig: usize,
+    pos: usize,
+    end: usize,
+    reverse: bool,
+    score: i32,
+    cigar: Vec<(u32, u8)>,
+    /// Edit distance to the reference (`NM`).
+    edits: usize,
+    md: String,
+}
+
+/// A read's alignments and the fraction of its seeds that were too
+/// repetitive to locate.
+struct Mapping {
+    hits: Vec<Hit>,
+    repetitive: f64,
+}
+
+/// BWA-style mapping quality from the chosen score and the best score of
+/// any other placement, scaled down by the fraction of repetitive seeds as
+/// BWA-MEM does: placements those seeds would have found were never
+/// compared.
+fn mapq(mapping: &Mapping, chosen: usize) -> u8 {
+    let hits = &mapping.hits;
+    let best = hits[chosen].score;
+    let second = hits
+        .iter()
+        .enumerate()
+        .filter(|&(i, _)| i != chosen)
+        .map(|(_, h)| h.score)
+        .max();
+    let unique = match second {
+        None => 1.0,
+        Some(s) if s >= best => 0.0,
+        Some(s) => (best - s) as f64 / best as f64,
+    };
+    (unique * (1.0 - mapping.repetitive) * MAX_MAPQ as f64).round() as u8
+}
+
+/// Hit `chosen` with its MAPQ, unless the read did not map.
+fn placement(mapping: &Mapping, chosen: usize) -> Option<(&Hit, u8)> {
+    mapping.hits.get(chosen).map(|hit| (hit, mapq(mapping, chosen)))
+}
+
+/// The best proper pair as indices into each mate's hits: same contig,
+/// opposite strands, facing each other within `max_insert`.
+fn best_pair(hits1: &[Hit], hits2: &[Hit], max_insert: usize) -> Option<(usize, usize)> {
+    let mut best: Option<(i32, usize, usize)> = None;
+    for (i, a) in hits1.iter().enumerate() {
+        for (j, b) in hits2.iter().enumerate() {
+            if a.contig != b.contig || a.reverse == b.reverse {
+                continue;
+            }
+            let (fwd, rev) = if a.reverse { (b, a) } else { (a, b) };
+            if fwd.pos > rev.pos || rev.end - fwd.pos > max_insert {
+                continue;
+            }
+            let score = a.score + b.score;
+            if best.is_none_or(|(s, ..)| score > s) {
+                best = Some((score, i, j));
+            }
+        }
+    }
+    best.map(|(_, i, j)| (i, j))
+}
+
+/// A read's SAM/BAM record, placed by `hit` (with its MAPQ) when mapped.
+fn read_record(read: &FastqRead, hit: Option<(&Hit, u8)>) -> BamRecord {
+    let name = read.name();
+    let name = name
+        .strip_suffix(b"/1")
+        .or_else(|| name.strip_suffix(b"/2"))
+        .unwrap_or(name);
+    let mut record = BamRecord {
+        tid: -1,
+        pos: -1,
+        mapq: 0,
+        flag: UNMAPPED,
+        next_tid: -1,
+        next_pos: -1,
+        tlen: 0,
+        qname: String::from_utf8_lossy(name).into_owned(),
+        cigar: Vec::new(),
+        seq: String::from_utf8_lossy(&read.seq).into_owned(),
+        qual: String::from_utf8_lossy(&read.qual).into_owned(),
+        aux: Vec::new(),
+    };
+    if let Some((hit, mapq)) = hit {
+        record.tid = hit.contig as i32;
+        record.pos = hit.pos as i32;
+        record.mapq = mapq;
+        record.flag = if hit.reverse { REVERSE } else { 0 };
+        record.cigar = hit.cigar.clone();
+        if hit.reverse {
+            let seq = reverse_complement(&normalize_text(&read.seq));
+            record.seq = String::from_utf8_lossy(&seq).into_owned();
+            record.qual = read.qual.iter().rev().map(|&q| q as char).collect();
+        }
+        record.push_aux_int(b"AS", hit.score);
+        record.push_aux_string(b"MD", &hit.md);
+        record.push_aux_int(b"NM", hit.edits as i32);
+    }
+    record
+}
+
+/// Fill in the mate fields of a pair's two records.
+fn link_mates(pair: &mut [BamRecord; 2], proper: bool) {
+    let mapped = pair.each_ref().map(|r| r.flag & UNMAPPED == 0);
+    // An unmapped mate takes its partner's position, as in BWA.
+    for i in 0..2 {
+        if !mapped[i] && mapped[1 - i] {
+            pair[i].tid = pair[1 - i].tid;
+            pair[i].pos = pair[1 - i].pos;
+        }
+    }
+    let tlen = if mapped == [true, true] && pair[0].tid == pair[1].tid {
+        let left = pair[0].pos.min(pair[1].pos);
+        let right = pair
+            .iter()
+            .map(|r| r.pos + r.reference_len() as i32)
+            .max()
+            .unwrap_or(left);
+        right - left
+    } else {
+        0
+    };
+    let first_left = pair[0].pos <= pair[1].pos;
+    for i in 0..2 {
+        let mate = 1 - i;
+        let (mate_tid, mate_pos, mate_flag) = (pair[mate].tid, pair[mate].pos, pair[mate].flag);
+        let r = &mut pair[i];
+        r.flag |= PAIRED | if i == 0 { FIRST_MATE } else { SECOND_MATE };
+        if proper {
+            r.flag |= PROPER_PAIR;
+        }
+        if !mapped[mate] {
+            r.flag |= MATE_UNMAPPED;
+        } else if mate_flag & REVERSE != 0 {
+            r.flag |= MATE_REVERSE;
+        }
+        r.next_tid = mate_tid;
+        r.next_pos = mate_pos;
+        r.tlen = if (i == 0) == first_left { tlen } else { -tlen };
+    }
+}
+
+// ===========================================================================
+// Output
+// ===========================================================================
+
+enum AlignmentWriter {
+    Sam(TextWriter),
+    Bam(BgzfWriter<BufWriter<File>>),
+}
+
+impl AlignmentWriter {
+    fn create(path: &str, format: &str, index: &ReadMapperIndexResource) -> Result<Self, NifError> {
+        let io_err = |e: std::io::Error| NifError::from(e).with_file(path);
+        let mut header = String::from("@HD\tVN:1.6\tSO:unknown\n");
+        for (name, len) in index.names.iter().zip(&index.lengths) {
+            header.push_str(&format!("@SQ\tSN:{name}\tLN:{len}\n"));
+        }
+        header.push_str("@PG\tID:cyanea\tPN:cyanea\n");
+        match format {
+            "sam" => {
+                let mut out = TextWriter::create(path, "none")?;
+                out.write_all(header.as_bytes()).map_err(io_err)?;
+                Ok(Self::Sam(out))
+            }
+            "bam" => {
+                let file = File::create(path).map_err(io_err)?;
+                let mut out = BgzfWriter::new(BufWriter::new(file), 6);
+                let refs: Vec<(String, u64)> = index
+                    .names
+                    .iter()
+                    .cloned()
+                    .zip(index.lengths.iter().map(|&l| l as u64))
+                    .collect();
+                write_bam_header(&mut out, &header, &refs).map_err(io_err)?;
+                Ok(Self::Bam(out))
+            }
+            _ => Err(NifError::invalid(format!("unknown format: {format} (expected sam or bam)"))),
+        }
+    }
+
+    fn write(&mut self, record: &BamRecord, names: &[String]) -> Result<(), NifError> {
+        match self {
+            Self::Bam(out) => out.write_all(&encode_bam_record(record)?)?,
+            Self::Sam(out) => {
+                let rname = |tid: i32| names.get(tid as usize).map_or("*", String::as_str);
+                let rnext = if record.next_tid < 0 {
+                    "*"
+                } else if record.next_tid == record.tid {
+                    "="
+                } else {
+                    rname(record.next_tid)
+                };
+                write!(
+                    out,
+                    "{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}\t{}",
+                    record.qname,
+                    record.flag,
+                    if record.tid < 0 { "*" } else { rname(record.tid) },
+                    record.pos + 1,
+                    record.mapq,
+                    record.cigar_string(),
+                    rnext,
+                    record.next_pos + 1,
+                    record.tlen,
+                    record.seq,
+                    record.qual
+                )?;
+                let tags = decode_bam_aux(&record.aux)?;
+                let mut keys: Vec<&String> = tags.keys().collect();
+                keys.sort();
+                for key in keys {
+                    write!(out, "\t{}", sam_tag_string(key, &tags[key]))?;
+                }
+                writeln!(out)?;
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(self) -> std::io::Result<()> {
+        match self {
+            Self::Sam(out) => out.finish(),
+            Self::Bam(out) => out.finish().map(|_| ()),
+        }
+    }
+}
+
+// ===========================================================================
+// NIFs
+// ===========================================================================
+
+/// Index a reference FASTA (plain or gzipped) for `map_reads`.
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn read_mapper_index(reference_path: String) -> Result<ResourceArc<ReadMapperIndexResource>, NifError> {
+    Ok(ResourceArc::new(ReadMapperIndexResource::build(&reference_path)?))
+}
+
+/// Map one FASTQ (single-end) or two (paired-end, read in lockstep) and
+/// write every read, mapped or not, to `output` as `"sam"` or `"bam"`.
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn map_reads(
+    index: ResourceArc<ReadMapperIndexResource>,
+    reads: Vec<String>,
+    output: String,
+    format: String,
+    options: MapOptionsNif,
+) -> Result<MapStatsNif, NifError> {
+    if !matches!(reads.len(), 1 | 2) {
+        return Err(NifError::invalid("expected one FASTQ file, or two for paired-end reads"));
+    }
+    if options.seed_length < 8 {
+        return Err(NifError::invalid("seed_length must be at least 8"));
+    }
+    let matrix = cyanea_align::ScoringMatrix::new(MATCH, MISMATCH, GAP_OPEN, GAP_EXTEND).map_err(to_nif_error)?;
+    let scoring = cyanea_align::ScoringScheme::Simple(matrix);
+    let mut readers = reads
+        .iter()
+        .map(|path| FastqReader::open(path))
+        .collect::<Result<Vec<_>, NifError>>()?;
+    let mut out = AlignmentWriter::create(&output, &format, &index)?;
+    let with_file = |e: NifError| e.with_file(&output);
+
+    let mut stats = MapStatsNif {
+        reads: 0,
+        mapped: 0,
+        properly_paired: 0,
+    };
+    while let Some(record) = next_record(&mut readers)? {
+        let mappings = record
+            .iter()
+            .map(|read| index.map(read, &options, &scoring))
+            .collect::<Result<Vec<_>, NifError>>()?;
+        let records: Vec<BamRecord> = match (record.as_slice(), mappings.as_slice()) {
+            ([read], [mapping]) => {
+                vec![read_record(read, placement(mapping, 0))]
+            }
+            ([r1, r2], [m1, m2]) => {
+                let pair = best_pair(&m1.hits, &m2.hits, options.max_insert);
+                let (i, j) = pair.unwrap_or((0, 0));
+                let mut records = [
+                    read_record(r1, placement(m1, i)),
+                    read_record(r2, placement(m2, j)),
+                ];
+                link_mates(&mut records, pair.is_some());
+                if pair.is_some() {
+                    stats.properly_paired += 2;
+                }
+                records.into()
+            }
+            _ => unreachable!("one or two reads per record"),
+        };
+        for r in &records {
+            stats.reads += 1;
+            if r.flag & UNMAPPED == 0 {
+                stats.mapped += 1;
+            }
+            out.write(r, &index.names).map_err(with_file)?;
+        }
+    }
+    out.finish().map_err(|e| NifError::from(e).with_file(&output))?;
+    Ok(stats)
+}
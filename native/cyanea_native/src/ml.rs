@@ -1,72 +1,127 @@
 //! cyanea-ml NIFs — Clustering, PCA, t-SNE, UMAP, embeddings, distances, KNN, regression, HMM.
 
 use crate::bridge::*;
+use crate::job::{self, CancelTokenResource, JobState, ProgressTarget};
+use crate::pool::{self, PoolId};
 use crate::to_nif_error;
+use rustler::{Encoder, Env, Reference, ResourceArc};
 
 // ===========================================================================
 // Helpers
 // ===========================================================================
 
-pub(crate) fn parse_distance_metric(s: &str) -> Result<cyanea_ml::DistanceMetric, String> {
+pub(crate) fn parse_distance_metric(s: &str) -> Result<cyanea_ml::DistanceMetric, NifError> {
     match s {
         "euclidean" => Ok(cyanea_ml::DistanceMetric::Euclidean),
         "manhattan" => Ok(cyanea_ml::DistanceMetric::Manhattan),
         "cosine" => Ok(cyanea_ml::DistanceMetric::Cosine),
-        _ => Err(format!(
+        _ => Err(NifError::invalid(format!(
             "unknown distance metric: {s} (expected euclidean, manhattan, or cosine)"
-        )),
+        ))),
     }
 }
 
-pub(crate) fn parse_alphabet(s: &str) -> Result<cyanea_ml::Alphabet, String> {
+pub(crate) fn parse_alphabet(s: &str) -> Result<cyanea_ml::Alphabet, NifError> {
     match s {
         "dna" => Ok(cyanea_ml::Alphabet::Dna),
         "rna" => Ok(cyanea_ml::Alphabet::Rna),
         "protein" => Ok(cyanea_ml::Alphabet::Protein),
-        _ => Err(format!(
+        _ => Err(NifError::invalid(format!(
             "unknown alphabet: {s} (expected dna, rna, or protein)"
-        )),
+        ))),
     }
 }
 
-pub(crate) fn flat_to_slices(data: &[f64], n_features: usize) -> Result<Vec<&[f64]>, String> {
+pub(crate) fn flat_to_slices(data: &[f64], n_features: usize) -> Result<Vec<&[f64]>, NifError> {
     if n_features == 0 {
-        return Err("n_features must be > 0".into());
+        return Err(NifError::invalid("n_features must be > 0"));
     }
     if data.len() % n_features != 0 {
-        return Err(format!(
+        return Err(NifError::invalid(format!(
             "data length {} is not divisible by n_features {}",
             data.len(),
             n_features
-        ));
+        )));
     }
     Ok(data.chunks(n_features).collect())
 }
 
-fn parse_linkage(s: &str) -> Result<cyanea_ml::Linkage, String> {
+fn parse_linkage(s: &str) -> Result<cyanea_ml::Linkage, NifError> {
     match s {
         "single" => Ok(cyanea_ml::Linkage::Single),
         "complete" => Ok(cyanea_ml::Linkage::Complete),
         "average" => Ok(cyanea_ml::Linkage::Average),
         "ward" => Ok(cyanea_ml::Linkage::Ward),
-        _ => Err(format!(
+        _ => Err(NifError::invalid(format!(
             "unknown linkage: {s} (expected single, complete, average, or ward)"
-        )),
+        ))),
     }
 }
 
+fn tsne_config(n_components: usize, perplexity: f64, n_iter: usize) -> cyanea_ml::TsneConfig {
+    cyanea_ml::TsneConfig {
+        n_components,
+        perplexity,
+        learning_rate: 200.0,
+        n_iter,
+        seed: 42,
+    }
+}
+
+fn umap_config(
+    n_components: usize,
+    n_neighbors: usize,
+    min_dist: f64,
+    n_epochs: usize,
+    metric: &str,
+    seed: u64,
+) -> Result<cyanea_ml::UmapConfig, NifError> {
+    Ok(cyanea_ml::UmapConfig {
+        n_components,
+        n_neighbors,
+        min_dist,
+        n_epochs,
+        metric: parse_distance_metric(metric)?,
+        seed,
+        ..Default::default()
+    })
+}
+
+fn hierarchical(
+    data: &[f64],
+    n_features: usize,
+    n_clusters: usize,
+    linkage: cyanea_ml::Linkage,
+    metric: cyanea_ml::DistanceMetric,
+) -> Result<HierarchicalResultNif, NifError> {
+    let slices = flat_to_slices(data, n_features)?;
+    let dm = cyanea_ml::pairwise_distances(&slices, metric).map_err(to_nif_error)?;
+    let config = cyanea_ml::HierarchicalConfig {
+        n_clusters,
+        linkage,
+    };
+    let result = cyanea_ml::hierarchical(&dm, &config).map_err(to_nif_error)?;
+    Ok(HierarchicalResultNif {
+        labels: result.labels,
+        merge_distances: result.merge_history.iter().map(|step| step.distance).collect(),
+    })
+}
+
 // ===========================================================================
 // Existing NIFs
 // ===========================================================================
 
 #[rustler::nif(schedule = "DirtyCpu")]
-pub fn kmeans(
-    data: Vec<f64>,
+pub fn kmeans<'a>(
+    env: Env<'a>,
+    data: MatrixInput,
     n_features: usize,
     k: usize,
     max_iter: usize,
     seed: u64,
-) -> Result<KMeansResultNif, String> {
+) -> Result<KMeansResultNif<'a>, NifError> {
+    let form = data.form();
+    let data = data.into_flat(n_features)?;
     let slices = flat_to_slices(&data, n_features)?;
     let config = cyanea_ml::KMeansConfig {
         n_clusters: k,
@@ -74,19 +129,19 @@ pub fn kmeans(
         tolerance: 1e-4,
         seed,
     };
-    cyanea_ml::kmeans(&slices, &config)
-        .map(KMeansResultNif::from)
-        .map_err(to_nif_error)
+    let result = cyanea_ml::kmeans(&slices, &config).map_err(to_nif_error)?;
+    KMeansResultNif::new(result, env, form)
 }
 
 #[rustler::nif(schedule = "DirtyCpu")]
 pub fn dbscan(
-    data: Vec<f64>,
+    data: MatrixInput,
     n_features: usize,
     eps: f64,
     min_samples: usize,
     metric: String,
-) -> Result<DbscanResultNif, String> {
+) -> Result<DbscanResultNif, NifError> {
+    let data = data.into_flat(n_features)?;
     let slices = flat_to_slices(&data, n_features)?;
     let metric = parse_distance_metric(&metric)?;
     let config = cyanea_ml::DbscanConfig {
@@ -100,44 +155,47 @@ pub fn dbscan(
 }
 
 #[rustler::nif(schedule = "DirtyCpu")]
-pub fn pca(
-    data: Vec<f64>,
+pub fn pca<'a>(
+    env: Env<'a>,
+    data: MatrixInput,
     n_features: usize,
     n_components: usize,
-) -> Result<PcaResultNif, String> {
+) -> Result<PcaResultNif<'a>, NifError> {
+    let form = data.form();
+    let data = data.into_flat(n_features)?;
     let config = cyanea_ml::PcaConfig {
         n_components,
         max_iter: 100,
         tolerance: 1e-6,
     };
-    cyanea_ml::pca(&data, n_features, &config)
-        .map(PcaResultNif::from)
-        .map_err(to_nif_error)
+    let result = cyanea_ml::pca(&data, n_features, &config).map_err(to_nif_error)?;
+    PcaResultNif::new(result, env, form)
 }
 
 #[rustler::nif(schedule = "DirtyCpu")]
-pub fn tsne(
-    data: Vec<f64>,
+pub fn tsne<'a>(
+    env: Env<'a>,
+    data: MatrixInput,
     n_features: usize,
     n_components: usize,
     perplexity: f64,
     n_iter: usize,
-) -> Result<TsneResultNif, String> {
-    let config = cyanea_ml::TsneConfig {
-        n_components,
-        perplexity,
-        learning_rate: 200.0,
-        n_iter,
-        seed: 42,
-    };
-    cyanea_ml::tsne(&data, n_features, &config)
-        .map(TsneResultNif::from)
-        .map_err(to_nif_error)
+    cancel: Option<ResourceArc<CancelTokenResource>>,
+    progress: ProgressTarget<'a>,
+) -> Result<TsneResultNif<'a>, NifError> {
+    let form = data.form();
+    let data = data.into_flat(n_features)?;
+    let config = tsne_config(n_components, perplexity, n_iter);
+    let result = job::run(env, cancel, progress, move |_| {
+        cyanea_ml::tsne(&data, n_features, &config).map_err(to_nif_error)
+    })?;
+    TsneResultNif::new(result, env, form)
 }
 
 #[rustler::nif(schedule = "DirtyCpu")]
-pub fn umap(
-    data: Vec<f64>,
+pub fn umap<'a>(
+    env: Env<'a>,
+    data: MatrixInput,
     n_features: usize,
     n_components: usize,
     n_neighbors: usize,
@@ -145,24 +203,20 @@ pub fn umap(
     n_epochs: usize,
     metric: String,
     seed: u64,
-) -> Result<UmapResultNif, String> {
-    let metric = parse_distance_metric(&metric)?;
-    let config = cyanea_ml::UmapConfig {
-        n_components,
-        n_neighbors,
-        min_dist,
-        n_epochs,
-        metric,
-        seed,
-        ..Default::default()
-    };
-    cyanea_ml::umap(&data, n_features, &config)
-        .map(UmapResultNif::from)
-        .map_err(to_nif_error)
+    cancel: Option<ResourceArc<CancelTokenResource>>,
+    progress: ProgressTarget<'a>,
+) -> Result<UmapResultNif<'a>, NifError> {
+    let form = data.form();
+    let data = data.into_flat(n_features)?;
+    let config = umap_config(n_components, n_neighbors, min_dist, n_epochs, &metric, seed)?;
+    let result = job::run(env, cancel, progress, move |_| {
+        cyanea_ml::umap(&data, n_features, &config).map_err(to_nif_error)
+    })?;
+    UmapResultNif::new(result, env, form)
 }
 
 #[rustler::nif]
-pub fn kmer_embedding(sequence: Vec<u8>, k: usize, alphabet: String) -> Result<Vec<f64>, String> {
+pub fn kmer_embedding(sequence: Vec<u8>, k: usize, alphabet: String) -> Result<Vec<f64>, NifError> {
     let alphabet = parse_alphabet(&alphabet)?;
     let config = cyanea_ml::embedding::EmbeddingConfig {
         k,
@@ -179,7 +233,7 @@ pub fn batch_embed(
     sequences: Vec<Vec<u8>>,
     k: usize,
     alphabet: String,
-) -> Result<Vec<Vec<f64>>, String> {
+) -> Result<Vec<Vec<f64>>, NifError> {
     let alphabet = parse_alphabet(&alphabet)?;
     let config = cyanea_ml::embedding::EmbeddingConfig {
         k,
@@ -193,16 +247,18 @@ pub fn batch_embed(
 }
 
 #[rustler::nif(schedule = "DirtyCpu")]
-pub fn pairwise_distances(
-    data: Vec<f64>,
+pub fn pairwise_distances<'a>(
+    env: Env<'a>,
+    data: MatrixInput,
     n_features: usize,
     metric: String,
-) -> Result<Vec<f64>, String> {
+) -> Result<MatrixOutput<'a>, NifError> {
+    let form = data.form();
+    let data = data.into_flat(n_features)?;
     let slices = flat_to_slices(&data, n_features)?;
     let metric = parse_distance_metric(&metric)?;
-    cyanea_ml::pairwise_distances(&slices, metric)
-        .map(|dm| dm.condensed().to_vec())
-        .map_err(to_nif_error)
+    let dm = cyanea_ml::pairwise_distances(&slices, metric).map_err(to_nif_error)?;
+    form.vector(env, dm.condensed().to_vec())
 }
 
 // ===========================================================================
@@ -211,36 +267,28 @@ pub fn pairwise_distances(
 
 #[rustler::nif(schedule = "DirtyCpu")]
 pub fn hierarchical_cluster(
-    data: Vec<f64>,
+    data: MatrixInput,
     n_features: usize,
     n_clusters: usize,
     linkage: String,
     metric: String,
-) -> Result<HierarchicalResultNif, String> {
-    let slices = flat_to_slices(&data, n_features)?;
+) -> Result<HierarchicalResultNif, NifError> {
+    let data = data.into_flat(n_features)?;
     let linkage = parse_linkage(&linkage)?;
     let metric = parse_distance_metric(&metric)?;
-    let dm = cyanea_ml::pairwise_distances(&slices, metric).map_err(to_nif_error)?;
-    let config = cyanea_ml::HierarchicalConfig {
-        n_clusters,
-        linkage,
-    };
-    let result = cyanea_ml::hierarchical(&dm, &config).map_err(to_nif_error)?;
-    Ok(HierarchicalResultNif {
-        labels: result.labels,
-        merge_distances: result.merge_history.iter().map(|step| step.distance).collect(),
-    })
+    hierarchical(&data, n_features, n_clusters, linkage, metric)
 }
 
 #[rustler::nif]
 pub fn knn_classify(
-    data: Vec<f64>,
+    data: MatrixInput,
     n_features: usize,
     k: usize,
     metric: String,
     labels: Vec<i32>,
     query: Vec<f64>,
-) -> Result<i32, String> {
+) -> Result<i32, NifError> {
+    let data = data.into_flat(n_features)?;
     let metric = parse_distance_metric(&metric)?;
     let config = cyanea_ml::KnnConfig { k, metric };
     let knn = cyanea_ml::KnnModel::fit(&data, n_features, config).map_err(to_nif_error)?;
@@ -249,10 +297,11 @@ pub fn knn_classify(
 
 #[rustler::nif(schedule = "DirtyCpu")]
 pub fn linear_regression_fit(
-    data: Vec<f64>,
+    data: MatrixInput,
     n_features: usize,
     targets: Vec<f64>,
-) -> Result<LinearRegressionResultNif, String> {
+) -> Result<LinearRegressionResultNif, NifError> {
+    let data = data.into_flat(n_features)?;
     let model = cyanea_ml::LinearRegression::fit(&data, n_features, &targets).map_err(to_nif_error)?;
     Ok(LinearRegressionResultNif {
         weights: model.weights.clone(),
@@ -262,12 +311,15 @@ pub fn linear_regression_fit(
 }
 
 #[rustler::nif]
-pub fn linear_regression_predict(
+pub fn linear_regression_predict<'a>(
+    env: Env<'a>,
     weights: Vec<f64>,
     bias: f64,
-    queries: Vec<f64>,
+    queries: MatrixInput,
     n_features: usize,
-) -> Result<Vec<f64>, String> {
+) -> Result<MatrixOutput<'a>, NifError> {
+    let form = queries.form();
+    let queries = queries.into_flat(n_features)?;
     let query_slices = flat_to_slices(&queries, n_features)?;
     let predictions: Vec<f64> = query_slices
         .iter()
@@ -275,50 +327,327 @@ pub fn linear_regression_predict(
             q.iter().zip(weights.iter()).map(|(x, w)| x * w).sum::<f64>() + bias
         })
         .collect();
-    Ok(predictions)
+    form.vector(env, predictions)
+}
+
+// ===========================================================================
+// Random forest (resource)
+// ===========================================================================
+
+/// Small deterministic PRNG (SplitMix64) for bootstrap sampling and
+/// permutation importance, so results are reproducible from the seed.
+struct SplitMix64(u64);
+
+impl SplitMix64 {
+    fn next_u64(&mut self) -> u64 {
+        self.0 = self.0.wrapping_add(0x9E37_79B9_7F4A_7C15);
+        let mut z = self.0;
+        z = (z ^ (z >> 30)).wrapping_mul(0xBF58_476D_1CE4_E5B9);
+        z = (z ^ (z >> 27)).wrapping_mul(0x94D0_49BB_1331_11EB);
+        z ^ (z >> 31)
+    }
+
+    fn below(&mut self, n: usize) -> usize {
+        (self.next_u64() % n as u64) as usize
+    }
+}
+
+/// A fitted random forest held on the Rust side.
+///
+/// Each tree is a single-tree `cyanea_ml::RandomForest` fitted on a bootstrap
+/// sample drawn here, so the in-bag sets are known.  Out-of-bag error and
+/// permutation importances are computed once at fit time; the training data
+/// is not kept, so the resource holds only the trees.
+pub struct RandomForestResource {
+    trees: Vec<cyanea_ml::RandomForest>,
+    n_features: usize,
+    n_classes: usize,
+    n_samples: usize,
+    oob_error: Option<f64>,
+    importances: Vec<f64>,
+}
+
+#[rustler::resource_impl]
+impl rustler::Resource for RandomForestResource {}
+
+/// Trees together with the bootstrap samples they were fitted on, alive only
+/// for the duration of a fit.
+struct BaggedForest<'d> {
+    trees: Vec<cyanea_ml::RandomForest>,
+    in_bag: Vec<Vec<bool>>,
+    data: &'d [f64],
+    labels: &'d [usize],
+    n_features: usize,
+    n_classes: usize,
+}
+
+impl RandomForestResource {
+    fn fit(
+        data: Vec<f64>,
+        n_features: usize,
+        labels: Vec<usize>,
+        n_trees: usize,
+        max_depth: usize,
+        seed: u64,
+        job: &JobState,
+    ) -> Result<Self, NifError> {
+        let n_samples = flat_to_slices(&data, n_features)?.len();
+        if n_samples == 0 {
+            return Err(NifError::invalid("data must not be empty"));
+        }
+        if labels.len() != n_samples {
+            return Err(NifError::invalid(format!(
+                "expected {} labels but got {}",
+                n_samples,
+                labels.len()
+            )));
+        }
+        if n_trees == 0 {
+            return Err(NifError::invalid("n_trees must be > 0"));
+        }
+        let n_classes = labels.iter().max().map_or(0, |&m| m + 1);
+        let mut rng = SplitMix64(seed);
+        let mut trees = Vec::with_capacity(n_trees);
+        let mut in_bag = Vec::with_capacity(n_trees);
+        for t in 0..n_trees {
+            job.check()?;
+            let mut bag = vec![false; n_samples];
+            let mut boot_data = Vec::with_capacity(data.len());
+            let mut boot_labels = Vec::with_capacity(n_samples);
+            for _ in 0..n_samples {
+                let i = rng.below(n_samples);
+                bag[i] = true;
+                boot_data.extend_from_slice(&data[i * n_features..(i + 1) * n_features]);
+                boot_labels.push(labels[i]);
+            }
+            let config = cyanea_ml::RandomForestConfig {
+                n_trees: 1,
+                max_depth,
+                seed: seed.wrapping_add(t as u64),
+                ..Default::default()
+            };
+            let tree = cyanea_ml::RandomForest::fit(&boot_data, n_features, &boot_labels, &config)
+                .map_err(to_nif_error)?;
+            trees.push(tree);
+            in_bag.push(bag);
+            // Tree fitting takes the first half of the progress range, the
+            // out-of-bag scoring below the second.
+            job.set_progress(0.5 * (t + 1) as f64 / n_trees as f64);
+        }
+        let forest = BaggedForest {
+            trees,
+            in_bag,
+            data: &data,
+            labels: &labels,
+            n_features,
+            n_classes,
+        };
+        let oob_error = forest.oob_error();
+        let importances = forest.importances(seed, job)?;
+        Ok(Self {
+            trees: forest.trees,
+            n_features,
+            n_classes,
+            n_samples,
+            oob_error,
+            importances,
+        })
+    }
+
+    fn votes(&self, sample: &[f64]) -> Vec<usize> {
+        let mut votes = vec![0usize; self.n_classes];
+        for tree in &self.trees {
+            let label = tree.predict(sample);
+            if label < self.n_classes {
+                votes[label] += 1;
+            }
+        }
+        votes
+    }
+
+    fn predict(&self, sample: &[f64]) -> usize {
+        majority(&self.votes(sample))
+    }
+
+    fn predict_proba(&self, sample: &[f64]) -> Vec<f64> {
+        let n_trees = self.trees.len() as f64;
+        self.votes(sample).into_iter().map(|v| v as f64 / n_trees).collect()
+    }
+}
+
+/// Class with the most votes; ties resolve to the lowest class label.
+fn majority(votes: &[usize]) -> usize {
+    let mut best = 0;
+    for (class, &count) in votes.iter().enumerate() {
+        if count > votes[best] {
+            best = class;
+        }
+    }
+    best
+}
+
+impl BaggedForest<'_> {
+    fn sample(&self, i: usize) -> &[f64] {
+        &self.data[i * self.n_features..(i + 1) * self.n_features]
+    }
+
+    /// Misclassification rate using, for each sample, only the trees that
+    /// did not see it during training. `None` when every sample was in-bag
+    /// for every tree.
+    fn oob_error(&self) -> Option<f64> {
+        let mut evaluated = 0usize;
+        let mut wrong = 0usize;
+        for i in 0..self.labels.len() {
+            let mut votes = vec![0usize; self.n_classes];
+            let mut any = false;
+            for (tree, bag) in self.trees.iter().zip(&self.in_bag) {
+                if !bag[i] {
+                    let label = tree.predict(self.sample(i));
+                    if label < self.n_classes {
+                        votes[label] += 1;
+                    }
+                    any = true;
+                }
+            }
+            if !any {
+                continue;
+            }
+            evaluated += 1;
+            if majority(&votes) != self.labels[i] {
+                wrong += 1;
+            }
+        }
+        (evaluated > 0).then(|| wrong as f64 / evaluated as f64)
+    }
+
+    /// Permutation importance: mean decrease in per-tree out-of-bag accuracy
+    /// when a feature's values are shuffled among that tree's OOB samples.
+    fn importances(&self, seed: u64, job: &JobState) -> Result<Vec<f64>, NifError> {
+        let mut rng = SplitMix64(seed ^ 0xA5A5_A5A5_A5A5_A5A5);
+        let mut importances = vec![0.0; self.n_features];
+        let mut n_scored = 0usize;
+        let n_trees = self.trees.len();
+        for (t, (tree, bag)) in self.trees.iter().zip(&self.in_bag).enumerate() {
+            job.check()?;
+            let oob: Vec<usize> = (0..self.labels.len()).filter(|&i| !bag[i]).collect();
+            if oob.is_empty() {
+                continue;
+            }
+            n_scored += 1;
+            let baseline = oob
+                .iter()
+                .filter(|&&i| tree.predict(self.sample(i)) == self.labels[i])
+                .count() as f64
+                / oob.len() as f64;
+            let mut row = vec![0.0; self.n_features];
+            for (feature, importance) in importances.iter_mut().enumerate() {
+                let mut shuffled: Vec<f64> =
+                    oob.iter().map(|&i| self.sample(i)[feature]).collect();
+                for j in (1..shuffled.len()).rev() {
+                    let k = rng.below(j + 1);
+                    shuffled.swap(j, k);
+                }
+                let mut correct = 0usize;
+                for (&i, &value) in oob.iter().zip(&shuffled) {
+                    row.copy_from_slice(self.sample(i));
+                    row[feature] = value;
+                    if tree.predict(&row) == self.labels[i] {
+                        correct += 1;
+                    }
+                }
+                *importance += baseline - correct as f64 / oob.len() as f64;
+            }
+            job.set_progress(0.5 + 0.5 * (t + 1) as f64 / n_trees as f64);
+        }
+        if n_scored > 0 {
+            for importance in &mut importances {
+                *importance /= n_scored as f64;
+            }
+        }
+        Ok(importances)
+    }
 }
 
 #[rustler::nif(schedule = "DirtyCpu")]
-pub fn random_forest_fit(
-    data: Vec<f64>,
+pub fn random_forest_fit<'a>(
+    env: Env<'a>,
+    data: MatrixInput,
     n_features: usize,
     labels: Vec<usize>,
     n_trees: usize,
     max_depth: usize,
     seed: u64,
-) -> Result<Vec<u8>, String> {
-    let config = cyanea_ml::RandomForestConfig {
-        n_trees,
-        max_depth,
-        seed,
-        ..Default::default()
-    };
-    let _model = cyanea_ml::RandomForest::fit(&data, n_features, &labels, &config).map_err(to_nif_error)?;
-    // RandomForest doesn't implement Serialize. Serialize the training state
-    // so predict can re-train (not ideal but avoids needing serde on the model).
-    let state = (data, n_features, labels, n_trees, max_depth, seed);
-    bincode::serialize(&state).map_err(|e| e.to_string())
+    cancel: Option<ResourceArc<CancelTokenResource>>,
+    progress: ProgressTarget<'a>,
+) -> Result<ResourceArc<RandomForestResource>, NifError> {
+    let data = data.into_flat(n_features)?;
+    job::run(env, cancel, progress, move |job| {
+        RandomForestResource::fit(data, n_features, labels, n_trees, max_depth, seed, job)
+    })
+    .map(ResourceArc::new)
 }
 
-#[rustler::nif]
+#[rustler::nif(schedule = "DirtyCpu")]
 pub fn random_forest_predict(
-    model_data: Vec<u8>,
-    sample: Vec<f64>,
-    _n_features: usize,
-) -> Result<usize, String> {
-    let (data, n_features, labels, n_trees, max_depth, seed): (Vec<f64>, usize, Vec<usize>, usize, usize, u64) =
-        bincode::deserialize(&model_data).map_err(|e| e.to_string())?;
-    let config = cyanea_ml::RandomForestConfig {
-        n_trees,
-        max_depth,
-        seed,
-        ..Default::default()
+    model: ResourceArc<RandomForestResource>,
+    samples: MatrixInput,
+    n_features: usize,
+) -> Result<Vec<usize>, NifError> {
+    if n_features != model.n_features {
+        return Err(NifError::invalid(format!(
+            "model was fitted with {} features but got {}",
+            model.n_features, n_features
+        )));
+    }
+    let samples = samples.into_flat(n_features)?;
+    let slices = flat_to_slices(&samples, n_features)?;
+    Ok(slices.iter().map(|s| model.predict(s)).collect())
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn random_forest_predict_proba<'a>(
+    env: Env<'a>,
+    model: ResourceArc<RandomForestResource>,
+    samples: MatrixInput,
+    n_features: usize,
+) -> Result<MatrixOutput<'a>, NifError> {
+    if n_features != model.n_features {
+        return Err(NifError::invalid(format!(
+            "model was fitted with {} features but got {}",
+            model.n_features, n_features
+        )));
+    }
+    // Probabilities come back as rows unless the samples were a binary matrix.
+    let form = match samples.form() {
+        MatrixForm::Flat => MatrixForm::Rows,
+        form => form,
     };
-    let model = cyanea_ml::RandomForest::fit(&data, n_features, &labels, &config)
-        .map_err(to_nif_error)?;
-    Ok(model.predict(&sample))
+    let samples = samples.into_flat(n_features)?;
+    let slices = flat_to_slices(&samples, n_features)?;
+    let proba: Vec<f64> = slices.iter().flat_map(|s| model.predict_proba(s)).collect();
+    form.matrix(env, proba, model.n_classes)
+}
+
+#[rustler::nif]
+pub fn random_forest_feature_importances(model: ResourceArc<RandomForestResource>) -> Vec<f64> {
+    model.importances.clone()
+}
+
+#[rustler::nif]
+pub fn random_forest_info(model: ResourceArc<RandomForestResource>) -> RandomForestInfoNif {
+    RandomForestInfoNif {
+        n_trees: model.trees.len(),
+        n_features: model.n_features,
+        n_classes: model.n_classes,
+        n_samples: model.n_samples,
+        oob_error: model.oob_error,
+    }
 }
 
+// ===========================================================================
+// HMM, normalization & evaluation
+// ===========================================================================
+
 #[rustler::nif]
 pub fn hmm_viterbi(
     n_states: usize,
@@ -327,7 +656,7 @@ pub fn hmm_viterbi(
     transition: Vec<f64>,
     emission: Vec<f64>,
     observations: Vec<usize>,
-) -> Result<(Vec<usize>, f64), String> {
+) -> Result<(Vec<usize>, f64), NifError> {
     let hmm = cyanea_ml::HmmModel::new(
         n_states,
         n_symbols,
@@ -347,7 +676,7 @@ pub fn hmm_forward(
     transition: Vec<f64>,
     emission: Vec<f64>,
     observations: Vec<usize>,
-) -> Result<f64, String> {
+) -> Result<f64, NifError> {
     let hmm = cyanea_ml::HmmModel::new(
         n_states,
         n_symbols,
@@ -361,58 +690,165 @@ pub fn hmm_forward(
 }
 
 #[rustler::nif]
-pub fn normalize_min_max(data: Vec<f64>) -> Result<Vec<f64>, String> {
-    if data.is_empty() {
-        return Err("data must not be empty".into());
-    }
-    let min = data.iter().cloned().fold(f64::INFINITY, f64::min);
-    let max = data.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
-    let range = max - min;
-    if range == 0.0 {
-        return Ok(vec![0.0; data.len()]);
-    }
-    Ok(data.iter().map(|&x| (x - min) / range).collect())
+pub fn normalize_min_max<'a>(env: Env<'a>, data: MatrixInput) -> Result<MatrixOutput<'a>, NifError> {
+    map_elements(env, data, |data| {
+        let min = data.iter().cloned().fold(f64::INFINITY, f64::min);
+        let max = data.iter().cloned().fold(f64::NEG_INFINITY, f64::max);
+        let range = max - min;
+        if range == 0.0 {
+            return vec![0.0; data.len()];
+        }
+        data.iter().map(|&x| (x - min) / range).collect()
+    })
 }
 
 #[rustler::nif]
-pub fn normalize_z_score(data: Vec<f64>) -> Result<Vec<f64>, String> {
-    if data.is_empty() {
-        return Err("data must not be empty".into());
-    }
-    let n = data.len() as f64;
-    let mean = data.iter().sum::<f64>() / n;
-    let variance = data.iter().map(|&x| (x - mean).powi(2)).sum::<f64>() / n;
-    let std_dev = variance.sqrt();
-    if std_dev == 0.0 {
-        return Ok(vec![0.0; data.len()]);
+pub fn normalize_z_score<'a>(env: Env<'a>, data: MatrixInput) -> Result<MatrixOutput<'a>, NifError> {
+    map_elements(env, data, |data| {
+        let n = data.len() as f64;
+        let mean = data.iter().sum::<f64>() / n;
+        let variance = data.iter().map(|&x| (x - mean).powi(2)).sum::<f64>() / n;
+        let std_dev = variance.sqrt();
+        if std_dev == 0.0 {
+            return vec![0.0; data.len()];
+        }
+        data.iter().map(|&x| (x - mean) / std_dev).collect()
+    })
+}
+
+/// Apply an element-wise transform over all values of a non-empty input,
+/// returning the result with the input's shape and representation.
+fn map_elements<'a>(
+    env: Env<'a>,
+    data: MatrixInput,
+    f: impl FnOnce(&[f64]) -> Vec<f64>,
+) -> Result<MatrixOutput<'a>, NifError> {
+    let form = data.form();
+    let n_cols = data.shape().map_or(1, |(_, cols)| cols);
+    let values = data.into_values()?;
+    if values.is_empty() {
+        return Err(NifError::invalid("data must not be empty"));
     }
-    Ok(data.iter().map(|&x| (x - mean) / std_dev).collect())
+    form.matrix(env, f(&values), n_cols)
 }
 
 #[rustler::nif(schedule = "DirtyCpu")]
 pub fn silhouette_score(
-    data: Vec<f64>,
+    data: MatrixInput,
     n_features: usize,
     labels: Vec<i32>,
     metric: String,
-) -> Result<f64, String> {
+) -> Result<f64, NifError> {
+    let data = data.into_flat(n_features)?;
     let slices = flat_to_slices(&data, n_features)?;
     let _metric = parse_distance_metric(&metric)?;
     cyanea_ml::silhouette_score(&slices, &labels).map_err(to_nif_error)
 }
 
+/// Jaccard estimate of two bottom-k sketches (the `hashes` of two
+/// `MinHashSketchNif`s), from the smallest hashes of their union.
 #[rustler::nif]
-pub fn minhash_jaccard(sketch_a: Vec<u64>, sketch_b: Vec<u64>) -> Result<f64, String> {
-    if sketch_a.len() != sketch_b.len() {
-        return Err("sketches must have equal length".into());
-    }
-    if sketch_a.is_empty() {
-        return Err("sketches must not be empty".into());
+pub fn minhash_jaccard(sketch_a: Vec<u64>, sketch_b: Vec<u64>) -> Result<f64, NifError> {
+    if sketch_a.is_empty() || sketch_b.is_empty() {
+        return Err(NifError::invalid("sketches must not be empty"));
     }
-    let matches = sketch_a
-        .iter()
-        .zip(sketch_b.iter())
-        .filter(|(a, b)| a == b)
-        .count();
-    Ok(matches as f64 / sketch_a.len() as f64)
+    Ok(crate::sketch::jaccard(&sketch_a, &sketch_b))
+}
+
+// ===========================================================================
+// Async variants (results are sent as {:cyanea_result, ref, result})
+// ===========================================================================
+//
+// t-SNE, UMAP and hierarchical clustering run inside the library without
+// checkpoints, so their `cancel` only skips a job that has not started.
+
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn tsne_async<'a>(
+    env: Env<'a>,
+    data: MatrixInput,
+    n_features: usize,
+    n_components: usize,
+    perplexity: f64,
+    n_iter: usize,
+    cancel: Option<ResourceArc<CancelTokenResource>>,
+) -> Result<Reference<'a>, NifError> {
+    let form = data.form();
+    let data = data.into_flat(n_features)?;
+    let config = tsne_config(n_components, perplexity, n_iter);
+    pool::spawn_with(
+        env,
+        PoolId::Ml,
+        cancel,
+        move |job| {
+            job::run_detached(job, move || {
+                cyanea_ml::tsne(&data, n_features, &config).map_err(to_nif_error)
+            })
+        },
+        move |env, result| Ok(TsneResultNif::new(result, env, form)?.encode(env)),
+    )
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn umap_async<'a>(
+    env: Env<'a>,
+    data: MatrixInput,
+    n_features: usize,
+    n_components: usize,
+    n_neighbors: usize,
+    min_dist: f64,
+    n_epochs: usize,
+    metric: String,
+    seed: u64,
+    cancel: Option<ResourceArc<CancelTokenResource>>,
+) -> Result<Reference<'a>, NifError> {
+    let form = data.form();
+    let data = data.into_flat(n_features)?;
+    let config = umap_config(n_components, n_neighbors, min_dist, n_epochs, &metric, seed)?;
+    pool::spawn_with(
+        env,
+        PoolId::Ml,
+        cancel,
+        move |job| {
+            job::run_detached(job, move || {
+                cyanea_ml::umap(&data, n_features, &config).map_err(to_nif_error)
+            })
+        },
+        move |env, result| Ok(UmapResultNif::new(result, env, form)?.encode(env)),
+    )
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn hierarchical_cluster_async<'a>(
+    env: Env<'a>,
+    data: MatrixInput,
+    n_features: usize,
+    n_clusters: usize,
+    linkage: String,
+    metric: String,
+    cancel: Option<ResourceArc<CancelTokenResource>>,
+) -> Result<Reference<'a>, NifError> {
+    let data = data.into_flat(n_features)?;
+    let linkage = parse_linkage(&linkage)?;
+    let metric = parse_distance_metric(&metric)?;
+    pool::spawn(env, PoolId::Ml, cancel, move |_| {
+        hierarchical(&data, n_features, n_clusters, linkage, metric)
+    })
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn random_forest_fit_async<'a>(
+    env: Env<'a>,
+    data: MatrixInput,
+    n_features: usize,
+    labels: Vec<usize>,
+    n_trees: usize,
+    max_depth: usize,
+    seed: u64,
+    cancel: Option<ResourceArc<CancelTokenResource>>,
+) -> Result<Reference<'a>, NifError> {
+    let data = data.into_flat(n_features)?;
+    pool::spawn(env, PoolId::Ml, cancel, move |job| {
+        RandomForestResource::fit(data, n_features, labels, n_trees, max_depth, seed, job)
+            .map(ResourceArc::new)
+    })
 }
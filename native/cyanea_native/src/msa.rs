@@ -0,0 +1,1356 @@
+//! Progressive multiple alignment under any [`ScoringScheme`].
+//!
+//! Sequences are clustered into a guide tree (UPGMA or neighbor joining)
+//! from shared k-mer distances and merged up the tree by profile-profile
+//! alignment.  Each refinement round cuts the tree at every edge, realigns
+//! the two halves and keeps the result when the sum-of-pairs score goes up.
+//!
+//! The guide tree is built by `cyanea_phylo` from the k-mer distance
+//! matrix.  End gaps are free both in profile alignment and in the
+//! sum-of-pairs score.
+//!
+//! New sequences can also be added to an existing alignment without
+//! realigning it: each is aligned to the alignment's profile, so existing
+//! columns stay as they are and only insertions add columns.  Two
+//! alignments merge by aligning their profiles.
+
+use crate::bridge::{
+    MsaAddOptionsNif, MsaFileNif, MsaMergeNif, MsaOptionsNif, MsaReportNif, MsaResultNif,
+    MsaSourceNif, NifError,
+};
+use crate::job::{self, CancelTokenResource, JobState, ProgressTarget};
+use crate::pool::{self, PoolId};
+use crate::scoring::{ScoringScheme, ScoringSchemeResource};
+use crate::to_nif_error;
+use rustler::{Env, Reference, ResourceArc};
+use std::collections::HashMap;
+
+/// Gap symbol written into aligned rows; `.` is also read as a gap.
+pub(crate) const GAP: u8 = b'-';
+
+const AMINO_ACIDS: &[u8] = b"ACDEFGHIKLMNPQRSTVWY";
+
+pub(crate) fn is_gap(symbol: u8) -> bool {
+    symbol == b'-' || symbol == b'.'
+}
+
+/// Whether the scheme scores all 20 amino acids; otherwise sequences are
+/// treated as nucleotides.
+fn is_protein(scheme: &ScoringScheme) -> bool {
+    AMINO_ACIDS.iter().all(|&aa| scheme.covers(aa))
+}
+
+// ===========================================================================
+// Profiles
+// ===========================================================================
+
+/// One alignment column: the frequency of each residue over all rows (gaps
+/// count towards the total), the fraction of rows with a residue, and, for
+/// every matrix symbol, the expected score of aligning that symbol against
+/// the column.
+struct Column {
+    residues: Vec<(u8, f64)>,
+    occupancy: f64,
+    expected: Vec<f64>,
+}
+
+impl Column {
+    fn score(&self, other: &Column) -> f64 {
+        other.residues.iter().map(|&(code, f)| f * self.expected[code as usize]).sum()
+    }
+}
+
+/// A block of aligned rows with its column frequencies.
+pub(crate) struct Profile {
+    /// Caller's index for each row, in row order.
+    pub members: Vec<usize>,
+    pub rows: Vec<Vec<u8>>,
+    columns: Vec<Column>,
+}
+
+impl Profile {
+    /// Profile of equal-length aligned rows; every non-gap symbol must be in
+    /// the scheme's matrix.
+    pub(crate) fn new(members: Vec<usize>, mut rows: Vec<Vec<u8>>, scheme: &ScoringScheme) -> Result<Self, NifError> {
+        let width = rows.first().map_or(0, Vec::len);
+        if let Some(i) = rows.iter().position(|row| row.len() != width) {
+            return Err(NifError::invalid(format!(
+                "aligned row {i} has {} columns, expected {width}",
+                rows[i].len()
+            )));
+        }
+        let n_symbols = scheme.alphabet.len();
+        let total = rows.len() as f64;
+        let mut counts = vec![0u32; n_symbols];
+        let mut columns = Vec::with_capacity(width);
+        for c in 0..width {
+            counts.fill(0);
+            for (i, row) in rows.iter_mut().enumerate() {
+                if is_gap(row[c]) {
+                    row[c] = GAP;
+                    continue;
+                }
+                let code = scheme.code(row[c]).ok_or_else(|| {
+                    NifError::invalid(format!(
+                        "symbol {:?} in row {i}, column {c} is not in matrix {}",
+                        row[c] as char, scheme.name
+                    ))
+                })?;
+                counts[code as usize] += 1;
+            }
+            let residues: Vec<(u8, f64)> = counts
+                .iter()
+                .enumerate()
+                .filter(|&(_, &n)| n > 0)
+                .map(|(code, &n)| (code as u8, n as f64 / total))
+                .collect();
+            let occupancy = residues.iter().map(|&(_, f)| f).sum();
+            let expected = (0..n_symbols as u8)
+                .map(|b| residues.iter().map(|&(a, f)| f * scheme.score(a, b) as f64).sum())
+                .collect();
+            columns.push(Column { residues, occupancy, expected });
+        }
+        Ok(Self { members, rows, columns })
+    }
+
+    pub(crate) fn width(&self) -> usize {
+        self.columns.len()
+    }
+
+    /// Most frequent residue code of every column that is at least half
+    /// occupied, with the columns' indices.
+    fn consensus(&self) -> (Vec<usize>, Vec<u8>) {
+        self.columns
+            .iter()
+            .enumerate()
+            .filter(|(_, column)| column.occupancy >= 0.5)
+            .filter_map(|(c, column)| {
+                let &(code, _) = column.residues.iter().max_by(|x, y| x.1.total_cmp(&y.1))?;
+                Some((c, code))
+            })
+            .unzip()
+    }
+
+    /// Merge two profiles along an alignment path; see [`interleave`].
+    pub(crate) fn merge(a: Profile, b: Profile, path: &[Step], scheme: &ScoringScheme) -> Result<Self, NifError> {
+        let rows = interleave(&a.rows, &b.rows, path);
+        let mut members = a.members;
+        members.extend(b.members);
+        Self::new(members, rows, scheme)
+    }
+
+    /// Rows back in caller order, assuming members are `0..n`.
+    pub(crate) fn into_rows(self) -> Vec<Vec<u8>> {
+        let mut rows = vec![Vec::new(); self.rows.len()];
+        for (member, row) in self.members.into_iter().zip(self.rows) {
+            rows[member] = row;
+        }
+        rows
+    }
+}
+
+/// Rows of `a` then `b`, gapped along an alignment path.  Existing
+/// columns of both are kept intact.
+fn interleave(a: &[Vec<u8>], b: &[Vec<u8>], path: &[Step]) -> Vec<Vec<u8>> {
+    let gapped = |rows: &[Vec<u8>], take: fn(Step) -> bool| -> Vec<Vec<u8>> {
+        rows.iter()
+            .map(|row| {
+                let mut source = row.iter();
+                path.iter()
+                    .map(|&step| if take(step) { *source.next().unwrap_or(&GAP) } else { GAP })
+                    .collect()
+            })
+            .collect()
+    };
+    let mut rows = gapped(a, |step| step != Step::OnlyB);
+    rows.extend(gapped(b, |step| step != Step::OnlyA));
+    rows
+}
+
+/// How one column of a merged alignment is formed.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum Step {
+    Both,
+    OnlyA,
+    OnlyB,
+}
+
+// Traceback bits, as in `scoring::align`.
+const FROM_DIAG: u8 = 0;
+const FROM_E: u8 = 1;
+const FROM_F: u8 = 2;
+const E_OPENED: u8 = 4;
+const F_OPENED: u8 = 8;
+
+/// Blocks of the DP with at most this many cells are traced back directly;
+/// larger ones are split at their middle row.
+const TRACE_CELLS: usize = 1 << 24;
+
+/// Gotoh alignment of two profiles with free end gaps.  `E` gaps consume
+/// columns of `b`, `F` gaps columns of `a`.  A gap opposite a column is
+/// scaled by the column's occupancy, so skipping columns that are mostly
+/// gaps already (another sequence's insertion) is cheap.
+///
+/// Memory stays linear in the profile widths: a first pass finds the best
+/// start and end, then the path between them is found by Hirschberg's
+/// divide and conquer, each pass carrying the column (and gap state) where
+/// the best path leaves the block's middle row.
+///
+/// With a `band`, only cells within `band` columns of a chain of k-mer
+/// seeds shared by the two consensus sequences are filled (see
+/// [`band_spans`]), which keeps time linear in the longer profile.  If the
+/// chain steers the band off every path between the best start and end,
+/// the alignment is redone without the band.
+/// Returns the score and the path from the first column to the last.
+pub(crate) fn align_profiles(
+    a: &Profile,
+    b: &Profile,
+    scheme: &ScoringScheme,
+    band: Option<usize>,
+) -> Result<(f64, Vec<Step>), NifError> {
+    let (n, m) = (a.width(), b.width());
+    if n == 0 || m == 0 {
+        let mut path = vec![Step::OnlyA; n];
+        path.extend(std::iter::repeat_n(Step::OnlyB, m));
+        return Ok((0.0, path));
+    }
+    let seed_k = if is_protein(scheme) { 5 } else { 12 };
+    let banded = Aligner {
+        a,
+        b,
+        open: scheme.gap_open as f64,
+        extend: scheme.gap_extend as f64,
+        spans: band_spans(a, b, seed_k, band),
+    };
+    if let Some(found) = banded.align() {
+        return Ok(found);
+    }
+    let full = Aligner { spans: band_spans(a, b, seed_k, None), ..banded };
+    full.align()
+        .ok_or_else(|| NifError::internal("profile alignment found no path through the DP"))
+}
+
+/// Columns of `b` (`0..=m`) filled in each row of the DP: all of them
+/// without a band.  With one, `band` columns either side of the chain of
+/// seeds from [`seed_chain`], followed along its diagonal past the first
+/// and last seed, so a sequence that is offset or trimmed relative to the
+/// other stays in the band.  Without seeds the band follows the line from
+/// the first cell to the last.  Each row also reaches the next row's
+/// centre, so the band stays connected where the chain jumps.
+fn band_spans(a: &Profile, b: &Profile, seed_k: usize, band: Option<usize>) -> Vec<(usize, usize)> {
+    let (n, m) = (a.width(), b.width());
+    let Some(band) = band else {
+        return vec![(0, m); n + 1];
+    };
+    let band = band.max(1);
+    let chain = seed_chain(a, b, seed_k);
+    let along = |i: usize, (ci, cj): (usize, usize)| (i + cj).saturating_sub(ci).min(m);
+    let centres: Vec<usize> = (0..=n)
+        .map(|i| {
+            let t = chain.partition_point(|&(ci, _)| ci <= i);
+            match (t.checked_sub(1).map(|t| chain[t]), chain.get(t)) {
+                (None, None) => (i * m + n / 2) / n,
+                (None, Some(&next)) => along(i, next),
+                (Some(last), None) => along(i, last),
+                (Some((i0, j0)), Some(&(i1, j1))) => j0 + (i - i0) * (j1 - j0) / (i1 - i0),
+            }
+        })
+        .collect();
+    (0..=n)
+        .map(|i| {
+            let reach = centres[i].max(centres[(i + 1).min(n)]);
+            (centres[i].saturating_sub(band), (reach + band).min(m))
+        })
+        .collect()
+}
+
+/// Longest chain of k-mers shared by the consensus sequences of `a` and
+/// `b`, increasing in both, as the DP cells of their first residues.
+/// K-mers repeated in `b` are skipped.
+fn seed_chain(a: &Profile, b: &Profile, k: usize) -> Vec<(usize, usize)> {
+    let (a_columns, a_seq) = a.consensus();
+    let (b_columns, b_seq) = b.consensus();
+    if a_seq.len() < k || b_seq.len() < k {
+        return Vec::new();
+    }
+    let mut unique: HashMap<&[u8], Option<usize>> = HashMap::new();
+    for (p, kmer) in b_seq.windows(k).enumerate() {
+        unique.entry(kmer).and_modify(|hit| *hit = None).or_insert(Some(p));
+    }
+    let seeds: Vec<(usize, usize)> = a_seq
+        .windows(k)
+        .enumerate()
+        .filter_map(|(p, kmer)| {
+            let q = unique.get(kmer).copied().flatten()?;
+            Some((a_columns[p] + 1, b_columns[q] + 1))
+        })
+        .collect();
+
+    // Seeds already increase in `a`; patience sorting finds the longest run
+    // increasing in `b`.  `tails[l]` ends the best chain of `l + 1` seeds.
+    let mut tails: Vec<usize> = Vec::new();
+    let mut prev = vec![None; seeds.len()];
+    for (x, &(_, j)) in seeds.iter().enumerate() {
+        let l = tails.partition_point(|&t| seeds[t].1 < j);
+        prev[x] = l.checked_sub(1).map(|l| tails[l]);
+        if l == tails.len() {
+            tails.push(x);
+        } else {
+            tails[l] = x;
+        }
+    }
+    let mut chain = Vec::with_capacity(tails.len());
+    let mut at = tails.last().copied();
+    while let Some(x) = at {
+        chain.push(seeds[x]);
+        at = prev[x];
+    }
+    chain.reverse();
+    chain
+}
+
+/// Rows `i0..=i1` and columns `j0..=j1` of the DP.
+#[derive(Clone, Copy)]
+struct Block {
+    i0: usize,
+    j0: usize,
+    i1: usize,
+    j1: usize,
+}
+
+/// Where a pass over a block starts.
+#[derive(Clone, Copy)]
+enum Start {
+    /// Anywhere in the first row or column, for free leading end gaps.
+    Free,
+    /// At the block's first cell, inside an `F` gap that may be extended
+    /// when `in_f`.
+    Corner { in_f: bool },
+}
+
+/// Labels reaching a block's last cell in `H` and in `F`, and, from a
+/// free start, the best score, cell and label in the last row or column.
+struct Reached {
+    h_label: usize,
+    f_label: usize,
+    best: (f64, usize, usize, usize),
+}
+
+struct Aligner<'p> {
+    a: &'p Profile,
+    b: &'p Profile,
+    open: f64,
+    extend: f64,
+    /// Allowed columns in each row, from [`band_spans`].
+    spans: Vec<(usize, usize)>,
+}
+
+impl Aligner<'_> {
+    /// Score and path of the best alignment within the spans, or `None`
+    /// when the spans hold no path from its start to its end.
+    fn align(&self) -> Option<(f64, Vec<Step>)> {
+        let (n, m) = (self.a.width(), self.b.width());
+        // Leading end gaps, the path between the start and end, trailing
+        // end gaps.
+        let (score, (i0, j0), (i1, j1)) = self.locate();
+        if !score.is_finite() {
+            return None;
+        }
+        let mut path = Vec::with_capacity(n + m);
+        path.extend(std::iter::repeat_n(Step::OnlyB, j0));
+        path.extend(std::iter::repeat_n(Step::OnlyA, i0));
+        self.solve(Block { i0, j0, i1, j1 }, false, false, &mut path)?;
+        path.extend(std::iter::repeat_n(Step::OnlyB, m - j1));
+        path.extend(std::iter::repeat_n(Step::OnlyA, n - i1));
+        Some((score, path))
+    }
+
+    /// Columns filled in row `i` of a block, if any.
+    fn row_span(&self, block: &Block, i: usize) -> Option<(usize, usize)> {
+        let (lo, hi) = self.spans[i];
+        let (lo, hi) = (lo.max(block.j0), hi.min(block.j1));
+        (lo <= hi).then_some((lo, hi))
+    }
+
+    fn cells(&self, block: &Block) -> usize {
+        (block.i0..=block.i1)
+            .filter_map(|i| self.row_span(block, i))
+            .map(|(lo, hi)| hi - lo + 1)
+            .sum()
+    }
+
+    /// Score and first and last cells of the best alignment; leading and
+    /// trailing end gaps are free.
+    fn locate(&self) -> (f64, (usize, usize), (usize, usize)) {
+        let (n, m) = (self.a.width(), self.b.width());
+        let reached = self.fill(&Block { i0: 0, j0: 0, i1: n, j1: m }, Start::Free, None, None);
+        let (score, i, j, start) = reached.best;
+        (score, (start / (m + 1), start % (m + 1)), (i, j))
+    }
+
+    /// Append the best path through a block from its first cell to its
+    /// last, starting and ending inside an `F` gap as given.  `None` when
+    /// the path would leave the spans.
+    fn solve(
+        &self,
+        block: Block,
+        start_in_f: bool,
+        end_in_f: bool,
+        path: &mut Vec<Step>,
+    ) -> Option<()> {
+        if block.i1 - block.i0 < 2 || self.cells(&block) <= TRACE_CELLS {
+            return self.trace_block(&block, start_in_f, end_in_f, path);
+        }
+        let mid = (block.i0 + block.i1) / 2;
+        let reached = self.fill(&block, Start::Corner { in_f: start_in_f }, Some(mid), None);
+        let crossing = if end_in_f { reached.f_label } else { reached.h_label };
+        let (j, mid_in_f) = (crossing / 2, crossing % 2 == 1);
+        if j < block.j0 || j > block.j1 {
+            return None;
+        }
+        self.solve(Block { i1: mid, j1: j, ..block }, start_in_f, mid_in_f, path)?;
+        self.solve(Block { i0: mid, j0: j, ..block }, mid_in_f, end_in_f, path)
+    }
+
+    fn trace_block(
+        &self,
+        block: &Block,
+        start_in_f: bool,
+        end_in_f: bool,
+        path: &mut Vec<Step>,
+    ) -> Option<()> {
+        let spans: Vec<Option<(usize, usize)>> =
+            (block.i0..=block.i1).map(|i| self.row_span(block, i)).collect();
+        let mut offsets = Vec::with_capacity(spans.len());
+        let mut cells = 0;
+        for span in &spans {
+            offsets.push(cells);
+            cells += span.map_or(0, |(lo, hi)| hi - lo + 1);
+        }
+        let mut trace = vec![0u8; cells];
+        self.fill(block, Start::Corner { in_f: start_in_f }, None, Some(&mut trace));
+
+        let (mut i, mut j) = (block.i1, block.j1);
+        let mut state = if end_in_f { FROM_F } else { FROM_DIAG };
+        let mut steps = Vec::new();
+        while (i, j) != (block.i0, block.j0) {
+            let row = i - block.i0;
+            let (lo, hi) = spans[row]?;
+            if j < lo || j > hi {
+                return None;
+            }
+            let bits = trace[offsets[row] + j - lo];
+            if state == FROM_DIAG {
+                state = bits & 3;
+                if state == FROM_DIAG {
+                    if i == block.i0 || j == block.j0 {
+                        return None;
+                    }
+                    steps.push(Step::Both);
+                    i -= 1;
+                    j -= 1;
+                    continue;
+                }
+            }
+            if state == FROM_E {
+                if j == block.j0 {
+                    return None;
+                }
+                steps.push(Step::OnlyB);
+                if bits & E_OPENED != 0 {
+                    state = FROM_DIAG;
+                }
+                j -= 1;
+            } else {
+                if i == block.i0 {
+                    return None;
+                }
+                steps.push(Step::OnlyA);
+                if bits & F_OPENED != 0 {
+                    state = FROM_DIAG;
+                }
+                i -= 1;
+            }
+        }
+        path.extend(steps.into_iter().rev());
+        Some(())
+    }
+
+    /// One pass over a block in linear space.  Every cell carries a label
+    /// from the cell its best path comes from: its start cell (`i * (m + 1)
+    /// + j`) from a free start, and below the `split` row the column and
+    /// gap state (`2 * j + in_f`) where the path left that row.
+    fn fill(
+        &self,
+        block: &Block,
+        start: Start,
+        split: Option<usize>,
+        mut trace: Option<&mut [u8]>,
+    ) -> Reached {
+        let (n, m) = (self.a.width(), self.b.width());
+        let width = block.j1 - block.j0 + 1;
+        let mut h_prev = vec![f64::NEG_INFINITY; width];
+        let mut f_prev = vec![f64::NEG_INFINITY; width];
+        let mut h_row = vec![f64::NEG_INFINITY; width];
+        let mut f_row = vec![f64::NEG_INFINITY; width];
+        let (mut hl_prev, mut fl_prev) = (vec![0; width], vec![0; width]);
+        let (mut hl_row, mut fl_row) = (vec![0; width], vec![0; width]);
+        let mut prev_span = None;
+        let mut offset = 0;
+        let mut best = (f64::NEG_INFINITY, n, m, 0);
+
+        for i in block.i0..=block.i1 {
+            let Some((lo, hi)) = self.row_span(block, i) else {
+                prev_span = None;
+                continue;
+            };
+            let above =
+                |row: &[f64], j: usize| cell(row, prev_span, block.j0, j, f64::NEG_INFINITY);
+            let label_above = |row: &[usize], j: usize| cell(row, prev_span, block.j0, j, 0);
+            let (mut e, mut e_label) = (f64::NEG_INFINITY, 0);
+            for j in lo..=hi {
+                let k = j - block.j0;
+                let origin = match start {
+                    Start::Free => i == 0 || j == 0,
+                    Start::Corner { .. } => i == block.i0 && j == block.j0,
+                };
+                if origin {
+                    h_row[k] = 0.0;
+                    f_row[k] = match start {
+                        Start::Corner { in_f: true } => 0.0,
+                        _ => f64::NEG_INFINITY,
+                    };
+                    (hl_row[k], fl_row[k]) = (i * (m + 1) + j, i * (m + 1) + j);
+                    (e, e_label) = (f64::NEG_INFINITY, 0);
+                    continue;
+                }
+
+                let mut bits = 0;
+                if j > lo {
+                    let weight = self.b.columns[j - 1].occupancy;
+                    let e_open = h_row[k - 1] + self.open * weight;
+                    let e_ext = e + self.extend * weight;
+                    if e_open >= e_ext {
+                        bits |= E_OPENED;
+                        (e, e_label) = (e_open, hl_row[k - 1]);
+                    } else {
+                        e = e_ext;
+                    }
+                } else {
+                    (e, e_label) = (f64::NEG_INFINITY, 0);
+                }
+                let (mut h, mut from, mut label) = (f64::NEG_INFINITY, FROM_DIAG, 0);
+                if i > block.i0 {
+                    let column = &self.a.columns[i - 1];
+                    let (f_open, f_ext) = (
+                        above(&h_prev, j) + self.open * column.occupancy,
+                        above(&f_prev, j) + self.extend * column.occupancy,
+                    );
+                    if f_open >= f_ext {
+                        bits |= F_OPENED;
+                        (f_row[k], fl_row[k]) = (f_open, label_above(&hl_prev, j));
+                    } else {
+                        (f_row[k], fl_row[k]) = (f_ext, label_above(&fl_prev, j));
+                    }
+                    if j > block.j0 {
+                        h = above(&h_prev, j - 1) + column.score(&self.b.columns[j - 1]);
+                        label = label_above(&hl_prev, j - 1);
+                    }
+                } else {
+                    f_row[k] = f64::NEG_INFINITY;
+                }
+                if e > h {
+                    (h, from, label) = (e, FROM_E, e_label);
+                }
+                if f_row[k] > h {
+                    (h, from, label) = (f_row[k], FROM_F, fl_row[k]);
+                }
+                (h_row[k], hl_row[k]) = (h, label);
+                if let Some(trace) = trace.as_deref_mut() {
+                    trace[offset + j - lo] = bits | from;
+                }
+                if (i == n || j == m) && h > best.0 {
+                    best = (h, i, j, label);
+                }
+            }
+            if split == Some(i) {
+                for j in lo..=hi {
+                    (hl_row[j - block.j0], fl_row[j - block.j0]) = (2 * j, 2 * j + 1);
+                }
+            }
+            offset += hi - lo + 1;
+            prev_span = Some((lo, hi));
+            std::mem::swap(&mut h_prev, &mut h_row);
+            std::mem::swap(&mut f_prev, &mut f_row);
+            std::mem::swap(&mut hl_prev, &mut hl_row);
+            std::mem::swap(&mut fl_prev, &mut fl_row);
+        }
+        Reached {
+            h_label: cell(&hl_prev, prev_span, block.j0, block.j1, 0),
+            f_label: cell(&fl_prev, prev_span, block.j0, block.j1, 0),
+            best,
+        }
+    }
+}
+
+/// Entry `j` of a DP row holding columns `j0..` whose filled cells are
+/// `span`, or `missing` outside it.
+fn cell<T: Copy>(row: &[T], span: Option<(usize, usize)>, j0: usize, j: usize, missing: T) -> T {
+    match span {
+        Some((lo, hi)) if lo <= j && j <= hi => row[j - j0],
+        _ => missing,
+    }
+}
+
+/// Drop columns that are gaps in every row.
+fn strip_gap_columns(rows: &mut [Vec<u8>]) {
+    let width = rows.first().map_or(0, Vec::len);
+    let keep: Vec<bool> = (0..width).map(|c| rows.iter().any(|row| !is_gap(row[c]))).collect();
+    for row in rows {
+        let mut column = 0;
+        row.retain(|_| {
+            column += 1;
+            keep[column - 1]
+        });
+    }
+}
+
+/// Sum over all row pairs of the pairwise alignment score, with affine
+/// gaps and free end gaps.
+fn sp_score(rows: &[Vec<u8>], scheme: &ScoringScheme) -> i64 {
+    const NONE: u8 = u8::MAX;
+    let codes: Vec<Vec<u8>> = rows
+        .iter()
+        .map(|row| row.iter().map(|&b| scheme.code(b).unwrap_or(NONE)).collect())
+        .collect();
+    let (open, extend) = (scheme.gap_open as i64, scheme.gap_extend as i64);
+    let mut total = 0;
+    for (i, x) in codes.iter().enumerate() {
+        for y in &codes[i + 1..] {
+            let both = |c: &usize| x[*c] != NONE && y[*c] != NONE;
+            let Some(first) = (0..x.len()).find(both) else { continue };
+            let last = (0..x.len()).rfind(both).unwrap_or(first);
+            // 0: residues, 1: gap in x, 2: gap in y
+            let mut state = 0;
+            for c in first..=last {
+                let gap_state = match (x[c], y[c]) {
+                    (NONE, NONE) => continue,
+                    (NONE, _) => 1,
+                    (_, NONE) => 2,
+                    (a, b) => {
+                        total += scheme.score(a, b) as i64;
+                        state = 0;
+                        continue;
+                    }
+                };
+                total += if state == gap_state { extend } else { open };
+                state = gap_state;
+            }
+        }
+    }
+    total
+}
+
+// ===========================================================================
+// Guide trees
+// ===========================================================================
+
+/// Tree-building method for the guide tree.
+#[derive(Clone, Copy)]
+enum GuideMethod {
+    Upgma,
+    NeighborJoining,
+}
+
+fn parse_guide_method(method: &str) -> Result<GuideMethod, NifError> {
+    match method {
+        "upgma" => Ok(GuideMethod::Upgma),
+        "nj" => Ok(GuideMethod::NeighborJoining),
+        _ => Err(NifError::invalid(format!("unknown guide tree method: {method} (expected upgma or nj)"))),
+    }
+}
+
+impl GuideMethod {
+    fn build(
+        self,
+        dm: &cyanea_phylo::DistanceMatrix,
+        names: &[String],
+    ) -> Result<cyanea_phylo::PhyloTree, NifError> {
+        match self {
+            GuideMethod::Upgma => cyanea_phylo::upgma(dm, names),
+            GuideMethod::NeighborJoining => cyanea_phylo::neighbor_joining(dm, names),
+        }
+        .map_err(to_nif_error)
+    }
+}
+
+/// Topology of a guide tree.  Nodes `0..n` are the leaves, in input order,
+/// and every internal node comes after its children; the root is last.
+/// Internal nodes may have more than two children (an unrooted neighbor
+/// joining tree has three at its root).
+struct GuideTree {
+    children: Vec<Vec<usize>>,
+}
+
+impl GuideTree {
+    /// Read the topology back from the Newick of a tree whose leaves are
+    /// named by their index, and rewrite that Newick with `names` as the
+    /// leaf labels.
+    fn from_newick(newick: &str, names: &[String]) -> Result<(Self, String), NifError> {
+        let n = names.len();
+        let mut tree = Self { children: vec![Vec::new(); n] };
+        let mut seen = vec![false; n];
+        let mut leaves = Vec::with_capacity(n);
+        let mut reader = NewickReader { text: newick.as_bytes(), pos: 0 };
+        tree.read_node(&mut reader, &mut seen, &mut leaves)?;
+        reader.skip_whitespace();
+        if reader.bump() != Some(b';') || seen.contains(&false) {
+            return Err(NifError::internal(format!("unexpected guide tree: {newick}")));
+        }
+        let mut named = String::with_capacity(newick.len());
+        let mut copied = 0;
+        for (start, end, leaf) in leaves {
+            named.push_str(&newick[copied..start]);
+            named.push_str(&newick_label(&names[leaf]));
+            copied = end;
+        }
+        named.push_str(&newick[copied..]);
+        Ok((tree, named))
+    }
+
+    /// Read one node; leaves are recorded in `leaves` as the byte range of
+    /// their label and their index.
+    fn read_node(
+        &mut self,
+        reader: &mut NewickReader,
+        seen: &mut [bool],
+        leaves: &mut Vec<(usize, usize, usize)>,
+    ) -> Result<usize, NifError> {
+        let unexpected = || NifError::internal("unexpected guide tree Newick");
+        reader.skip_whitespace();
+        let node = if reader.peek() == Some(b'(') {
+            reader.bump();
+            let mut children = Vec::new();
+            loop {
+                children.push(self.read_node(reader, seen, leaves)?);
+                reader.skip_whitespace();
+                match reader.bump() {
+                    Some(b',') => {}
+                    Some(b')') => break,
+                    _ => return Err(unexpected()),
+                }
+            }
+            // Internal labels (support values) carry nothing we need.
+            reader.label();
+            self.children.push(children);
+            self.children.len() - 1
+        } else {
+            let start = reader.pos;
+            let leaf: usize = reader.label().parse().map_err(|_| unexpected())?;
+            if leaf >= seen.len() || std::mem::replace(&mut seen[leaf], true) {
+                return Err(unexpected());
+            }
+            leaves.push((start, reader.pos, leaf));
+            leaf
+        };
+        reader.skip_whitespace();
+        if reader.peek() == Some(b':') {
+            reader.bump();
+            reader.label();
+        }
+        Ok(node)
+    }
+
+    fn root(&self) -> usize {
+        self.children.len() - 1
+    }
+
+    /// Leaves under every node.
+    fn leaf_sets(&self) -> Vec<Vec<usize>> {
+        let mut sets: Vec<Vec<usize>> = Vec::with_capacity(self.children.len());
+        for (i, children) in self.children.iter().enumerate() {
+            let set = if children.is_empty() {
+                vec![i]
+            } else {
+                children.iter().flat_map(|&c| sets[c].iter().copied()).collect()
+            };
+            sets.push(set);
+        }
+        sets
+    }
+}
+
+/// Quote a leaf name when it has Newick punctuation or whitespace.
+fn newick_label(name: &str) -> String {
+    if !name.is_empty() && !name.chars().any(|c| c.is_whitespace() || "()[]':;,".contains(c)) {
+        return name.to_string();
+    }
+    format!("'{}'", name.replace('\'', "''"))
+}
+
+struct NewickReader<'a> {
+    text: &'a [u8],
+    pos: usize,
+}
+
+impl NewickReader<'_> {
+    fn peek(&self) -> Option<u8> {
+        self.text.get(self.pos).copied()
+    }
+
+    fn bump(&mut self) -> Option<u8> {
+        let c = self.peek();
+        self.pos += usize::from(c.is_some());
+        c
+    }
+
+    fn skip_whitespace(&mut self) {
+        while self.peek().is_some_and(|c| c.is_ascii_whitespace()) {
+            self.pos += 1;
+        }
+    }
+
+    /// An unquoted label or branch length, up to the next punctuation.
+    fn label(&mut self) -> String {
+        self.skip_whitespace();
+        let start = self.pos;
+        while self.peek().is_some_and(|c| !b"(),:;'[".contains(&c) && !c.is_ascii_whitespace()) {
+            self.pos += 1;
+        }
+        String::from_utf8_lossy(&self.text[start..self.pos]).into_owned()
+    }
+}
+
+/// Fractional common k-mer distance (Edgar 2004): one minus the shared
+/// k-mer count over the most the shorter sequence could share.
+fn kmer_distances(encoded: &[Vec<u8>], n_symbols: usize, k: usize) -> Vec<Vec<f64>> {
+    let kmers: Vec<Vec<u64>> = encoded
+        .iter()
+        .map(|seq| {
+            let mut ids: Vec<u64> = seq
+                .windows(k)
+                .map(|w| w.iter().fold(0u64, |id, &code| id * n_symbols as u64 + code as u64))
+                .collect();
+            ids.sort_unstable();
+            ids
+        })
+        .collect();
+    let n = encoded.len();
+    let mut d = vec![vec![0.0; n]; n];
+    for i in 0..n {
+        for j in i + 1..n {
+            let (x, y) = (&kmers[i], &kmers[j]);
+            let (mut a, mut b, mut shared) = (0, 0, 0);
+            while a < x.len() && b < y.len() {
+                match x[a].cmp(&y[b]) {
+                    std::cmp::Ordering::Less => a += 1,
+                    std::cmp::Ordering::Greater => b += 1,
+                    std::cmp::Ordering::Equal => {
+                        shared += 1;
+                        a += 1;
+                        b += 1;
+                    }
+                }
+            }
+            let possible = x.len().min(y.len());
+            let distance = if possible == 0 { 1.0 } else { 1.0 - shared as f64 / possible as f64 };
+            d[i][j] = distance;
+            d[j][i] = distance;
+        }
+    }
+    d
+}
+
+// ===========================================================================
+// Column statistics
+// ===========================================================================
+
+/// Per-column Shannon entropy (bits, over residues only), conservation and
+/// the majority-rule consensus.  Conservation is `1 - H / log2(K)` scaled
+/// by the fraction of rows without a gap, with `K` = 20 for protein and 4
+/// for nucleotides; columns that are mostly gaps are left out of the
+/// consensus.
+fn column_stats(rows: &[Vec<u8>], protein: bool) -> (Vec<f64>, Vec<f64>, Vec<u8>) {
+    let width = rows.first().map_or(0, Vec::len);
+    let max_entropy = if protein { 20f64.log2() } else { 2.0 };
+    let n = rows.len() as f64;
+    let mut counts = [0u32; 256];
+    let (mut conservation, mut entropy) = (Vec::with_capacity(width), Vec::with_capacity(width));
+    let mut consensus = Vec::with_capacity(width);
+    for c in 0..width {
+        counts.fill(0);
+        let mut residues = 0;
+        for row in rows {
+            if !is_gap(row[c]) {
+                counts[row[c].to_ascii_uppercase() as usize] += 1;
+                residues += 1;
+            }
+        }
+        if residues == 0 {
+            conservation.push(0.0);
+            entropy.push(0.0);
+            continue;
+        }
+        let h: f64 = counts
+            .iter()
+            .filter(|&&count| count > 0)
+            .map(|&count| {
+                let p = count as f64 / residues as f64;
+                -p * p.log2()
+            })
+            .sum();
+        entropy.push(h);
+        conservation.push((1.0 - h / max_entropy).max(0.0) * residues as f64 / n);
+        if residues as f64 * 2.0 >= n {
+            let (symbol, _) = counts
+                .iter()
+                .enumerate()
+                .fold((0, 0), |best, (b, &count)| if count > best.1 { (b, count) } else { best });
+            consensus.push(symbol as u8);
+        }
+    }
+    (conservation, entropy, consensus)
+}
+
+// ===========================================================================
+// Progressive alignment
+// ===========================================================================
+
+fn msa_report(
+    sequences: &[Vec<u8>],
+    names: &[String],
+    scheme: &ScoringScheme,
+    options: &MsaOptionsNif,
+    job: &JobState,
+) -> Result<MsaReportNif, NifError> {
+    let method = parse_guide_method(&options.guide_tree)?;
+    if sequences.is_empty() {
+        return Err(NifError::invalid("at least one sequence required"));
+    }
+    if !names.is_empty() && names.len() != sequences.len() {
+        return Err(NifError::invalid(format!(
+            "{} names given for {} sequences",
+            names.len(),
+            sequences.len()
+        )));
+    }
+    let names: Vec<String> = if names.is_empty() {
+        (1..=sequences.len()).map(|i| format!("seq{i}")).collect()
+    } else {
+        names.to_vec()
+    };
+    let encoded = sequences
+        .iter()
+        .enumerate()
+        .map(|(i, seq)| {
+            scheme
+                .encode(seq)
+                .map_err(|e| NifError::invalid(format!("sequence {i}: {}", e.message)))
+        })
+        .collect::<Result<Vec<_>, _>>()?;
+
+    let protein = is_protein(scheme);
+    let d = kmer_distances(&encoded, scheme.alphabet.len(), if protein { 3 } else { 6 });
+    let n = sequences.len();
+    let (tree, guide_tree) = if n == 1 {
+        (GuideTree { children: vec![Vec::new()] }, format!("{};", newick_label(&names[0])))
+    } else {
+        // The tree is built with leaves named by index, so the topology
+        // doesn't depend on how names are quoted; the names go in after.
+        let dm = crate::phylo::to_distance_matrix(&d);
+        let indices: Vec<String> = (0..n).map(|i| i.to_string()).collect();
+        let topology = cyanea_phylo::write_newick(&method.build(&dm, &indices)?);
+        GuideTree::from_newick(&topology, &names)?
+    };
+    let leaf_sets = tree.leaf_sets();
+
+    // Progress counts merges, then refinement edges.
+    let edges = tree.children.len() - 1;
+    let total_steps = (n - 1 + options.refine_rounds * edges).max(1) as f64;
+    let mut done = 0;
+
+    let mut profiles: Vec<Option<Profile>> = Vec::with_capacity(tree.children.len());
+    for (i, seq) in sequences.iter().enumerate() {
+        profiles.push(Some(Profile::new(vec![i], vec![seq.clone()], scheme)?));
+    }
+    for children in &tree.children[n..] {
+        let mut merged = profiles[children[0]].take().expect("child aligned");
+        for &child in &children[1..] {
+            let b = profiles[child].take().expect("child aligned");
+            let (_, path) = align_profiles(&merged, &b, scheme, None)?;
+            merged = Profile::merge(merged, b, &path, scheme)?;
+            job.check()?;
+            done += 1;
+            job.set_progress(done as f64 / total_steps);
+        }
+        profiles.push(Some(merged));
+    }
+    let mut rows = profiles.pop().flatten().expect("root aligned").into_rows();
+    let mut sp = sp_score(&rows, scheme);
+
+    for _ in 0..options.refine_rounds {
+        let mut improved = false;
+        for (node, leaves) in leaf_sets.iter().enumerate().take(edges) {
+            let mut inside = vec![false; n];
+            for &leaf in leaves {
+                inside[leaf] = true;
+            }
+            let (mut a_rows, mut b_rows) = (Vec::new(), Vec::new());
+            let (mut a_members, mut b_members) = (Vec::new(), Vec::new());
+            for (i, row) in rows.iter().enumerate() {
+                if inside[i] {
+                    a_members.push(i);
+                    a_rows.push(row.clone());
+                } else {
+                    b_members.push(i);
+                    b_rows.push(row.clone());
+                }
+            }
+            // With two children at the root, both root edges cut the same
+            // bipartition.
+            let root_children = &tree.children[tree.root()];
+            let is_second_root_child = root_children.len() == 2 && root_children[1] == node;
+            if !b_rows.is_empty() && !is_second_root_child {
+                strip_gap_columns(&mut a_rows);
+                strip_gap_columns(&mut b_rows);
+                let a = Profile::new(a_members, a_rows, scheme)?;
+                let b = Profile::new(b_members, b_rows, scheme)?;
+                let (_, path) = align_profiles(&a, &b, scheme, None)?;
+                let candidate = Profile::merge(a, b, &path, scheme)?.into_rows();
+                let candidate_sp = sp_score(&candidate, scheme);
+                if candidate_sp > sp {
+                    (rows, sp) = (candidate, candidate_sp);
+                    improved = true;
+                }
+            }
+            job.check()?;
+            done += 1;
+            job.set_progress(done as f64 / total_steps);
+        }
+        if !improved {
+            break;
+        }
+    }
+
+    let (conservation, entropy, consensus) = column_stats(&rows, protein);
+    Ok(MsaReportNif {
+        n_sequences: rows.len(),
+        n_columns: rows.first().map_or(0, Vec::len),
+        guide_tree,
+        aligned: rows,
+        names,
+        conservation,
+        entropy,
+        consensus: String::from_utf8_lossy(&consensus).into_owned(),
+        sp_score: sp,
+    })
+}
+
+/// The scheme `progressive_msa` uses for `mode`: NUC.4.4 with gaps -16/-4
+/// or BLOSUM62 with gaps -10/-1, the defaults of `Align.msa_report/2`.
+fn default_scheme(mode: &str) -> Result<ScoringScheme, NifError> {
+    match mode {
+        "dna" => ScoringScheme::builtin("nuc.4.4", -16, -4),
+        "protein" => ScoringScheme::builtin("blosum62", -10, -1),
+        _ => Err(NifError::invalid(format!("unknown MSA mode: {mode} (expected dna or protein)"))),
+    }
+}
+
+/// [`msa_report`] with a UPGMA guide tree and no refinement, cut down to an
+/// `MsaResultNif`; `conservation` is the mean over columns.
+fn default_msa(
+    sequences: &[Vec<u8>],
+    scheme: &ScoringScheme,
+    job: &JobState,
+) -> Result<MsaResultNif, NifError> {
+    let options = MsaOptionsNif {
+        guide_tree: "upgma".to_string(),
+        refine_rounds: 0,
+    };
+    let report = msa_report(sequences, &[], scheme, &options, job)?;
+    let conservation = report.conservation.iter().sum::<f64>() / report.n_columns.max(1) as f64;
+    Ok(MsaResultNif {
+        aligned: report.aligned,
+        n_sequences: report.n_sequences,
+        n_columns: report.n_columns,
+        conservation,
+    })
+}
+
+// ===========================================================================
+// Adding to and merging alignments
+// ===========================================================================
+
+/// Names and rows of an existing alignment; bare rows have no names.
+fn load_alignment(source: MsaSourceNif) -> Result<(Vec<Option<String>>, Vec<Vec<u8>>), NifError> {
+    fn named<N: ToString, S: AsRef<[u8]>>(sequences: Vec<(N, S)>) -> (Vec<Option<String>>, Vec<Vec<u8>>) {
+        sequences
+            .into_iter()
+            .map(|(name, row)| (Some(name.to_string()), row.as_ref().to_vec()))
+            .unzip()
+    }
+
+    let (names, rows) = match source {
+        MsaSourceNif::Rows(rows) => (vec![None; rows.len()], rows),
+        MsaSourceNif::Named(pairs) => named(pairs),
+        MsaSourceNif::File(MsaFileNif { path, format }) => {
+            let contents = std::fs::read_to_string(&path).map_err(|e| NifError::from(e).with_file(&path))?;
+            match format.as_str() {
+                "stockholm" => {
+                    let alignment = cyanea_io::parse_stockholm(&contents)
+                        .map_err(|e| to_nif_error(e).with_file(&path))?
+                        .into_iter()
+                        .next()
+                        .ok_or_else(|| NifError::parse("no alignment in Stockholm file").with_file(&path))?;
+                    named(alignment.sequences)
+                }
+                "clustal" => {
+                    let alignment =
+                        cyanea_io::parse_clustal(&contents).map_err(|e| to_nif_error(e).with_file(&path))?;
+                    named(alignment.sequences)
+                }
+                _ => {
+                    return Err(NifError::invalid(format!(
+                        "unknown alignment format: {format} (expected stockholm or clustal)"
+                    )))
+                }
+            }
+        }
+    };
+    if rows.is_empty() {
+        return Err(NifError::invalid("alignment has no sequences"));
+    }
+    Ok((names, rows))
+}
+
+/// Row names, with `seq<N>` (1-based row number) for unnamed rows.
+fn fill_names(names: Vec<Option<String>>) -> Vec<String> {
+    names
+        .into_iter()
+        .enumerate()
+        .map(|(i, name)| name.unwrap_or_else(|| format!("seq{}", i + 1)))
+        .collect()
+}
+
+/// Lay out one row with `slots[s]` insertion columns before existing
+/// column `s` (slot `cells.len()` is after the last column), filled
+/// left-aligned from `inserts`.
+fn with_insertions(cells: &[u8], inserts: Option<&[Vec<u8>]>, slots: &[usize], width: usize) -> Vec<u8> {
+    let mut row = Vec::with_capacity(width);
+    for (s, &slot) in slots.iter().enumerate() {
+        let inserted: &[u8] = inserts.map_or(&[], |inserts| &inserts[s]);
+        row.extend_from_slice(inserted);
+        row.resize(row.len() + slot - inserted.len(), GAP);
+        if let Some(&cell) = cells.get(s) {
+            row.push(cell);
+        }
+    }
+    row
+}
+
+fn merge_result(
+    rows: Vec<Vec<u8>>,
+    names: Vec<Option<String>>,
+    inserted_columns: usize,
+    score: f64,
+    scheme: &ScoringScheme,
+) -> MsaMergeNif {
+    let (conservation, entropy, consensus) = column_stats(&rows, is_protein(scheme));
+    MsaMergeNif {
+        n_sequences: rows.len(),
+        n_columns: rows.first().map_or(0, Vec::len),
+        aligned: rows,
+        names: fill_names(names),
+        inserted_columns,
+        score,
+        conservation,
+        entropy,
+        consensus: String::from_utf8_lossy(&consensus).into_owned(),
+    }
+}
+
+/// Align each new sequence to the profile of the existing alignment.  The
+/// profile is not updated between sequences, so the result does not
+/// depend on their order.  Insertions relative to the alignment at the
+/// same place share columns; with `keep_length` they are dropped instead.
+fn add_sequences(
+    alignment: MsaSourceNif,
+    sequences: &[Vec<u8>],
+    scheme: &ScoringScheme,
+    options: &MsaAddOptionsNif,
+    job: &JobState,
+) -> Result<MsaMergeNif, NifError> {
+    if !options.names.is_empty() && options.names.len() != sequences.len() {
+        return Err(NifError::invalid(format!(
+            "{} names given for {} sequences",
+            options.names.len(),
+            sequences.len()
+        )));
+    }
+    let (mut names, rows) = load_alignment(alignment)?;
+    let existing = rows.len();
+    let profile = Profile::new((0..existing).collect(), rows, scheme)?;
+    let width = profile.width();
+
+    // Per new sequence: its symbol in each existing column, and the
+    // residues it inserts before each column.
+    let mut placed: Vec<(Vec<u8>, Vec<Vec<u8>>)> = Vec::with_capacity(sequences.len());
+    let mut score = 0.0;
+    for (k, seq) in sequences.iter().enumerate() {
+        let residues: Vec<u8> = seq.iter().copied().filter(|&b| !is_gap(b)).collect();
+        let new = Profile::new(vec![existing + k], vec![residues], scheme)
+            .map_err(|e| NifError::invalid(format!("sequence {k}: {}", e.message)))?;
+        let (seq_score, path) = align_profiles(&profile, &new, scheme, options.band)?;
+        score += seq_score;
+
+        let mut cells = Vec::with_capacity(width);
+        let mut inserts = vec![Vec::new(); width + 1];
+        let mut residues = new.rows[0].iter().copied();
+        for step in path {
+            match step {
+                Step::Both => cells.push(residues.next().unwrap_or(GAP)),
+                Step::OnlyA => cells.push(GAP),
+                Step::OnlyB => {
+                    let residue = residues.next().unwrap_or(GAP);
+                    if !options.keep_length {
+                        inserts[cells.len()].push(residue);
+                    }
+                }
+            }
+        }
+        placed.push((cells, inserts));
+        job.check()?;
+        job.set_progress((k + 1) as f64 / sequences.len() as f64);
+    }
+
+    let slots: Vec<usize> = (0..=width)
+        .map(|s| placed.iter().map(|(_, inserts)| inserts[s].len()).max().unwrap_or(0))
+        .collect();
+    let inserted: usize = slots.iter().sum();
+    let mut rows = profile.rows;
+    if inserted > 0 {
+        for row in &mut rows {
+            *row = with_insertions(row, None, &slots, width + inserted);
+        }
+    }
+    for (cells, inserts) in &placed {
+        rows.push(with_insertions(cells, Some(inserts), &slots, width + inserted));
+    }
+    if options.names.is_empty() {
+        names.resize(rows.len(), None);
+    } else {
+        names.extend(options.names.iter().cloned().map(Some));
+    }
+    Ok(merge_result(rows, names, inserted, score, scheme))
+}
+
+/// Merge two alignments by aligning their profiles; rows of `a` come first.
+fn merge_alignments(
+    a: MsaSourceNif,
+    b: MsaSourceNif,
+    scheme: &ScoringScheme,
+    band: Option<usize>,
+    job: &JobState,
+) -> Result<MsaMergeNif, NifError> {
+    let (mut names, a_rows) = load_alignment(a)?;
+    let (b_names, b_rows) = load_alignment(b)?;
+    let a = Profile::new(Vec::new(), a_rows, scheme)?;
+    let b = Profile::new(Vec::new(), b_rows, scheme)
+        .map_err(|e| NifError::invalid(format!("second alignment: {}", e.message)))?;
+    job.check()?;
+    let (score, path) = align_profiles(&a, &b, scheme, band)?;
+    let rows = interleave(&a.rows, &b.rows, &path);
+    names.extend(b_names);
+    Ok(merge_result(rows, names, path.len() - a.width(), score, scheme))
+}
+
+// ===========================================================================
+// NIFs
+// ===========================================================================
+
+/// Progressive MSA with the default scheme for `mode` (`"dna"` or
+/// `"protein"`).
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn progressive_msa<'a>(
+    env: Env<'a>,
+    sequences: Vec<Vec<u8>>,
+    mode: String,
+    cancel: Option<ResourceArc<CancelTokenResource>>,
+    progress: ProgressTarget<'a>,
+) -> Result<MsaResultNif, NifError> {
+    let scheme = default_scheme(&mode)?;
+    job::run(env, cancel, progress, move |job| default_msa(&sequences, &scheme, job))
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn progressive_msa_async<'a>(
+    env: Env<'a>,
+    sequences: Vec<Vec<u8>>,
+    mode: String,
+    cancel: Option<ResourceArc<CancelTokenResource>>,
+) -> Result<Reference<'a>, NifError> {
+    let scheme = default_scheme(&mode)?;
+    pool::spawn(env, PoolId::Align, cancel, move |job| default_msa(&sequences, &scheme, job))
+}
+
+/// Progressive MSA under a scoring scheme, with guide tree, refinement and
+/// column scores.  `names` label the guide tree leaves; empty means
+/// `seq1`, `seq2`, ...
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn progressive_msa_with_scheme<'a>(
+    env: Env<'a>,
+    sequences: Vec<Vec<u8>>,
+    names: Vec<String>,
+    scheme: ResourceArc<ScoringSchemeResource>,
+    options: MsaOptionsNif,
+    cancel: Option<ResourceArc<CancelTokenResource>>,
+    progress: ProgressTarget<'a>,
+) -> Result<MsaReportNif, NifError> {
+    job::run(env, cancel, progress, move |job| {
+        msa_report(&sequences, &names, &scheme.scheme, &options, job)
+    })
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn progressive_msa_with_scheme_async<'a>(
+    env: Env<'a>,
+    sequences: Vec<Vec<u8>>,
+    names: Vec<String>,
+    scheme: ResourceArc<ScoringSchemeResource>,
+    options: MsaOptionsNif,
+    cancel: Option<ResourceArc<CancelTokenResource>>,
+) -> Result<Reference<'a>, NifError> {
+    pool::spawn(env, PoolId::Align, cancel, move |job| {
+        msa_report(&sequences, &names, &scheme.scheme, &options, job)
+    })
+}
+
+/// Add sequences to an existing alignment, keeping its columns.
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn msa_add_sequences<'a>(
+    env: Env<'a>,
+    alignment: MsaSourceNif,
+    sequences: Vec<Vec<u8>>,
+    scheme: ResourceArc<ScoringSchemeResource>,
+    options: MsaAddOptionsNif,
+    cancel: Option<ResourceArc<CancelTokenResource>>,
+    progress: ProgressTarget<'a>,
+) -> Result<MsaMergeNif, NifError> {
+    job::run(env, cancel, progress, move |job| {
+        add_sequences(alignment, &sequences, &scheme.scheme, &options, job)
+    })
+}
+
+/// Merge two alignments by profile-profile alignment.
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn msa_merge<'a>(
+    env: Env<'a>,
+    a: MsaSourceNif,
+    b: MsaSourceNif,
+    scheme: ResourceArc<ScoringSchemeResource>,
+    band: Option<usize>,
+    cancel: Option<ResourceArc<CancelTokenResource>>,
+    progress: ProgressTarget<'a>,
+) -> Result<MsaMergeNif, NifError> {
+    job::run(env, cancel, progress, move |job| merge_alignments(a, b, &scheme.scheme, band, job))
+}
@@ -2,6 +2,7 @@
 
 use crate::bridge::*;
 use crate::to_nif_error;
+use rustler::Env;
 
 #[rustler::nif]
 pub fn classify_variant(
@@ -9,7 +10,7 @@ pub fn classify_variant(
     position: u64,
     ref_allele: Vec<u8>,
     alt_alleles: Vec<Vec<u8>>,
-) -> Result<VariantClassificationNif, String> {
+) -> Result<VariantClassificationNif, NifError> {
     let variant = cyanea_omics::Variant::new(chrom, position, ref_allele, alt_alleles)
         .map_err(to_nif_error)?;
     let vtype = variant.variant_type();
@@ -29,9 +30,9 @@ pub fn merge_genomic_intervals(
     chroms: Vec<String>,
     starts: Vec<u64>,
     ends: Vec<u64>,
-) -> Result<Vec<GenomicIntervalNif>, String> {
+) -> Result<Vec<GenomicIntervalNif>, NifError> {
     if chroms.len() != starts.len() || chroms.len() != ends.len() {
-        return Err("chroms, starts, and ends must have equal length".into());
+        return Err(NifError::invalid("chroms, starts, and ends must have equal length"));
     }
     let mut intervals = Vec::with_capacity(chroms.len());
     for i in 0..chroms.len() {
@@ -50,9 +51,9 @@ pub fn genomic_coverage(
     starts: Vec<u64>,
     ends: Vec<u64>,
     query_chrom: String,
-) -> Result<u64, String> {
+) -> Result<u64, NifError> {
     if chroms.len() != starts.len() || chroms.len() != ends.len() {
-        return Err("chroms, starts, and ends must have equal length".into());
+        return Err(NifError::invalid("chroms, starts, and ends must have equal length"));
     }
     let mut intervals = Vec::with_capacity(chroms.len());
     for i in 0..chroms.len() {
@@ -66,10 +67,11 @@ pub fn genomic_coverage(
 
 #[rustler::nif]
 pub fn expression_summary(
-    data: Vec<Vec<f64>>,
+    data: MatrixInput,
     feature_names: Vec<String>,
     sample_names: Vec<String>,
-) -> Result<ExpressionSummaryNif, String> {
+) -> Result<ExpressionSummaryNif, NifError> {
+    let data = data.into_rows()?;
     let feat_names = feature_names.clone();
     let samp_names = sample_names.clone();
     let matrix = cyanea_omics::ExpressionMatrix::new(data, feature_names, sample_names)
@@ -92,11 +94,14 @@ pub fn expression_summary(
 }
 
 #[rustler::nif]
-pub fn log_transform_matrix(
-    data: Vec<Vec<f64>>,
+pub fn log_transform_matrix<'a>(
+    env: Env<'a>,
+    data: MatrixInput,
     pseudocount: f64,
-) -> Vec<Vec<f64>> {
-    data.iter()
-        .map(|row| row.iter().map(|&x| (x + pseudocount).log2()).collect())
-        .collect()
+) -> Result<MatrixOutput<'a>, NifError> {
+    let form = data.form();
+    let n_cols = data.shape().map_or(1, |(_, cols)| cols);
+    let values = data.into_values()?;
+    let logged = values.iter().map(|&x| (x + pseudocount).log2()).collect();
+    form.matrix(env, logged, n_cols)
 }
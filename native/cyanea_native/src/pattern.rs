@@ -0,0 +1,307 @@
+//! Multi-pattern search over many records — primer and probe checks,
+//! guide off-target scans and restriction site maps against a reference.
+//!
+//! Every pattern is searched on both strands by also searching for its
+//! reverse complement on the forward strand, so hits always carry forward
+//! coordinates.  Exact searches over plain ACGT patterns run through one
+//! Aho-Corasick automaton; patterns with IUPAC ambiguity codes and all
+//! approximate searches use Myers' bit-vector algorithm (patterns up to 64
+//! bases), with each hit's start recovered by a small banded alignment.
+//! The bit-vector matcher is local rather than `cyanea_seq`'s because its
+//! match table expands IUPAC codes in the pattern.
+
+use crate::bridge::{FastaRecordNif, NifError, PatternHitNif};
+use crate::seq::{iupac_bases, reverse_complement, to_dna, FastaReader, SeqAlphabet};
+use aho_corasick::AhoCorasick;
+
+/// Longest pattern the bit-vector search supports.
+const MAX_MYERS_LEN: usize = 64;
+
+/// A pattern, or its reverse complement, as searched on the forward strand.
+struct SearchUnit {
+    pattern: usize,
+    strand: &'static str,
+    seq: Vec<u8>,
+}
+
+impl SearchUnit {
+    fn is_degenerate(&self) -> bool {
+        self.seq.iter().any(|b| !matches!(b, b'A' | b'C' | b'G' | b'T'))
+    }
+}
+
+/// Bit-vector matcher for one unit: `peq[c]` has bit `i` set when text
+/// byte `c` matches pattern position `i`.  Only A, C, G and T in the text
+/// match anything, so `N` runs in a reference never produce hits.
+struct Myers {
+    peq: [u64; 256],
+    len: usize,
+}
+
+impl Myers {
+    fn new(seq: &[u8]) -> Self {
+        let mut peq = [0u64; 256];
+        for (i, &code) in seq.iter().enumerate() {
+            for &base in iupac_bases(code) {
+                peq[base as usize] |= 1 << i;
+            }
+        }
+        Self { peq, len: seq.len() }
+    }
+
+    fn matches(&self, i: usize, c: u8) -> bool {
+        self.peq[c as usize] >> i & 1 == 1
+    }
+
+    /// `(end, distance)` for every text position where a match ends within
+    /// `max_distance` edits.
+    fn ends(&self, text: &[u8], max_distance: usize) -> Vec<(usize, usize)> {
+        let high = 1u64 << (self.len - 1);
+        let (mut pv, mut mv) = (!0u64, 0u64);
+        let mut score = self.len;
+        let mut ends = Vec::new();
+        for (j, &c) in text.iter().enumerate() {
+            let eq = self.peq[c as usize];
+            let xv = eq | mv;
+            let xh = ((eq & pv).wrapping_add(pv) ^ pv) | eq;
+            let mut ph = mv | !(xh | pv);
+            let mut mh = pv & xh;
+            if ph & high != 0 {
+                score += 1;
+            } else if mh & high != 0 {
+                score -= 1;
+            }
+            ph <<= 1;
+            mh <<= 1;
+            pv = mh | !(xv | ph);
+            mv = ph & xv;
+            if score <= max_distance {
+                ends.push((j + 1, score));
+            }
+        }
+        ends
+    }
+
+    /// Start of the best match ending at `end`, aligning the pattern
+    /// backwards from `end` over at most `len + max_distance` bases.
+    /// Ties go to the span closest to the pattern length.
+    fn start(&self, text: &[u8], end: usize, max_distance: usize) -> (usize, usize) {
+        let span = (self.len + max_distance).min(end);
+        let window = &text[end - span..end];
+        // prev[j] / row[j]: cost of the last i pattern bases against the
+        // last j window bases.
+        let mut prev: Vec<usize> = (0..=span).collect();
+        let mut row = vec![0; span + 1];
+        for i in 1..=self.len {
+            row[0] = i;
+            let p = self.len - i;
+            for j in 1..=span {
+                let c = window[span - j];
+                let diag = prev[j - 1] + usize::from(!self.matches(p, c));
+                row[j] = diag.min(prev[j] + 1).min(row[j - 1] + 1);
+            }
+            std::mem::swap(&mut prev, &mut row);
+        }
+        let (j, distance) = (0..=span)
+            .map(|j| (j, prev[j]))
+            .min_by_key(|&(j, d)| (d, j.abs_diff(self.len)))
+            .unwrap();
+        (end - j, distance)
+    }
+}
+
+/// Uppercase a target sequence and read U as T.
+pub(crate) fn normalize_text(sequence: &[u8]) -> Vec<u8> {
+    sequence
+        .iter()
+        .map(|b| match b.to_ascii_uppercase() {
+            b'U' => b'T',
+            b => b,
+        })
+        .collect()
+}
+
+/// Patterns compiled once and run over each record in turn.
+pub(crate) struct PatternSet {
+    names: Vec<String>,
+    exact_units: Vec<SearchUnit>,
+    exact: Option<AhoCorasick>,
+    myers_units: Vec<(SearchUnit, Myers)>,
+    max_distance: usize,
+}
+
+impl PatternSet {
+    pub(crate) fn new(
+        patterns: Vec<(String, String)>,
+        max_distance: usize,
+        both_strands: bool,
+    ) -> Result<Self, NifError> {
+        if patterns.is_empty() {
+            return Err(NifError::invalid("at least one pattern is required"));
+        }
+        let mut names = Vec::with_capacity(patterns.len());
+        let mut units = Vec::new();
+        for (index, (name, seq)) in patterns.into_iter().enumerate() {
+            let seq = to_dna(
+                &SeqAlphabet::Iupac
+                    .normalize(seq.as_bytes())
+                    .map_err(|e| NifError::invalid(format!("pattern {name}: {}", e.message)))?,
+            );
+            if seq.is_empty() || seq.contains(&b'-') {
+                return Err(NifError::invalid(format!(
+                    "pattern {name}: must be a non-empty nucleotide sequence without gaps"
+                )));
+            }
+            if max_distance >= seq.len() {
+                return Err(NifError::invalid(format!(
+                    "pattern {name}: max_distance {max_distance} must be less than its length {}",
+                    seq.len()
+                )));
+            }
+            let reverse = reverse_complement(&seq);
+            // Palindromes (most restriction sites) would report every hit twice.
+            if both_strands && reverse != seq {
+                units.push(SearchUnit {
+                    pattern: index,
+                    strand: "-",
+                    seq: reverse,
+                });
+            }
+            units.push(SearchUnit {
+                pattern: index,
+                strand: "+",
+                seq,
+            });
+            names.push(name);
+        }
+
+        let (exact_units, myers_units): (Vec<_>, Vec<_>) = units
+            .into_iter()
+            .partition(|u| max_distance == 0 && !u.is_degenerate());
+        let myers_units = myers_units
+            .into_iter()
+            .map(|u| {
+                if u.seq.len() > MAX_MYERS_LEN {
+                    return Err(NifError::invalid(format!(
+                        "pattern {}: approximate and IUPAC patterns are limited to {MAX_MYERS_LEN} bases",
+                        names[u.pattern]
+                    )));
+                }
+                let myers = Myers::new(&u.seq);
+                Ok((u, myers))
+            })
+            .collect::<Result<Vec<_>, NifError>>()?;
+        let exact = if exact_units.is_empty() {
+            None
+        } else {
+            let automaton = AhoCorasick::new(exact_units.iter().map(|u| &u.seq))
+                .map_err(|e| NifError::internal(e.to_string()))?;
+            Some(automaton)
+        };
+
+        Ok(Self {
+            names,
+            exact_units,
+            exact,
+            myers_units,
+            max_distance,
+        })
+    }
+
+    /// All hits in one record, ordered by position.
+    pub(crate) fn search(&self, record: &str, sequence: &[u8]) -> Vec<PatternHitNif> {
+        let text = normalize_text(sequence);
+        let mut hits = Vec::new();
+        let mut push = |unit: &SearchUnit, start: usize, end: usize, distance: usize| {
+            hits.push((unit.pattern, unit.strand, start, end, distance));
+        };
+
+        if let Some(automaton) = &self.exact {
+            for m in automaton.find_overlapping_iter(&text) {
+                push(&self.exact_units[m.pattern().as_usize()], m.start(), m.end(), 0);
+            }
+        }
+        for (unit, myers) in &self.myers_units {
+            let ends = myers.ends(&text, self.max_distance);
+            if self.max_distance == 0 {
+                for (end, _) in ends {
+                    push(unit, end - unit.seq.len(), end, 0);
+                }
+                continue;
+            }
+            // Adjacent end positions with the same start are one occurrence
+            // with a base more or less of slack; keep its best end.  Ends that
+            // trace back to different starts are separate occurrences.
+            let mut run: Option<(usize, usize, usize)> = None;
+            let mut last_end = 0;
+            for (end, _) in ends {
+                let (start, distance) = myers.start(&text, end, self.max_distance);
+                match &mut run {
+                    Some((s, best_end, d)) if *s == start && last_end + 1 == end => {
+                        if distance < *d {
+                            (*best_end, *d) = (end, distance);
+                        }
+                    }
+                    _ => {
+                        if let Some((s, e, d)) = run {
+                            push(unit, s, e, d);
+                        }
+                        run = Some((start, end, distance));
+                    }
+                }
+                last_end = end;
+            }
+            if let Some((s, e, d)) = run {
+                push(unit, s, e, d);
+            }
+        }
+
+        hits.sort_unstable_by_key(|&(pattern, strand, start, end, _)| (start, end, pattern, strand));
+        hits.into_iter()
+            .map(|(pattern, strand, start, end, distance)| PatternHitNif {
+                pattern: self.names[pattern].clone(),
+                record: record.to_string(),
+                strand: strand.to_string(),
+                start,
+                end,
+                distance,
+            })
+            .collect()
+    }
+}
+
+// ===========================================================================
+// NIFs
+// ===========================================================================
+
+/// Search every record of a FASTA file (plain or gzipped), one record in
+/// memory at a time.  `patterns` are `{name, sequence}` pairs.
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn pattern_search_fasta(
+    path: String,
+    patterns: Vec<(String, String)>,
+    max_distance: usize,
+    both_strands: bool,
+) -> Result<Vec<PatternHitNif>, NifError> {
+    let set = PatternSet::new(patterns, max_distance, both_strands)?;
+    let mut reader = FastaReader::open(&path)?;
+    let mut hits = Vec::new();
+    while let Some(record) = reader.next_record()? {
+        hits.extend(set.search(&record.name, &record.sequence));
+    }
+    Ok(hits)
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn pattern_search_records(
+    records: Vec<FastaRecordNif>,
+    patterns: Vec<(String, String)>,
+    max_distance: usize,
+    both_strands: bool,
+) -> Result<Vec<PatternHitNif>, NifError> {
+    let set = PatternSet::new(patterns, max_distance, both_strands)?;
+    Ok(records
+        .iter()
+        .flat_map(|record| set.search(&record.name, &record.sequence))
+        .collect())
+}
@@ -1,29 +1,127 @@
 //! cyanea-phylo NIFs — Newick/NEXUS I/O, tree distances, tree building, bootstrap.
 
 use crate::bridge::*;
+use crate::job::{self, CancelTokenResource, JobState, ProgressTarget};
+use crate::pool::{self, PoolId};
 use crate::to_nif_error;
+use rustler::{Env, Reference, ResourceArc};
+use std::sync::atomic::{AtomicUsize, Ordering};
 
 // ===========================================================================
 // Helpers
 // ===========================================================================
 
-fn parse_distance_model(s: &str) -> Result<cyanea_phylo::DistanceModel, String> {
+fn parse_distance_model(s: &str) -> Result<cyanea_phylo::DistanceModel, NifError> {
     match s {
         "p" => Ok(cyanea_phylo::DistanceModel::P),
         "jc" => Ok(cyanea_phylo::DistanceModel::JukesCantor),
         "k2p" => Ok(cyanea_phylo::DistanceModel::Kimura2P),
-        _ => Err(format!(
+        _ => Err(NifError::invalid(format!(
             "unknown distance model: {s} (expected p, jc, or k2p)"
-        )),
+        ))),
     }
 }
 
+/// A `cyanea_phylo::DistanceMatrix` filled from a symmetric `n x n` table.
+pub(crate) fn to_distance_matrix(dist: &[Vec<f64>]) -> cyanea_phylo::DistanceMatrix {
+    let mut dm = cyanea_phylo::DistanceMatrix::new(dist.len());
+    for (i, row) in dist.iter().enumerate() {
+        for (j, &d) in row.iter().enumerate().skip(i + 1) {
+            dm.set(i, j, d);
+        }
+    }
+    dm
+}
+
+/// Pairwise distances.  A watched job computes them one row at a time,
+/// checking for cancellation and reporting progress (up to 90%) per row;
+/// otherwise they come from a single batch call.
+fn distance_rows(
+    sequences: &[Vec<u8>],
+    model: cyanea_phylo::DistanceModel,
+    job: &JobState,
+) -> Result<cyanea_phylo::DistanceMatrix, NifError> {
+    if !job.is_watched() {
+        let refs: Vec<&[u8]> = sequences.iter().map(|s| s.as_slice()).collect();
+        return cyanea_phylo::sequence_distance_matrix(&refs, model).map_err(to_nif_error);
+    }
+    let n = sequences.len();
+    let total_pairs = (n * n.saturating_sub(1) / 2).max(1);
+    let mut done_pairs = 0;
+    let mut dist = vec![vec![0.0; n]; n];
+    for i in 0..n {
+        job.check()?;
+        for j in i + 1..n {
+            let pair = [sequences[i].as_slice(), sequences[j].as_slice()];
+            let d = cyanea_phylo::sequence_distance_matrix(&pair, model)
+                .map_err(to_nif_error)?
+                .get(0, 1);
+            dist[i][j] = d;
+            dist[j][i] = d;
+        }
+        done_pairs += n - 1 - i;
+        job.set_progress(0.9 * done_pairs as f64 / total_pairs as f64);
+    }
+    job.check()?;
+    Ok(to_distance_matrix(&dist))
+}
+
+fn upgma_tree(
+    sequences: &[Vec<u8>],
+    names: &[String],
+    model: cyanea_phylo::DistanceModel,
+    job: &JobState,
+) -> Result<String, NifError> {
+    let dm = distance_rows(sequences, model, job)?;
+    let tree = cyanea_phylo::upgma(&dm, names).map_err(to_nif_error)?;
+    Ok(cyanea_phylo::write_newick(&tree))
+}
+
+/// Neighbour-joining tree as Newick; the joining itself is the last 10%.
+fn nj_tree(
+    sequences: &[Vec<u8>],
+    names: &[String],
+    model: cyanea_phylo::DistanceModel,
+    job: &JobState,
+) -> Result<String, NifError> {
+    let dm = distance_rows(sequences, model, job)?;
+    let tree = cyanea_phylo::neighbor_joining(&dm, names).map_err(to_nif_error)?;
+    Ok(cyanea_phylo::write_newick(&tree))
+}
+
+fn bootstrap(
+    sequences: &[Vec<u8>],
+    tree: &cyanea_phylo::PhyloTree,
+    n_replicates: usize,
+    model: cyanea_phylo::DistanceModel,
+    job: &JobState,
+) -> Result<Vec<f64>, NifError> {
+    let refs: Vec<&[u8]> = sequences.iter().map(|s| s.as_slice()).collect();
+    let done = AtomicUsize::new(0);
+    let builder = |seqs: &[Vec<u8>]| -> cyanea_core::Result<cyanea_phylo::PhyloTree> {
+        // Bail out of the remaining replicates once cancelled.
+        if job.is_cancelled() {
+            return Err(std::io::Error::from(std::io::ErrorKind::Interrupted).into());
+        }
+        let seq_refs: Vec<&[u8]> = seqs.iter().map(|s| s.as_slice()).collect();
+        let names: Vec<String> = (0..seqs.len()).map(|i| format!("t{}", i)).collect();
+        let dm = cyanea_phylo::sequence_distance_matrix(&seq_refs, model)?;
+        let replicate = cyanea_phylo::neighbor_joining(&dm, &names);
+        let done = done.fetch_add(1, Ordering::Relaxed) + 1;
+        job.set_progress(done as f64 / n_replicates.max(1) as f64);
+        replicate
+    };
+    let support = cyanea_phylo::bootstrap_support(&refs, tree, builder, n_replicates);
+    job.check()?;
+    support.map_err(to_nif_error)
+}
+
 // ===========================================================================
 // Existing NIFs
 // ===========================================================================
 
 #[rustler::nif]
-pub fn newick_info(newick: String) -> Result<NewickInfoNif, String> {
+pub fn newick_info(newick: String) -> Result<NewickInfoNif, NifError> {
     let tree = cyanea_phylo::parse_newick(&newick).map_err(to_nif_error)?;
     let leaf_count = tree.leaf_count();
     let leaf_names = tree.leaf_names();
@@ -36,7 +134,7 @@ pub fn newick_info(newick: String) -> Result<NewickInfoNif, String> {
 }
 
 #[rustler::nif]
-pub fn newick_robinson_foulds(newick_a: String, newick_b: String) -> Result<usize, String> {
+pub fn newick_robinson_foulds(newick_a: String, newick_b: String) -> Result<usize, NifError> {
     let tree_a = cyanea_phylo::parse_newick(&newick_a).map_err(to_nif_error)?;
     let tree_b = cyanea_phylo::parse_newick(&newick_b).map_err(to_nif_error)?;
     cyanea_phylo::robinson_foulds(&tree_a, &tree_b).map_err(to_nif_error)
@@ -47,7 +145,7 @@ pub fn evolutionary_distance(
     seq_a: Vec<u8>,
     seq_b: Vec<u8>,
     model: String,
-) -> Result<f64, String> {
+) -> Result<f64, NifError> {
     match model.as_str() {
         "p" => cyanea_phylo::p_distance(&seq_a, &seq_b).map_err(to_nif_error),
         "jc" => {
@@ -63,9 +161,9 @@ pub fn evolutionary_distance(
             .map_err(to_nif_error)?;
             Ok(dm.get(0, 1))
         }
-        _ => Err(format!(
+        _ => Err(NifError::invalid(format!(
             "unknown distance model: {model} (expected p, jc, or k2p)"
-        )),
+        ))),
     }
 }
 
@@ -74,25 +172,21 @@ pub fn build_upgma(
     sequences: Vec<Vec<u8>>,
     names: Vec<String>,
     model: String,
-) -> Result<String, String> {
-    let model = parse_distance_model(&model)?;
-    let refs: Vec<&[u8]> = sequences.iter().map(|s| s.as_slice()).collect();
-    let dm = cyanea_phylo::sequence_distance_matrix(&refs, model).map_err(to_nif_error)?;
-    let tree = cyanea_phylo::upgma(&dm, &names).map_err(to_nif_error)?;
-    Ok(cyanea_phylo::write_newick(&tree))
+) -> Result<String, NifError> {
+    upgma_tree(&sequences, &names, parse_distance_model(&model)?, &JobState::new(None))
 }
 
 #[rustler::nif(schedule = "DirtyCpu")]
-pub fn build_nj(
+pub fn build_nj<'a>(
+    env: Env<'a>,
     sequences: Vec<Vec<u8>>,
     names: Vec<String>,
     model: String,
-) -> Result<String, String> {
+    cancel: Option<ResourceArc<CancelTokenResource>>,
+    progress: ProgressTarget<'a>,
+) -> Result<String, NifError> {
     let model = parse_distance_model(&model)?;
-    let refs: Vec<&[u8]> = sequences.iter().map(|s| s.as_slice()).collect();
-    let dm = cyanea_phylo::sequence_distance_matrix(&refs, model).map_err(to_nif_error)?;
-    let tree = cyanea_phylo::neighbor_joining(&dm, &names).map_err(to_nif_error)?;
-    Ok(cyanea_phylo::write_newick(&tree))
+    job::run(env, cancel, progress, move |job| nj_tree(&sequences, &names, model, job))
 }
 
 // ===========================================================================
@@ -100,7 +194,7 @@ pub fn build_nj(
 // ===========================================================================
 
 #[rustler::nif]
-pub fn nexus_parse(nexus_text: String) -> Result<NexusFileNif, String> {
+pub fn nexus_parse(nexus_text: String) -> Result<NexusFileNif, NifError> {
     let nexus = cyanea_phylo::nexus::parse(&nexus_text).map_err(to_nif_error)?;
     Ok(NexusFileNif {
         taxa: nexus.taxa.clone(),
@@ -110,7 +204,7 @@ pub fn nexus_parse(nexus_text: String) -> Result<NexusFileNif, String> {
 }
 
 #[rustler::nif]
-pub fn nexus_write(taxa: Vec<String>, trees_newick: Vec<String>) -> Result<String, String> {
+pub fn nexus_write(taxa: Vec<String>, trees_newick: Vec<String>) -> Result<String, NifError> {
     let mut parsed_trees = Vec::new();
     let mut names = Vec::new();
     for (i, newick) in trees_newick.iter().enumerate() {
@@ -127,44 +221,42 @@ pub fn nexus_write(taxa: Vec<String>, trees_newick: Vec<String>) -> Result<Strin
 }
 
 #[rustler::nif]
-pub fn robinson_foulds_normalized(newick_a: String, newick_b: String) -> Result<f64, String> {
+pub fn robinson_foulds_normalized(newick_a: String, newick_b: String) -> Result<f64, NifError> {
     let tree_a = cyanea_phylo::parse_newick(&newick_a).map_err(to_nif_error)?;
     let tree_b = cyanea_phylo::parse_newick(&newick_b).map_err(to_nif_error)?;
     cyanea_phylo::robinson_foulds_normalized(&tree_a, &tree_b).map_err(to_nif_error)
 }
 
 #[rustler::nif(schedule = "DirtyCpu")]
-pub fn bootstrap_support(
+pub fn bootstrap_support<'a>(
+    env: Env<'a>,
     sequences: Vec<Vec<u8>>,
     tree_newick: String,
     n_replicates: usize,
     model: String,
-) -> Result<Vec<f64>, String> {
+    cancel: Option<ResourceArc<CancelTokenResource>>,
+    progress: ProgressTarget<'a>,
+) -> Result<Vec<f64>, NifError> {
     let model = parse_distance_model(&model)?;
-    let refs: Vec<&[u8]> = sequences.iter().map(|s| s.as_slice()).collect();
     let tree = cyanea_phylo::parse_newick(&tree_newick).map_err(to_nif_error)?;
-    let builder = |seqs: &[Vec<u8>]| -> cyanea_core::Result<cyanea_phylo::PhyloTree> {
-        let seq_refs: Vec<&[u8]> = seqs.iter().map(|s| s.as_slice()).collect();
-        let names: Vec<String> = (0..seqs.len()).map(|i| format!("t{}", i)).collect();
-        let dm = cyanea_phylo::sequence_distance_matrix(&seq_refs, model)?;
-        cyanea_phylo::neighbor_joining(&dm, &names)
-    };
-    cyanea_phylo::bootstrap_support(&refs, &tree, builder, n_replicates).map_err(to_nif_error)
+    job::run(env, cancel, progress, move |job| {
+        bootstrap(&sequences, &tree, n_replicates, model, job)
+    })
 }
 
 #[rustler::nif]
 pub fn ancestral_reconstruction(
     tree_newick: String,
     leaf_states: Vec<String>,
-) -> Result<Vec<String>, String> {
+) -> Result<Vec<String>, NifError> {
     let tree = cyanea_phylo::parse_newick(&tree_newick).map_err(to_nif_error)?;
     let leaves = tree.leaves();
     if leaves.len() != leaf_states.len() {
-        return Err(format!(
+        return Err(NifError::invalid(format!(
             "expected {} leaf states but got {}",
             leaves.len(),
             leaf_states.len()
-        ));
+        )));
     }
     // Convert string states to u8 indices and pair with NodeIds
     let mut state_map: Vec<String> = Vec::new();
@@ -184,8 +276,54 @@ pub fn ancestral_reconstruction(
 }
 
 #[rustler::nif]
-pub fn branch_score_distance(newick_a: String, newick_b: String) -> Result<f64, String> {
+pub fn branch_score_distance(newick_a: String, newick_b: String) -> Result<f64, NifError> {
     let tree_a = cyanea_phylo::parse_newick(&newick_a).map_err(to_nif_error)?;
     let tree_b = cyanea_phylo::parse_newick(&newick_b).map_err(to_nif_error)?;
     cyanea_phylo::branch_score_distance(&tree_a, &tree_b).map_err(to_nif_error)
 }
+
+// ===========================================================================
+// Async variants (results are sent as {:cyanea_result, ref, result})
+// ===========================================================================
+
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn build_upgma_async<'a>(
+    env: Env<'a>,
+    sequences: Vec<Vec<u8>>,
+    names: Vec<String>,
+    model: String,
+    cancel: Option<ResourceArc<CancelTokenResource>>,
+) -> Result<Reference<'a>, NifError> {
+    let model = parse_distance_model(&model)?;
+    pool::spawn(env, PoolId::Phylo, cancel, move |job| {
+        upgma_tree(&sequences, &names, model, job)
+    })
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn build_nj_async<'a>(
+    env: Env<'a>,
+    sequences: Vec<Vec<u8>>,
+    names: Vec<String>,
+    model: String,
+    cancel: Option<ResourceArc<CancelTokenResource>>,
+) -> Result<Reference<'a>, NifError> {
+    let model = parse_distance_model(&model)?;
+    pool::spawn(env, PoolId::Phylo, cancel, move |job| nj_tree(&sequences, &names, model, job))
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn bootstrap_support_async<'a>(
+    env: Env<'a>,
+    sequences: Vec<Vec<u8>>,
+    tree_newick: String,
+    n_replicates: usize,
+    model: String,
+    cancel: Option<ResourceArc<CancelTokenResource>>,
+) -> Result<Reference<'a>, NifError> {
+    let model = parse_distance_model(&model)?;
+    let tree = cyanea_phylo::parse_newick(&tree_newick).map_err(to_nif_error)?;
+    pool::spawn(env, PoolId::Phylo, cancel, move |job| {
+        bootstrap(&sequences, &tree, n_replicates, model, job)
+    })
+}
@@ -0,0 +1,328 @@
+//! Bounded native thread pools for async job NIFs.
+//!
+//! The `*_async` NIFs decode their arguments on a dirty scheduler, queue
+//! the work on one of these pools and return a reference at once, sending
+//! `{:cyanea_result, ref, {:ok, value} | {:error, error}}` to the caller
+//! when done.  Each area (align, ml, phylo) has its own pool so a flood of
+//! alignments cannot starve clustering jobs; the concurrency of each pool
+//! can be changed at runtime and its queue depth inspected.  A job waits
+//! for one of `max_concurrency` slots before it runs, so the limit holds
+//! while the concurrency changes.  With the `parallel` feature jobs are
+//! queued on a rayon thread pool; without it on a queue served by a fixed
+//! set of worker threads.  A job that panics is reported to its caller as
+//! an `:internal` error.
+
+use crate::bridge::{AsyncPoolStatsNif, NifError};
+use crate::job::{CancelTokenResource, JobState};
+use rustler::{Encoder, Env, OwnedEnv, Reference, ResourceArc, Term};
+#[cfg(not(feature = "parallel"))]
+use std::collections::VecDeque;
+use std::panic::{self, AssertUnwindSafe};
+use std::sync::atomic::{AtomicUsize, Ordering};
+#[cfg(feature = "parallel")]
+use std::sync::{Arc, RwLock};
+use std::sync::{Condvar, Mutex, OnceLock};
+
+mod atoms {
+    rustler::atoms! {
+        cyanea_result,
+    }
+}
+
+/// The pools async NIFs run on.
+#[derive(Debug, Clone, Copy)]
+pub enum PoolId {
+    Align,
+    Ml,
+    Phylo,
+}
+
+fn parse_pool(s: &str) -> Result<PoolId, NifError> {
+    match s {
+        "align" => Ok(PoolId::Align),
+        "ml" => Ok(PoolId::Ml),
+        "phylo" => Ok(PoolId::Phylo),
+        _ => Err(NifError::invalid(format!(
+            "unknown pool: {s} (expected align, ml, or phylo)"
+        ))),
+    }
+}
+
+struct Pool {
+    name: &'static str,
+    max_concurrency: AtomicUsize,
+    queued: AtomicUsize,
+    running: AtomicUsize,
+    /// Number of jobs holding a slot, and the signal that one was freed.
+    slots: (Mutex<usize>, Condvar),
+    /// Built on first use with `max_concurrency` threads and rebuilt larger
+    /// when the concurrency grows; jobs queued on a replaced pool still run
+    /// there, but only once they get a slot.
+    #[cfg(feature = "parallel")]
+    threads: RwLock<Option<(usize, Arc<rayon::ThreadPool>)>>,
+    /// Jobs waiting for a worker, and the signal that one was queued.
+    #[cfg(not(feature = "parallel"))]
+    tasks: (Mutex<VecDeque<Task>>, Condvar),
+    /// Worker threads started so far; grows with the concurrency and never
+    /// shrinks, surplus workers wait for a slot.
+    #[cfg(not(feature = "parallel"))]
+    workers: Mutex<usize>,
+}
+
+#[cfg(not(feature = "parallel"))]
+type Task = Box<dyn FnOnce() + Send>;
+
+/// A held slot, released when dropped.
+struct Slot<'p>(&'p Pool);
+
+impl Drop for Slot<'_> {
+    fn drop(&mut self) {
+        let (lock, freed) = &self.0.slots;
+        *lock.lock().unwrap_or_else(|e| e.into_inner()) -= 1;
+        freed.notify_all();
+    }
+}
+
+impl Pool {
+    fn new(name: &'static str) -> Self {
+        // Half the cores per pool leaves room for the dirty schedulers.
+        let cores = std::thread::available_parallelism().map_or(1, |n| n.get());
+        Self {
+            name,
+            max_concurrency: AtomicUsize::new((cores / 2).max(1)),
+            queued: AtomicUsize::new(0),
+            running: AtomicUsize::new(0),
+            slots: (Mutex::new(0), Condvar::new()),
+            #[cfg(feature = "parallel")]
+            threads: RwLock::new(None),
+            #[cfg(not(feature = "parallel"))]
+            tasks: (Mutex::new(VecDeque::new()), Condvar::new()),
+            #[cfg(not(feature = "parallel"))]
+            workers: Mutex::new(0),
+        }
+    }
+
+    fn set_concurrency(&self, n: usize) {
+        self.max_concurrency.store(n, Ordering::SeqCst);
+        #[cfg(feature = "parallel")]
+        {
+            let mut threads = self.threads.write().unwrap_or_else(|e| e.into_inner());
+            if threads.as_ref().is_some_and(|(size, _)| *size < n) {
+                *threads = None;
+            }
+        }
+        self.slots.1.notify_all();
+    }
+
+    /// Block until fewer than `max_concurrency` jobs are running.
+    fn acquire(&self) -> Slot<'_> {
+        let (lock, freed) = &self.slots;
+        let mut in_use = lock.lock().unwrap_or_else(|e| e.into_inner());
+        while *in_use >= self.max_concurrency.load(Ordering::SeqCst) {
+            in_use = freed.wait(in_use).unwrap_or_else(|e| e.into_inner());
+        }
+        *in_use += 1;
+        Slot(self)
+    }
+
+    #[cfg(feature = "parallel")]
+    fn execute(&'static self, task: impl FnOnce() + Send + 'static) -> Result<(), NifError> {
+        let current = self
+            .threads
+            .read()
+            .unwrap_or_else(|e| e.into_inner())
+            .as_ref()
+            .map(|(_, threads)| threads.clone());
+        let threads = match current {
+            Some(threads) => threads,
+            None => {
+                let mut slot = self.threads.write().unwrap_or_else(|e| e.into_inner());
+                match &*slot {
+                    Some((_, threads)) => threads.clone(),
+                    None => {
+                        let size = self.max_concurrency.load(Ordering::SeqCst);
+                        let threads = rayon::ThreadPoolBuilder::new()
+                            .num_threads(size)
+                            .thread_name(move |i| format!("cyanea-{}-{i}", self.name))
+                            .build()
+                            .map_err(|e| {
+                                let msg = format!("failed to start {} pool: {e}", self.name);
+                                NifError::internal(msg)
+                            })?;
+                        let threads = Arc::new(threads);
+                        *slot = Some((size, threads.clone()));
+                        threads
+                    }
+                }
+            }
+        };
+        threads.spawn(move || {
+            let _slot = self.acquire();
+            task();
+        });
+        Ok(())
+    }
+
+    #[cfg(not(feature = "parallel"))]
+    fn execute(&'static self, task: impl FnOnce() + Send + 'static) -> Result<(), NifError> {
+        self.start_workers()?;
+        let (lock, queued) = &self.tasks;
+        lock.lock().unwrap_or_else(|e| e.into_inner()).push_back(Box::new(task));
+        queued.notify_one();
+        Ok(())
+    }
+
+    /// Start workers until there are `max_concurrency` of them.  Fails only
+    /// when no worker could be started at all.
+    #[cfg(not(feature = "parallel"))]
+    fn start_workers(&'static self) -> Result<(), NifError> {
+        let mut workers = self.workers.lock().unwrap_or_else(|e| e.into_inner());
+        while *workers < self.max_concurrency.load(Ordering::SeqCst) {
+            let started = std::thread::Builder::new()
+                .name(format!("cyanea-{}-{}", self.name, *workers))
+                .spawn(move || self.work());
+            match started {
+                Ok(_) => *workers += 1,
+                Err(e) if *workers == 0 => {
+                    return Err(NifError::internal(format!("failed to start job thread: {e}")));
+                }
+                Err(_) => break,
+            }
+        }
+        Ok(())
+    }
+
+    /// Worker loop: take the next queued job and run it once it has a slot.
+    #[cfg(not(feature = "parallel"))]
+    fn work(&self) {
+        let (lock, queued) = &self.tasks;
+        loop {
+            let task = {
+                let mut tasks = lock.lock().unwrap_or_else(|e| e.into_inner());
+                loop {
+                    match tasks.pop_front() {
+                        Some(task) => break task,
+                        None => tasks = queued.wait(tasks).unwrap_or_else(|e| e.into_inner()),
+                    }
+                }
+            };
+            let _slot = self.acquire();
+            task();
+        }
+    }
+
+    fn stats(&self) -> AsyncPoolStatsNif {
+        AsyncPoolStatsNif {
+            name: self.name.to_string(),
+            max_concurrency: self.max_concurrency.load(Ordering::SeqCst),
+            queued: self.queued.load(Ordering::SeqCst),
+            running: self.running.load(Ordering::SeqCst),
+        }
+    }
+}
+
+fn pool(id: PoolId) -> &'static Pool {
+    static POOLS: OnceLock<[Pool; 3]> = OnceLock::new();
+    let pools = POOLS.get_or_init(|| [Pool::new("align"), Pool::new("ml"), Pool::new("phylo")]);
+    &pools[id as usize]
+}
+
+/// Counts a job as running until dropped.
+struct Running<'p>(&'p Pool);
+
+impl<'p> Running<'p> {
+    fn start(pool: &'p Pool) -> Self {
+        pool.running.fetch_add(1, Ordering::SeqCst);
+        Self(pool)
+    }
+}
+
+impl Drop for Running<'_> {
+    fn drop(&mut self) {
+        self.0.running.fetch_sub(1, Ordering::SeqCst);
+    }
+}
+
+/// Run `f`, turning a panic into an `:internal` error so it neither aborts
+/// the VM (rayon aborts on a panicking task) nor loses the caller's result.
+fn catch_panic<T>(f: impl FnOnce() -> Result<T, NifError>) -> Result<T, NifError> {
+    panic::catch_unwind(AssertUnwindSafe(f)).unwrap_or_else(|payload| {
+        let message = payload
+            .downcast_ref::<&str>()
+            .copied()
+            .or_else(|| payload.downcast_ref::<String>().map(String::as_str))
+            .unwrap_or("unknown cause");
+        Err(NifError::internal(format!("job panicked: {message}")))
+    })
+}
+
+/// Queue `work` on a pool and return the reference its result will be
+/// tagged with.  `encode` turns the result into a term once the work is
+/// done, for results that need an environment (binaries, matrices).
+pub fn spawn_with<'a, R, W, E>(
+    env: Env<'a>,
+    id: PoolId,
+    cancel: Option<ResourceArc<CancelTokenResource>>,
+    work: W,
+    encode: E,
+) -> Result<Reference<'a>, NifError>
+where
+    R: Send + 'static,
+    W: FnOnce(&JobState) -> Result<R, NifError> + Send + 'static,
+    E: for<'b> FnOnce(Env<'b>, R) -> Result<Term<'b>, NifError> + Send + 'static,
+{
+    let pool = pool(id);
+    let job_ref = env.make_ref();
+    let caller = env.pid();
+    let mut owned = OwnedEnv::new();
+    let saved_ref = owned.save(job_ref);
+    let state = JobState::new(cancel);
+
+    pool.queued.fetch_add(1, Ordering::SeqCst);
+    let task = move || {
+        pool.queued.fetch_sub(1, Ordering::SeqCst);
+        let _running = Running::start(pool);
+        let result = catch_panic(|| state.check().and_then(|_| work(&state)));
+        let _ = owned.send_and_clear(&caller, |env| {
+            let result = result.and_then(|r| catch_panic(|| encode(env, r)));
+            (atoms::cyanea_result(), saved_ref.load(env), result).encode(env)
+        });
+    };
+    if let Err(e) = pool.execute(task) {
+        pool.queued.fetch_sub(1, Ordering::SeqCst);
+        return Err(e);
+    }
+    Ok(job_ref)
+}
+
+/// [`spawn_with`] for results that encode without an environment.
+pub fn spawn<'a, R, W>(
+    env: Env<'a>,
+    id: PoolId,
+    cancel: Option<ResourceArc<CancelTokenResource>>,
+    work: W,
+) -> Result<Reference<'a>, NifError>
+where
+    R: Encoder + Send + 'static,
+    W: FnOnce(&JobState) -> Result<R, NifError> + Send + 'static,
+{
+    spawn_with(env, id, cancel, work, |env, r| Ok(r.encode(env)))
+}
+
+#[rustler::nif]
+pub fn async_pool_stats(pool_name: String) -> Result<AsyncPoolStatsNif, NifError> {
+    Ok(pool(parse_pool(&pool_name)?).stats())
+}
+
+#[rustler::nif]
+pub fn async_pool_set_concurrency(
+    pool_name: String,
+    max_concurrency: usize,
+) -> Result<AsyncPoolStatsNif, NifError> {
+    if max_concurrency == 0 {
+        return Err(NifError::invalid("max_concurrency must be > 0"));
+    }
+    let pool = pool(parse_pool(&pool_name)?);
+    pool.set_concurrency(max_concurrency);
+    Ok(pool.stats())
+}
@@ -0,0 +1,537 @@
+//! Substitution matrices and affine-gap pairwise alignment.
+//!
+//! Matrices are read from NCBI-format text (the layout of BLOSUM, PAM,
+//! VTML and NUC.4.4 files): `#` comments, a header row of symbols, then one
+//! row per symbol.  A parsed matrix plus gap penalties is kept as a
+//! [`ScoringSchemeResource`], so repeated alignments reuse it instead of
+//! parsing again.  Gap penalties are negative scores, and a gap of length
+//! `L` scores `gap_open + (L - 1) * gap_extend`.
+
+use crate::align::parse_alignment_mode;
+use crate::bridge::{AlignmentResultNif, NifError, ScoringSchemeInfoNif};
+use cyanea_align::AlignmentMode;
+use rustler::ResourceArc;
+
+const NUC_4_4: &str = "\
+# NUC.4.4 (EDNAFULL): nucleotides with IUPAC ambiguity codes
+    A   T   G   C   S   W   R   Y   K   M   B   V   H   D   N
+A   5  -4  -4  -4  -4   1   1  -4  -4   1  -4  -1  -1  -1  -2
+T  -4   5  -4  -4  -4   1  -4   1   1  -4  -1  -4  -1  -1  -2
+G  -4  -4   5  -4   1  -4   1  -4   1  -4  -1  -1  -4  -1  -2
+C  -4  -4  -4   5   1  -4  -4   1  -4   1  -1  -1  -1  -4  -2
+S  -4  -4   1   1  -1  -4  -2  -2  -2  -2  -1  -1  -3  -3  -1
+W   1   1  -4  -4  -4  -1  -2  -2  -2  -2  -3  -3  -1  -1  -1
+R   1  -4   1  -4  -2  -2  -1  -4  -2  -2  -3  -1  -3  -1  -1
+Y  -4   1  -4   1  -2  -2  -4  -1  -2  -2  -1  -3  -1  -3  -1
+K  -4   1   1  -4  -2  -2  -2  -2  -1  -4  -1  -3  -3  -1  -1
+M   1  -4  -4   1  -2  -2  -2  -2  -4  -1  -3  -1  -1  -3  -1
+B  -4  -1  -1  -1  -1  -3  -3  -1  -1  -3  -1  -2  -2  -2  -1
+V  -1  -4  -1  -1  -1  -3  -1  -3  -3  -1  -2  -1  -2  -2  -1
+H  -1  -1  -4  -1  -3  -1  -3  -1  -3  -1  -2  -2  -1  -2  -1
+D  -1  -1  -1  -4  -3  -1  -1  -3  -1  -3  -2  -2  -2  -1  -1
+N  -2  -2  -2  -2  -1  -1  -1  -1  -1  -1  -1  -1  -1  -1  -1
+";
+
+/// Symbol order of the protein matrices taken from `cyanea_align`, as in
+/// NCBI's matrix files.
+const PROTEIN_SYMBOLS: &[u8] = b"ARNDCQEGHILKMFPSTWYVBZX*";
+
+/// Header symbols of nucleotide matrices; only these get the `U` -> `T`
+/// fallback, so a protein matrix never scores selenocysteine as threonine.
+const NUCLEOTIDE_SYMBOLS: &[u8] = b"ACGTURYSWKMBDHVN-*";
+
+/// Byte code for symbols a matrix does not cover.
+const UNMAPPED: u8 = u8::MAX;
+/// "Minus infinity" that survives adding a few penalties without overflow.
+pub(crate) const NEG_INF: i32 = i32::MIN / 4;
+
+// ===========================================================================
+// Scoring schemes
+// ===========================================================================
+
+/// A square substitution matrix over an arbitrary alphabet with affine gap
+/// penalties.  Symbols are matched case-insensitively; `U` falls back to
+/// `T` in nucleotide matrices without a `U` row.
+pub(crate) struct ScoringScheme {
+    pub name: String,
+    /// Matrix symbols in header order.
+    pub alphabet: Vec<u8>,
+    codes: [u8; 256],
+    scores: Vec<i32>,
+    pub gap_open: i32,
+    pub gap_extend: i32,
+}
+
+impl ScoringScheme {
+    /// Parse an NCBI-format matrix.
+    pub(crate) fn parse(name: &str, text: &str, gap_open: i32, gap_extend: i32) -> Result<Self, NifError> {
+        check_gaps(gap_open, gap_extend)?;
+        let mut lines = text
+            .lines()
+            .enumerate()
+            .map(|(i, line)| (i as u64 + 1, line.trim()))
+            .filter(|(_, line)| !line.is_empty() && !line.starts_with('#'));
+
+        let (header_line, header) = lines
+            .next()
+            .ok_or_else(|| NifError::parse("substitution matrix has no header row"))?;
+        let alphabet = header
+            .split_whitespace()
+            .enumerate()
+            .map(|(i, sym)| match sym.as_bytes() {
+                [b] => Ok(b.to_ascii_uppercase()),
+                _ => Err(NifError::parse(format!(
+                    "matrix column {} header {sym:?} is not a single symbol",
+                    i + 1
+                ))
+                .at_line(header_line)),
+            })
+            .collect::<Result<Vec<u8>, NifError>>()?;
+        let n = alphabet.len();
+        let codes = symbol_codes(&alphabet)?;
+
+        let mut scores = vec![0; n * n];
+        let mut seen = vec![false; n];
+        for (line_no, line) in lines {
+            let mut fields = line.split_whitespace();
+            let sym = fields.next().unwrap_or_default();
+            let row = match sym.as_bytes() {
+                [b] if codes[*b as usize] != UNMAPPED => codes[*b as usize] as usize,
+                _ => {
+                    return Err(NifError::parse(format!("row symbol {sym:?} is not in the matrix header"))
+                        .at_line(line_no))
+                }
+            };
+            let values = fields
+                .map(|f| {
+                    f.parse::<i32>()
+                        .map_err(|_| NifError::parse(format!("invalid score {f:?}")).at_line(line_no))
+                })
+                .collect::<Result<Vec<i32>, NifError>>()?;
+            if values.len() != n {
+                return Err(NifError::parse(format!(
+                    "row {sym} has {} scores, expected {n}",
+                    values.len()
+                ))
+                .at_line(line_no));
+            }
+            scores[row * n..(row + 1) * n].copy_from_slice(&values);
+            seen[row] = true;
+        }
+        if let Some(missing) = seen.iter().position(|&s| !s) {
+            return Err(NifError::parse(format!(
+                "matrix has no row for {:?}",
+                alphabet[missing] as char
+            )));
+        }
+
+        Ok(Self {
+            name: name.to_string(),
+            alphabet,
+            codes,
+            scores,
+            gap_open,
+            gap_extend,
+        })
+    }
+
+    /// A protein matrix from `cyanea_align`'s tables.
+    fn from_library(
+        name: &str,
+        matrix: &cyanea_align::SubstitutionMatrix,
+        gap_open: i32,
+        gap_extend: i32,
+    ) -> Result<Self, NifError> {
+        check_gaps(gap_open, gap_extend)?;
+        let scores = PROTEIN_SYMBOLS
+            .iter()
+            .flat_map(|&a| PROTEIN_SYMBOLS.iter().map(move |&b| i32::from(matrix.score(a, b))))
+            .collect();
+        Ok(Self {
+            name: name.to_string(),
+            alphabet: PROTEIN_SYMBOLS.to_vec(),
+            codes: symbol_codes(PROTEIN_SYMBOLS)?,
+            scores,
+            gap_open,
+            gap_extend,
+        })
+    }
+
+    /// The matrices `align_protein` accepts plus NUC.4.4; others (PAM30,
+    /// PAM70, the rest of the BLOSUM series, VTML) load from a file.
+    pub(crate) fn builtin(name: &str, gap_open: i32, gap_extend: i32) -> Result<Self, NifError> {
+        if matches!(name.to_ascii_lowercase().as_str(), "nuc.4.4" | "nuc44" | "ednafull") {
+            return Self::parse(name, NUC_4_4, gap_open, gap_extend);
+        }
+        match library_matrix(name) {
+            Some(library) => Self::from_library(name, &library, gap_open, gap_extend),
+            None => Err(NifError::not_found(format!(
+                "unknown built-in matrix: {name} (expected blosum62, blosum45, blosum80, pam250 \
+                 or nuc.4.4; load others from a file)"
+            ))),
+        }
+    }
+
+    /// Map a sequence to matrix indices.
+    pub(crate) fn encode(&self, seq: &[u8]) -> Result<Vec<u8>, NifError> {
+        seq.iter()
+            .enumerate()
+            .map(|(i, &b)| match self.codes[b as usize] {
+                UNMAPPED => Err(NifError::invalid(format!(
+                    "symbol {:?} at position {i} is not in matrix {}",
+                    b as char, self.name
+                ))),
+                code => Ok(code),
+            })
+            .collect()
+    }
+
+    /// Matrix index of one symbol, if the matrix covers it.
+    pub(crate) fn code(&self, symbol: u8) -> Option<u8> {
+        Some(self.codes[symbol as usize]).filter(|&code| code != UNMAPPED)
+    }
+
+    /// Whether the matrix scores `symbol` (case-insensitively).
+    pub(crate) fn covers(&self, symbol: u8) -> bool {
+        self.codes[symbol as usize] != UNMAPPED
+    }
+
+    /// The matrix's "any residue" symbol, `X` or `N`, if it has one.
+    pub(crate) fn wildcard(&self) -> Option<u8> {
+        [b'X', b'N'].into_iter().find(|&b| self.covers(b))
+    }
+
+    /// Score of two encoded symbols.
+    pub(crate) fn score(&self, a: u8, b: u8) -> i32 {
+        self.scores[a as usize * self.alphabet.len() + b as usize]
+    }
+
+    pub(crate) fn info(&self) -> ScoringSchemeInfoNif {
+        ScoringSchemeInfoNif {
+            name: self.name.clone(),
+            alphabet: String::from_utf8_lossy(&self.alphabet).into_owned(),
+            gap_open: self.gap_open,
+            gap_extend: self.gap_extend,
+            min_score: self.scores.iter().copied().min().unwrap_or_default(),
+            max_score: self.scores.iter().copied().max().unwrap_or_default(),
+        }
+    }
+}
+
+/// `cyanea_align`'s protein matrix called `name`, if it has one.
+pub(crate) fn library_matrix(name: &str) -> Option<cyanea_align::SubstitutionMatrix> {
+    match name.to_ascii_lowercase().as_str() {
+        "blosum62" => Some(cyanea_align::SubstitutionMatrix::blosum62()),
+        "blosum45" => Some(cyanea_align::SubstitutionMatrix::blosum45()),
+        "blosum80" => Some(cyanea_align::SubstitutionMatrix::blosum80()),
+        "pam250" => Some(cyanea_align::SubstitutionMatrix::pam250()),
+        _ => None,
+    }
+}
+
+fn check_gaps(gap_open: i32, gap_extend: i32) -> Result<(), NifError> {
+    if gap_open > 0 || gap_extend > 0 {
+        return Err(NifError::invalid(format!(
+            "gap penalties must be <= 0 (got open {gap_open}, extend {gap_extend})"
+        )));
+    }
+    Ok(())
+}
+
+/// Byte -> matrix index table for a header, upper and lower case.
+fn symbol_codes(alphabet: &[u8]) -> Result<[u8; 256], NifError> {
+    let n = alphabet.len();
+    if n == 0 || n >= UNMAPPED as usize {
+        return Err(NifError::parse(format!("matrix has {n} symbols")));
+    }
+    let mut codes = [UNMAPPED; 256];
+    for (i, &sym) in alphabet.iter().enumerate() {
+        if codes[sym as usize] != UNMAPPED {
+            return Err(NifError::parse(format!("symbol {:?} repeated in matrix header", sym as char)));
+        }
+        codes[sym as usize] = i as u8;
+        codes[sym.to_ascii_lowercase() as usize] = i as u8;
+    }
+    let nucleotide = alphabet.iter().all(|s| NUCLEOTIDE_SYMBOLS.contains(s));
+    if nucleotide && codes[b'U' as usize] == UNMAPPED && codes[b'T' as usize] != UNMAPPED {
+        codes[b'U' as usize] = codes[b'T' as usize];
+        codes[b'u' as usize] = codes[b'T' as usize];
+    }
+    Ok(codes)
+}
+
+/// A parsed scoring scheme shared across NIF calls.
+pub struct ScoringSchemeResource {
+    pub(crate) scheme: ScoringScheme,
+}
+
+#[rustler::resource_impl]
+impl rustler::Resource for ScoringSchemeResource {}
+
+// ===========================================================================
+// Affine-gap alignment (Gotoh)
+// ===========================================================================
+
+// Traceback bits per cell: where H came from, and whether E / F were
+// opened from H or extended.
+const FROM_DIAG: u8 = 0;
+const FROM_E: u8 = 1;
+const FROM_F: u8 = 2;
+const FROM_START: u8 = 3;
+const E_OPENED: u8 = 4;
+const F_OPENED: u8 = 8;
+
+/// Align `query` against `target`.  `E` gaps consume target bases
+/// (deletions from the query), `F` gaps consume query bases (insertions).
+/// Semiglobal mode leaves end gaps in both sequences unpenalized.
+pub(crate) fn align(
+    query: &[u8],
+    target: &[u8],
+    mode: &AlignmentMode,
+    scheme: &ScoringScheme,
+) -> Result<AlignmentResultNif, NifError> {
+    let (q, t) = (scheme.encode(query)?, scheme.encode(target)?);
+    let (n, m) = (q.len(), t.len());
+    let (open, extend) = (scheme.gap_open, scheme.gap_extend);
+    let local = matches!(mode, AlignmentMode::Local);
+    let global = matches!(mode, AlignmentMode::Global);
+    let gap = |len: usize| if len == 0 { 0 } else { open + (len as i32 - 1) * extend };
+
+    let width = m + 1;
+    let mut trace = vec![0u8; (n + 1) * width];
+    // Rolling rows of H and F; E is carried along the row.
+    let mut h_prev: Vec<i32> = (0..=m).map(|j| if global { gap(j) } else { 0 }).collect();
+    let mut f_prev = vec![NEG_INF; m + 1];
+    let mut h_row = vec![0; m + 1];
+    let mut f_row = vec![NEG_INF; m + 1];
+    for j in 1..=m {
+        trace[j] = if global { FROM_E | if j == 1 { E_OPENED } else { 0 } } else { FROM_START };
+    }
+    trace[0] = FROM_START;
+
+    let mut best = (if local { 0 } else { NEG_INF }, 0, 0);
+    for i in 1..=n {
+        h_row[0] = if global { gap(i) } else { 0 };
+        trace[i * width] = if global { FROM_F | if i == 1 { F_OPENED } else { 0 } } else { FROM_START };
+        let mut e = NEG_INF;
+        for j in 1..=m {
+            let mut bits = 0;
+            let (e_open, e_ext) = (h_row[j - 1] + open, e + extend);
+            e = if e_open >= e_ext {
+                bits |= E_OPENED;
+                e_open
+            } else {
+                e_ext
+            };
+            let (f_open, f_ext) = (h_prev[j] + open, f_prev[j] + extend);
+            f_row[j] = if f_open >= f_ext {
+                bits |= F_OPENED;
+                f_open
+            } else {
+                f_ext
+            };
+            let diag = h_prev[j - 1] + scheme.score(q[i - 1], t[j - 1]);
+            let (mut h, mut from) = (diag, FROM_DIAG);
+            if e > h {
+                (h, from) = (e, FROM_E);
+            }
+            if f_row[j] > h {
+                (h, from) = (f_row[j], FROM_F);
+            }
+            if local && h <= 0 {
+                (h, from) = (0, FROM_START);
+            }
+            h_row[j] = h;
+            trace[i * width + j] = bits | from;
+            let candidate = local || (!global && (i == n || j == m));
+            if candidate && h > best.0 {
+                best = (h, i, j);
+            }
+        }
+        std::mem::swap(&mut h_prev, &mut h_row);
+        std::mem::swap(&mut f_prev, &mut f_row);
+    }
+    if global {
+        best = (h_prev[m], n, m);
+    } else if !local && (n == 0 || m == 0) {
+        best = (0, n, m);
+    }
+
+    // Traceback from the best cell.
+    let (score, mut i, mut j) = best;
+    let (query_end, target_end) = (i, j);
+    let (mut aq, mut at) = (Vec::new(), Vec::new());
+    let mut state = FROM_DIAG;
+    while i > 0 || j > 0 {
+        let bits = trace[i * width + j];
+        if state == FROM_DIAG {
+            match bits & 3 {
+                FROM_START => break,
+                FROM_E => state = FROM_E,
+                FROM_F => state = FROM_F,
+                _ => {
+                    aq.push(query[i - 1]);
+                    at.push(target[j - 1]);
+                    i -= 1;
+                    j -= 1;
+                    continue;
+                }
+            }
+            // A global boundary cell is a gap with no H to come back to.
+            if i == 0 || j == 0 {
+                state = if i == 0 { FROM_E } else { FROM_F };
+            }
+        }
+        if state == FROM_E {
+            aq.push(b'-');
+            at.push(target[j - 1]);
+            if bits & E_OPENED != 0 || i == 0 {
+                state = FROM_DIAG;
+            }
+            j -= 1;
+        } else {
+            aq.push(query[i - 1]);
+            at.push(b'-');
+            if bits & F_OPENED != 0 || j == 0 {
+                state = FROM_DIAG;
+            }
+            i -= 1;
+        }
+    }
+    aq.reverse();
+    at.reverse();
+    Ok(alignment_result(score, aq, at, (i, query_end), (j, target_end)))
+}
+
+/// Summarize two gapped rows; the CIGAR uses `M`/`I`/`D` with the query as
+/// the read.
+fn alignment_result(
+    score: i32,
+    aligned_query: Vec<u8>,
+    aligned_target: Vec<u8>,
+    (query_start, query_end): (usize, usize),
+    (target_start, target_end): (usize, usize),
+) -> AlignmentResultNif {
+    let (mut matches, mut mismatches, mut gaps) = (0, 0, 0);
+    let mut cigar = String::new();
+    let mut run: Option<(char, usize)> = None;
+    for (&a, &b) in aligned_query.iter().zip(&aligned_target) {
+        let op = match (a, b) {
+            (b'-', _) => {
+                gaps += 1;
+                'D'
+            }
+            (_, b'-') => {
+                gaps += 1;
+                'I'
+            }
+            _ => {
+                if a.eq_ignore_ascii_case(&b) {
+                    matches += 1;
+                } else {
+                    mismatches += 1;
+                }
+                'M'
+            }
+        };
+        run = match run {
+            Some((prev, len)) if prev == op => Some((op, len + 1)),
+            Some((prev, len)) => {
+                cigar.push_str(&format!("{len}{prev}"));
+                Some((op, 1))
+            }
+            None => Some((op, 1)),
+        };
+    }
+    if let Some((op, len)) = run {
+        cigar.push_str(&format!("{len}{op}"));
+    }
+    let alignment_length = aligned_query.len();
+    AlignmentResultNif {
+        score,
+        aligned_query,
+        aligned_target,
+        query_start,
+        query_end,
+        target_start,
+        target_end,
+        cigar,
+        identity: if alignment_length == 0 { 0.0 } else { matches as f64 / alignment_length as f64 },
+        num_matches: matches,
+        num_mismatches: mismatches,
+        num_gaps: gaps,
+        alignment_length,
+    }
+}
+
+// ===========================================================================
+// NIFs
+// ===========================================================================
+
+/// A built-in matrix: `blosum62`, `blosum45`, `blosum80`, `pam250` or
+/// `nuc.4.4` (IUPAC nucleotides).
+#[rustler::nif]
+pub fn scoring_scheme_builtin(
+    name: String,
+    gap_open: i32,
+    gap_extend: i32,
+) -> Result<ResourceArc<ScoringSchemeResource>, NifError> {
+    let scheme = ScoringScheme::builtin(&name, gap_open, gap_extend)?;
+    Ok(ResourceArc::new(ScoringSchemeResource { scheme }))
+}
+
+/// Load an NCBI-format matrix file, named after the file.
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn scoring_scheme_load(
+    path: String,
+    gap_open: i32,
+    gap_extend: i32,
+) -> Result<ResourceArc<ScoringSchemeResource>, NifError> {
+    let text = std::fs::read_to_string(&path).map_err(|e| NifError::from(e).with_file(&path))?;
+    let name = std::path::Path::new(&path)
+        .file_name()
+        .map(|f| f.to_string_lossy().into_owned())
+        .unwrap_or_else(|| path.clone());
+    let scheme = ScoringScheme::parse(&name, &text, gap_open, gap_extend).map_err(|e| e.with_file(&path))?;
+    Ok(ResourceArc::new(ScoringSchemeResource { scheme }))
+}
+
+/// Parse an inline NCBI-format matrix.
+#[rustler::nif]
+pub fn scoring_scheme_parse(
+    name: String,
+    text: String,
+    gap_open: i32,
+    gap_extend: i32,
+) -> Result<ResourceArc<ScoringSchemeResource>, NifError> {
+    let scheme = ScoringScheme::parse(&name, &text, gap_open, gap_extend)?;
+    Ok(ResourceArc::new(ScoringSchemeResource { scheme }))
+}
+
+#[rustler::nif]
+pub fn scoring_scheme_info(scheme: ResourceArc<ScoringSchemeResource>) -> ScoringSchemeInfoNif {
+    scheme.scheme.info()
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn align_with_scheme(
+    query: Vec<u8>,
+    target: Vec<u8>,
+    mode: String,
+    scheme: ResourceArc<ScoringSchemeResource>,
+) -> Result<AlignmentResultNif, NifError> {
+    align(&query, &target, &parse_alignment_mode(&mode)?, &scheme.scheme)
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn align_batch_with_scheme(
+    pairs: Vec<(Vec<u8>, Vec<u8>)>,
+    mode: String,
+    scheme: ResourceArc<ScoringSchemeResource>,
+) -> Result<Vec<AlignmentResultNif>, NifError> {
+    let mode = parse_alignment_mode(&mode)?;
+    pairs
+        .iter()
+        .map(|(query, target)| align(query, target, &mode, &scheme.scheme))
+        .collect()
+}
@@ -0,0 +1,399 @@
+//! Database search: one query against every record of a FASTA file.
+//!
+//! Records are streamed one at a time and scored with `cyanea_align`'s SIMD
+//! kernel when the scheme has a library form (one match and one mismatch
+//! score, or one of the library's protein matrices), otherwise with a
+//! linear-space Smith-Waterman over the matrix.  Only the best `max_hits`
+//! records are kept; once the database has been read, each is traced back
+//! over just the span of its best local alignment, found by a forward and a
+//! reverse score pass.  Significance follows Karlin-Altschul:
+//! `E = K·m·n·e^(−λS)` with `S` the score of the reported alignment, `m` the
+//! query length and `n` the total database length.
+
+use crate::bridge::{DbSearchHitNif, DbSearchNif, DbSearchOptionsNif, NifError};
+use crate::scoring::{align, library_matrix, ScoringScheme, ScoringSchemeResource, NEG_INF};
+use crate::seq::FastaReader;
+use crate::to_nif_error;
+use cyanea_align::AlignmentMode;
+use rustler::ResourceArc;
+use std::cmp::Reverse;
+use std::collections::BinaryHeap;
+use std::ops::Range;
+
+// ===========================================================================
+// Karlin-Altschul statistics
+// ===========================================================================
+
+/// Gapped BLOSUM62 parameters from BLAST as `(gap_open, gap_extend, λ, K)`,
+/// in this crate's gap scores: BLAST's "open 11, extend 1" charges 12 for a
+/// one-residue gap, so it is `(-12, -1)` here.
+const BLOSUM62_GAPPED: [(i32, i32, f64, f64); 11] = [
+    (-13, -2, 0.297, 0.082),
+    (-12, -2, 0.291, 0.075),
+    (-11, -2, 0.279, 0.058),
+    (-10, -2, 0.264, 0.045),
+    (-9, -2, 0.239, 0.027),
+    (-8, -2, 0.201, 0.012),
+    (-14, -1, 0.292, 0.071),
+    (-13, -1, 0.283, 0.059),
+    (-12, -1, 0.267, 0.041),
+    (-11, -1, 0.243, 0.024),
+    (-10, -1, 0.206, 0.010),
+];
+
+/// Robinson & Robinson (1991) amino acid background frequencies.
+const ROBINSON_FREQUENCIES: [(u8, f64); 20] = [
+    (b'A', 0.07805),
+    (b'C', 0.01925),
+    (b'D', 0.05364),
+    (b'E', 0.06295),
+    (b'F', 0.03856),
+    (b'G', 0.07377),
+    (b'H', 0.02199),
+    (b'I', 0.05142),
+    (b'K', 0.05744),
+    (b'L', 0.09019),
+    (b'M', 0.02243),
+    (b'N', 0.04487),
+    (b'P', 0.05203),
+    (b'Q', 0.04264),
+    (b'R', 0.05129),
+    (b'S', 0.07120),
+    (b'T', 0.05841),
+    (b'V', 0.06441),
+    (b'W', 0.01330),
+    (b'Y', 0.03216),
+];
+
+/// Terms of the series for `K`; later terms are negligible.
+const K_SERIES_TERMS: usize = 100;
+
+#[derive(Debug, Clone, Copy)]
+pub(crate) struct KarlinParams {
+    pub lambda: f64,
+    pub k: f64,
+}
+
+/// Background residue frequencies for a matrix: Robinson & Robinson when it
+/// covers the 20 amino acids, uniform ACGT when it covers those, otherwise
+/// uniform over its symbols other than `*`.
+fn background(scheme: &ScoringScheme) -> Vec<(u8, f64)> {
+    if ROBINSON_FREQUENCIES.iter().all(|&(aa, _)| scheme.covers(aa)) {
+        return ROBINSON_FREQUENCIES.to_vec();
+    }
+    let symbols: Vec<u8> = if b"ACGT".iter().all(|&b| scheme.covers(b)) {
+        b"ACGT".to_vec()
+    } else {
+        scheme.alphabet.iter().copied().filter(|&b| b != b'*').collect()
+    };
+    let p = 1.0 / symbols.len() as f64;
+    symbols.into_iter().map(|b| (b, p)).collect()
+}
+
+fn gcd(a: i32, b: i32) -> i32 {
+    if b == 0 {
+        a.abs()
+    } else {
+        gcd(b, a % b)
+    }
+}
+
+/// Ungapped λ and K for a matrix under its background frequencies
+/// (Karlin & Altschul 1990).
+pub(crate) fn ungapped_params(scheme: &ScoringScheme) -> Result<KarlinParams, NifError> {
+    let freqs: Vec<(u8, f64)> = background(scheme)
+        .into_iter()
+        .map(|(b, p)| Ok((scheme.encode(&[b])?[0], p)))
+        .collect::<Result<_, NifError>>()?;
+    let total: f64 = freqs.iter().map(|&(_, p)| p).sum();
+
+    // Distribution of the score of one aligned pair.
+    let pairs: Vec<(i32, f64)> = freqs
+        .iter()
+        .flat_map(|&(a, pa)| freqs.iter().map(move |&(b, pb)| (scheme.score(a, b), pa * pb / (total * total))))
+        .collect();
+    let low = pairs.iter().map(|&(s, _)| s).min().unwrap_or_default();
+    let high = pairs.iter().map(|&(s, _)| s).max().unwrap_or_default();
+    let expected: f64 = pairs.iter().map(|&(s, p)| s as f64 * p).sum();
+    if high <= 0 || expected >= 0.0 {
+        return Err(NifError::invalid(format!(
+            "matrix {} needs a positive score and a negative expected score for E-values; pass lambda and k",
+            scheme.name
+        )));
+    }
+    let delta = pairs.iter().fold(0, |g, &(s, _)| gcd(g, s));
+    let (low, high) = (low / delta, high / delta);
+    let mut probs = vec![0.0; (high - low + 1) as usize];
+    for &(s, p) in &pairs {
+        probs[(s / delta - low) as usize] += p;
+    }
+    let scores = || (low..=high).zip(probs.iter().copied());
+
+    // λ is the positive root of Σ p(s)·e^(λs) = 1.
+    let excess = |lambda: f64| scores().map(|(s, p)| p * (lambda * s as f64).exp()).sum::<f64>() - 1.0;
+    let mut upper = 1.0;
+    while excess(upper) <= 0.0 {
+        upper *= 2.0;
+    }
+    let mut lower = 0.0;
+    for _ in 0..100 {
+        let mid = (lower + upper) / 2.0;
+        if excess(mid) > 0.0 {
+            upper = mid;
+        } else {
+            lower = mid;
+        }
+    }
+    let lambda = (lower + upper) / 2.0;
+
+    // K = e^(−2σ) / ((H/λ)·(1 − e^(−λ))), with σ summed over the score
+    // distributions of 1, 2, ... aligned pairs.
+    let h_over_lambda: f64 = scores().map(|(s, p)| s as f64 * p * (lambda * s as f64).exp()).sum();
+    let mut sigma = 0.0;
+    let mut dist = probs.clone();
+    let mut dist_low = low;
+    for k in 1..=K_SERIES_TERMS {
+        let term: f64 = dist
+            .iter()
+            .enumerate()
+            .map(|(i, &p)| {
+                let s = dist_low + i as i32;
+                if s < 0 {
+                    p * (lambda * s as f64).exp()
+                } else {
+                    p
+                }
+            })
+            .sum();
+        sigma += term / k as f64;
+        let mut next = vec![0.0; dist.len() + probs.len() - 1];
+        for (i, &p) in dist.iter().enumerate() {
+            for (j, &q) in probs.iter().enumerate() {
+                next[i + j] += p * q;
+            }
+        }
+        dist = next;
+        dist_low += low;
+    }
+    let k = (-2.0 * sigma).exp() / (h_over_lambda * (1.0 - (-lambda).exp()));
+
+    Ok(KarlinParams {
+        lambda: lambda / delta as f64,
+        k,
+    })
+}
+
+/// λ and K for a search: caller overrides, BLAST's gapped BLOSUM62 values,
+/// or the ungapped values (which overstate significance for gapped hits
+/// unless gaps are expensive).  Also names the source.
+fn karlin_params(
+    scheme: &ScoringScheme,
+    lambda: Option<f64>,
+    k: Option<f64>,
+) -> Result<(KarlinParams, &'static str), NifError> {
+    match (lambda, k) {
+        (Some(lambda), Some(k)) if lambda > 0.0 && k > 0.0 => return Ok((KarlinParams { lambda, k }, "custom")),
+        (None, None) => {}
+        _ => return Err(NifError::invalid("lambda and k must be given together, and be positive")),
+    }
+    if scheme.name.eq_ignore_ascii_case("blosum62") {
+        if let Some(&(_, _, lambda, k)) = BLOSUM62_GAPPED
+            .iter()
+            .find(|&&(open, extend, _, _)| open == scheme.gap_open && extend == scheme.gap_extend)
+        {
+            return Ok((KarlinParams { lambda, k }, "gapped"));
+        }
+    }
+    Ok((ungapped_params(scheme)?, "ungapped"))
+}
+
+// ===========================================================================
+// Scoring
+// ===========================================================================
+
+/// The scheme in `cyanea_align`'s terms, so records can be scored with its
+/// SIMD kernel: a matrix with one match and one mismatch score carries over
+/// as a simple scheme, and one of the library's protein matrices as a
+/// substitution scheme, both with the scheme's gap penalties.  Other
+/// matrices have no library form.
+fn library_scheme(scheme: &ScoringScheme) -> Option<cyanea_align::ScoringScheme> {
+    let n = scheme.alphabet.len() as u8;
+    if n < 2 {
+        return None;
+    }
+    let (matched, mismatched) = (scheme.score(0, 0), scheme.score(0, 1));
+    let uniform = (0..n).all(|a| {
+        (0..n).all(|b| scheme.score(a, b) == if a == b { matched } else { mismatched })
+    });
+    let (open, extend) = (scheme.gap_open, scheme.gap_extend);
+    if uniform {
+        return cyanea_align::ScoringMatrix::new(matched, mismatched, open, extend)
+            .ok()
+            .map(cyanea_align::ScoringScheme::Simple);
+    }
+    // Matched by name, then score by score: a matrix file may reuse a name.
+    let mut matrix = library_matrix(&scheme.name)?;
+    let symbols = || scheme.alphabet.iter().copied().zip(0..n);
+    let same = symbols().all(|(a, i)| {
+        symbols().all(|(b, j)| scheme.score(i, j) == i32::from(matrix.score(a, b)))
+    });
+    if !same {
+        return None;
+    }
+    matrix.gap_open = open;
+    matrix.gap_extend = extend;
+    Some(cyanea_align::ScoringScheme::Substitution(matrix))
+}
+
+/// Best local score of two encoded sequences and the (exclusive) end of an
+/// alignment reaching it, in linear space.
+fn local_end(query: &[u8], target: &[u8], scheme: &ScoringScheme) -> (i32, usize, usize) {
+    let (open, extend) = (scheme.gap_open, scheme.gap_extend);
+    let mut h = vec![0; target.len() + 1];
+    let mut f = vec![NEG_INF; target.len() + 1];
+    let mut best = (0, 0, 0);
+    for (i, &q) in query.iter().enumerate() {
+        let (mut diag, mut left, mut e) = (0, 0, NEG_INF);
+        for j in 1..=target.len() {
+            e = (e + extend).max(left + open);
+            f[j] = (f[j] + extend).max(h[j] + open);
+            let cell = (diag + scheme.score(q, target[j - 1])).max(e).max(f[j]).max(0);
+            diag = h[j];
+            h[j] = cell;
+            left = cell;
+            if cell > best.0 {
+                best = (cell, i + 1, j);
+            }
+        }
+    }
+    best
+}
+
+/// Query and target ranges of the best local alignment: a forward pass
+/// finds where it ends, a pass over the reversed prefixes where it starts.
+fn local_span(query: &[u8], target: &[u8], scheme: &ScoringScheme) -> (Range<usize>, Range<usize>) {
+    let (_, query_end, target_end) = local_end(query, target, scheme);
+    let reversed = |s: &[u8]| s.iter().rev().copied().collect::<Vec<u8>>();
+    let (_, query_len, target_len) =
+        local_end(&reversed(&query[..query_end]), &reversed(&target[..target_end]), scheme);
+    (query_end - query_len..query_end, target_end - target_len..target_end)
+}
+
+// ===========================================================================
+// NIFs
+// ===========================================================================
+
+/// Search `query` against every record of a FASTA file (plain or gzipped)
+/// under a scoring scheme.  Symbols a matrix lacks in database records are
+/// scored as its wildcard (`X` / `N`).  Hits are ranked by score, ties in
+/// file order.
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn search_fasta(
+    query: Vec<u8>,
+    db_path: String,
+    scheme: ResourceArc<ScoringSchemeResource>,
+    options: DbSearchOptionsNif,
+) -> Result<DbSearchNif, NifError> {
+    let scheme = &scheme.scheme;
+    if query.is_empty() {
+        return Err(NifError::invalid("query is empty"));
+    }
+    if options.max_hits == 0 {
+        return Err(NifError::invalid("max_hits must be at least 1"));
+    }
+    let (params, statistics) = karlin_params(scheme, options.lambda, options.k)?;
+    let encoded_query = scheme.encode(&query)?;
+    let library = library_scheme(scheme);
+    let library_query = query.to_ascii_uppercase();
+
+    // Min-heap on (score, file order): the root is the hit to drop next.
+    let mut kept: BinaryHeap<(Reverse<i32>, usize, String, String, Vec<u8>, Vec<u8>)> =
+        BinaryHeap::new();
+    let mut reader = FastaReader::open(&db_path)?;
+    let (mut db_records, mut db_length) = (0usize, 0usize);
+    while let Some(record) = reader.next_record()? {
+        let index = db_records;
+        db_records += 1;
+        db_length += record.sequence.len();
+        let sequence = match scheme.wildcard() {
+            Some(wildcard) => record
+                .sequence
+                .iter()
+                .map(|&b| if scheme.covers(b) { b } else { wildcard })
+                .collect(),
+            None => record.sequence,
+        };
+        let target = scheme
+            .encode(&sequence)
+            .map_err(|e| NifError::invalid(format!("record {}: {}", record.name, e.message)).with_file(&db_path))?;
+        let score = match &library {
+            Some(library) => {
+                // A band as wide as both sequences leaves the kernel unbanded.
+                let width = library_query.len() + sequence.len();
+                cyanea_align::simd::banded_score_only(
+                    &library_query,
+                    &sequence.to_ascii_uppercase(),
+                    library,
+                    width,
+                    AlignmentMode::Local,
+                )
+                .map_err(to_nif_error)?
+            }
+            None => local_end(&encoded_query, &target, scheme).0,
+        };
+        if score <= 0 {
+            continue;
+        }
+        if kept.len() == options.max_hits {
+            match kept.peek() {
+                Some(&(Reverse(worst), ..)) if score > worst => {
+                    kept.pop();
+                }
+                _ => continue,
+            }
+        }
+        kept.push((Reverse(score), index, record.name, record.description, sequence, target));
+    }
+
+    let search_space = encoded_query.len() as f64 * db_length as f64;
+    let mut hits = kept
+        .into_sorted_vec()
+        .into_iter()
+        .map(|(_, _, record, description, sequence, target)| {
+            // Full traceback only over the aligned span, not query x record.
+            let (q, t) = local_span(&encoded_query, &target, scheme);
+            let mut alignment =
+                align(&query[q.clone()], &sequence[t.clone()], &AlignmentMode::Local, scheme)?;
+            alignment.query_start += q.start;
+            alignment.query_end += q.start;
+            alignment.target_start += t.start;
+            alignment.target_end += t.start;
+            // Statistics use the reported alignment's score, not the ranking
+            // kernel's, so the two can never disagree in the output.
+            let score = alignment.score;
+            let evalue = params.k * search_space * (-params.lambda * score as f64).exp();
+            Ok((evalue <= options.max_evalue).then(|| DbSearchHitNif {
+                record,
+                description,
+                length: sequence.len(),
+                score,
+                bit_score: (params.lambda * score as f64 - params.k.ln()) / std::f64::consts::LN_2,
+                evalue,
+                alignment,
+            }))
+        })
+        .filter_map(Result::transpose)
+        .collect::<Result<Vec<_>, NifError>>()?;
+    // Stable, so ties stay in file order.
+    hits.sort_by_key(|hit| Reverse(hit.score));
+
+    Ok(DbSearchNif {
+        query_length: query.len(),
+        db_records,
+        db_length,
+        lambda: params.lambda,
+        k: params.k,
+        statistics: statistics.to_string(),
+        hits,
+    })
+}
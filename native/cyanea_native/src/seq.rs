@@ -2,57 +2,60 @@
 
 use crate::bridge::*;
 use crate::to_nif_error;
-
+use rustler::{Binary, Env, OwnedBinary, ResourceArc};
+use std::fs::File;
+use std::io::{BufRead, BufReader, Read, Seek, SeekFrom, Write};
+use std::sync::Mutex;
 
 // ===========================================================================
 // Existing NIFs
 // ===========================================================================
 
 #[rustler::nif(schedule = "DirtyCpu")]
-pub fn fasta_stats(path: String) -> Result<FastaStatsNif, String> {
+pub fn fasta_stats(path: String) -> Result<FastaStatsNif, NifError> {
     cyanea_seq::parse_fasta_stats(&path)
         .map(FastaStatsNif::from)
         .map_err(to_nif_error)
 }
 
 #[rustler::nif]
-pub fn validate_dna(data: Vec<u8>) -> Result<Vec<u8>, String> {
+pub fn validate_dna(data: Vec<u8>) -> Result<Vec<u8>, NifError> {
     let seq = cyanea_seq::DnaSequence::new(&data).map_err(to_nif_error)?;
     Ok(seq.into_bytes())
 }
 
 #[rustler::nif]
-pub fn validate_rna(data: Vec<u8>) -> Result<Vec<u8>, String> {
+pub fn validate_rna(data: Vec<u8>) -> Result<Vec<u8>, NifError> {
     let seq = cyanea_seq::RnaSequence::new(&data).map_err(to_nif_error)?;
     Ok(seq.into_bytes())
 }
 
 #[rustler::nif]
-pub fn validate_protein(data: Vec<u8>) -> Result<Vec<u8>, String> {
+pub fn validate_protein(data: Vec<u8>) -> Result<Vec<u8>, NifError> {
     let seq = cyanea_seq::ProteinSequence::new(&data).map_err(to_nif_error)?;
     Ok(seq.into_bytes())
 }
 
 #[rustler::nif]
-pub fn dna_reverse_complement(data: Vec<u8>) -> Result<Vec<u8>, String> {
+pub fn dna_reverse_complement(data: Vec<u8>) -> Result<Vec<u8>, NifError> {
     let seq = cyanea_seq::DnaSequence::new(&data).map_err(to_nif_error)?;
     Ok(seq.reverse_complement().into_bytes())
 }
 
 #[rustler::nif]
-pub fn dna_transcribe(data: Vec<u8>) -> Result<Vec<u8>, String> {
+pub fn dna_transcribe(data: Vec<u8>) -> Result<Vec<u8>, NifError> {
     let seq = cyanea_seq::DnaSequence::new(&data).map_err(to_nif_error)?;
     Ok(seq.transcribe().into_bytes())
 }
 
 #[rustler::nif]
-pub fn dna_gc_content(data: Vec<u8>) -> Result<f64, String> {
+pub fn dna_gc_content(data: Vec<u8>) -> Result<f64, NifError> {
     let seq = cyanea_seq::DnaSequence::new(&data).map_err(to_nif_error)?;
     Ok(seq.gc_content())
 }
 
 #[rustler::nif]
-pub fn rna_translate(data: Vec<u8>) -> Result<Vec<u8>, String> {
+pub fn rna_translate(data: Vec<u8>) -> Result<Vec<u8>, NifError> {
     let seq = cyanea_seq::RnaSequence::new(&data).map_err(to_nif_error)?;
     seq.translate()
         .map(|p| p.into_bytes())
@@ -60,32 +63,40 @@ pub fn rna_translate(data: Vec<u8>) -> Result<Vec<u8>, String> {
 }
 
 #[rustler::nif]
-pub fn sequence_kmers(data: Vec<u8>, k: usize) -> Result<Vec<Vec<u8>>, String> {
-    let seq = cyanea_seq::DnaSequence::new(&data).map_err(to_nif_error)?;
-    let kmers = seq
-        .kmers(k)
-        .map_err(to_nif_error)?
-        .map(|kmer| kmer.to_vec())
-        .collect();
-    Ok(kmers)
+pub fn sequence_kmers(data: Vec<u8>, k: usize, alphabet: String) -> Result<Vec<Vec<u8>>, NifError> {
+    let alphabet = parse_seq_alphabet(&alphabet)?;
+    if alphabet == SeqAlphabet::Dna {
+        let seq = cyanea_seq::DnaSequence::new(&data).map_err(to_nif_error)?;
+        let kmers = seq
+            .kmers(k)
+            .map_err(to_nif_error)?
+            .map(|kmer| kmer.to_vec())
+            .collect();
+        return Ok(kmers);
+    }
+    if k == 0 {
+        return Err(NifError::invalid("k must be > 0"));
+    }
+    let seq = alphabet.normalize(&data)?;
+    Ok(seq.windows(k).map(|kmer| kmer.to_vec()).collect())
 }
 
 #[rustler::nif(schedule = "DirtyCpu")]
-pub fn parse_fastq(path: String) -> Result<Vec<FastqRecordNif>, String> {
+pub fn parse_fastq(path: String) -> Result<Vec<FastqRecordNif>, NifError> {
     cyanea_seq::parse_fastq_file(&path)
         .map(|records| records.into_iter().map(FastqRecordNif::from).collect())
         .map_err(to_nif_error)
 }
 
 #[rustler::nif(schedule = "DirtyCpu")]
-pub fn fastq_stats(path: String) -> Result<FastqStatsNif, String> {
+pub fn fastq_stats(path: String) -> Result<FastqStatsNif, NifError> {
     cyanea_seq::parse_fastq_stats(&path)
         .map(FastqStatsNif::from)
         .map_err(to_nif_error)
 }
 
 #[rustler::nif]
-pub fn protein_molecular_weight(data: Vec<u8>) -> Result<f64, String> {
+pub fn protein_molecular_weight(data: Vec<u8>) -> Result<f64, NifError> {
     let seq = cyanea_seq::ProteinSequence::new(&data).map_err(to_nif_error)?;
     Ok(seq.molecular_weight())
 }
@@ -104,21 +115,537 @@ pub fn myers_search(text: Vec<u8>, pattern: Vec<u8>, max_dist: usize) -> Vec<(us
     cyanea_seq::myers_bitparallel(&text, &pattern, max_dist)
 }
 
+// ===========================================================================
+// FASTA records & .fai index
+// ===========================================================================
+
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn parse_fasta(path: String) -> Result<Vec<FastaRecordNif>, NifError> {
+    let mut reader = FastaReader::open(&path)?;
+    let mut records = Vec::new();
+    while let Some(record) = reader.next_record()? {
+        records.push(record);
+    }
+    Ok(records)
+}
+
+/// Record-at-a-time FASTA reader (plain or gzipped), for NIFs that scan
+/// whole references without holding every record in memory.
+pub(crate) struct FastaReader {
+    path: String,
+    lines: Box<dyn BufRead + Send>,
+    buf: Vec<u8>,
+    line_no: u64,
+    pending: Option<FastaRecordNif>,
+}
+
+impl FastaReader {
+    pub(crate) fn open(path: &str) -> Result<Self, NifError> {
+        Ok(Self {
+            path: path.to_string(),
+            lines: crate::io::open_text_reader(path)?,
+            buf: Vec::new(),
+            line_no: 0,
+            pending: None,
+        })
+    }
+
+    pub(crate) fn next_record(&mut self) -> Result<Option<FastaRecordNif>, NifError> {
+        loop {
+            self.buf.clear();
+            let n = self
+                .lines
+                .read_until(b'\n', &mut self.buf)
+                .map_err(|e| NifError::from(e).with_file(&self.path).at_line(self.line_no + 1))?;
+            if n == 0 {
+                return Ok(self.pending.take());
+            }
+            self.line_no += 1;
+            let line = self.buf.trim_ascii_end();
+            if let Some(header) = line.strip_prefix(b">") {
+                let header = String::from_utf8_lossy(header);
+                let (name, description) = header
+                    .trim()
+                    .split_once(char::is_whitespace)
+                    .map(|(n, d)| (n.to_string(), d.trim().to_string()))
+                    .unwrap_or_else(|| (header.trim().to_string(), String::new()));
+                let record = FastaRecordNif {
+                    name,
+                    description,
+                    sequence: Vec::new(),
+                };
+                if let Some(done) = self.pending.replace(record) {
+                    return Ok(Some(done));
+                }
+            } else if !line.is_empty() && !line.starts_with(b";") {
+                let record = self.pending.as_mut().ok_or_else(|| {
+                    NifError::parse("sequence data before first header")
+                        .with_file(&self.path)
+                        .at_line(self.line_no)
+                })?;
+                record
+                    .sequence
+                    .extend(line.iter().filter(|b| !b.is_ascii_whitespace()));
+            }
+        }
+    }
+}
+
+fn fai_path(path: &str) -> String {
+    format!("{path}.fai")
+}
+
+/// Scan an uncompressed FASTA file and compute its `.fai` entries.
+fn build_fai(path: &str) -> Result<Vec<FaiEntryNif>, NifError> {
+    let file = File::open(path).map_err(|e| NifError::from(e).with_file(path))?;
+    let mut reader = BufReader::new(file);
+    let mut entries: Vec<FaiEntryNif> = Vec::new();
+    // Set once a record has a line shorter than its line length; any
+    // further sequence line in that record breaks random access.
+    let mut short_line_seen = false;
+    let mut offset = 0u64;
+    let mut buf = Vec::new();
+    loop {
+        buf.clear();
+        let n = reader
+            .read_until(b'\n', &mut buf)
+            .map_err(|e| NifError::from(e).with_file(path).at_offset(offset))? as u64;
+        if n == 0 {
+            break;
+        }
+        if offset == 0 && buf.starts_with(&[0x1f, 0x8b]) {
+            return Err(
+                NifError::unsupported("cannot index a compressed FASTA; decompress it first")
+                    .with_file(path),
+            );
+        }
+        offset += n;
+        if let Some(header) = buf.strip_prefix(b">") {
+            let name = String::from_utf8_lossy(header.trim_ascii())
+                .split_whitespace()
+                .next()
+                .unwrap_or_default()
+                .to_string();
+            if name.is_empty() {
+                return Err(NifError::parse("empty sequence name")
+                    .with_file(path)
+                    .at_offset(offset - n));
+            }
+            entries.push(FaiEntryNif {
+                name,
+                length: 0,
+                offset,
+                line_bases: 0,
+                line_width: 0,
+            });
+            short_line_seen = false;
+            continue;
+        }
+        let Some(entry) = entries.last_mut() else {
+            continue;
+        };
+        let bases = buf.trim_ascii_end().len() as u64;
+        if entry.line_bases == 0 {
+            if bases == 0 {
+                continue;
+            }
+            entry.line_bases = bases;
+            entry.line_width = n;
+        } else if short_line_seen
+            || bases > entry.line_bases
+            || (bases == entry.line_bases && n != entry.line_width && buf.ends_with(b"\n"))
+        {
+            if bases == 0 {
+                short_line_seen = true;
+                continue;
+            }
+            return Err(NifError::parse(format!(
+                "inconsistent line lengths in sequence {}",
+                entry.name
+            ))
+            .with_file(path)
+            .at_offset(offset - n));
+        } else if bases < entry.line_bases {
+            short_line_seen = true;
+        }
+        entry.length += bases;
+    }
+    Ok(entries)
+}
+
+/// Read a `.fai` file.
+fn read_fai(path: &str) -> Result<Vec<FaiEntryNif>, NifError> {
+    let text = std::fs::read_to_string(path).map_err(|e| NifError::from(e).with_file(path))?;
+    text.lines()
+        .enumerate()
+        .filter(|(_, line)| !line.trim().is_empty())
+        .map(|(i, line)| {
+            let cols: Vec<&str> = line.split('\t').collect();
+            let field = |j: usize| {
+                cols.get(j)
+                    .and_then(|v| v.trim().parse::<u64>().ok())
+                    .ok_or_else(|| {
+                        NifError::parse("malformed .fai entry")
+                            .with_file(path)
+                            .at_line(i as u64 + 1)
+                    })
+            };
+            let entry = FaiEntryNif {
+                name: cols[0].to_string(),
+                length: field(1)?,
+                offset: field(2)?,
+                line_bases: field(3)?,
+                line_width: field(4)?,
+            };
+            // Offsets are computed per line, so a non-empty sequence needs
+            // bases on every line.
+            if entry.length > 0 && (entry.line_bases == 0 || entry.line_width < entry.line_bases) {
+                return Err(NifError::parse(format!(
+                    "invalid line layout for {} ({} bases in {} bytes per line)",
+                    entry.name, entry.line_bases, entry.line_width
+                ))
+                .with_file(path)
+                .at_line(i as u64 + 1));
+            }
+            Ok(entry)
+        })
+        .collect()
+}
+
+/// Build `<path>.fai` for an uncompressed FASTA file. Returns the index path.
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn fasta_index_build(path: String) -> Result<String, NifError> {
+    let entries = build_fai(&path)?;
+    let out: String = entries
+        .iter()
+        .map(|e| {
+            format!(
+                "{}\t{}\t{}\t{}\t{}\n",
+                e.name, e.length, e.offset, e.line_bases, e.line_width
+            )
+        })
+        .collect();
+    let index_path = fai_path(&path);
+    std::fs::write(&index_path, out).map_err(|e| NifError::from(e).with_file(&index_path))?;
+    Ok(index_path)
+}
+
+/// Load the `.fai` entries for a FASTA file, from `<path>.fai` if present or
+/// by scanning the file otherwise.
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn fasta_index_load(path: String) -> Result<Vec<FaiEntryNif>, NifError> {
+    load_fai(&path)
+}
+
+fn load_fai(path: &str) -> Result<Vec<FaiEntryNif>, NifError> {
+    let index_path = fai_path(path);
+    if std::path::Path::new(&index_path).exists() {
+        read_fai(&index_path)
+    } else {
+        build_fai(path)
+    }
+}
+
+/// An uncompressed FASTA file opened for random access, with its `.fai`
+/// entries loaded once so repeated fetches only seek and read.
+pub struct IndexedFastaResource {
+    path: String,
+    entries: Vec<FaiEntryNif>,
+    file: Mutex<File>,
+}
+
+#[rustler::resource_impl]
+impl rustler::Resource for IndexedFastaResource {}
+
+/// Open a FASTA file for repeated `fasta_fetch_handle` calls, reading
+/// `<path>.fai` if present or scanning the file once otherwise.
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn fasta_open(path: String) -> Result<ResourceArc<IndexedFastaResource>, NifError> {
+    let entries = load_fai(&path)?;
+    let file = File::open(&path).map_err(|e| NifError::from(e).with_file(&path))?;
+    Ok(ResourceArc::new(IndexedFastaResource {
+        path,
+        entries,
+        file: Mutex::new(file),
+    }))
+}
+
+/// Read bases `start..=end` (1-based, inclusive) of `chrom`, seeking
+/// straight to the region instead of reading the file.  The `.fai` entries
+/// are loaded for this one fetch; see `fasta_fetch_handle` for repeated
+/// fetches.
 #[rustler::nif(schedule = "DirtyCpu")]
-pub fn fm_index_build(text: Vec<u8>) -> Vec<u8> {
-    // Build to validate input, then return the raw text.
-    // FmIndex doesn't implement Serialize, so we store the raw text and
-    // rebuild the index lazily on each query.
-    let _index = cyanea_seq::FmIndex::build(&text);
-    text
+pub fn fasta_fetch(path: String, chrom: String, start: u64, end: u64) -> Result<Vec<u8>, NifError> {
+    let entries = load_fai(&path)?;
+    let mut file = File::open(&path).map_err(|e| NifError::from(e).with_file(&path))?;
+    fetch_region(&mut file, &path, &entries, &chrom, start, end)
+}
+
+/// `fasta_fetch` on a FASTA opened with `fasta_open`.
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn fasta_fetch_handle(
+    fasta: ResourceArc<IndexedFastaResource>,
+    chrom: String,
+    start: u64,
+    end: u64,
+) -> Result<Vec<u8>, NifError> {
+    let mut file = fasta
+        .file
+        .lock()
+        .map_err(|_| NifError::internal("FASTA reader lock poisoned"))?;
+    fetch_region(&mut file, &fasta.path, &fasta.entries, &chrom, start, end)
+}
+
+fn fetch_region(
+    file: &mut File,
+    path: &str,
+    entries: &[FaiEntryNif],
+    chrom: &str,
+    start: u64,
+    end: u64,
+) -> Result<Vec<u8>, NifError> {
+    let entry = entries
+        .iter()
+        .find(|e| e.name == chrom)
+        .ok_or_else(|| NifError::not_found(format!("unknown sequence: {chrom}")).with_file(path))?;
+    if start == 0 || end < start {
+        return Err(NifError::invalid(format!("invalid range {start}-{end} (1-based, inclusive)")));
+    }
+    if start > entry.length {
+        return Err(NifError::invalid(format!(
+            "start {start} is beyond the end of {chrom} (length {})",
+            entry.length
+        )));
+    }
+    let end = end.min(entry.length);
+    let byte_offset = |i: u64| {
+        entry.offset + (i / entry.line_bases) * entry.line_width + i % entry.line_bases
+    };
+    let first = byte_offset(start - 1);
+    let last = byte_offset(end - 1);
+    file.seek(SeekFrom::Start(first))
+        .map_err(|e| NifError::from(e).with_file(path))?;
+    let mut raw = vec![0u8; (last - first + 1) as usize];
+    file.read_exact(&mut raw)
+        .map_err(|e| NifError::from(e).with_file(path))?;
+    raw.retain(|b| !b.is_ascii_whitespace());
+    Ok(raw)
+}
+
+/// Write FASTA records, wrapping sequence lines at `line_width` bases
+/// (0 = no wrapping). Returns the number of records written.
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn write_fasta(
+    path: String,
+    records: Vec<FastaRecordNif>,
+    line_width: usize,
+    compression: String,
+) -> Result<usize, NifError> {
+    crate::io::write_text_file(&path, &compression, |out| {
+        for r in &records {
+            write_record_header(out, b'>', &r.name, &r.description)?;
+            let width = if line_width == 0 { r.sequence.len().max(1) } else { line_width };
+            for line in r.sequence.chunks(width) {
+                out.write_all(line)?;
+                out.write_all(b"\n")?;
+            }
+        }
+        Ok(())
+    })?;
+    Ok(records.len())
+}
+
+/// Write FASTQ records. `quality` holds Phred scores and is written as
+/// Phred+33. Returns the number of records written.
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn write_fastq(
+    path: String,
+    records: Vec<FastqRecordNif>,
+    compression: String,
+) -> Result<usize, NifError> {
+    if let Some(r) = records.iter().find(|r| r.sequence.len() != r.quality.len()) {
+        return Err(NifError::invalid(format!(
+            "record {}: sequence and quality lengths differ ({} vs {})",
+            r.name,
+            r.sequence.len(),
+            r.quality.len()
+        )));
+    }
+    crate::io::write_text_file(&path, &compression, |out| {
+        for r in &records {
+            write_record_header(out, b'@', &r.name, &r.description)?;
+            out.write_all(&r.sequence)?;
+            out.write_all(b"\n+\n")?;
+            let quality: Vec<u8> = r.quality.iter().map(|q| q.saturating_add(33).min(126)).collect();
+            out.write_all(&quality)?;
+            out.write_all(b"\n")?;
+        }
+        Ok(())
+    })?;
+    Ok(records.len())
+}
+
+fn write_record_header(
+    out: &mut impl Write,
+    marker: u8,
+    name: &str,
+    description: &str,
+) -> std::io::Result<()> {
+    out.write_all(&[marker])?;
+    out.write_all(name.as_bytes())?;
+    if !description.is_empty() {
+        out.write_all(b" ")?;
+        out.write_all(description.as_bytes())?;
+    }
+    out.write_all(b"\n")
+}
+
+// ===========================================================================
+// FM-Index (resource)
+// ===========================================================================
+
+/// Version tag written at the start of a serialized FM-index blob.
+const FM_INDEX_FORMAT_VERSION: u32 = 2;
+
+/// A built FM-index held on the Rust side and passed to Elixir as an opaque
+/// reference, so queries reuse the index instead of rebuilding it.  Text
+/// and patterns are compared as given, so searches are case-sensitive.
+pub struct FmIndexResource {
+    index: cyanea_seq::FmIndex,
+    text: Vec<u8>,
+}
+
+#[rustler::resource_impl]
+impl rustler::Resource for FmIndexResource {}
+
+impl FmIndexResource {
+    fn new(text: Vec<u8>) -> Self {
+        let index = cyanea_seq::FmIndex::build(&text);
+        Self { index, text }
+    }
+
+    fn count(&self, pattern: &[u8]) -> usize {
+        self.index.count(pattern)
+    }
+
+    fn locate(&self, pattern: &[u8]) -> Vec<usize> {
+        let mut positions = self.index.locate(pattern);
+        positions.sort_unstable();
+        positions
+    }
+
+    /// Positions of `pattern` with at most `max_mismatches` substitutions.
+    ///
+    /// Uses the pigeonhole principle: splitting the pattern into
+    /// `max_mismatches + 1` pieces guarantees at least one piece matches
+    /// exactly, so each piece is located with the index and the surrounding
+    /// window verified against the stored text.  With `max_mismatches` at
+    /// least the pattern length there are not enough pieces, and every
+    /// window matches anyway, so the text is scanned instead.
+    fn locate_mismatches(&self, pattern: &[u8], max_mismatches: usize) -> Vec<(usize, usize)> {
+        let m = pattern.len();
+        if m == 0 || m > self.text.len() {
+            return Vec::new();
+        }
+        let mismatches = |candidate: usize| {
+            self.text[candidate..candidate + m]
+                .iter()
+                .zip(pattern)
+                .filter(|(a, b)| a != b)
+                .count()
+        };
+        if max_mismatches == 0 {
+            return self.locate(pattern).into_iter().map(|p| (p, 0)).collect();
+        }
+        if max_mismatches >= m {
+            return (0..=self.text.len() - m).map(|p| (p, mismatches(p))).collect();
+        }
+        let n_pieces = max_mismatches + 1;
+        let piece_len = m / n_pieces;
+        let mut hits = std::collections::BTreeMap::new();
+        for piece in 0..n_pieces {
+            let start = piece * piece_len;
+            let end = if piece + 1 == n_pieces { m } else { start + piece_len };
+            for pos in self.index.locate(&pattern[start..end]) {
+                if pos < start || pos - start + m > self.text.len() {
+                    continue;
+                }
+                let candidate = pos - start;
+                if hits.contains_key(&candidate) {
+                    continue;
+                }
+                let found = mismatches(candidate);
+                if found <= max_mismatches {
+                    hits.insert(candidate, found);
+                }
+            }
+        }
+        hits.into_iter().collect()
+    }
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn fm_index_build(text: Vec<u8>) -> ResourceArc<FmIndexResource> {
+    ResourceArc::new(FmIndexResource::new(text))
 }
 
 #[rustler::nif]
-pub fn fm_index_count(index_data: Vec<u8>, pattern: Vec<u8>) -> usize {
-    let index = cyanea_seq::FmIndex::build(&index_data);
+pub fn fm_index_count(index: ResourceArc<FmIndexResource>, pattern: Vec<u8>) -> usize {
     index.count(&pattern)
 }
 
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn fm_index_locate(index: ResourceArc<FmIndexResource>, pattern: Vec<u8>) -> Vec<usize> {
+    index.locate(&pattern)
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn fm_index_search_mismatches(
+    index: ResourceArc<FmIndexResource>,
+    pattern: Vec<u8>,
+    max_mismatches: usize,
+) -> Vec<(usize, usize)> {
+    index.locate_mismatches(&pattern, max_mismatches)
+}
+
+#[rustler::nif]
+pub fn fm_index_text_length(index: ResourceArc<FmIndexResource>) -> usize {
+    index.text.len()
+}
+
+/// Serialize an FM-index, with its text, to a zstd-compressed binary that
+/// `fm_index_deserialize` loads without rebuilding the index.
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn fm_index_serialize<'a>(
+    env: Env<'a>,
+    index: ResourceArc<FmIndexResource>,
+) -> Result<Binary<'a>, NifError> {
+    let payload = bincode::serialize(&(FM_INDEX_FORMAT_VERSION, &index.index, &index.text))
+        .map_err(|e| NifError::internal(e.to_string()))?;
+    let compressed = cyanea_core::compress::zstd_compress(&payload, 3).map_err(to_nif_error)?;
+    let mut out = OwnedBinary::new(compressed.len()).ok_or_else(|| NifError::internal("failed to allocate binary"))?;
+    out.as_mut_slice().copy_from_slice(&compressed);
+    Ok(out.release(env))
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn fm_index_deserialize(data: Binary) -> Result<ResourceArc<FmIndexResource>, NifError> {
+    let payload = cyanea_core::compress::zstd_decompress(data.as_slice()).map_err(to_nif_error)?;
+    let version: u32 = bincode::deserialize(&payload).map_err(|e| NifError::parse(e.to_string()))?;
+    if version != FM_INDEX_FORMAT_VERSION {
+        return Err(NifError::unsupported(format!(
+            "unsupported FM-index format version {version} (expected {FM_INDEX_FORMAT_VERSION})"
+        )));
+    }
+    let (_, index, text): (u32, cyanea_seq::FmIndex, Vec<u8>) =
+        bincode::deserialize(&payload).map_err(|e| NifError::parse(e.to_string()))?;
+    Ok(ResourceArc::new(FmIndexResource { index, text }))
+}
+
+// ===========================================================================
+// ORFs
+// ===========================================================================
+
 #[rustler::nif]
 pub fn find_orfs(seq: Vec<u8>, min_length: usize) -> Vec<OrfResultNif> {
     let results = cyanea_seq::find_orfs_both_strands(&seq, min_length);
@@ -137,9 +664,875 @@ pub fn find_orfs(seq: Vec<u8>, min_length: usize) -> Vec<OrfResultNif> {
         .collect()
 }
 
+// ===========================================================================
+// Alphabets & genetic codes
+// ===========================================================================
+
+/// Sequence alphabets accepted by the k-mer, composition and sketch NIFs.
+/// `Iupac` is nucleotides with ambiguity codes and gaps, DNA or RNA.
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub(crate) enum SeqAlphabet {
+    Dna,
+    Rna,
+    Protein,
+    Iupac,
+}
+
+pub(crate) fn parse_seq_alphabet(s: &str) -> Result<SeqAlphabet, NifError> {
+    match s {
+        "dna" => Ok(SeqAlphabet::Dna),
+        "rna" => Ok(SeqAlphabet::Rna),
+        "protein" => Ok(SeqAlphabet::Protein),
+        "iupac" => Ok(SeqAlphabet::Iupac),
+        _ => Err(NifError::invalid(format!(
+            "unknown alphabet: {s} (expected dna, rna, protein, or iupac)"
+        ))),
+    }
+}
+
+impl SeqAlphabet {
+    /// Validate `data` and return it uppercased.
+    pub(crate) fn normalize(self, data: &[u8]) -> Result<Vec<u8>, NifError> {
+        match self {
+            SeqAlphabet::Dna => cyanea_seq::DnaSequence::new(data)
+                .map(|s| s.into_bytes())
+                .map_err(to_nif_error),
+            SeqAlphabet::Rna => cyanea_seq::RnaSequence::new(data)
+                .map(|s| s.into_bytes())
+                .map_err(to_nif_error),
+            SeqAlphabet::Protein => cyanea_seq::ProteinSequence::new(data)
+                .map(|s| s.into_bytes())
+                .map_err(to_nif_error),
+            SeqAlphabet::Iupac => data
+                .iter()
+                .enumerate()
+                .map(|(i, &b)| match b.to_ascii_uppercase() {
+                    b'.' => Ok(b'-'),
+                    b @ (b'A' | b'C' | b'G' | b'T' | b'U' | b'R' | b'Y' | b'S' | b'W' | b'K'
+                    | b'M' | b'B' | b'D' | b'H' | b'V' | b'N' | b'-') => Ok(b),
+                    _ => Err(NifError::invalid(format!(
+                        "invalid IUPAC nucleotide {:?} at position {i}",
+                        b as char
+                    ))),
+                })
+                .collect(),
+        }
+    }
+}
+
+/// RNA to DNA (U -> T); other symbols are kept.
+pub(crate) fn to_dna(seq: &[u8]) -> Vec<u8> {
+    seq.iter().map(|&b| if b == b'U' { b'T' } else { b }).collect()
+}
+
+/// The bases an uppercase IUPAC nucleotide code stands for; empty for gaps.
+pub(crate) fn iupac_bases(code: u8) -> &'static [u8] {
+    match code {
+        b'A' => b"A",
+        b'C' => b"C",
+        b'G' => b"G",
+        b'T' | b'U' => b"T",
+        b'R' => b"AG",
+        b'Y' => b"CT",
+        b'S' => b"CG",
+        b'W' => b"AT",
+        b'K' => b"GT",
+        b'M' => b"AC",
+        b'B' => b"CGT",
+        b'D' => b"AGT",
+        b'H' => b"ACT",
+        b'V' => b"ACG",
+        b'N' => b"ACGT",
+        _ => b"",
+    }
+}
+
+pub(crate) fn iupac_complement(code: u8) -> u8 {
+    match code {
+        b'A' => b'T',
+        b'T' | b'U' => b'A',
+        b'C' => b'G',
+        b'G' => b'C',
+        b'R' => b'Y',
+        b'Y' => b'R',
+        b'K' => b'M',
+        b'M' => b'K',
+        b'B' => b'V',
+        b'V' => b'B',
+        b'D' => b'H',
+        b'H' => b'D',
+        other => other,
+    }
+}
+
+/// Reverse complement of an uppercase nucleotide sequence, IUPAC codes
+/// included; U complements to A, so RNA comes back as DNA.
+pub(crate) fn reverse_complement(seq: &[u8]) -> Vec<u8> {
+    seq.iter().rev().map(|&b| iupac_complement(b)).collect()
+}
+
+/// NCBI genetic codes: id, name, and amino acids for the 64 codons in
+/// TCAG order (TTT, TTC, TTA, TTG, TCT, ...), as in NCBI's `gc.prt`.
+const GENETIC_CODES: &[(u32, &str, &[u8; 64])] = &[
+    (1, "Standard", b"FFLLSSSSYY**CC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG"),
+    (2, "Vertebrate Mitochondrial", b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIMMTTTTNNKKSS**VVVVAAAADDEEGGGG"),
+    (3, "Yeast Mitochondrial", b"FFLLSSSSYY**CCWWTTTTPPPPHHQQRRRRIIMMTTTTNNKKSSRRVVVVAAAADDEEGGGG"),
+    (4, "Mold, Protozoan, and Coelenterate Mitochondrial and Mycoplasma/Spiroplasma", b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG"),
+    (5, "Invertebrate Mitochondrial", b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIMMTTTTNNKKSSSSVVVVAAAADDEEGGGG"),
+    (6, "Ciliate, Dasycladacean and Hexamita Nuclear", b"FFLLSSSSYYQQCC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG"),
+    (9, "Echinoderm and Flatworm Mitochondrial", b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNNKSSSSVVVVAAAADDEEGGGG"),
+    (10, "Euplotid Nuclear", b"FFLLSSSSYY**CCCWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG"),
+    (11, "Bacterial, Archaeal and Plant Plastid", b"FFLLSSSSYY**CC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG"),
+    (12, "Alternative Yeast Nuclear", b"FFLLSSSSYY**CC*WLLLSPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG"),
+    (13, "Ascidian Mitochondrial", b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIMMTTTTNNKKSSGGVVVVAAAADDEEGGGG"),
+    (14, "Alternative Flatworm Mitochondrial", b"FFLLSSSSYYY*CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNNKSSSSVVVVAAAADDEEGGGG"),
+    (15, "Blepharisma Nuclear", b"FFLLSSSSYY*QCC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG"),
+    (16, "Chlorophycean Mitochondrial", b"FFLLSSSSYY*LCC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG"),
+    (21, "Trematode Mitochondrial", b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIMMTTTTNNNKSSSSVVVVAAAADDEEGGGG"),
+    (22, "Scenedesmus obliquus Mitochondrial", b"FFLLSS*SYY*LCC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG"),
+    (23, "Thraustochytrium Mitochondrial", b"FF*LSSSSYY**CC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG"),
+    (24, "Rhabdopleuridae Mitochondrial", b"FFLLSSSSYY**CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSSKVVVVAAAADDEEGGGG"),
+    (25, "Candidate Division SR1 and Gracilibacteria", b"FFLLSSSSYY**CCGWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG"),
+    (26, "Pachysolen tannophilus Nuclear", b"FFLLSSSSYY**CC*WLLLAPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG"),
+    (27, "Karyorelict Nuclear", b"FFLLSSSSYYQQCCWWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG"),
+    (28, "Condylostoma Nuclear", b"FFLLSSSSYYQQCCWWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG"),
+    (29, "Mesodinium Nuclear", b"FFLLSSSSYYYYCC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG"),
+    (30, "Peritrich Nuclear", b"FFLLSSSSYYEECC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG"),
+    (31, "Blastocrithidia Nuclear", b"FFLLSSSSYYEECCWWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG"),
+    (32, "Balanophoraceae Plastid", b"FFLLSSSSYY*WCC*WLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSRRVVVVAAAADDEEGGGG"),
+    (33, "Cephalodiscidae Mitochondrial", b"FFLLSSSSYYY*CCWWLLLLPPPPHHQQRRRRIIIMTTTTNNKKSSSKVVVVAAAADDEEGGGG"),
+];
+
+fn genetic_code(id: u32) -> Result<&'static [u8; 64], NifError> {
+    GENETIC_CODES
+        .iter()
+        .find(|(code, _, _)| *code == id)
+        .map(|(_, _, table)| *table)
+        .ok_or_else(|| {
+            NifError::invalid(format!(
+                "unknown genetic code: {id} (expected 1-6, 9-16, or 21-33)"
+            ))
+        })
+}
+
+fn codon_index(codon: [u8; 3]) -> usize {
+    let base = |b: u8| match b {
+        b'T' => 0,
+        b'C' => 1,
+        b'A' => 2,
+        _ => 3,
+    };
+    16 * base(codon[0]) + 4 * base(codon[1]) + base(codon[2])
+}
+
+/// Translate one codon.  Ambiguous codons resolve to an amino acid only
+/// when every codon they stand for agrees (e.g. `GCN` is A, `YTR` is L),
+/// otherwise `X`; an all-gap codon becomes a gap.
+fn translate_codon(table: &[u8; 64], codon: &[u8]) -> u8 {
+    if codon == b"---" {
+        return b'-';
+    }
+    let mut aa = None;
+    for &x in iupac_bases(codon[0]) {
+        for &y in iupac_bases(codon[1]) {
+            for &z in iupac_bases(codon[2]) {
+                let a = table[codon_index([x, y, z])];
+                match aa {
+                    None => aa = Some(a),
+                    Some(prev) if prev != a => return b'X',
+                    _ => {}
+                }
+            }
+        }
+    }
+    aa.unwrap_or(b'X')
+}
+
+#[rustler::nif]
+pub fn sequence_gc_content(data: Vec<u8>, alphabet: String) -> Result<f64, NifError> {
+    let alphabet = parse_seq_alphabet(&alphabet)?;
+    if alphabet == SeqAlphabet::Protein {
+        return Err(NifError::unsupported("GC content is undefined for protein sequences"));
+    }
+    // Ambiguity codes count by the fraction of their bases that are G or C;
+    // N and gaps carry no information and are left out.
+    let seq = alphabet.normalize(&data)?;
+    let (mut gc, mut total) = (0.0, 0usize);
+    for &b in &seq {
+        let bases = iupac_bases(b);
+        if bases.is_empty() || b == b'N' {
+            continue;
+        }
+        let strong = bases.iter().filter(|&&x| x == b'G' || x == b'C').count();
+        gc += strong as f64 / bases.len() as f64;
+        total += 1;
+    }
+    Ok(if total == 0 { 0.0 } else { gc / total as f64 })
+}
+
+#[rustler::nif]
+pub fn sequence_composition(data: Vec<u8>, alphabet: String) -> Result<Vec<(String, usize)>, NifError> {
+    let seq = parse_seq_alphabet(&alphabet)?.normalize(&data)?;
+    let mut counts = std::collections::BTreeMap::new();
+    for b in seq {
+        *counts.entry(b).or_insert(0usize) += 1;
+    }
+    Ok(counts
+        .into_iter()
+        .map(|(b, n)| ((b as char).to_string(), n))
+        .collect())
+}
+
+#[rustler::nif]
+pub fn genetic_codes() -> Vec<(u32, String)> {
+    GENETIC_CODES
+        .iter()
+        .map(|(id, name, _)| (*id, name.to_string()))
+        .collect()
+}
+
+/// Translate DNA or RNA (IUPAC codes allowed) in the given reading frames.
+/// Frames 1-3 start at offsets 0-2 of the sequence, -1 to -3 at offsets
+/// 0-2 of its reverse complement; a trailing partial codon is dropped.
+#[rustler::nif]
+pub fn sequence_translate(
+    data: Vec<u8>,
+    table: u32,
+    frames: Vec<i32>,
+) -> Result<Vec<(i32, Vec<u8>)>, NifError> {
+    let code = genetic_code(table)?;
+    let forward = to_dna(&SeqAlphabet::Iupac.normalize(&data)?);
+    let reverse = reverse_complement(&forward);
+    frames
+        .into_iter()
+        .map(|frame| {
+            let (strand, offset) = match frame {
+                1..=3 => (&forward, frame as usize - 1),
+                -3..=-1 => (&reverse, (-frame) as usize - 1),
+                _ => {
+                    return Err(NifError::invalid(format!(
+                        "invalid frame: {frame} (expected 1, 2, 3, -1, -2, or -3)"
+                    )))
+                }
+            };
+            let protein = strand
+                .get(offset..)
+                .unwrap_or_default()
+                .chunks_exact(3)
+                .map(|codon| translate_codon(code, codon))
+                .collect();
+            Ok((frame, protein))
+        })
+        .collect()
+}
+
+// ===========================================================================
+// K-mer counting
+// ===========================================================================
+
+/// Streaming counter of canonical k-mers packed 2 bits per base (k <= 31).
+/// Any base other than A, C, G or T ends the current k-mer run, so k-mers
+/// spanning an `N` are not counted.
+struct KmerCounter {
+    k: usize,
+    mask: u64,
+    fwd: u64,
+    rev: u64,
+    filled: usize,
+    counts: std::collections::HashMap<u64, u64>,
+    total: u64,
+}
+
+impl KmerCounter {
+    fn new(k: usize) -> Result<Self, NifError> {
+        if !(1..=31).contains(&k) {
+            return Err(NifError::invalid(format!("k must be between 1 and 31, got {k}")));
+        }
+        Ok(Self {
+            k,
+            mask: (1u64 << (2 * k)) - 1,
+            fwd: 0,
+            rev: 0,
+            filled: 0,
+            counts: std::collections::HashMap::new(),
+            total: 0,
+        })
+    }
+
+    /// Start a new sequence; the next k-mer will not span the boundary.
+    fn reset(&mut self) {
+        self.filled = 0;
+    }
+
+    fn feed(&mut self, bases: &[u8]) {
+        for &b in bases {
+            let code = match b {
+                b'A' | b'a' => 0,
+                b'C' | b'c' => 1,
+                b'G' | b'g' => 2,
+                b'T' | b't' | b'U' | b'u' => 3,
+                _ => {
+                    self.filled = 0;
+                    continue;
+                }
+            };
+            self.fwd = ((self.fwd << 2) | code) & self.mask;
+            self.rev = (self.rev >> 2) | ((3 - code) << (2 * (self.k - 1)));
+            self.filled += 1;
+            if self.filled >= self.k {
+                *self.counts.entry(self.fwd.min(self.rev)).or_insert(0) += 1;
+                self.total += 1;
+            }
+        }
+    }
+
+    /// Count a single k-mer of unambiguous bases.
+    fn add(&mut self, kmer: &[u8]) {
+        self.reset();
+        self.feed(kmer);
+    }
+
+    fn decode(&self, kmer: u64) -> String {
+        (0..self.k)
+            .rev()
+            .map(|i| b"ACGT"[((kmer >> (2 * i)) & 3) as usize] as char)
+            .collect()
+    }
+
+    fn finish(self, top_n: usize) -> KmerCountsNif {
+        let mut spectrum = std::collections::BTreeMap::new();
+        for &count in self.counts.values() {
+            *spectrum.entry(count).or_insert(0u64) += 1;
+        }
+        let spectrum: Vec<(u64, u64)> = spectrum.into_iter().collect();
+
+        let mut top: Vec<(u64, u64)> = self.counts.iter().map(|(&kmer, &n)| (kmer, n)).collect();
+        let by_count = |a: &(u64, u64), b: &(u64, u64)| b.1.cmp(&a.1).then(a.0.cmp(&b.0));
+        if top.len() > top_n && top_n > 0 {
+            top.select_nth_unstable_by(top_n - 1, by_count);
+        }
+        top.truncate(top_n);
+        top.sort_unstable_by(by_count);
+
+        let (peak_coverage, genome_size, heterozygosity) = estimate_genome(&spectrum, self.k);
+        KmerCountsNif {
+            k: self.k,
+            total_kmers: self.total,
+            distinct_kmers: self.counts.len(),
+            top_kmers: top.into_iter().map(|(kmer, n)| (self.decode(kmer), n)).collect(),
+            spectrum,
+            peak_coverage,
+            genome_size,
+            heterozygosity,
+        }
+    }
+}
+
+/// Spectrum-based genome size and heterozygosity, in the spirit of
+/// GenomeScope but without model fitting.
+///
+/// The error k-mers are everything below the first valley of the spectrum;
+/// the highest peak above it is taken as the homozygous coverage, so the
+/// genome size is the number of solid k-mers divided by that coverage.  A
+/// separate peak at around half coverage is read as heterozygous k-mers,
+/// each SNP contributing `2k` of them.  Returns `None`s when the spectrum
+/// has no valley, as with low coverage or a single short sequence.
+fn estimate_genome(spectrum: &[(u64, u64)], k: usize) -> (Option<u64>, Option<f64>, Option<f64>) {
+    // Peak finding only needs the low end; repeats can reach huge counts.
+    const MAX_BIN: u64 = 10_000;
+    let mut hist = vec![0u64; MAX_BIN as usize + 2];
+    for &(count, n) in spectrum {
+        if count <= MAX_BIN {
+            hist[count as usize] = n;
+        }
+    }
+    // Filtered or subsampled input can have no k-mers at count 1; a valley
+    // before the first populated bin would be spurious.
+    let Some(first) = (1..=MAX_BIN as usize).find(|&c| hist[c] > 0) else {
+        return (None, None, None);
+    };
+    let Some(valley) = (first..=MAX_BIN as usize).find(|&c| hist[c] < hist[c + 1]) else {
+        return (None, None, None);
+    };
+    let peak = (valley..=MAX_BIN as usize)
+        .max_by_key(|&c| (hist[c], std::cmp::Reverse(c)))
+        .filter(|&c| hist[c] > 0);
+    let Some(peak) = peak else {
+        return (None, None, None);
+    };
+
+    let solid: u64 = spectrum
+        .iter()
+        .filter(|&&(count, _)| count >= valley as u64)
+        .map(|&(count, n)| count * n)
+        .sum();
+    let genome_size = solid as f64 / peak as f64;
+
+    let het_end = peak * 3 / 4;
+    let has_het_peak = (valley + 1..het_end)
+        .any(|c| hist[c] > hist[valley] && hist[c] >= hist[c - 1] && hist[c] >= hist[c + 1]);
+    let heterozygosity = if has_het_peak {
+        let het_kmers: u64 = hist[valley..het_end].iter().sum();
+        (het_kmers as f64 / (2 * k) as f64 / genome_size).clamp(0.0, 1.0)
+    } else {
+        0.0
+    };
+
+    (Some(peak as u64), Some(genome_size), Some(heterozygosity))
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn kmer_count_sequence(data: Vec<u8>, k: usize, top_n: usize) -> Result<KmerCountsNif, NifError> {
+    let mut counter = KmerCounter::new(k)?;
+    // K-mers spanning an ambiguous base are skipped: only runs of ACGT are
+    // handed to `DnaSequence`, which would reject the whole sequence.
+    let runs = data.split(|b| !b"ACGTacgt".contains(b));
+    for run in runs.filter(|run| run.len() >= k) {
+        let seq = cyanea_seq::DnaSequence::new(run).map_err(to_nif_error)?;
+        for kmer in seq.kmers(k).map_err(to_nif_error)? {
+            counter.add(kmer);
+        }
+    }
+    Ok(counter.finish(top_n))
+}
+
+/// Count k-mers across every record of a FASTA or FASTQ file (plain or
+/// gzipped), streaming it line by line.
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn kmer_count_file(path: String, k: usize, top_n: usize) -> Result<KmerCountsNif, NifError> {
+    let mut counter = KmerCounter::new(k)?;
+    let mut lines = crate::io::open_text_reader(&path)?;
+    let mut buf = Vec::new();
+    let mut line_no = 0u64;
+    let mut fastq = None;
+    // Line within the current FASTQ record: 0 header, 1 sequence, 2 '+', 3 quality.
+    let mut fastq_line = 0;
+    loop {
+        buf.clear();
+        let n = lines
+            .read_until(b'\n', &mut buf)
+            .map_err(|e| NifError::from(e).with_file(&path).at_line(line_no + 1))?;
+        if n == 0 {
+            break;
+        }
+        line_no += 1;
+        let line = buf.trim_ascii_end();
+        let is_fastq = match fastq {
+            Some(is_fastq) => is_fastq,
+            None if line.is_empty() => continue,
+            None => match line[0] {
+                b'>' => *fastq.insert(false),
+                b'@' => *fastq.insert(true),
+                _ => {
+                    return Err(NifError::parse("expected a FASTA or FASTQ file")
+                        .with_file(&path)
+                        .at_line(line_no))
+                }
+            },
+        };
+        if is_fastq {
+            if fastq_line == 0 && line.is_empty() {
+                continue;
+            }
+            match fastq_line {
+                0 if !line.starts_with(b"@") => {
+                    return Err(NifError::parse("expected FASTQ header starting with '@'")
+                        .with_file(&path)
+                        .at_line(line_no))
+                }
+                1 => {
+                    counter.reset();
+                    counter.feed(line);
+                }
+                _ => {}
+            }
+            fastq_line = (fastq_line + 1) % 4;
+        } else if line.starts_with(b">") {
+            counter.reset();
+        } else if !line.starts_with(b";") {
+            counter.feed(line);
+        }
+    }
+    Ok(counter.finish(top_n))
+}
+
+// ===========================================================================
+// Oligo thermodynamics & primer design
+// ===========================================================================
+
+/// Gas constant in cal/(K·mol).
+const GAS_CONSTANT: f64 = 1.987;
+const KELVIN: f64 = 273.15;
+/// 37 °C, where ΔG values are reported.
+const T37: f64 = 310.15;
+/// Duplex initiation ΔG at 37 °C (SantaLucia & Hicks 2004), kcal/mol.
+const INITIATION_DG: f64 = 1.96;
+/// At most this many candidates per side are paired up in primer design.
+const MAX_PRIMER_CANDIDATES: usize = 500;
+
+/// SantaLucia (1998) unified nearest-neighbour stacks as (ΔH kcal/mol,
+/// ΔS cal/K/mol), indexed by the top-strand dinucleotide `4 * x + y` with
+/// A, C, G, T = 0..3.
+const NN_STACKS: [(f64, f64); 16] = [
+    (-7.9, -22.2),  // AA
+    (-8.4, -22.4),  // AC
+    (-7.8, -21.0),  // AG
+    (-7.2, -20.4),  // AT
+    (-8.5, -22.7),  // CA
+    (-8.0, -19.9),  // CC
+    (-10.6, -27.2), // CG
+    (-7.8, -21.0),  // CT
+    (-8.2, -22.2),  // GA
+    (-9.8, -24.4),  // GC
+    (-8.0, -19.9),  // GG
+    (-8.4, -22.4),  // GT
+    (-7.2, -21.3),  // TA
+    (-8.2, -22.2),  // TC
+    (-8.5, -22.7),  // TG
+    (-7.9, -22.2),  // TT
+];
+
+fn base_code(b: u8) -> usize {
+    match b {
+        b'A' => 0,
+        b'C' => 1,
+        b'G' => 2,
+        _ => 3,
+    }
+}
+
+fn pairs(x: u8, y: u8) -> bool {
+    matches!((x, y), (b'A', b'T') | (b'T', b'A') | (b'C', b'G') | (b'G', b'C'))
+}
+
+fn stack(x: u8, y: u8) -> (f64, f64) {
+    NN_STACKS[4 * base_code(x) + base_code(y)]
+}
+
+fn stack_dg(x: u8, y: u8) -> f64 {
+    let (dh, ds) = stack(x, y);
+    dh - T37 * ds / 1000.0
+}
+
+/// Uppercase an oligo, read U as T and require plain A/C/G/T.
+fn oligo_bases(data: &[u8]) -> Result<Vec<u8>, NifError> {
+    let seq = to_dna(&data.to_ascii_uppercase());
+    if seq.len() < 2 {
+        return Err(NifError::invalid("oligo must be at least 2 bases"));
+    }
+    if let Some(i) = seq.iter().position(|b| !matches!(b, b'A' | b'C' | b'G' | b'T')) {
+        return Err(NifError::invalid(format!(
+            "invalid oligo base {:?} at position {i} (expected A, C, G, or T)",
+            data[i] as char
+        )));
+    }
+    Ok(seq)
+}
+
+fn gc_fraction(seq: &[u8]) -> f64 {
+    seq.iter().filter(|&&b| b == b'G' || b == b'C').count() as f64 / seq.len() as f64
+}
+
+fn is_self_complementary(seq: &[u8]) -> bool {
+    seq.iter().zip(seq.iter().rev()).all(|(&x, &y)| pairs(x, y))
+}
+
+/// Duplex ΔH (kcal/mol) and ΔS (cal/K/mol) of `seq` with its perfect
+/// complement at 1 M Na+, including terminal initiation and symmetry.
+fn duplex_thermo(seq: &[u8]) -> (f64, f64) {
+    let (mut dh, mut ds) = (0.0, 0.0);
+    for w in seq.windows(2) {
+        let (h, s) = stack(w[0], w[1]);
+        dh += h;
+        ds += s;
+    }
+    for end in [seq[0], seq[seq.len() - 1]] {
+        let (h, s) = if end == b'G' || end == b'C' { (0.1, -2.8) } else { (2.3, 4.1) };
+        dh += h;
+        ds += s;
+    }
+    if is_self_complementary(seq) {
+        ds -= 1.4;
+    }
+    (dh, ds)
+}
+
+/// Nearest-neighbour Tm in °C.  Mg2+ is folded into an equivalent Na+
+/// concentration after subtracting what dNTPs chelate (von Ahsen et al.
+/// 2001) and applied as the SantaLucia entropy correction.
+fn melting_temp(seq: &[u8], conditions: &OligoConditionsNif) -> f64 {
+    let (dh, ds) = duplex_thermo(seq);
+    let free_mg = (conditions.mg_mm - conditions.dntp_mm).max(0.0);
+    let sodium = (conditions.na_mm + 120.0 * free_mg.sqrt()) / 1000.0;
+    let ds = ds + 0.368 * (seq.len() - 1) as f64 * sodium.ln();
+    let strands = if is_self_complementary(seq) { 1.0 } else { 4.0 };
+    let concentration = conditions.oligo_nm * 1e-9 / strands;
+    1000.0 * dh / (ds + GAS_CONSTANT * concentration.ln()) - KELVIN
+}
+
+fn check_conditions(conditions: &OligoConditionsNif) -> Result<(), NifError> {
+    if conditions.na_mm < 0.0 || conditions.mg_mm < 0.0 || conditions.dntp_mm < 0.0 {
+        return Err(NifError::invalid("salt and dNTP concentrations must not be negative"));
+    }
+    if conditions.na_mm == 0.0 && conditions.mg_mm <= conditions.dntp_mm {
+        return Err(NifError::invalid("Na+ or free Mg2+ concentration must be > 0"));
+    }
+    if conditions.oligo_nm <= 0.0 {
+        return Err(NifError::invalid("oligo concentration must be > 0"));
+    }
+    Ok(())
+}
+
+/// Hairpin loop ΔG at 37 °C by loop size (SantaLucia & Hicks 2004), with
+/// the Jacobson-Stockmayer extrapolation past 9 bases.
+fn hairpin_loop_dg(size: usize) -> f64 {
+    match size {
+        3 | 4 => 3.5,
+        5 => 3.3,
+        6 => 4.0,
+        7 => 4.2,
+        8 => 4.3,
+        9 => 4.5,
+        _ => 4.6 + 2.44 * GAS_CONSTANT / 1000.0 * T37 * (size as f64 / 10.0).ln(),
+    }
+}
+
+/// Most stable ungapped antiparallel duplex between `a` and `b` (both
+/// 5'->3'), in kcal/mol at 37 °C: the best run of adjacent Watson-Crick
+/// stacks over all offsets plus initiation.  0 when nothing forms.
+fn dimer_dg(a: &[u8], b: &[u8]) -> f64 {
+    let mut best: f64 = 0.0;
+    // a[i] pairs with b[j] along diagonals i + j = d.
+    for d in 1..a.len() + b.len() - 2 {
+        let mut run = 0.0;
+        for i in d.saturating_sub(b.len() - 1)..(a.len() - 1).min(d) {
+            let j = d - i;
+            if pairs(a[i], b[j]) && pairs(a[i + 1], b[j - 1]) {
+                run += stack_dg(a[i], a[i + 1]);
+                best = best.min(run + INITIATION_DG);
+            } else {
+                run = 0.0;
+            }
+        }
+    }
+    best
+}
+
+/// Most stable single-stem hairpin of `seq` (loops of 3+ bases), in
+/// kcal/mol at 37 °C.  0 when nothing forms.
+fn hairpin_dg(seq: &[u8]) -> f64 {
+    let n = seq.len();
+    let mut best: f64 = 0.0;
+    // Outer pair (i, j) stacks on (i + 1, j - 1); the loop is what is left
+    // inside the inner pair.
+    for d in 0..2 * n {
+        let mut run = 0.0;
+        for i in d.saturating_sub(n - 1)..d / 2 {
+            let j = d - i;
+            if j < i + 6 {
+                break;
+            }
+            if pairs(seq[i], seq[j]) && pairs(seq[i + 1], seq[j - 1]) {
+                run += stack_dg(seq[i], seq[i + 1]);
+                best = best.min(run + hairpin_loop_dg(j - i - 3));
+            } else {
+                run = 0.0;
+            }
+        }
+    }
+    best
+}
+
+/// A primer that passed the single-primer filters.
+struct PrimerCandidate {
+    seq: Vec<u8>,
+    start: usize,
+    tm: f64,
+    gc: f64,
+    hairpin_dg: f64,
+    self_dimer_dg: f64,
+    penalty: f64,
+}
+
+impl PrimerCandidate {
+    /// Check GC, 3' clamp (at most 3 G/C in the last 5 bases), Tm and
+    /// secondary structure, cheapest first.
+    fn new(seq: Vec<u8>, start: usize, opts: &PrimerDesignOptionsNif) -> Option<Self> {
+        if seq.iter().any(|b| !matches!(b, b'A' | b'C' | b'G' | b'T')) {
+            return None;
+        }
+        let gc = gc_fraction(&seq);
+        if gc < opts.min_gc || gc > opts.max_gc {
+            return None;
+        }
+        if gc_fraction(&seq[seq.len().saturating_sub(5)..]) > 0.6 {
+            return None;
+        }
+        let tm = melting_temp(&seq, &opts.conditions);
+        if tm < opts.min_tm || tm > opts.max_tm {
+            return None;
+        }
+        let hairpin_dg = hairpin_dg(&seq);
+        if hairpin_dg < opts.max_hairpin_dg {
+            return None;
+        }
+        let self_dimer_dg = dimer_dg(&seq, &seq);
+        if self_dimer_dg < opts.max_dimer_dg {
+            return None;
+        }
+        Some(Self {
+            penalty: (tm - opts.opt_tm).abs(),
+            seq,
+            start,
+            tm,
+            gc,
+            hairpin_dg,
+            self_dimer_dg,
+        })
+    }
+
+    fn to_nif(&self) -> PrimerNif {
+        PrimerNif {
+            sequence: String::from_utf8_lossy(&self.seq).into_owned(),
+            start: self.start,
+            length: self.seq.len(),
+            tm: self.tm,
+            gc_content: self.gc,
+            hairpin_dg: self.hairpin_dg,
+            self_dimer_dg: self.self_dimer_dg,
+        }
+    }
+}
+
+/// Best `MAX_PRIMER_CANDIDATES` primers, over all allowed lengths, whose
+/// binding sites start in `starts`, lowest penalty first.
+fn primer_candidates(
+    template: &[u8],
+    starts: std::ops::Range<usize>,
+    limit: usize,
+    reverse: bool,
+    opts: &PrimerDesignOptionsNif,
+) -> Vec<PrimerCandidate> {
+    let mut candidates = Vec::new();
+    for start in starts {
+        for len in opts.min_length..=opts.max_length {
+            if start + len > limit {
+                break;
+            }
+            let site = &template[start..start + len];
+            let seq = if reverse { reverse_complement(site) } else { site.to_vec() };
+            candidates.extend(PrimerCandidate::new(seq, start, opts));
+        }
+    }
+    candidates.sort_by(|a, b| a.penalty.total_cmp(&b.penalty).then(a.start.cmp(&b.start)));
+    candidates.truncate(MAX_PRIMER_CANDIDATES);
+    candidates
+}
+
 #[rustler::nif]
-pub fn minhash_sketch(seq: Vec<u8>, k: usize, sketch_size: usize) -> Result<Vec<u64>, String> {
-    let mut mh = cyanea_seq::MinHash::new(k, sketch_size).map_err(to_nif_error)?;
-    mh.add_sequence(&seq);
-    Ok(mh.hashes().to_vec())
+pub fn oligo_tm(data: Vec<u8>, conditions: OligoConditionsNif) -> Result<f64, NifError> {
+    check_conditions(&conditions)?;
+    Ok(melting_temp(&oligo_bases(&data)?, &conditions))
+}
+
+#[rustler::nif]
+pub fn oligo_thermo(data: Vec<u8>, conditions: OligoConditionsNif) -> Result<OligoThermoNif, NifError> {
+    check_conditions(&conditions)?;
+    let seq = oligo_bases(&data)?;
+    let (delta_h, delta_s) = duplex_thermo(&seq);
+    Ok(OligoThermoNif {
+        length: seq.len(),
+        tm: melting_temp(&seq, &conditions),
+        gc_content: gc_fraction(&seq),
+        delta_h,
+        delta_s,
+        delta_g: delta_h - T37 * delta_s / 1000.0,
+        hairpin_dg: hairpin_dg(&seq),
+        self_dimer_dg: dimer_dg(&seq, &seq),
+    })
+}
+
+/// ΔG (kcal/mol, 37 °C) of the most stable duplex two oligos can form.
+#[rustler::nif]
+pub fn oligo_dimer_dg(a: Vec<u8>, b: Vec<u8>) -> Result<f64, NifError> {
+    Ok(dimer_dg(&oligo_bases(&a)?, &oligo_bases(&b)?))
+}
+
+/// Design primer pairs flanking `[target_start, target_end)` of
+/// `template`, ranked by penalty: each primer's distance from the optimal
+/// Tm plus the Tm difference between them.  Reverse primers are reported
+/// by their binding site on the forward strand.
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn primer_design(
+    template: Vec<u8>,
+    target_start: usize,
+    target_end: usize,
+    options: PrimerDesignOptionsNif,
+) -> Result<Vec<PrimerDesignNif>, NifError> {
+    check_conditions(&options.conditions)?;
+    let template = to_dna(&template.to_ascii_uppercase());
+    let len = template.len();
+    if target_start >= target_end || target_end > len {
+        return Err(NifError::invalid(format!(
+            "target {target_start}..{target_end} must be a non-empty region of the template (length {len})"
+        )));
+    }
+    if options.min_length < 2 || options.min_length > options.max_length {
+        return Err(NifError::invalid(format!(
+            "invalid primer length range {}..{}",
+            options.min_length, options.max_length
+        )));
+    }
+
+    let forward = primer_candidates(
+        &template,
+        target_end.saturating_sub(options.max_product)..target_start,
+        target_start,
+        false,
+        &options,
+    );
+    let reverse_limit = len.min(target_start + options.max_product);
+    let reverse = primer_candidates(
+        &template,
+        target_end..reverse_limit,
+        reverse_limit,
+        true,
+        &options,
+    );
+
+    let mut ranked = Vec::new();
+    for f in &forward {
+        for r in &reverse {
+            let product = r.start + r.seq.len() - f.start;
+            let tm_difference = (f.tm - r.tm).abs();
+            if product < options.min_product
+                || product > options.max_product
+                || tm_difference > options.max_tm_diff
+            {
+                continue;
+            }
+            ranked.push((f.penalty + r.penalty + tm_difference, f, r, product));
+        }
+    }
+    ranked.sort_by(|a, b| a.0.total_cmp(&b.0).then(a.3.cmp(&b.3)));
+
+    let mut designs = Vec::new();
+    for (penalty, f, r, product_size) in ranked {
+        if designs.len() >= options.num_return {
+            break;
+        }
+        let pair_dimer_dg = dimer_dg(&f.seq, &r.seq);
+        if pair_dimer_dg < options.max_dimer_dg {
+            continue;
+        }
+        designs.push(PrimerDesignNif {
+            forward: f.to_nif(),
+            reverse: r.to_nif(),
+            product_size,
+            tm_difference: (f.tm - r.tm).abs(),
+            pair_dimer_dg,
+            penalty,
+        });
+    }
+    Ok(designs)
 }
@@ -0,0 +1,454 @@
+//! MinHash sketches — building, comparing, and saving sketch databases.
+//!
+//! Sketches hash k-mers with MurmurHash3 (x64, 128-bit, low 64 bits kept),
+//! the hash Mash uses, and keep the `sketch_size` smallest distinct hashes.
+//! Nucleotide k-mers are canonicalized to the lexicographically smaller of
+//! the k-mer and its reverse complement and k-mers containing anything but
+//! A, C, G or T are skipped.  Distances follow Ondov et al. (2016): Jaccard
+//! from the bottom-k of the union, the Mash distance
+//! `-ln(2j / (1 + j)) / k`, and a binomial p-value for the shared hashes.
+
+use crate::bridge::{MinHashDistanceNif, MinHashSketchNif, NifError};
+use crate::seq::{parse_seq_alphabet, to_dna, SeqAlphabet};
+use crate::to_nif_error;
+use std::collections::BTreeSet;
+use std::io::BufRead;
+
+const HASH_FUNCTION: &str = "murmur3_x64_128";
+
+/// The seed Mash uses by default.
+const DEFAULT_SEED: u32 = 42;
+
+/// Bumped whenever the layout of saved sketch files changes.
+const SKETCH_FORMAT_VERSION: u32 = 1;
+
+/// On-disk form of a sketch: name, hash function, alphabet, k, sketch size,
+/// seed, length, hashes.
+type StoredSketch = (String, String, String, u32, u32, u32, u64, Vec<u64>);
+
+// ===========================================================================
+// Hashing
+// ===========================================================================
+
+fn fmix64(mut k: u64) -> u64 {
+    k ^= k >> 33;
+    k = k.wrapping_mul(0xff51_afd7_ed55_8ccd);
+    k ^= k >> 33;
+    k = k.wrapping_mul(0xc4ce_b9fe_1a85_ec53);
+    k ^ (k >> 33)
+}
+
+/// The first 64 bits of MurmurHash3_x64_128.
+fn murmur3_x64_64(data: &[u8], seed: u32) -> u64 {
+    const C1: u64 = 0x87c3_7b91_1142_53d5;
+    const C2: u64 = 0x4cf5_ad43_2745_937f;
+    let (mut h1, mut h2) = (u64::from(seed), u64::from(seed));
+
+    let mut blocks = data.chunks_exact(16);
+    for block in &mut blocks {
+        let k1 = u64::from_le_bytes(block[..8].try_into().unwrap());
+        let k2 = u64::from_le_bytes(block[8..].try_into().unwrap());
+        h1 ^= k1.wrapping_mul(C1).rotate_left(31).wrapping_mul(C2);
+        h1 = h1.rotate_left(27).wrapping_add(h2).wrapping_mul(5).wrapping_add(0x52dc_e729);
+        h2 ^= k2.wrapping_mul(C2).rotate_left(33).wrapping_mul(C1);
+        h2 = h2.rotate_left(31).wrapping_add(h1).wrapping_mul(5).wrapping_add(0x3849_5ab5);
+    }
+
+    let tail = blocks.remainder();
+    if tail.len() > 8 {
+        let k2 = tail[8..]
+            .iter()
+            .enumerate()
+            .fold(0u64, |k, (i, &b)| k ^ (u64::from(b) << (8 * i)));
+        h2 ^= k2.wrapping_mul(C2).rotate_left(33).wrapping_mul(C1);
+    }
+    if !tail.is_empty() {
+        let k1 = tail[..tail.len().min(8)]
+            .iter()
+            .enumerate()
+            .fold(0u64, |k, (i, &b)| k ^ (u64::from(b) << (8 * i)));
+        h1 ^= k1.wrapping_mul(C1).rotate_left(31).wrapping_mul(C2);
+    }
+
+    h1 ^= data.len() as u64;
+    h2 ^= data.len() as u64;
+    h1 = h1.wrapping_add(h2);
+    h2 = h2.wrapping_add(h1);
+    h1 = fmix64(h1);
+    h2 = fmix64(h2);
+    h1.wrapping_add(h2)
+}
+
+// ===========================================================================
+// Building
+// ===========================================================================
+
+struct SketchBuilder {
+    k: usize,
+    sketch_size: usize,
+    seed: u32,
+    alphabet: SeqAlphabet,
+    hashes: BTreeSet<u64>,
+    length: u64,
+}
+
+impl SketchBuilder {
+    fn new(k: usize, sketch_size: usize, seed: u32, alphabet: &str) -> Result<Self, NifError> {
+        if !(1..=32).contains(&k) {
+            return Err(NifError::invalid(format!("k must be between 1 and 32, got {k}")));
+        }
+        if sketch_size == 0 {
+            return Err(NifError::invalid("sketch_size must be > 0"));
+        }
+        Ok(Self {
+            k,
+            sketch_size,
+            seed,
+            alphabet: parse_seq_alphabet(alphabet)?,
+            hashes: BTreeSet::new(),
+            length: 0,
+        })
+    }
+
+    fn insert(&mut self, hash: u64) {
+        if self.hashes.len() < self.sketch_size {
+            self.hashes.insert(hash);
+        } else if self.hashes.last().is_some_and(|&max| hash < max) && self.hashes.insert(hash) {
+            self.hashes.pop_last();
+        }
+    }
+
+    /// Add one sequence (a record; k-mers never span two calls).
+    fn add(&mut self, data: &[u8]) -> Result<(), NifError> {
+        let seq = self.alphabet.normalize(data)?;
+        self.length += seq.len() as u64;
+        if self.alphabet == SeqAlphabet::Protein {
+            for kmer in seq.windows(self.k) {
+                self.insert(murmur3_x64_64(kmer, self.seed));
+            }
+            return Ok(());
+        }
+        let seq = to_dna(&seq);
+        let mut rc = vec![0u8; self.k];
+        for run in seq.split(|b| !matches!(b, b'A' | b'C' | b'G' | b'T')) {
+            for kmer in run.windows(self.k) {
+                for (dst, &b) in rc.iter_mut().zip(kmer.iter().rev()) {
+                    *dst = match b {
+                        b'A' => b'T',
+                        b'C' => b'G',
+                        b'G' => b'C',
+                        _ => b'A',
+                    };
+                }
+                let canonical = if rc.as_slice() < kmer { rc.as_slice() } else { kmer };
+                self.insert(murmur3_x64_64(canonical, self.seed));
+            }
+        }
+        Ok(())
+    }
+
+    fn finish(self, name: String) -> MinHashSketchNif {
+        MinHashSketchNif {
+            name,
+            k: self.k,
+            sketch_size: self.sketch_size,
+            seed: self.seed,
+            hash_function: HASH_FUNCTION.to_string(),
+            alphabet: match self.alphabet {
+                SeqAlphabet::Protein => "protein",
+                _ => "dna",
+            }
+            .to_string(),
+            length: self.length,
+            hashes: self.hashes.into_iter().collect(),
+        }
+    }
+}
+
+// ===========================================================================
+// Comparison
+// ===========================================================================
+
+/// Sorted, deduplicated copy of a sketch's hashes (sketches built here are
+/// already sorted; hand-made ones may not be).
+fn sorted_hashes(hashes: &[u64]) -> Vec<u64> {
+    let mut hashes = hashes.to_vec();
+    if !hashes.windows(2).all(|w| w[0] < w[1]) {
+        hashes.sort_unstable();
+        hashes.dedup();
+    }
+    hashes
+}
+
+/// Walk the bottom `limit` hashes of the union of two sorted sketches.
+/// Returns `(shared, union)`.
+fn merge_union(a: &[u64], b: &[u64], limit: usize) -> (usize, usize) {
+    let (mut i, mut j, mut shared, mut union) = (0, 0, 0, 0);
+    while union < limit && (i < a.len() || j < b.len()) {
+        match (a.get(i), b.get(j)) {
+            (Some(x), Some(y)) if x == y => {
+                shared += 1;
+                i += 1;
+                j += 1;
+            }
+            (Some(x), Some(y)) if x < y => i += 1,
+            (Some(_), None) => i += 1,
+            _ => j += 1,
+        }
+        union += 1;
+    }
+    (shared, union)
+}
+
+/// Bottom-k Jaccard estimate of two sketches of hash values.
+pub(crate) fn jaccard(a: &[u64], b: &[u64]) -> f64 {
+    let (a, b) = (sorted_hashes(a), sorted_hashes(b));
+    let (shared, union) = merge_union(&a, &b, a.len().min(b.len()));
+    if union == 0 {
+        0.0
+    } else {
+        shared as f64 / union as f64
+    }
+}
+
+/// Fraction of `query`'s hashes found in `reference`, counting only the
+/// range both sketches cover.
+fn containment(query: &[u64], reference: &[u64]) -> f64 {
+    let Some(&max) = reference.last() else {
+        return 0.0;
+    };
+    let covered: Vec<u64> = query.iter().copied().take_while(|&h| h <= max).collect();
+    if covered.is_empty() {
+        return 0.0;
+    }
+    let (shared, _) = merge_union(&covered, reference, usize::MAX);
+    shared as f64 / covered.len() as f64
+}
+
+/// `P(X >= x)` for `X ~ Binomial(n, r)`, summed in log space.
+fn binomial_upper_tail(n: usize, x: usize, r: f64) -> f64 {
+    if x == 0 || r >= 1.0 {
+        return 1.0;
+    }
+    if x > n || r <= 0.0 {
+        return 0.0;
+    }
+    let ln_choose: f64 = (1..=x).map(|i| ((n - x + i) as f64 / i as f64).ln()).sum();
+    let mut term = ln_choose + x as f64 * r.ln() + (n - x) as f64 * (1.0 - r).ln();
+    let mut terms = Vec::with_capacity(n - x + 1);
+    for i in x..=n {
+        terms.push(term);
+        term += ((n - i) as f64 / (i + 1) as f64).ln() + r.ln() - (1.0 - r).ln();
+    }
+    let max = terms.iter().copied().fold(f64::NEG_INFINITY, f64::max);
+    let sum: f64 = terms.iter().map(|t| (t - max).exp()).sum();
+    (max.exp() * sum).min(1.0)
+}
+
+fn compare(query: &MinHashSketchNif, reference: &MinHashSketchNif) -> Result<MinHashDistanceNif, NifError> {
+    if query.k != reference.k
+        || query.seed != reference.seed
+        || query.hash_function != reference.hash_function
+        || query.alphabet != reference.alphabet
+    {
+        return Err(NifError::invalid(format!(
+            "incompatible sketches {} and {}: k, seed, hash function and alphabet must match",
+            query.name, reference.name
+        )));
+    }
+    let (a, b) = (sorted_hashes(&query.hashes), sorted_hashes(&reference.hashes));
+    let limit = query.sketch_size.min(reference.sketch_size);
+    let (shared, union) = merge_union(&a, &b, limit);
+    let jaccard = if union == 0 { 0.0 } else { shared as f64 / union as f64 };
+    let mash_distance = if jaccard == 0.0 {
+        1.0
+    } else {
+        (-(2.0 * jaccard / (1.0 + jaccard)).ln() / query.k as f64).clamp(0.0, 1.0)
+    };
+
+    // Chance that a random k-mer of each sequence hits the other.
+    let alphabet_size: f64 = if query.alphabet == "protein" { 20.0 } else { 4.0 };
+    let kmer_space = alphabet_size.powi(query.k as i32);
+    let p_hit = |length: u64| 1.0 / (1.0 + kmer_space / length.max(1) as f64);
+    let (px, py) = (p_hit(query.length), p_hit(reference.length));
+    let r = px * py / (px + py - px * py);
+
+    Ok(MinHashDistanceNif {
+        query: query.name.clone(),
+        reference: reference.name.clone(),
+        jaccard,
+        containment: containment(&a, &b),
+        mash_distance,
+        ani: 1.0 - mash_distance,
+        p_value: binomial_upper_tail(union, shared, r),
+        shared_hashes: shared,
+        compared_hashes: union,
+    })
+}
+
+// ===========================================================================
+// NIFs
+// ===========================================================================
+
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn minhash_sketch_build(
+    seq: Vec<u8>,
+    name: String,
+    k: usize,
+    sketch_size: usize,
+    seed: u32,
+    alphabet: String,
+) -> Result<MinHashSketchNif, NifError> {
+    let mut builder = SketchBuilder::new(k, sketch_size, seed, &alphabet)?;
+    builder.add(&seq)?;
+    Ok(builder.finish(name))
+}
+
+/// Unnamed sketch with the Mash default seed, for quick comparisons with
+/// `minhash_jaccard`; sketches from `minhash_sketch_build` with seed 42 are
+/// interchangeable with it.
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn minhash_sketch(
+    seq: Vec<u8>,
+    k: usize,
+    sketch_size: usize,
+    alphabet: String,
+) -> Result<MinHashSketchNif, NifError> {
+    let mut builder = SketchBuilder::new(k, sketch_size, DEFAULT_SEED, &alphabet)?;
+    builder.add(&seq)?;
+    Ok(builder.finish(String::new()))
+}
+
+/// Sketch all records of a FASTA file (plain or gzipped) into one sketch
+/// named after the path, as `mash sketch` does for an assembly.
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn minhash_sketch_fasta(
+    path: String,
+    k: usize,
+    sketch_size: usize,
+    seed: u32,
+    alphabet: String,
+) -> Result<MinHashSketchNif, NifError> {
+    let mut builder = SketchBuilder::new(k, sketch_size, seed, &alphabet)?;
+    let mut lines = crate::io::open_text_reader(&path)?;
+    let mut buf = Vec::new();
+    let mut record = Vec::new();
+    let mut line_no = 0u64;
+    let mut seen_header = false;
+    loop {
+        buf.clear();
+        let n = lines
+            .read_until(b'\n', &mut buf)
+            .map_err(|e| NifError::from(e).with_file(&path).at_line(line_no + 1))?;
+        if n == 0 {
+            break;
+        }
+        line_no += 1;
+        let line = buf.trim_ascii_end();
+        if line.starts_with(b">") {
+            if seen_header {
+                builder
+                    .add(&record)
+                    .map_err(|e| e.with_file(&path).at_line(line_no - 1))?;
+                record.clear();
+            }
+            seen_header = true;
+        } else if !line.is_empty() && !line.starts_with(b";") {
+            if !seen_header {
+                return Err(NifError::parse("sequence data before first header")
+                    .with_file(&path)
+                    .at_line(line_no));
+            }
+            record.extend(line.iter().filter(|b| !b.is_ascii_whitespace()));
+        }
+    }
+    if seen_header {
+        builder
+            .add(&record)
+            .map_err(|e| e.with_file(&path).at_line(line_no))?;
+    }
+    Ok(builder.finish(path))
+}
+
+#[rustler::nif]
+pub fn minhash_compare(
+    query: MinHashSketchNif,
+    reference: MinHashSketchNif,
+) -> Result<MinHashDistanceNif, NifError> {
+    compare(&query, &reference)
+}
+
+/// Compare every query against every reference, keeping pairs within
+/// `max_distance`.
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn minhash_compare_many(
+    queries: Vec<MinHashSketchNif>,
+    references: Vec<MinHashSketchNif>,
+    max_distance: f64,
+) -> Result<Vec<MinHashDistanceNif>, NifError> {
+    let mut hits = Vec::new();
+    for query in &queries {
+        for reference in &references {
+            let d = compare(query, reference)?;
+            if d.mash_distance <= max_distance {
+                hits.push(d);
+            }
+        }
+    }
+    Ok(hits)
+}
+
+/// Write sketches to a zstd-compressed sketch file. Returns the count.
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn minhash_sketch_save(path: String, sketches: Vec<MinHashSketchNif>) -> Result<usize, NifError> {
+    let stored: Vec<StoredSketch> = sketches
+        .into_iter()
+        .map(|s| {
+            (
+                s.name,
+                s.hash_function,
+                s.alphabet,
+                s.k as u32,
+                s.sketch_size as u32,
+                s.seed,
+                s.length,
+                s.hashes,
+            )
+        })
+        .collect();
+    let payload = bincode::serialize(&(SKETCH_FORMAT_VERSION, &stored))
+        .map_err(|e| NifError::internal(e.to_string()))?;
+    let compressed = cyanea_core::compress::zstd_compress(&payload, 3).map_err(to_nif_error)?;
+    std::fs::write(&path, compressed).map_err(|e| NifError::from(e).with_file(&path))?;
+    Ok(stored.len())
+}
+
+#[rustler::nif(schedule = "DirtyCpu")]
+pub fn minhash_sketch_load(path: String) -> Result<Vec<MinHashSketchNif>, NifError> {
+    let data = std::fs::read(&path).map_err(|e| NifError::from(e).with_file(&path))?;
+    let payload = cyanea_core::compress::zstd_decompress(&data)
+        .map_err(|e| to_nif_error(e).with_file(&path))?;
+    let (version, stored): (u32, Vec<StoredSketch>) = bincode::deserialize(&payload)
+        .map_err(|e| NifError::parse(format!("not a sketch file: {e}")).with_file(&path))?;
+    if version != SKETCH_FORMAT_VERSION {
+        return Err(NifError::unsupported(format!(
+            "unsupported sketch format version {version} (expected {SKETCH_FORMAT_VERSION})"
+        ))
+        .with_file(&path));
+    }
+    Ok(stored
+        .into_iter()
+        .map(
+            |(name, hash_function, alphabet, k, sketch_size, seed, length, hashes)| MinHashSketchNif {
+                name,
+                k: k as usize,
+                sketch_size: sketch_size as usize,
+                seed,
+                hash_function,
+                alphabet,
+                length,
+                hashes,
+            },
+        )
+        .collect())
+}
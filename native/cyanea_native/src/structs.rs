@@ -8,14 +8,14 @@ use crate::to_nif_error;
 // ===========================================================================
 
 #[rustler::nif]
-pub fn pdb_info(pdb_text: String) -> Result<PdbInfoNif, String> {
+pub fn pdb_info(pdb_text: String) -> Result<PdbInfoNif, NifError> {
     let structure = cyanea_struct::parse_pdb(&pdb_text).map_err(to_nif_error)?;
     Ok(structure_to_pdb_info(&structure))
 }
 
 #[rustler::nif(schedule = "DirtyCpu")]
-pub fn pdb_file_info(path: String) -> Result<PdbInfoNif, String> {
-    let contents = std::fs::read_to_string(&path).map_err(|e| e.to_string())?;
+pub fn pdb_file_info(path: String) -> Result<PdbInfoNif, NifError> {
+    let contents = std::fs::read_to_string(&path).map_err(|e| NifError::from(e).with_file(&path))?;
     let structure = cyanea_struct::parse_pdb(&contents).map_err(to_nif_error)?;
     Ok(structure_to_pdb_info(&structure))
 }
@@ -24,15 +24,15 @@ pub fn pdb_file_info(path: String) -> Result<PdbInfoNif, String> {
 pub fn pdb_secondary_structure(
     pdb_text: String,
     chain_id: String,
-) -> Result<SecondaryStructureNif, String> {
+) -> Result<SecondaryStructureNif, NifError> {
     let structure = cyanea_struct::parse_pdb(&pdb_text).map_err(to_nif_error)?;
     let chain_char = chain_id
         .chars()
         .next()
-        .ok_or("chain_id must be a single character")?;
+        .ok_or_else(|| NifError::invalid("chain_id must be a single character"))?;
     let chain = structure
         .get_chain(chain_char)
-        .ok_or_else(|| format!("chain '{chain_char}' not found"))?;
+        .ok_or_else(|| NifError::not_found(format!("chain '{chain_char}' not found")))?;
     let assignment =
         cyanea_struct::assign_secondary_structure(chain).map_err(to_nif_error)?;
     let assignments: Vec<String> = assignment
@@ -57,23 +57,23 @@ pub fn pdb_rmsd(
     pdb_b: String,
     chain_a: String,
     chain_b: String,
-) -> Result<f64, String> {
+) -> Result<f64, NifError> {
     let struct_a = cyanea_struct::parse_pdb(&pdb_a).map_err(to_nif_error)?;
     let struct_b = cyanea_struct::parse_pdb(&pdb_b).map_err(to_nif_error)?;
     let chain_a_char = chain_a
         .chars()
         .next()
-        .ok_or("chain_a must be a single character")?;
+        .ok_or_else(|| NifError::invalid("chain_a must be a single character"))?;
     let chain_b_char = chain_b
         .chars()
         .next()
-        .ok_or("chain_b must be a single character")?;
+        .ok_or_else(|| NifError::invalid("chain_b must be a single character"))?;
     let ca = struct_a
         .get_chain(chain_a_char)
-        .ok_or_else(|| format!("chain '{}' not found in first structure", chain_a_char))?;
+        .ok_or_else(|| NifError::not_found(format!("chain '{}' not found in first structure", chain_a_char)))?;
     let cb = struct_b
         .get_chain(chain_b_char)
-        .ok_or_else(|| format!("chain '{}' not found in second structure", chain_b_char))?;
+        .ok_or_else(|| NifError::not_found(format!("chain '{}' not found in second structure", chain_b_char)))?;
     let atoms_a: Vec<&cyanea_struct::Atom> = ca
         .residues
         .iter()
@@ -92,7 +92,7 @@ pub fn pdb_rmsd(
 // ===========================================================================
 
 #[rustler::nif]
-pub fn mmcif_info(mmcif_text: String) -> Result<PdbInfoNif, String> {
+pub fn mmcif_info(mmcif_text: String) -> Result<PdbInfoNif, NifError> {
     let structure = cyanea_struct::parse_mmcif(&mmcif_text).map_err(to_nif_error)?;
     Ok(structure_to_pdb_info(&structure))
 }
@@ -102,15 +102,15 @@ pub fn pdb_contact_map(
     pdb_text: String,
     chain_id: String,
     cutoff: f64,
-) -> Result<ContactMapResultNif, String> {
+) -> Result<ContactMapResultNif, NifError> {
     let structure = cyanea_struct::parse_pdb(&pdb_text).map_err(to_nif_error)?;
     let chain_char = chain_id
         .chars()
         .next()
-        .ok_or("chain_id must be a single character")?;
+        .ok_or_else(|| NifError::invalid("chain_id must be a single character"))?;
     let chain = structure
         .get_chain(chain_char)
-        .ok_or_else(|| format!("chain '{chain_char}' not found"))?;
+        .ok_or_else(|| NifError::not_found(format!("chain '{chain_char}' not found")))?;
     let cmap = cyanea_struct::compute_contact_map(chain).map_err(to_nif_error)?;
     let n_residues = cmap.size;
     let mut contacts = Vec::new();
@@ -141,13 +141,13 @@ pub fn pdb_kabsch(
     pdb_b: String,
     chain_a: String,
     chain_b: String,
-) -> Result<SuperpositionResultNif, String> {
+) -> Result<SuperpositionResultNif, NifError> {
     let struct_a = cyanea_struct::parse_pdb(&pdb_a).map_err(to_nif_error)?;
     let struct_b = cyanea_struct::parse_pdb(&pdb_b).map_err(to_nif_error)?;
-    let ca_char = chain_a.chars().next().ok_or("chain_a must be a single character")?;
-    let cb_char = chain_b.chars().next().ok_or("chain_b must be a single character")?;
-    let ca = struct_a.get_chain(ca_char).ok_or_else(|| format!("chain '{ca_char}' not found"))?;
-    let cb = struct_b.get_chain(cb_char).ok_or_else(|| format!("chain '{cb_char}' not found"))?;
+    let ca_char = chain_a.chars().next().ok_or_else(|| NifError::invalid("chain_a must be a single character"))?;
+    let cb_char = chain_b.chars().next().ok_or_else(|| NifError::invalid("chain_b must be a single character"))?;
+    let ca = struct_a.get_chain(ca_char).ok_or_else(|| NifError::not_found(format!("chain '{ca_char}' not found")))?;
+    let cb = struct_b.get_chain(cb_char).ok_or_else(|| NifError::not_found(format!("chain '{cb_char}' not found")))?;
     let atoms_a: Vec<&cyanea_struct::Atom> = ca.residues.iter()
         .filter_map(|r| r.atoms.iter().find(|a| a.name == "CA")).collect();
     let atoms_b: Vec<&cyanea_struct::Atom> = cb.residues.iter()
@@ -161,7 +161,7 @@ pub fn pdb_kabsch(
 }
 
 #[rustler::nif]
-pub fn pdb_ramachandran(pdb_text: String) -> Result<Vec<RamachandranEntryNif>, String> {
+pub fn pdb_ramachandran(pdb_text: String) -> Result<Vec<RamachandranEntryNif>, NifError> {
     let structure = cyanea_struct::parse_pdb(&pdb_text).map_err(to_nif_error)?;
     let report = cyanea_struct::ramachandran_report(&structure).map_err(to_nif_error)?;
     Ok(report
@@ -177,7 +177,7 @@ pub fn pdb_ramachandran(pdb_text: String) -> Result<Vec<RamachandranEntryNif>, S
 }
 
 #[rustler::nif]
-pub fn pdb_bfactor_analysis(pdb_text: String) -> Result<BfactorResultNif, String> {
+pub fn pdb_bfactor_analysis(pdb_text: String) -> Result<BfactorResultNif, NifError> {
     let structure = cyanea_struct::parse_pdb(&pdb_text).map_err(to_nif_error)?;
     let mut all_bfactors = Vec::new();
     let mut per_chain = Vec::new();
@@ -195,7 +195,7 @@ pub fn pdb_bfactor_analysis(pdb_text: String) -> Result<BfactorResultNif, String
         }
     }
     if all_bfactors.is_empty() {
-        return Err("no atoms found in structure".into());
+        return Err(NifError::parse("no atoms found in structure"));
     }
     let n = all_bfactors.len() as f64;
     let mean = all_bfactors.iter().sum::<f64>() / n;
@@ -212,8 +212,8 @@ pub fn pdb_bfactor_analysis(pdb_text: String) -> Result<BfactorResultNif, String
 }
 
 #[rustler::nif(schedule = "DirtyCpu")]
-pub fn mmcif_file_info(path: String) -> Result<PdbInfoNif, String> {
-    let contents = std::fs::read_to_string(&path).map_err(|e| e.to_string())?;
+pub fn mmcif_file_info(path: String) -> Result<PdbInfoNif, NifError> {
+    let contents = std::fs::read_to_string(&path).map_err(|e| NifError::from(e).with_file(&path))?;
     let structure = cyanea_struct::parse_mmcif(&contents).map_err(to_nif_error)?;
     Ok(structure_to_pdb_info(&structure))
 }
@@ -65,6 +65,91 @@ defmodule Cyanea.AlignTest do
     end
   end
 
+  describe "protein/3 with a scoring scheme" do
+    test "routes a scheme reference to the scheme aligner" do
+      assert {:error, :nif_not_loaded} = Align.protein("MVLK", "MVLK", matrix: make_ref())
+    end
+  end
+
+  # ===========================================================================
+  # Scoring schemes
+  # ===========================================================================
+
+  describe "scoring_scheme/2" do
+    test "accepts built-in, file and inline matrices" do
+      assert {:error, :nif_not_loaded} = Align.scoring_scheme(:blosum62)
+      assert {:error, :nif_not_loaded} = Align.scoring_scheme(:nuc44, gap_open: -16, gap_extend: -4)
+      assert {:error, :nif_not_loaded} = Align.scoring_scheme(:pam250)
+      assert {:error, :nif_not_loaded} = Align.scoring_scheme({:file, "/tmp/PAM30"})
+      assert {:error, :nif_not_loaded} = Align.scoring_scheme({:matrix, "ab", "  A B\nA 1 -1\nB -1 1\n"})
+    end
+
+    test "rejects unknown sources" do
+      assert_raise CaseClauseError, fn -> Align.scoring_scheme(:pam30) end
+    end
+  end
+
+  describe "scoring_scheme_info/1" do
+    test "returns nif_not_loaded without NIF" do
+      assert {:error, :nif_not_loaded} = Align.scoring_scheme_info(make_ref())
+    end
+  end
+
+  describe "pairwise/4" do
+    test "returns nif_not_loaded without NIF" do
+      assert {:error, :nif_not_loaded} = Align.pairwise("MVLK", "MVK", make_ref(), mode: :local)
+    end
+
+    test "requires a scheme reference" do
+      assert_raise FunctionClauseError, fn -> Align.pairwise("MVLK", "MVK", :blosum62) end
+    end
+  end
+
+  # ===========================================================================
+  # Database search
+  # ===========================================================================
+
+  describe "search_fasta/3" do
+    test "returns nif_not_loaded without NIF" do
+      assert {:error, :nif_not_loaded} = Align.search_fasta("MVLK", "/tmp/db.fa")
+    end
+
+    test "accepts a scheme and statistics options" do
+      assert {:error, :nif_not_loaded} =
+               Align.search_fasta("ACGT", "/tmp/db.fa", scheme: make_ref(), max_hits: 5, lambda: 0.19, k: 0.18)
+    end
+
+    test "rejects unknown types" do
+      assert_raise CaseClauseError, fn -> Align.search_fasta("MVLK", "/tmp/db.fa", type: :rna) end
+    end
+  end
+
+  # ===========================================================================
+  # Read mapping
+  # ===========================================================================
+
+  describe "mapper_index/1" do
+    test "returns nif_not_loaded without NIF" do
+      assert {:error, :nif_not_loaded} = Align.mapper_index("/tmp/ref.fa")
+    end
+  end
+
+  describe "map_reads/4" do
+    test "accepts single and paired-end reads" do
+      assert {:error, :nif_not_loaded} = Align.map_reads(make_ref(), "/tmp/r.fq", "/tmp/out.sam")
+      assert {:error, :nif_not_loaded} = Align.map_reads(make_ref(), {"/tmp/r1.fq", "/tmp/r2.fq"}, "/tmp/out.bam")
+    end
+
+    test "accepts format and seeding options" do
+      assert {:error, :nif_not_loaded} =
+               Align.map_reads(make_ref(), "/tmp/r.fq", "/tmp/out", format: :bam, seed_length: 15, min_score: 20)
+    end
+
+    test "requires an index reference" do
+      assert_raise FunctionClauseError, fn -> Align.map_reads("/tmp/ref.fa", "/tmp/r.fq", "/tmp/out.sam") end
+    end
+  end
+
   # ===========================================================================
   # Batch
   # ===========================================================================
@@ -78,6 +163,14 @@ defmodule Cyanea.AlignTest do
       assert {:error, :nif_not_loaded} = Align.batch([{"AT", "AT"}], mode: :global)
     end
 
+    test "accepts async option" do
+      assert {:error, :nif_not_loaded} = Align.batch([{"AT", "AT"}], async: true, cancel: make_ref())
+    end
+
+    test "accepts scheme option" do
+      assert {:error, :nif_not_loaded} = Align.batch([{"MVLK", "MVK"}], scheme: make_ref())
+    end
+
     test "rejects non-list" do
       assert_raise FunctionClauseError, fn -> Align.batch("not_a_list") end
     end
@@ -101,6 +194,51 @@ defmodule Cyanea.AlignTest do
     end
   end
 
+  describe "msa_report/2" do
+    test "returns nif_not_loaded without NIF" do
+      assert {:error, :nif_not_loaded} = Align.msa_report(["ATCG", "ATCG"])
+    end
+
+    test "accepts guide tree, refinement and scheme options" do
+      assert {:error, :nif_not_loaded} = Align.msa_report(["ATCG", "ATCG"], guide_tree: :nj, refine: 2)
+
+      assert {:error, :nif_not_loaded} =
+               Align.msa_report(["MVLK", "MVLK"], scheme: make_ref(), names: ["a", "b"], async: true)
+    end
+
+    test "rejects unknown guide tree method" do
+      assert_raise FunctionClauseError, fn ->
+        Align.msa_report(["MVLK", "MVLK"], scheme: make_ref(), guide_tree: :nnj)
+      end
+    end
+  end
+
+  describe "msa_add/3" do
+    test "returns nif_not_loaded without NIF" do
+      assert {:error, :nif_not_loaded} = Align.msa_add(["AC-GT", "ACAGT"], ["ACGT"])
+    end
+
+    test "accepts alignment structs and files" do
+      report = %Cyanea.Native.MsaReport{names: ["a", "b"], aligned: ["AC-GT", "ACAGT"]}
+
+      assert {:error, :nif_not_loaded} =
+               Align.msa_add(report, ["ACGT"], scheme: make_ref(), names: ["c"], keep_length: true)
+
+      assert {:error, :nif_not_loaded} =
+               Align.msa_add({:stockholm, "/tmp/aln.sto"}, ["ACGT"], scheme: make_ref(), band: 100)
+    end
+
+    test "rejects unknown alignment sources" do
+      assert_raise FunctionClauseError, fn -> Align.msa_add({:fasta, "/tmp/aln.fa"}, ["ACGT"]) end
+    end
+  end
+
+  describe "msa_merge/3" do
+    test "returns nif_not_loaded without NIF" do
+      assert {:error, :nif_not_loaded} = Align.msa_merge(["AC-GT"], {:clustal, "/tmp/aln.aln"})
+    end
+  end
+
   # ===========================================================================
   # Banded
   # ===========================================================================
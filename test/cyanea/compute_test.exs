@@ -77,7 +77,7 @@ defmodule Cyanea.ComputeTest do
 
   describe "fm_index_count" do
     test "returns nif_not_loaded without NIF" do
-      assert {:error, :nif_not_loaded} = Compute.fm_index_count(<<0, 1, 2>>, "ATC")
+      assert {:error, :nif_not_loaded} = Compute.fm_index_count(make_ref(), "ATC")
     end
   end
 
@@ -539,7 +539,7 @@ defmodule Cyanea.ComputeTest do
 
   describe "random_forest_predict" do
     test "returns nif_not_loaded without NIF" do
-      assert {:error, :nif_not_loaded} = Compute.random_forest_predict(<<0, 1, 2>>, [1.0, 2.0], 2)
+      assert {:error, :nif_not_loaded} = Compute.random_forest_predict(make_ref(), [1.0, 2.0], 2)
     end
   end
 
@@ -912,12 +912,12 @@ defmodule Cyanea.ComputeTest do
       assert_raise FunctionClauseError, fn -> Compute.fm_index_build(123) end
     end
 
-    test "fm_index_count rejects non-binary index_data" do
-      assert_raise FunctionClauseError, fn -> Compute.fm_index_count(123, "ATC") end
+    test "fm_index_count rejects non-reference index" do
+      assert_raise FunctionClauseError, fn -> Compute.fm_index_count(<<0>>, "ATC") end
     end
 
     test "fm_index_count rejects non-binary pattern" do
-      assert_raise FunctionClauseError, fn -> Compute.fm_index_count(<<0>>, 123) end
+      assert_raise FunctionClauseError, fn -> Compute.fm_index_count(make_ref(), 123) end
     end
 
     test "find_orfs rejects non-binary seq" do
@@ -1268,16 +1268,16 @@ defmodule Cyanea.ComputeTest do
       assert_raise FunctionClauseError, fn -> Compute.random_forest_fit([1.0], 2, "not") end
     end
 
-    test "random_forest_predict rejects non-binary model_data" do
-      assert_raise FunctionClauseError, fn -> Compute.random_forest_predict(123, [1.0], 2) end
+    test "random_forest_predict rejects non-reference model" do
+      assert_raise FunctionClauseError, fn -> Compute.random_forest_predict(<<0>>, [1.0], 2) end
     end
 
-    test "random_forest_predict rejects non-list sample" do
-      assert_raise FunctionClauseError, fn -> Compute.random_forest_predict(<<0>>, "not", 2) end
+    test "random_forest_predict rejects non-list samples" do
+      assert_raise FunctionClauseError, fn -> Compute.random_forest_predict(make_ref(), "not", 2) end
     end
 
     test "random_forest_predict rejects non-integer n_features" do
-      assert_raise FunctionClauseError, fn -> Compute.random_forest_predict(<<0>>, [1.0], "2") end
+      assert_raise FunctionClauseError, fn -> Compute.random_forest_predict(make_ref(), [1.0], "2") end
     end
 
     test "hmm_viterbi rejects non-integer n_states" do
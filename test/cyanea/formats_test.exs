@@ -55,6 +55,46 @@ defmodule Cyanea.FormatsTest do
     end
   end
 
+  describe "open_vcf/1" do
+    test "returns nif_not_loaded without NIF" do
+      assert {:error, :nif_not_loaded} = Formats.open_vcf("/tmp/test.vcf.gz")
+    end
+
+    test "rejects non-binary" do
+      assert_raise FunctionClauseError, fn -> Formats.open_vcf(123) end
+    end
+  end
+
+  describe "vcf_header/1" do
+    test "returns nif_not_loaded without NIF" do
+      assert {:error, :nif_not_loaded} = Formats.vcf_header(make_ref())
+    end
+
+    test "rejects non-reference" do
+      assert_raise FunctionClauseError, fn -> Formats.vcf_header("/tmp/test.vcf") end
+    end
+  end
+
+  describe "next_vcf_batch/2" do
+    test "returns nif_not_loaded without NIF" do
+      assert {:error, :nif_not_loaded} = Formats.next_vcf_batch(make_ref(), 100)
+    end
+
+    test "rejects non-positive batch size" do
+      assert_raise FunctionClauseError, fn -> Formats.next_vcf_batch(make_ref(), 0) end
+    end
+  end
+
+  describe "stream_vcf/2" do
+    test "returns nif_not_loaded without NIF" do
+      assert {:error, :nif_not_loaded} = Formats.stream_vcf("/tmp/test.vcf", batch_size: 10)
+    end
+
+    test "rejects non-binary" do
+      assert_raise FunctionClauseError, fn -> Formats.stream_vcf(123) end
+    end
+  end
+
   # ===========================================================================
   # BED
   # ===========================================================================
@@ -156,4 +196,134 @@ defmodule Cyanea.FormatsTest do
       assert_raise FunctionClauseError, fn -> Formats.parse_bam(123) end
     end
   end
+
+  describe "sam_flags/1" do
+    test "returns nif_not_loaded without NIF" do
+      assert {:error, :nif_not_loaded} = Formats.sam_flags(99)
+    end
+
+    test "accepts a SamRecord" do
+      record = %Cyanea.Native.SamRecord{flag: 1024}
+      assert {:error, :nif_not_loaded} = Formats.sam_flags(record)
+    end
+
+    test "rejects out-of-range flags" do
+      assert_raise FunctionClauseError, fn -> Formats.sam_flags(-1) end
+      assert_raise FunctionClauseError, fn -> Formats.sam_flags(70_000) end
+    end
+  end
+
+  # ===========================================================================
+  # Indexed region queries
+  # ===========================================================================
+
+  describe "open_indexed_bam/1" do
+    test "returns nif_not_loaded without NIF" do
+      assert {:error, :nif_not_loaded} = Formats.open_indexed_bam("/tmp/test.bam")
+    end
+
+    test "rejects non-binary" do
+      assert_raise FunctionClauseError, fn -> Formats.open_indexed_bam(123) end
+    end
+  end
+
+  describe "fetch_bam/2" do
+    test "returns nif_not_loaded without NIF" do
+      assert {:error, :nif_not_loaded} = Formats.fetch_bam(make_ref(), "chr1:100-200")
+    end
+
+    test "accepts a region tuple" do
+      assert {:error, :nif_not_loaded} = Formats.fetch_bam(make_ref(), {"chr1", 100, 200})
+    end
+
+    test "rejects non-reference handle" do
+      assert_raise FunctionClauseError, fn -> Formats.fetch_bam("/tmp/test.bam", "chr1") end
+    end
+  end
+
+  describe "open_tabix/1" do
+    test "returns nif_not_loaded without NIF" do
+      assert {:error, :nif_not_loaded} = Formats.open_tabix("/tmp/test.vcf.gz")
+    end
+  end
+
+  describe "fetch_vcf/2" do
+    test "returns nif_not_loaded without NIF" do
+      assert {:error, :nif_not_loaded} = Formats.fetch_vcf(make_ref(), "chr1:100-200")
+    end
+
+    test "rejects malformed region tuple" do
+      assert_raise FunctionClauseError, fn -> Formats.fetch_vcf(make_ref(), {"chr1", "100"}) end
+    end
+  end
+
+  describe "fetch_bed/2" do
+    test "returns nif_not_loaded without NIF" do
+      assert {:error, :nif_not_loaded} = Formats.fetch_bed(make_ref(), "chr1")
+    end
+  end
+
+  describe "fetch_lines/2" do
+    test "returns nif_not_loaded without NIF" do
+      assert {:error, :nif_not_loaded} = Formats.fetch_lines(make_ref(), "chr1:1-1000")
+    end
+  end
+
+  describe "index_bam/2" do
+    test "returns nif_not_loaded without NIF" do
+      assert {:error, :nif_not_loaded} = Formats.index_bam("/tmp/test.bam", format: :csi)
+    end
+  end
+
+  describe "index_tabix/2" do
+    test "returns nif_not_loaded without NIF" do
+      assert {:error, :nif_not_loaded} = Formats.index_tabix("/tmp/test.bed.gz", preset: :bed)
+    end
+
+    test "rejects non-binary" do
+      assert_raise FunctionClauseError, fn -> Formats.index_tabix(123) end
+    end
+  end
+
+  # ===========================================================================
+  # Writers
+  # ===========================================================================
+
+  describe "write_vcf/4" do
+    test "returns nif_not_loaded without NIF" do
+      header = %Cyanea.Native.VcfHeader{samples: [], contigs: [], filters: [], info: [], format: [], meta: []}
+      assert {:error, :nif_not_loaded} = Formats.write_vcf("/tmp/out.vcf.gz", header, [])
+    end
+
+    test "rejects non-struct header" do
+      assert_raise FunctionClauseError, fn -> Formats.write_vcf("/tmp/out.vcf", %{}, []) end
+    end
+  end
+
+  describe "write_bed/3" do
+    test "returns nif_not_loaded without NIF" do
+      assert {:error, :nif_not_loaded} = Formats.write_bed("/tmp/out.bed", [], compression: :gzip)
+    end
+
+    test "rejects non-list records" do
+      assert_raise FunctionClauseError, fn -> Formats.write_bed("/tmp/out.bed", :records) end
+    end
+  end
+
+  describe "write_gff3/3" do
+    test "returns nif_not_loaded without NIF" do
+      assert {:error, :nif_not_loaded} = Formats.write_gff3("/tmp/out.gff3", [])
+    end
+  end
+
+  describe "write_sam/3" do
+    test "returns nif_not_loaded without NIF" do
+      assert {:error, :nif_not_loaded} =
+               Formats.write_sam("/tmp/out.sam", [], header: "@HD\tVN:1.6")
+    end
+
+    test "rejects non-binary path" do
+      assert_raise FunctionClauseError, fn -> Formats.write_sam(123, []) end
+    end
+  end
 end
@@ -18,6 +18,11 @@ defmodule Cyanea.GPUTest do
       assert {:error, :nif_not_loaded} = GPU.distances([0.0, 0.0, 1.0, 1.0], 2, 2, metric: :manhattan)
     end
 
+    test "accepts a binary matrix" do
+      matrix = Cyanea.Native.Matrix.from_rows([[0.0, 0.0], [1.0, 1.0]])
+      assert {:error, :nif_not_loaded} = GPU.distances(matrix, 2, 2)
+    end
+
     test "rejects non-list data" do
       assert_raise FunctionClauseError, fn -> GPU.distances("not", 2, 2) end
     end
@@ -0,0 +1,60 @@
+defmodule Cyanea.JobTest do
+  use ExUnit.Case, async: false
+
+  alias Cyanea.Job
+
+  describe "cancel_token/0" do
+    test "returns nif_not_loaded without NIF" do
+      assert {:error, :nif_not_loaded} = Job.cancel_token()
+    end
+  end
+
+  describe "cancel/1" do
+    test "returns nif_not_loaded without NIF" do
+      assert {:error, :nif_not_loaded} = Job.cancel(make_ref())
+    end
+
+    test "rejects non-reference token" do
+      assert_raise FunctionClauseError, fn -> Job.cancel("token") end
+    end
+  end
+
+  describe "await/2" do
+    test "returns the result sent for the job" do
+      ref = make_ref()
+      send(self(), {:cyanea_result, ref, {:ok, "((A,B),C);"}})
+      assert {:ok, "((A,B),C);"} = Job.await(ref, 100)
+    end
+
+    test "ignores results for other jobs" do
+      send(self(), {:cyanea_result, make_ref(), {:ok, 1}})
+      assert {:error, :timeout} = Job.await(make_ref(), 10)
+    end
+  end
+
+  describe "pool_stats/1" do
+    test "returns nif_not_loaded without NIF" do
+      assert {:error, :nif_not_loaded} = Job.pool_stats(:align)
+    end
+  end
+
+  describe "set_pool_concurrency/2" do
+    test "returns nif_not_loaded without NIF" do
+      assert {:error, :nif_not_loaded} = Job.set_pool_concurrency(:ml, 4)
+    end
+
+    test "rejects non-positive limits" do
+      assert_raise FunctionClauseError, fn -> Job.set_pool_concurrency(:ml, 0) end
+    end
+  end
+
+  describe "cancelled?/1" do
+    test "returns nif_not_loaded without NIF" do
+      assert {:error, :nif_not_loaded} = Job.cancelled?(make_ref())
+    end
+
+    test "rejects non-reference token" do
+      assert_raise FunctionClauseError, fn -> Job.cancelled?(:token) end
+    end
+  end
+end
@@ -16,6 +16,15 @@ defmodule Cyanea.MLTest do
       assert {:error, :nif_not_loaded} = ML.kmeans([0.0, 0.0, 1.0, 1.0], 2, 2, max_iter: 50, seed: 123)
     end
 
+    test "accepts a binary matrix" do
+      matrix = Cyanea.Native.Matrix.from_rows([[0.0, 0.0], [1.0, 1.0]])
+      assert {:error, :nif_not_loaded} = ML.kmeans(matrix, 2, 2)
+    end
+
+    test "accepts a list of rows" do
+      assert {:error, :nif_not_loaded} = ML.kmeans([[0.0, 0.0], [1.0, 1.0]], 2, 2)
+    end
+
     test "rejects non-list data" do
       assert_raise FunctionClauseError, fn -> ML.kmeans("not", 2, 2) end
     end
@@ -128,6 +137,16 @@ defmodule Cyanea.MLTest do
       )
     end
 
+    test "accepts cancel and progress opts" do
+      assert {:error, :nif_not_loaded} = ML.umap([1.0, 2.0, 3.0, 4.0], 2,
+        cancel: make_ref(), progress: self()
+      )
+    end
+
+    test "accepts async opt" do
+      assert {:error, :nif_not_loaded} = ML.umap([1.0, 2.0, 3.0, 4.0], 2, async: true)
+    end
+
     test "rejects non-list data" do
       assert_raise FunctionClauseError, fn -> ML.umap("not", 2) end
     end
@@ -291,19 +310,49 @@ defmodule Cyanea.MLTest do
 
   describe "predict_forest/3" do
     test "returns nif_not_loaded without NIF" do
-      assert {:error, :nif_not_loaded} = ML.predict_forest(<<0, 1, 2>>, [1.0, 2.0], 2)
+      assert {:error, :nif_not_loaded} = ML.predict_forest(make_ref(), [1.0, 2.0, 3.0, 4.0], 2)
     end
 
-    test "rejects non-binary model" do
-      assert_raise FunctionClauseError, fn -> ML.predict_forest(123, [1.0], 2) end
+    test "rejects non-reference model" do
+      assert_raise FunctionClauseError, fn -> ML.predict_forest(<<0, 1, 2>>, [1.0], 2) end
     end
 
-    test "rejects non-list sample" do
-      assert_raise FunctionClauseError, fn -> ML.predict_forest(<<0>>, "not", 2) end
+    test "rejects non-list samples" do
+      assert_raise FunctionClauseError, fn -> ML.predict_forest(make_ref(), "not", 2) end
     end
 
     test "rejects non-integer n_features" do
-      assert_raise FunctionClauseError, fn -> ML.predict_forest(<<0>>, [1.0], "2") end
+      assert_raise FunctionClauseError, fn -> ML.predict_forest(make_ref(), [1.0], "2") end
+    end
+  end
+
+  describe "predict_forest_proba/3" do
+    test "returns nif_not_loaded without NIF" do
+      assert {:error, :nif_not_loaded} = ML.predict_forest_proba(make_ref(), [1.0, 2.0], 2)
+    end
+
+    test "rejects non-reference model" do
+      assert_raise FunctionClauseError, fn -> ML.predict_forest_proba(<<0>>, [1.0], 2) end
+    end
+  end
+
+  describe "forest_importances/1" do
+    test "returns nif_not_loaded without NIF" do
+      assert {:error, :nif_not_loaded} = ML.forest_importances(make_ref())
+    end
+
+    test "rejects non-reference model" do
+      assert_raise FunctionClauseError, fn -> ML.forest_importances(<<0>>) end
+    end
+  end
+
+  describe "forest_info/1" do
+    test "returns nif_not_loaded without NIF" do
+      assert {:error, :nif_not_loaded} = ML.forest_info(make_ref())
+    end
+
+    test "rejects non-reference model" do
+      assert_raise FunctionClauseError, fn -> ML.forest_info(<<0>>) end
     end
   end
 
@@ -372,6 +421,11 @@ defmodule Cyanea.MLTest do
       assert {:error, :nif_not_loaded} = ML.normalize([1.0, 2.0, 3.0], :z_score)
     end
 
+    test "accepts a binary matrix" do
+      matrix = Cyanea.Native.Matrix.from_rows([[1.0, 2.0, 3.0]], :f32)
+      assert {:error, :nif_not_loaded} = ML.normalize(matrix, :z_score)
+    end
+
     test "rejects non-list data" do
       assert_raise FunctionClauseError, fn -> ML.normalize("not", :min_max) end
     end
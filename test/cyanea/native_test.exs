@@ -79,6 +79,48 @@ defmodule Cyanea.NativeTest do
     end
   end
 
+  describe "parse_fasta/1" do
+    test "raises nif_not_loaded" do
+      assert_nif_not_loaded(fn -> Native.parse_fasta("/tmp/test.fasta") end)
+    end
+  end
+
+  describe "fasta_index_build/1" do
+    test "raises nif_not_loaded" do
+      assert_nif_not_loaded(fn -> Native.fasta_index_build("/tmp/test.fasta") end)
+    end
+  end
+
+  describe "fasta_index_load/1" do
+    test "raises nif_not_loaded" do
+      assert_nif_not_loaded(fn -> Native.fasta_index_load("/tmp/test.fasta") end)
+    end
+  end
+
+  describe "fasta_open/1" do
+    test "raises nif_not_loaded" do
+      assert_nif_not_loaded(fn -> Native.fasta_open("/tmp/test.fasta") end)
+    end
+  end
+
+  describe "fasta_fetch/4" do
+    test "raises nif_not_loaded" do
+      assert_nif_not_loaded(fn -> Native.fasta_fetch("/tmp/test.fasta", "chr1", 1, 100) end)
+    end
+  end
+
+  describe "fasta_fetch_handle/4" do
+    test "raises nif_not_loaded" do
+      assert_nif_not_loaded(fn -> Native.fasta_fetch_handle(make_ref(), "chr1", 1, 100) end)
+    end
+  end
+
+  describe "write_fasta/4" do
+    test "raises nif_not_loaded" do
+      assert_nif_not_loaded(fn -> Native.write_fasta("/tmp/out.fa", [], 60, "none") end)
+    end
+  end
+
   describe "validate_dna/1" do
     test "raises nif_not_loaded" do
       assert_nif_not_loaded(fn -> Native.validate_dna("ATCG") end)
@@ -121,9 +163,132 @@ defmodule Cyanea.NativeTest do
     end
   end
 
-  describe "sequence_kmers/2" do
+  describe "sequence_kmers/3" do
+    test "raises nif_not_loaded" do
+      assert_nif_not_loaded(fn -> Native.sequence_kmers("ATCGATCG", 3, "dna") end)
+    end
+  end
+
+  describe "kmer_count_sequence/3" do
+    test "raises nif_not_loaded" do
+      assert_nif_not_loaded(fn -> Native.kmer_count_sequence("ATCGATCG", 3, 10) end)
+    end
+  end
+
+  describe "kmer_count_file/3" do
+    test "raises nif_not_loaded" do
+      assert_nif_not_loaded(fn -> Native.kmer_count_file("/tmp/reads.fq", 21, 10) end)
+    end
+  end
+
+  describe "minhash_sketch_build/6" do
+    test "raises nif_not_loaded" do
+      assert_nif_not_loaded(fn -> Native.minhash_sketch_build("ATCGATCG", "s", 3, 100, 42, "dna") end)
+    end
+  end
+
+  describe "minhash_sketch_fasta/5" do
+    test "raises nif_not_loaded" do
+      assert_nif_not_loaded(fn -> Native.minhash_sketch_fasta("/tmp/a.fa", 21, 1000, 42, "iupac") end)
+    end
+  end
+
+  describe "minhash_compare/2" do
+    test "raises nif_not_loaded" do
+      sketch = %Native.MinHashSketch{name: "a", k: 21, hashes: [1, 2, 3]}
+      assert_nif_not_loaded(fn -> Native.minhash_compare(sketch, sketch) end)
+    end
+  end
+
+  describe "minhash_compare_many/3" do
+    test "raises nif_not_loaded" do
+      assert_nif_not_loaded(fn -> Native.minhash_compare_many([], [], 0.05) end)
+    end
+  end
+
+  describe "minhash_sketch_save/2" do
+    test "raises nif_not_loaded" do
+      assert_nif_not_loaded(fn -> Native.minhash_sketch_save("/tmp/db.sketch", []) end)
+    end
+  end
+
+  describe "minhash_sketch_load/1" do
+    test "raises nif_not_loaded" do
+      assert_nif_not_loaded(fn -> Native.minhash_sketch_load("/tmp/db.sketch") end)
+    end
+  end
+
+  describe "fastq_demultiplex/6" do
+    test "raises nif_not_loaded" do
+      samples = [%Native.SampleBarcode{sample: "s1", i7: "ACGTACGT"}]
+
+      assert_nif_not_loaded(fn ->
+        Native.fastq_demultiplex(["/tmp/pool.fq"], samples, "index", 1, "/tmp/demux", "none")
+      end)
+    end
+  end
+
+  describe "fastq_report/1" do
+    test "raises nif_not_loaded" do
+      assert_nif_not_loaded(fn -> Native.fastq_report("/tmp/reads.fq.gz") end)
+    end
+  end
+
+  describe "fastq_trim/4" do
+    test "raises nif_not_loaded" do
+      assert_nif_not_loaded(fn ->
+        Native.fastq_trim(["/tmp/r1.fq"], ["/tmp/out.fq"], %Native.TrimOptions{}, "none")
+      end)
+    end
+  end
+
+  describe "sequence_gc_content/2" do
+    test "raises nif_not_loaded" do
+      assert_nif_not_loaded(fn -> Native.sequence_gc_content("GCSN", "iupac") end)
+    end
+  end
+
+  describe "sequence_composition/2" do
+    test "raises nif_not_loaded" do
+      assert_nif_not_loaded(fn -> Native.sequence_composition("MVLK", "protein") end)
+    end
+  end
+
+  describe "genetic_codes/0" do
+    test "raises nif_not_loaded" do
+      assert_nif_not_loaded(fn -> Native.genetic_codes() end)
+    end
+  end
+
+  describe "sequence_translate/3" do
+    test "raises nif_not_loaded" do
+      assert_nif_not_loaded(fn -> Native.sequence_translate("ATGTGA", 2, [1, -1]) end)
+    end
+  end
+
+  describe "oligo_tm/2" do
     test "raises nif_not_loaded" do
-      assert_nif_not_loaded(fn -> Native.sequence_kmers("ATCGATCG", 3) end)
+      conditions = %Native.OligoConditions{na_mm: 50.0, mg_mm: 1.5, dntp_mm: 0.6, oligo_nm: 50.0}
+      assert_nif_not_loaded(fn -> Native.oligo_tm("AGCGGATAACAATTTCACACAGGA", conditions) end)
+    end
+  end
+
+  describe "oligo_thermo/2" do
+    test "raises nif_not_loaded" do
+      conditions = %Native.OligoConditions{na_mm: 50.0, mg_mm: 0.0, dntp_mm: 0.0, oligo_nm: 250.0}
+      assert_nif_not_loaded(fn -> Native.oligo_thermo("GTAAAACGACGGCCAGT", conditions) end)
+    end
+  end
+
+  describe "oligo_dimer_dg/2" do
+    test "raises nif_not_loaded" do
+      assert_nif_not_loaded(fn -> Native.oligo_dimer_dg("GAATTCGG", "CCGAATTC") end)
+    end
+  end
+
+  describe "primer_design/4" do
+    test "raises nif_not_loaded" do
+      assert_nif_not_loaded(fn -> Native.primer_design("ACGT", 1, 2, %Native.PrimerDesignOptions{}) end)
     end
   end
 
@@ -133,6 +298,12 @@ defmodule Cyanea.NativeTest do
     end
   end
 
+  describe "write_fastq/3" do
+    test "raises nif_not_loaded" do
+      assert_nif_not_loaded(fn -> Native.write_fastq("/tmp/out.fq", [], "bgzip") end)
+    end
+  end
+
   describe "fastq_stats/1" do
     test "raises nif_not_loaded" do
       assert_nif_not_loaded(fn -> Native.fastq_stats("/tmp/test.fastq") end)
@@ -159,6 +330,39 @@ defmodule Cyanea.NativeTest do
     end
   end
 
+  describe "pattern_search_fasta/4" do
+    test "raises nif_not_loaded" do
+      assert_nif_not_loaded(fn -> Native.pattern_search_fasta("/tmp/ref.fa", [{"p1", "GAATTC"}], 0, true) end)
+    end
+  end
+
+  describe "pattern_search_records/4" do
+    test "raises nif_not_loaded" do
+      records = [%Native.FastaRecord{name: "chr1", description: "", sequence: "GAATTC"}]
+      assert_nif_not_loaded(fn -> Native.pattern_search_records(records, [{"p1", "GAATTC"}], 1, false) end)
+    end
+  end
+
+  describe "restriction_enzymes/0" do
+    test "raises nif_not_loaded" do
+      assert_nif_not_loaded(fn -> Native.restriction_enzymes() end)
+    end
+  end
+
+  describe "restriction_digest/3" do
+    test "raises nif_not_loaded" do
+      assert_nif_not_loaded(fn -> Native.restriction_digest("GAATTCGGATCC", ["EcoRI"], false) end)
+    end
+  end
+
+  describe "pcr_simulate/5" do
+    test "raises nif_not_loaded" do
+      templates = [%Native.FastaRecord{name: "t", description: "", sequence: "ACGTACGTAA"}]
+      primers = [%Native.PrimerPair{name: "p", forward: "ACGT", reverse: "TTAC"}]
+      assert_nif_not_loaded(fn -> Native.pcr_simulate(templates, primers, 0, 1000, false) end)
+    end
+  end
+
   describe "fm_index_build/1" do
     test "raises nif_not_loaded" do
       assert_nif_not_loaded(fn -> Native.fm_index_build("ATCGATCG") end)
@@ -167,7 +371,37 @@ defmodule Cyanea.NativeTest do
 
   describe "fm_index_count/2" do
     test "raises nif_not_loaded" do
-      assert_nif_not_loaded(fn -> Native.fm_index_count(<<0, 1, 2>>, "ATC") end)
+      assert_nif_not_loaded(fn -> Native.fm_index_count(make_ref(), "ATC") end)
+    end
+  end
+
+  describe "fm_index_locate/2" do
+    test "raises nif_not_loaded" do
+      assert_nif_not_loaded(fn -> Native.fm_index_locate(make_ref(), "ATC") end)
+    end
+  end
+
+  describe "fm_index_search_mismatches/3" do
+    test "raises nif_not_loaded" do
+      assert_nif_not_loaded(fn -> Native.fm_index_search_mismatches(make_ref(), "ATC", 1) end)
+    end
+  end
+
+  describe "fm_index_text_length/1" do
+    test "raises nif_not_loaded" do
+      assert_nif_not_loaded(fn -> Native.fm_index_text_length(make_ref()) end)
+    end
+  end
+
+  describe "fm_index_serialize/1" do
+    test "raises nif_not_loaded" do
+      assert_nif_not_loaded(fn -> Native.fm_index_serialize(make_ref()) end)
+    end
+  end
+
+  describe "fm_index_deserialize/1" do
+    test "raises nif_not_loaded" do
+      assert_nif_not_loaded(fn -> Native.fm_index_deserialize(<<0, 1, 2>>) end)
     end
   end
 
@@ -177,9 +411,9 @@ defmodule Cyanea.NativeTest do
     end
   end
 
-  describe "minhash_sketch/3" do
+  describe "minhash_sketch/4" do
     test "raises nif_not_loaded" do
-      assert_nif_not_loaded(fn -> Native.minhash_sketch("ATCGATCG", 3, 100) end)
+      assert_nif_not_loaded(fn -> Native.minhash_sketch("ATCGATCG", 3, 100, "dna") end)
     end
   end
 
@@ -229,6 +463,24 @@ defmodule Cyanea.NativeTest do
     end
   end
 
+  describe "vcf_open/1" do
+    test "raises nif_not_loaded" do
+      assert_nif_not_loaded(fn -> Native.vcf_open("/tmp/test.vcf.gz") end)
+    end
+  end
+
+  describe "vcf_header/1" do
+    test "raises nif_not_loaded" do
+      assert_nif_not_loaded(fn -> Native.vcf_header(make_ref()) end)
+    end
+  end
+
+  describe "vcf_next_batch/2" do
+    test "raises nif_not_loaded" do
+      assert_nif_not_loaded(fn -> Native.vcf_next_batch(make_ref(), 100) end)
+    end
+  end
+
   describe "parse_bed/1" do
     test "raises nif_not_loaded" do
       assert_nif_not_loaded(fn -> Native.parse_bed("/tmp/test.bed") end)
@@ -265,12 +517,90 @@ defmodule Cyanea.NativeTest do
     end
   end
 
+  describe "sam_flags/1" do
+    test "raises nif_not_loaded" do
+      assert_nif_not_loaded(fn -> Native.sam_flags(99) end)
+    end
+  end
+
   describe "parse_bed_intervals/1" do
     test "raises nif_not_loaded" do
       assert_nif_not_loaded(fn -> Native.parse_bed_intervals("/tmp/test.bed") end)
     end
   end
 
+  describe "write_vcf/4" do
+    test "raises nif_not_loaded" do
+      assert_nif_not_loaded(fn -> Native.write_vcf("/tmp/out.vcf", %Native.VcfHeader{}, [], "none") end)
+    end
+  end
+
+  describe "write_bed/3" do
+    test "raises nif_not_loaded" do
+      assert_nif_not_loaded(fn -> Native.write_bed("/tmp/out.bed", [], "none") end)
+    end
+  end
+
+  describe "write_gff3/3" do
+    test "raises nif_not_loaded" do
+      assert_nif_not_loaded(fn -> Native.write_gff3("/tmp/out.gff3", [], "gzip") end)
+    end
+  end
+
+  describe "write_sam/4" do
+    test "raises nif_not_loaded" do
+      assert_nif_not_loaded(fn -> Native.write_sam("/tmp/out.sam", "", [], "none") end)
+    end
+  end
+
+  describe "bam_index_open/1" do
+    test "raises nif_not_loaded" do
+      assert_nif_not_loaded(fn -> Native.bam_index_open("/tmp/test.bam") end)
+    end
+  end
+
+  describe "bam_fetch/2" do
+    test "raises nif_not_loaded" do
+      assert_nif_not_loaded(fn -> Native.bam_fetch(make_ref(), "chr1:100-200") end)
+    end
+  end
+
+  describe "tabix_open/1" do
+    test "raises nif_not_loaded" do
+      assert_nif_not_loaded(fn -> Native.tabix_open("/tmp/test.vcf.gz") end)
+    end
+  end
+
+  describe "vcf_fetch/2" do
+    test "raises nif_not_loaded" do
+      assert_nif_not_loaded(fn -> Native.vcf_fetch(make_ref(), "chr1:100-200") end)
+    end
+  end
+
+  describe "bed_fetch/2" do
+    test "raises nif_not_loaded" do
+      assert_nif_not_loaded(fn -> Native.bed_fetch(make_ref(), "chr1") end)
+    end
+  end
+
+  describe "tabix_fetch_lines/2" do
+    test "raises nif_not_loaded" do
+      assert_nif_not_loaded(fn -> Native.tabix_fetch_lines(make_ref(), "chr1") end)
+    end
+  end
+
+  describe "bam_index_build/2" do
+    test "raises nif_not_loaded" do
+      assert_nif_not_loaded(fn -> Native.bam_index_build("/tmp/test.bam", "bai") end)
+    end
+  end
+
+  describe "tabix_index_build/3" do
+    test "raises nif_not_loaded" do
+      assert_nif_not_loaded(fn -> Native.tabix_index_build("/tmp/test.vcf.gz", "vcf", "tbi") end)
+    end
+  end
+
   # ===========================================================================
   # cyanea-align — Sequence Alignment
   # ===========================================================================
@@ -289,6 +619,62 @@ defmodule Cyanea.NativeTest do
     end
   end
 
+  describe "scoring_scheme_builtin/3" do
+    test "raises nif_not_loaded" do
+      assert_nif_not_loaded(fn -> Native.scoring_scheme_builtin("blosum62", -11, -1) end)
+    end
+  end
+
+  describe "scoring_scheme_load/3" do
+    test "raises nif_not_loaded" do
+      assert_nif_not_loaded(fn -> Native.scoring_scheme_load("/tmp/PAM30", -9, -1) end)
+    end
+  end
+
+  describe "scoring_scheme_parse/4" do
+    test "raises nif_not_loaded" do
+      assert_nif_not_loaded(fn -> Native.scoring_scheme_parse("ab", "  A B\nA 1 -1\nB -1 1\n", -2, -1) end)
+    end
+  end
+
+  describe "scoring_scheme_info/1" do
+    test "raises nif_not_loaded" do
+      assert_nif_not_loaded(fn -> Native.scoring_scheme_info(make_ref()) end)
+    end
+  end
+
+  describe "align_with_scheme/4" do
+    test "raises nif_not_loaded" do
+      assert_nif_not_loaded(fn -> Native.align_with_scheme("MVLK", "MVK", "global", make_ref()) end)
+    end
+  end
+
+  describe "align_batch_with_scheme/3" do
+    test "raises nif_not_loaded" do
+      assert_nif_not_loaded(fn -> Native.align_batch_with_scheme([{"MVLK", "MVK"}], "local", make_ref()) end)
+    end
+  end
+
+  describe "read_mapper_index/1" do
+    test "raises nif_not_loaded" do
+      assert_nif_not_loaded(fn -> Native.read_mapper_index("/tmp/ref.fa") end)
+    end
+  end
+
+  describe "map_reads/5" do
+    test "raises nif_not_loaded" do
+      options = %Cyanea.Native.MapOptions{seed_length: 19, max_occurrences: 100, bandwidth: 16, min_score: 30, max_insert: 1000}
+      assert_nif_not_loaded(fn -> Native.map_reads(make_ref(), ["/tmp/r1.fq"], "/tmp/out.sam", "sam", options) end)
+    end
+  end
+
+  describe "search_fasta/4" do
+    test "raises nif_not_loaded" do
+      options = %Cyanea.Native.DbSearchOptions{max_hits: 10, max_evalue: 10.0}
+      assert_nif_not_loaded(fn -> Native.search_fasta("MVLK", "/tmp/db.fa", make_ref(), options) end)
+    end
+  end
+
   describe "align_protein/4" do
     test "raises nif_not_loaded" do
       assert_nif_not_loaded(fn ->
@@ -305,10 +691,37 @@ defmodule Cyanea.NativeTest do
     end
   end
 
-  describe "progressive_msa/2" do
+  describe "progressive_msa/4" do
+    test "raises nif_not_loaded" do
+      assert_nif_not_loaded(fn ->
+        Native.progressive_msa(["ATCG", "ATCG", "ATCG"], "dna", nil, nil)
+      end)
+    end
+  end
+
+  describe "msa_add_sequences/6" do
+    test "raises nif_not_loaded" do
+      options = %Cyanea.Native.MsaAddOptions{names: [], keep_length: false, band: nil}
+
+      assert_nif_not_loaded(fn ->
+        Native.msa_add_sequences(["AC-GT", "ACAGT"], ["ACGT"], make_ref(), options, nil, nil)
+      end)
+    end
+  end
+
+  describe "msa_merge/6" do
     test "raises nif_not_loaded" do
+      file = %Cyanea.Native.MsaFile{path: "/tmp/aln.sto", format: "stockholm"}
+      assert_nif_not_loaded(fn -> Native.msa_merge(["AC-GT"], file, make_ref(), 50, nil, nil) end)
+    end
+  end
+
+  describe "progressive_msa_with_scheme/6" do
+    test "raises nif_not_loaded" do
+      options = %Cyanea.Native.MsaOptions{guide_tree: "nj", refine_rounds: 1}
+
       assert_nif_not_loaded(fn ->
-        Native.progressive_msa(["ATCG", "ATCG", "ATCG"], "dna")
+        Native.progressive_msa_with_scheme(["ATCG", "ATCG"], ["a", "b"], make_ref(), options, nil, nil)
       end)
     end
   end
@@ -497,18 +910,18 @@ defmodule Cyanea.NativeTest do
     end
   end
 
-  describe "tsne/5" do
+  describe "tsne/7" do
     test "raises nif_not_loaded" do
       assert_nif_not_loaded(fn ->
-        Native.tsne([1.0, 2.0, 3.0, 4.0], 2, 2, 5.0, 100)
+        Native.tsne([1.0, 2.0, 3.0, 4.0], 2, 2, 5.0, 100, nil, nil)
       end)
     end
   end
 
-  describe "umap/8" do
+  describe "umap/10" do
     test "raises nif_not_loaded" do
       assert_nif_not_loaded(fn ->
-        Native.umap([1.0, 2.0, 3.0, 4.0], 2, 2, 15, 0.1, 200, "euclidean", 42)
+        Native.umap([1.0, 2.0, 3.0, 4.0], 2, 2, 15, 0.1, 200, "euclidean", 42, nil, nil)
       end)
     end
   end
@@ -573,11 +986,11 @@ defmodule Cyanea.NativeTest do
     end
   end
 
-  describe "random_forest_fit/6" do
+  describe "random_forest_fit/8" do
     test "raises nif_not_loaded" do
       assert_nif_not_loaded(fn ->
         Native.random_forest_fit(
-          [1.0, 2.0, 3.0, 4.0], 2, [0, 1], 10, 5, 42
+          [1.0, 2.0, 3.0, 4.0], 2, [0, 1], 10, 5, 42, nil, nil
         )
       end)
     end
@@ -586,11 +999,31 @@ defmodule Cyanea.NativeTest do
   describe "random_forest_predict/3" do
     test "raises nif_not_loaded" do
       assert_nif_not_loaded(fn ->
-        Native.random_forest_predict(<<0, 1, 2>>, [1.0, 2.0], 2)
+        Native.random_forest_predict(make_ref(), [1.0, 2.0], 2)
+      end)
+    end
+  end
+
+  describe "random_forest_predict_proba/3" do
+    test "raises nif_not_loaded" do
+      assert_nif_not_loaded(fn ->
+        Native.random_forest_predict_proba(make_ref(), [1.0, 2.0], 2)
       end)
     end
   end
 
+  describe "random_forest_feature_importances/1" do
+    test "raises nif_not_loaded" do
+      assert_nif_not_loaded(fn -> Native.random_forest_feature_importances(make_ref()) end)
+    end
+  end
+
+  describe "random_forest_info/1" do
+    test "raises nif_not_loaded" do
+      assert_nif_not_loaded(fn -> Native.random_forest_info(make_ref()) end)
+    end
+  end
+
   describe "hmm_viterbi/6" do
     test "raises nif_not_loaded" do
       assert_nif_not_loaded(fn ->
@@ -821,10 +1254,10 @@ defmodule Cyanea.NativeTest do
     end
   end
 
-  describe "build_nj/3" do
+  describe "build_nj/5" do
     test "raises nif_not_loaded" do
       assert_nif_not_loaded(fn ->
-        Native.build_nj(["ATCG", "ATCG", "ATCG"], ["A", "B", "C"], "jc")
+        Native.build_nj(["ATCG", "ATCG", "ATCG"], ["A", "B", "C"], "jc", nil, nil)
       end)
     end
   end
@@ -855,10 +1288,75 @@ defmodule Cyanea.NativeTest do
     end
   end
 
-  describe "bootstrap_support/4" do
+  describe "bootstrap_support/6" do
     test "raises nif_not_loaded" do
       assert_nif_not_loaded(fn ->
-        Native.bootstrap_support(["ATCG", "ATCA"], "((A,B));", 10, "p")
+        Native.bootstrap_support(["ATCG", "ATCA"], "((A,B));", 10, "p", nil, {self(), make_ref()})
+      end)
+    end
+  end
+
+  # --- Jobs -------------------------------------------------------------------
+
+  describe "cancel_token_new/0" do
+    test "raises nif_not_loaded" do
+      assert_nif_not_loaded(fn -> Native.cancel_token_new() end)
+    end
+  end
+
+  describe "cancel_token_cancel/1" do
+    test "raises nif_not_loaded" do
+      assert_nif_not_loaded(fn -> Native.cancel_token_cancel(make_ref()) end)
+    end
+  end
+
+  describe "cancel_token_cancelled/1" do
+    test "raises nif_not_loaded" do
+      assert_nif_not_loaded(fn -> Native.cancel_token_cancelled(make_ref()) end)
+    end
+  end
+
+  describe "async_pool_stats/1" do
+    test "raises nif_not_loaded" do
+      assert_nif_not_loaded(fn -> Native.async_pool_stats("align") end)
+    end
+  end
+
+  describe "async_pool_set_concurrency/2" do
+    test "raises nif_not_loaded" do
+      assert_nif_not_loaded(fn -> Native.async_pool_set_concurrency("ml", 2) end)
+    end
+  end
+
+  describe "async variants" do
+    test "raise nif_not_loaded" do
+      assert_nif_not_loaded(fn -> Native.align_batch_dna_async([{"AT", "AT"}], "local", nil) end)
+      assert_nif_not_loaded(fn -> Native.progressive_msa_async(["ATCG", "ATCG"], "dna", nil) end)
+
+      assert_nif_not_loaded(fn ->
+        Native.progressive_msa_with_scheme_async(["ATCG", "ATCG"], [], make_ref(), %Cyanea.Native.MsaOptions{}, nil)
+      end)
+
+      assert_nif_not_loaded(fn -> Native.poa_consensus_async(["ATCG", "ATCG"], nil) end)
+      assert_nif_not_loaded(fn -> Native.tsne_async([1.0, 2.0, 3.0, 4.0], 2, 2, 5.0, 100, nil) end)
+
+      assert_nif_not_loaded(fn ->
+        Native.umap_async([1.0, 2.0, 3.0, 4.0], 2, 2, 15, 0.1, 200, "euclidean", 42, nil)
+      end)
+
+      assert_nif_not_loaded(fn ->
+        Native.hierarchical_cluster_async([1.0, 2.0, 3.0, 4.0], 2, 1, "average", "euclidean", nil)
+      end)
+
+      assert_nif_not_loaded(fn ->
+        Native.random_forest_fit_async([1.0, 2.0, 3.0, 4.0], 2, [0, 1], 10, 5, 42, nil)
+      end)
+
+      assert_nif_not_loaded(fn -> Native.build_upgma_async(["ATCG", "ATCG"], ["A", "B"], "p", nil) end)
+      assert_nif_not_loaded(fn -> Native.build_nj_async(["ATCG", "ATCG"], ["A", "B"], "p", nil) end)
+
+      assert_nif_not_loaded(fn ->
+        Native.bootstrap_support_async(["ATCG", "ATCA"], "((A,B));", 10, "p", nil)
       end)
     end
   end
@@ -942,6 +1440,51 @@ defmodule Cyanea.NativeTest do
       ])
     end
 
+    test "AsyncPoolStats has correct fields" do
+      assert_struct_fields(Native.AsyncPoolStats, [:name, :max_concurrency, :queued, :running])
+    end
+
+    test "Error has correct fields" do
+      assert_struct_fields(Native.Error, [:kind, :message, :file, :line, :offset])
+    end
+
+    test "Error message includes the line when known" do
+      error = %Native.Error{kind: :parse, message: "invalid base 'J'", line: 1432}
+      assert Native.Error.message(error) == "line 1432: invalid base 'J'"
+      assert Native.Error.message(%Native.Error{kind: :io, message: "boom"}) == "boom"
+    end
+
+    test "Matrix has correct fields" do
+      assert_struct_fields(Native.Matrix, [:data, :shape, :dtype])
+    end
+
+    test "Matrix round-trips rows through a little-endian binary" do
+      matrix = Native.Matrix.from_rows([[1.0, 2.0], [3.0, 4.5]], :f32)
+      assert matrix.shape == {2, 2}
+      assert byte_size(matrix.data) == 16
+      assert binary_part(matrix.data, 0, 4) == <<1.0::float-little-32>>
+      assert Native.Matrix.to_rows(matrix) == [[1.0, 2.0], [3.0, 4.5]]
+      assert Native.Matrix.to_list(matrix) == [1.0, 2.0, 3.0, 4.5]
+      assert Native.Matrix.nx_type(matrix) == {:f, 32}
+    end
+
+    test "Matrix.new accepts Nx types and checks the binary size" do
+      data = <<1.0::float-little-64, 2.0::float-little-64>>
+      assert %Native.Matrix{dtype: :f64, shape: {1, 2}} = Native.Matrix.new(data, {1, 2}, {:f, 64})
+      assert_raise ArgumentError, fn -> Native.Matrix.new(data, {2, 2}, :f64) end
+      assert_raise ArgumentError, fn -> Native.Matrix.new(data, {1, 2}, {:s, 64}) end
+    end
+
+    test "FastaRecord has correct fields" do
+      assert_struct_fields(Native.FastaRecord, [:name, :description, :sequence])
+    end
+
+    test "FaiEntry has correct fields" do
+      assert_struct_fields(Native.FaiEntry, [
+        :name, :length, :offset, :line_bases, :line_width
+      ])
+    end
+
     test "FastqRecord has correct fields" do
       assert_struct_fields(Native.FastqRecord, [
         :name, :description, :sequence, :quality
@@ -968,6 +1511,20 @@ defmodule Cyanea.NativeTest do
       assert_struct_fields(Native.MsaResult, [:aligned, :n_sequences, :n_columns, :conservation])
     end
 
+    test "MsaMerge has correct fields" do
+      assert_struct_fields(Native.MsaMerge, [
+        :aligned, :names, :n_sequences, :n_columns, :inserted_columns,
+        :score, :conservation, :entropy, :consensus
+      ])
+    end
+
+    test "MsaReport has correct fields" do
+      assert_struct_fields(Native.MsaReport, [
+        :aligned, :names, :n_sequences, :n_columns, :guide_tree,
+        :conservation, :entropy, :consensus, :sp_score
+      ])
+    end
+
     test "DescriptiveStats has correct fields" do
       assert_struct_fields(Native.DescriptiveStats, [
         :count, :mean, :median, :variance, :sample_variance,
@@ -1085,7 +1642,20 @@ defmodule Cyanea.NativeTest do
 
     test "VcfRecord has correct fields" do
       assert_struct_fields(Native.VcfRecord, [
-        :chrom, :position, :ref_allele, :alt_alleles, :quality, :filter
+        :chrom, :position, :id, :ref_allele, :alt_alleles, :quality, :filter,
+        :info, :format, :samples
+      ])
+    end
+
+    test "VcfSample has correct fields" do
+      assert_struct_fields(Native.VcfSample, [
+        :genotype, :alleles, :phased, :depth, :genotype_quality, :allele_depths, :other
+      ])
+    end
+
+    test "VcfHeader has correct fields" do
+      assert_struct_fields(Native.VcfHeader, [
+        :fileformat, :samples, :contigs, :filters, :info, :format, :meta
       ])
     end
 
@@ -1103,13 +1673,25 @@ defmodule Cyanea.NativeTest do
 
     test "SamRecord has correct fields" do
       assert_struct_fields(Native.SamRecord, [
-        :qname, :flag, :rname, :pos, :mapq, :cigar, :sequence, :quality
+        :qname, :flag, :rname, :pos, :mapq, :cigar, :rnext, :pnext, :tlen,
+        :sequence, :quality, :tags
+      ])
+    end
+
+    test "SamFlags has correct fields" do
+      assert_struct_fields(Native.SamFlags, [
+        :paired, :proper_pair, :unmapped, :mate_unmapped, :reverse, :mate_reverse,
+        :first_in_pair, :second_in_pair, :secondary, :qc_fail, :duplicate, :supplementary
       ])
     end
 
     test "SamStats has correct fields" do
       assert_struct_fields(Native.SamStats, [
-        :total_reads, :mapped, :unmapped, :avg_mapq, :avg_length
+        :total_reads, :mapped, :unmapped, :avg_mapq, :avg_length, :qc_failed,
+        :secondary, :supplementary, :duplicates, :duplicate_rate, :paired, :read1,
+        :read2, :properly_paired, :properly_paired_fraction, :both_mapped, :singletons,
+        :mate_on_different_chrom, :insert_size_mean, :insert_size_sd,
+        :insert_size_median, :insert_size_histogram
       ])
     end
 
@@ -1131,6 +1713,12 @@ defmodule Cyanea.NativeTest do
       ])
     end
 
+    test "RandomForestInfo has correct fields" do
+      assert_struct_fields(Native.RandomForestInfo, [
+        :n_trees, :n_features, :n_classes, :n_samples, :oob_error
+      ])
+    end
+
     test "ContactMapResult has correct fields" do
       assert_struct_fields(Native.ContactMapResult, [
         :contacts, :n_residues, :contact_density
@@ -1160,6 +1748,66 @@ defmodule Cyanea.NativeTest do
         :taxa, :tree_names, :tree_newicks
       ])
     end
+
+    test "TrimOptions has correct fields" do
+      assert_struct_fields(Native.TrimOptions, [
+        :adapter_preset, :adapters, :adapter_min_overlap, :adapter_error_rate,
+        :poly_g_min_length, :window_size, :window_quality, :trailing_quality,
+        :max_n, :min_length
+      ])
+    end
+
+    test "FastqTrimStats has correct fields" do
+      assert_struct_fields(Native.FastqTrimStats, [
+        :paired, :records_in, :records_out, :bases_in, :bases_out,
+        :adapter_trimmed_reads, :adapter_trimmed_bases,
+        :poly_g_trimmed_reads, :poly_g_trimmed_bases,
+        :quality_trimmed_reads, :quality_trimmed_bases,
+        :too_many_n, :too_short
+      ])
+    end
+
+    test "SampleBarcode has correct fields" do
+      assert_struct_fields(Native.SampleBarcode, [:sample, :i7, :i5, :inline])
+    end
+
+    test "DemuxResult has correct fields" do
+      assert_struct_fields(Native.DemuxResult, [
+        :records, :undetermined, :ambiguous, :undetermined_paths, :samples
+      ])
+    end
+
+    test "FastqReport has correct fields" do
+      assert_struct_fields(Native.FastqReport, [
+        :summary, :per_position_quality, :per_position_content, :gc_distribution,
+        :length_distribution, :duplication_levels, :deduplicated_fraction,
+        :overrepresented, :adapter_content
+      ])
+    end
+
+    test "PositionQuality has correct fields" do
+      assert_struct_fields(Native.PositionQuality, [:position, :mean, :p10, :q1, :median, :q3, :p90])
+    end
+
+    test "MinHashSketch has correct fields" do
+      assert_struct_fields(Native.MinHashSketch, [
+        :name, :k, :sketch_size, :seed, :hash_function, :alphabet, :length, :hashes
+      ])
+    end
+
+    test "MinHashDistance has correct fields" do
+      assert_struct_fields(Native.MinHashDistance, [
+        :query, :reference, :jaccard, :containment, :mash_distance, :ani,
+        :p_value, :shared_hashes, :compared_hashes
+      ])
+    end
+
+    test "KmerCounts has correct fields" do
+      assert_struct_fields(Native.KmerCounts, [
+        :k, :total_kmers, :distinct_kmers, :top_kmers, :spectrum,
+        :peak_coverage, :genome_size, :heterozygosity
+      ])
+    end
   end
 
   describe "bridge struct instantiation" do
@@ -0,0 +1,62 @@
+defmodule Cyanea.NifHelperTest do
+  use ExUnit.Case, async: true
+
+  alias Cyanea.Native
+  alias Cyanea.NifHelper
+
+  describe "nif_call/1" do
+    test "passes structured errors through" do
+      error = %Native.Error{kind: :parse, message: "invalid POS 'x'", line: 12}
+      assert {:error, ^error} = NifHelper.nif_call(fn -> {:error, error} end)
+    end
+
+    test "wraps bare results" do
+      assert {:ok, 3} = NifHelper.nif_call(fn -> 3 end)
+    end
+  end
+
+  describe "http_status/1" do
+    test "maps input errors to 4xx" do
+      assert NifHelper.http_status(%Native.Error{kind: :parse}) == 422
+      assert NifHelper.http_status(%Native.Error{kind: :invalid_input}) == 422
+      assert NifHelper.http_status(%Native.Error{kind: :not_found}) == 404
+      assert NifHelper.http_status(%Native.Error{kind: :unsupported}) == 415
+      assert NifHelper.http_status(%Native.Error{kind: :cancelled}) == 499
+    end
+
+    test "maps everything else to 5xx" do
+      assert NifHelper.http_status(%Native.Error{kind: :io}) == 500
+      assert NifHelper.http_status(%Native.Error{kind: :internal}) == 500
+      assert NifHelper.http_status(:nif_not_loaded) == 503
+      assert NifHelper.http_status("boom") == 500
+    end
+  end
+
+  describe "job_args/1" do
+    test "defaults to no token and no progress target" do
+      assert NifHelper.job_args([]) == {nil, nil}
+    end
+
+    test "tags progress from a bare pid with the cancel token" do
+      token = make_ref()
+      assert NifHelper.job_args(cancel: token, progress: self()) == {token, {self(), token}}
+    end
+
+    test "keeps an explicit job ref" do
+      ref = make_ref()
+      assert NifHelper.job_args(progress: {self(), ref}) == {nil, {self(), ref}}
+    end
+  end
+
+  describe "error_message/1" do
+    test "formats structured errors with their location" do
+      error = %Native.Error{kind: :parse, message: "invalid base 'J'", line: 1432}
+      assert NifHelper.error_message(error) == "line 1432: invalid base 'J'"
+    end
+
+    test "passes strings through and inspects other terms" do
+      assert NifHelper.error_message("boom") == "boom"
+      assert NifHelper.error_message(:nif_not_loaded) == ":nif_not_loaded"
+    end
+  end
+end
@@ -152,6 +152,16 @@ defmodule Cyanea.PhyloTest do
       )
     end
 
+    test "accepts cancel and progress opts" do
+      assert {:error, :nif_not_loaded} = Phylo.bootstrap(["ATCG", "ATCA"], "((A,B));",
+        cancel: make_ref(), progress: {self(), make_ref()}
+      )
+    end
+
+    test "accepts async opt" do
+      assert {:error, :nif_not_loaded} = Phylo.bootstrap(["ATCG", "ATCA"], "((A,B));", async: true)
+    end
+
     test "rejects non-list sequences" do
       assert_raise FunctionClauseError, fn -> Phylo.bootstrap("ATCG", "((A,B));") end
     end
@@ -61,19 +61,31 @@ defmodule Cyanea.SeqTest do
     end
   end
 
-  describe "translate/1" do
+  describe "translate/2" do
     test "returns nif_not_loaded without NIF" do
       assert {:error, :nif_not_loaded} = Seq.translate("AUGCGA")
     end
 
+    test "returns nif_not_loaded with table and frame options" do
+      assert {:error, :nif_not_loaded} = Seq.translate("ATGAGATGA", table: 2, frame: -1)
+      assert {:error, :nif_not_loaded} = Seq.translate("ATGAGATGA", frame: :all)
+    end
+
     test "rejects non-binary" do
       assert_raise FunctionClauseError, fn -> Seq.translate(123) end
     end
   end
 
-  describe "gc_content/1" do
+  describe "genetic_codes/0" do
+    test "returns nif_not_loaded without NIF" do
+      assert {:error, :nif_not_loaded} = Seq.genetic_codes()
+    end
+  end
+
+  describe "gc_content/2" do
     test "returns nif_not_loaded without NIF" do
       assert {:error, :nif_not_loaded} = Seq.gc_content("GCGC")
+      assert {:error, :nif_not_loaded} = Seq.gc_content("GCSRN", alphabet: :iupac)
     end
 
     test "rejects non-binary" do
@@ -81,9 +93,20 @@ defmodule Cyanea.SeqTest do
     end
   end
 
-  describe "kmers/2" do
+  describe "composition/2" do
+    test "returns nif_not_loaded without NIF" do
+      assert {:error, :nif_not_loaded} = Seq.composition("MVLKMV", alphabet: :protein)
+    end
+
+    test "rejects non-binary" do
+      assert_raise FunctionClauseError, fn -> Seq.composition(123) end
+    end
+  end
+
+  describe "kmers/3" do
     test "returns nif_not_loaded without NIF" do
       assert {:error, :nif_not_loaded} = Seq.kmers("ATCGATCG", 3)
+      assert {:error, :nif_not_loaded} = Seq.kmers("MVLKMV", 2, alphabet: :protein)
     end
 
     test "rejects non-binary seq" do
@@ -95,6 +118,28 @@ defmodule Cyanea.SeqTest do
     end
   end
 
+  describe "count_kmers/2" do
+    test "returns nif_not_loaded without NIF" do
+      assert {:error, :nif_not_loaded} = Seq.count_kmers("ATCGATCG")
+      assert {:error, :nif_not_loaded} = Seq.count_kmers("ATCGATCG", k: 3, top: 5)
+      assert {:error, :nif_not_loaded} = Seq.count_kmers("ACGTNACGTN", k: 3)
+    end
+
+    test "rejects non-binary" do
+      assert_raise FunctionClauseError, fn -> Seq.count_kmers(123) end
+    end
+  end
+
+  describe "count_kmers_file/2" do
+    test "returns nif_not_loaded without NIF" do
+      assert {:error, :nif_not_loaded} = Seq.count_kmers_file("/tmp/reads.fq.gz", k: 25)
+    end
+
+    test "rejects non-binary path" do
+      assert_raise FunctionClauseError, fn -> Seq.count_kmers_file(:reads) end
+    end
+  end
+
   describe "molecular_weight/1" do
     test "returns nif_not_loaded without NIF" do
       assert {:error, :nif_not_loaded} = Seq.molecular_weight("MVLK")
@@ -105,6 +150,49 @@ defmodule Cyanea.SeqTest do
     end
   end
 
+  # ===========================================================================
+  # Oligos & primer design
+  # ===========================================================================
+
+  describe "melting_temp/2" do
+    test "accepts reaction conditions" do
+      assert {:error, :nif_not_loaded} = Seq.melting_temp("AGCGGATAACAATTTCACACAGGA")
+      assert {:error, :nif_not_loaded} = Seq.melting_temp("AGCGGATAACAATTTCACACAGGA", na_mm: 100, mg_mm: 2)
+    end
+
+    test "rejects non-binary input" do
+      assert_raise FunctionClauseError, fn -> Seq.melting_temp(123) end
+    end
+  end
+
+  describe "oligo_thermo/2" do
+    test "returns nif_not_loaded without NIF" do
+      assert {:error, :nif_not_loaded} = Seq.oligo_thermo("GTAAAACGACGGCCAGT", oligo_nm: 250)
+    end
+  end
+
+  describe "dimer_dg/2" do
+    test "returns nif_not_loaded without NIF" do
+      assert {:error, :nif_not_loaded} = Seq.dimer_dg("GAATTCGG", "CCGAATTC")
+    end
+  end
+
+  describe "design_primers/3" do
+    test "accepts ranges and tuples as constraints" do
+      assert {:error, :nif_not_loaded} =
+               Seq.design_primers(String.duplicate("ACGT", 100), {150, 200},
+                 length: 20..24,
+                 tm: {58, 60, 62},
+                 gc: {0.35, 0.65},
+                 product_size: 80..300
+               )
+    end
+
+    test "rejects a target that is not a tuple" do
+      assert_raise FunctionClauseError, fn -> Seq.design_primers("ACGT", 1..2) end
+    end
+  end
+
   # ===========================================================================
   # Pattern matching
   # ===========================================================================
@@ -137,6 +225,65 @@ defmodule Cyanea.SeqTest do
     end
   end
 
+  describe "search_patterns/3" do
+    test "searches a FASTA path" do
+      assert {:error, :nif_not_loaded} = Seq.search_patterns("/tmp/ref.fa", ["GAATTC"])
+    end
+
+    test "accepts records, tuples and named patterns with opts" do
+      records = [
+        %Cyanea.Native.FastaRecord{name: "chr1", description: "", sequence: "ACGAATTCGT"},
+        {"chr2", "GGATCC"}
+      ]
+
+      assert {:error, :nif_not_loaded} =
+               Seq.search_patterns(records, [{"EcoRI", "GAATTC"}, "GGATCC"],
+                 max_distance: 1,
+                 both_strands: false
+               )
+    end
+
+    test "rejects non-list patterns" do
+      assert_raise FunctionClauseError, fn -> Seq.search_patterns("/tmp/ref.fa", "GAATTC") end
+    end
+  end
+
+  # ===========================================================================
+  # Cloning
+  # ===========================================================================
+
+  describe "restriction_enzymes/0" do
+    test "returns nif_not_loaded without NIF" do
+      assert {:error, :nif_not_loaded} = Seq.restriction_enzymes()
+    end
+  end
+
+  describe "digest/3" do
+    test "accepts one enzyme or a list, and :circular" do
+      assert {:error, :nif_not_loaded} = Seq.digest("GAATTCGGATCC", "EcoRI")
+      assert {:error, :nif_not_loaded} = Seq.digest("GAATTCGGATCC", ["EcoRI", "BamHI"], circular: true)
+    end
+
+    test "rejects non-binary sequence" do
+      assert_raise FunctionClauseError, fn -> Seq.digest(123, ["EcoRI"]) end
+    end
+  end
+
+  describe "pcr/3" do
+    test "accepts a sequence with tuple primer pairs" do
+      assert {:error, :nif_not_loaded} =
+               Seq.pcr("ACGTACGTAAGGTTCC", [{"ACGT", "GGAA"}, {"p2", "ACG", "GGA"}], max_mismatches: 1)
+    end
+
+    test "accepts records and primer pair structs" do
+      templates = [{"plasmid", "ACGTACGTAAGGTTCC"}]
+      primers = [%Cyanea.Native.PrimerPair{name: "p", forward: "ACGT", reverse: "GGAA"}]
+
+      assert {:error, :nif_not_loaded} =
+               Seq.pcr(templates, primers, circular: true, max_length: 500)
+    end
+  end
+
   # ===========================================================================
   # FM-Index
   # ===========================================================================
@@ -153,15 +300,47 @@ defmodule Cyanea.SeqTest do
 
   describe "count_occurrences/2" do
     test "returns nif_not_loaded without NIF" do
-      assert {:error, :nif_not_loaded} = Seq.count_occurrences(<<0, 1, 2>>, "ATC")
+      assert {:error, :nif_not_loaded} = Seq.count_occurrences(make_ref(), "ATC")
     end
 
-    test "rejects non-binary index_data" do
-      assert_raise FunctionClauseError, fn -> Seq.count_occurrences(123, "ATC") end
+    test "rejects non-reference index" do
+      assert_raise FunctionClauseError, fn -> Seq.count_occurrences(<<0, 1, 2>>, "ATC") end
     end
 
     test "rejects non-binary pattern" do
-      assert_raise FunctionClauseError, fn -> Seq.count_occurrences(<<0>>, 123) end
+      assert_raise FunctionClauseError, fn -> Seq.count_occurrences(make_ref(), 123) end
+    end
+  end
+
+  describe "locate/3" do
+    test "returns nif_not_loaded for exact matches" do
+      assert {:error, :nif_not_loaded} = Seq.locate(make_ref(), "ATC")
+    end
+
+    test "returns nif_not_loaded with max_mismatches" do
+      assert {:error, :nif_not_loaded} = Seq.locate(make_ref(), "ATC", max_mismatches: 1)
+    end
+
+    test "rejects non-reference index" do
+      assert_raise FunctionClauseError, fn -> Seq.locate("ATCG", "ATC") end
+    end
+  end
+
+  describe "dump_index/1 and load_index/1" do
+    test "dump_index returns nif_not_loaded without NIF" do
+      assert {:error, :nif_not_loaded} = Seq.dump_index(make_ref())
+    end
+
+    test "load_index returns nif_not_loaded without NIF" do
+      assert {:error, :nif_not_loaded} = Seq.load_index(<<0, 1, 2>>)
+    end
+
+    test "dump_index rejects non-reference" do
+      assert_raise FunctionClauseError, fn -> Seq.dump_index("ATCG") end
+    end
+
+    test "load_index rejects non-binary" do
+      assert_raise FunctionClauseError, fn -> Seq.load_index(123) end
     end
   end
 
@@ -201,16 +380,66 @@ defmodule Cyanea.SeqTest do
       assert {:error, :nif_not_loaded} = Seq.minhash("ATCGATCG", k: 3, sketch_size: 100)
     end
 
+    test "accepts an alphabet option" do
+      assert {:error, :nif_not_loaded} = Seq.minhash("MVLKMVLK", k: 3, alphabet: :protein)
+    end
+
     test "rejects non-binary seq" do
       assert_raise FunctionClauseError, fn -> Seq.minhash(123) end
     end
   end
 
+  describe "sketch/2" do
+    test "returns nif_not_loaded without NIF" do
+      assert {:error, :nif_not_loaded} = Seq.sketch("ATCGATCG", name: "a", k: 3, seed: 7)
+    end
+
+    test "rejects non-binary seq" do
+      assert_raise FunctionClauseError, fn -> Seq.sketch(123) end
+    end
+  end
+
+  describe "sketch_file/2" do
+    test "returns nif_not_loaded without NIF" do
+      assert {:error, :nif_not_loaded} = Seq.sketch_file("/tmp/assembly.fa.gz")
+    end
+  end
+
+  describe "compare_sketches/2" do
+    test "returns nif_not_loaded without NIF" do
+      sketch = %Cyanea.Native.MinHashSketch{name: "a", k: 21, hashes: [1, 2, 3]}
+      assert {:error, :nif_not_loaded} = Seq.compare_sketches(sketch, sketch)
+    end
+
+    test "rejects raw hash lists" do
+      assert_raise FunctionClauseError, fn -> Seq.compare_sketches([1, 2], [1, 2]) end
+    end
+  end
+
+  describe "compare_sketches_many/3" do
+    test "returns nif_not_loaded without NIF" do
+      assert {:error, :nif_not_loaded} = Seq.compare_sketches_many([], [], max_distance: 0.05)
+    end
+  end
+
+  describe "save_sketches/2 and load_sketches/1" do
+    test "return nif_not_loaded without NIF" do
+      assert {:error, :nif_not_loaded} = Seq.save_sketches("/tmp/db.sketch", [])
+      assert {:error, :nif_not_loaded} = Seq.load_sketches("/tmp/db.sketch")
+    end
+  end
+
   describe "minhash_jaccard/2" do
     test "returns nif_not_loaded without NIF" do
       assert {:error, :nif_not_loaded} = Seq.minhash_jaccard([1, 2, 3], [2, 3, 4])
     end
 
+    test "accepts sketches from minhash/2" do
+      a = %Cyanea.Native.MinHashSketch{k: 21, hashes: [1, 2, 3]}
+      b = %Cyanea.Native.MinHashSketch{k: 21, hashes: [2, 3, 4]}
+      assert {:error, :nif_not_loaded} = Seq.minhash_jaccard(a, b)
+    end
+
     test "rejects non-list sketch_a" do
       assert_raise FunctionClauseError, fn -> Seq.minhash_jaccard("not", [1]) end
     end
@@ -234,6 +463,54 @@ defmodule Cyanea.SeqTest do
     end
   end
 
+  describe "parse_fasta/1" do
+    test "returns nif_not_loaded without NIF" do
+      assert {:error, :nif_not_loaded} = Seq.parse_fasta("/tmp/test.fasta")
+    end
+
+    test "rejects non-binary" do
+      assert_raise FunctionClauseError, fn -> Seq.parse_fasta(123) end
+    end
+  end
+
+  describe "index_fasta/1" do
+    test "returns nif_not_loaded without NIF" do
+      assert {:error, :nif_not_loaded} = Seq.index_fasta("/tmp/test.fasta")
+    end
+  end
+
+  describe "fasta_index/1" do
+    test "returns nif_not_loaded without NIF" do
+      assert {:error, :nif_not_loaded} = Seq.fasta_index("/tmp/test.fasta")
+    end
+  end
+
+  describe "open_fasta/1" do
+    test "returns nif_not_loaded without NIF" do
+      assert {:error, :nif_not_loaded} = Seq.open_fasta("/tmp/test.fasta")
+    end
+  end
+
+  describe "fetch_fasta/4" do
+    test "returns nif_not_loaded without NIF" do
+      assert {:error, :nif_not_loaded} = Seq.fetch_fasta("/tmp/test.fasta", "chr1", 1, 100)
+    end
+
+    test "returns nif_not_loaded for an open handle without NIF" do
+      assert {:error, :nif_not_loaded} = Seq.fetch_fasta(make_ref(), "chr1", 1, 100)
+    end
+
+    test "rejects zero start" do
+      assert_raise FunctionClauseError, fn -> Seq.fetch_fasta("/tmp/test.fasta", "chr1", 0, 100) end
+    end
+
+    test "rejects inverted range" do
+      assert_raise FunctionClauseError, fn ->
+        Seq.fetch_fasta("/tmp/test.fasta", "chr1", 200, 100)
+      end
+    end
+  end
+
   describe "fastq_stats/1" do
     test "returns nif_not_loaded without NIF" do
       assert {:error, :nif_not_loaded} = Seq.fastq_stats("/tmp/test.fastq")
@@ -244,6 +521,123 @@ defmodule Cyanea.SeqTest do
     end
   end
 
+  describe "write_fasta/3" do
+    test "returns nif_not_loaded without NIF" do
+      records = [%Cyanea.Native.FastaRecord{name: "s1", description: "", sequence: ~c"ACGT"}]
+      assert {:error, :nif_not_loaded} = Seq.write_fasta("/tmp/out.fa", records, line_width: 80)
+    end
+
+    test "rejects non-list records" do
+      assert_raise FunctionClauseError, fn -> Seq.write_fasta("/tmp/out.fa", "ACGT") end
+    end
+  end
+
+  describe "write_fastq/3" do
+    test "returns nif_not_loaded without NIF" do
+      assert {:error, :nif_not_loaded} = Seq.write_fastq("/tmp/out.fq.gz", [])
+    end
+
+    test "rejects non-binary path" do
+      assert_raise FunctionClauseError, fn -> Seq.write_fastq(:path, []) end
+    end
+  end
+
+  describe "demultiplex/4" do
+    test "returns nif_not_loaded without NIF" do
+      samples = [%{sample: "s1", i7: "ACGTACGT", i5: "TTGGCCAA"}]
+      assert {:error, :nif_not_loaded} = Seq.demultiplex("/tmp/pool.fq.gz", samples, "/tmp/demux")
+    end
+
+    test "accepts paired input and inline barcodes" do
+      samples = [%Cyanea.Native.SampleBarcode{sample: "s1", inline: "ACGTAC"}]
+
+      assert {:error, :nif_not_loaded} =
+               Seq.demultiplex({"/tmp/r1.fq", "/tmp/r2.fq"}, samples, "/tmp/demux",
+                 mode: :inline,
+                 max_mismatches: 0,
+                 compression: :gzip
+               )
+    end
+
+    test "rejects a non-list sample sheet" do
+      assert_raise FunctionClauseError, fn -> Seq.demultiplex("/tmp/pool.fq", %{}, "/tmp/demux") end
+    end
+  end
+
+  # Demultiplex a single read by inline barcode; returns the sample it went
+  # to, whether the match was perfect and the trimmed sequence.
+  defp demux_one(tmp_dir, samples, seq, max_mismatches) do
+    input = Path.join(tmp_dir, "pool.fastq")
+    File.write!(input, "@r1\n#{seq}\n+\n#{String.duplicate("I", byte_size(seq))}\n")
+    out = Path.join(tmp_dir, "out#{max_mismatches}")
+
+    {:ok, result} =
+      Seq.demultiplex(input, samples, out, mode: :inline, max_mismatches: max_mismatches)
+
+    case Enum.find(result.samples, &(&1.records == 1)) do
+      nil ->
+        {:undetermined, result}
+
+      sample ->
+        [_, trimmed | _] = sample.paths |> hd() |> File.read!() |> String.split("\n")
+        {sample.sample, sample.perfect == 1, trimmed}
+    end
+  end
+
+  describe "demultiplex/4 inline barcode matching" do
+    @describetag :native
+    @describetag :tmp_dir
+
+    setup do
+      %{samples: [%{sample: "s1", inline: "ACGTAC"}, %{sample: "s2", inline: "TTGGCC"}]}
+    end
+
+    test "trims a barcode found at the read start", %{samples: samples, tmp_dir: dir} do
+      assert {"s1", true, "GGGGGG"} = demux_one(dir, samples, "ACGTACGGGGGG", 1)
+    end
+
+    test "charges an edit for a barcode shifted by a base", %{samples: samples, tmp_dir: dir} do
+      assert {"s1", false, "GGGGG"} = demux_one(dir, samples, "GACGTACGGGGG", 1)
+      assert {:undetermined, %{undetermined: 1}} = demux_one(dir, samples, "GACGTACGGGGG", 0)
+    end
+
+    test "trims at the end of a barcode with a deletion", %{samples: samples, tmp_dir: dir} do
+      assert {"s1", false, "GGGGG"} = demux_one(dir, samples, "ACTACGGGGG", 1)
+    end
+  end
+
+  describe "fastq_report/1" do
+    test "returns nif_not_loaded without NIF" do
+      assert {:error, :nif_not_loaded} = Seq.fastq_report("/tmp/reads.fq.gz")
+    end
+
+    test "rejects non-binary path" do
+      assert_raise FunctionClauseError, fn -> Seq.fastq_report(:reads) end
+    end
+  end
+
+  describe "trim_fastq/3" do
+    test "returns nif_not_loaded without NIF" do
+      assert {:error, :nif_not_loaded} = Seq.trim_fastq("/tmp/r.fq.gz", "/tmp/clean.fq.gz")
+    end
+
+    test "accepts paired paths and step options" do
+      assert {:error, :nif_not_loaded} =
+               Seq.trim_fastq({"/tmp/r1.fq", "/tmp/r2.fq"}, {"/tmp/o1.fq", "/tmp/o2.fq"},
+                 adapters: ["AGATCGGAAGAGC"],
+                 window: {4, 15},
+                 max_n: nil,
+                 min_length: 36
+               )
+    end
+
+    test "rejects mismatched single and paired paths" do
+      assert_raise FunctionClauseError, fn ->
+        Seq.trim_fastq({"/tmp/r1.fq", "/tmp/r2.fq"}, "/tmp/out.fq")
+      end
+    end
+  end
+
   describe "parse_fastq/1" do
     test "returns nif_not_loaded without NIF" do
       assert {:error, :nif_not_loaded} = Seq.parse_fastq("/tmp/test.fastq")
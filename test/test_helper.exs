@@ -1,2 +1,4 @@
-ExUnit.start()
+# Tests tagged :native exercise the NIFs and only run when they are loaded.
+native_loaded? = match?({:ok, _}, Cyanea.NifHelper.nif_call(&Cyanea.Native.cancel_token_new/0))
+ExUnit.start(exclude: if(native_loaded?, do: [], else: [:native]))
 Ecto.Adapters.SQL.Sandbox.mode(Cyanea.Repo, :manual)